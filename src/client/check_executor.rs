@@ -0,0 +1,146 @@
+use crate::client::cli::ClientConfig;
+use crate::client::commands::deployer::Deployer;
+use crate::client::module::CheckDefinition;
+use crate::dependency::DependencyGraph;
+use crate::thread_control::Control;
+use anyhow::{Context, Result};
+use crossbeam_utils::thread;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget};
+use std::collections::{HashMap, HashSet};
+
+pub type Environment = HashMap<String, String>;
+
+/// The outcome of running the checks of a dependency graph.
+pub struct CheckResults {
+    /// The failures of the checks, in the order the checks were run.
+    pub failures: Vec<anyhow::Error>,
+    /// The names of the checks which failed or were skipped.
+    pub not_passed: HashSet<String>,
+}
+
+/// Runs the checks of a dependency graph, running the checks which don't
+/// depend on each other concurrently.
+///
+/// The checks are split in layers, the checks of each layer only depending on
+/// checks of the previous layers. The checks of a layer are run at the same
+/// time, and the next layer is started once they have all completed. A check
+/// is skipped if any of the checks it depends on did not pass.
+///
+/// Checks with a suggested fix ask whether to apply it when they fail, so they
+/// are run one at a time once the rest of their layer has completed.
+///
+/// A check is run once for each of its `environments` (eg. the distinct
+/// environments of the modules requiring it), or once without any, and passes
+/// if all of its runs pass.
+///
+/// No more layers are started once `cancel` is interrupted.
+pub fn execute_checks(
+    graph: &DependencyGraph<CheckDefinition, ()>,
+    environments: &HashMap<String, Vec<Environment>>,
+    cancel: &Control,
+    cfg: &ClientConfig,
+) -> Result<CheckResults> {
+    let layers = graph
+        .group_sort()
+        .context("Checks cannot depend on each other in a cycle")?
+        .groups;
+
+    let no_environment = vec![HashMap::new()];
+    let mut not_passed = HashSet::new();
+    let mut failures = Vec::new();
+    for layer in layers {
+        if cancel.is_interrupted() {
+            break;
+        }
+        let mut concurrent = Vec::new();
+        let mut interactive = Vec::new();
+        for node in layer {
+            let check = node.value;
+            let check_environments = environments
+                .get(&check.name)
+                .filter(|envs| !envs.is_empty())
+                .unwrap_or(&no_environment);
+            let runs = check_environments.iter().map(|env| (check, env));
+            let failed_dependency = check
+                .depends_on
+                .iter()
+                .find(|dep| not_passed.contains(dep.as_str()));
+
+            if let Some(dependency) = failed_dependency {
+                Deployer::skip_check(check, dependency, cfg);
+                not_passed.insert(check.name.as_str());
+            } else if check.suggested_fix.is_some() {
+                interactive.extend(runs);
+            } else {
+                concurrent.extend(runs);
+            }
+        }
+
+        let mut results = run_concurrently(&concurrent, cancel, cfg);
+        for &(check, environment) in &interactive {
+            let result =
+                Deployer::perform_check(check, environment, None, cancel, cfg);
+            results.push((check, result));
+        }
+        for (check, result) in results {
+            if let Err(e) = result {
+                failures.push(e);
+                not_passed.insert(check.name.as_str());
+            }
+        }
+    }
+    Ok(CheckResults {
+        failures,
+        not_passed: not_passed.into_iter().map(String::from).collect(),
+    })
+}
+
+/// Runs checks on a thread each, drawing their progress together.
+fn run_concurrently<'c>(
+    checks: &[(&'c CheckDefinition, &Environment)],
+    cancel: &Control,
+    cfg: &ClientConfig,
+) -> Vec<(&'c CheckDefinition, Result<()>)> {
+    if checks.is_empty() {
+        return Vec::new();
+    }
+
+    let multiprogress = MultiProgress::new();
+    if cfg.no_spinner {
+        multiprogress.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    // All the progress bars are added before joining the multiprogress, so
+    // that the join only returns once every check has completed.
+    let progress_bars: Vec<_> = checks
+        .iter()
+        .map(|_| multiprogress.add(ProgressBar::new(std::u64::MAX)))
+        .collect();
+
+    thread::scope(|s| {
+        let handles: Vec<_> = checks
+            .iter()
+            .zip(progress_bars)
+            .map(|(&(check, environment), pb)| {
+                s.spawn(move |_| {
+                    Deployer::perform_check(
+                        check,
+                        environment,
+                        Some(pb),
+                        cancel,
+                        cfg,
+                    )
+                })
+            })
+            .collect();
+
+        multiprogress.join().expect("Failed to draw the checks progress");
+        checks
+            .iter()
+            .zip(handles)
+            .map(|(&(check, _), handle)| {
+                (check, handle.join().expect("Check thread panicked"))
+            })
+            .collect()
+    })
+    .expect("Failed to run the checks")
+}
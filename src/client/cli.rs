@@ -1,8 +1,18 @@
 use crate::client::commands::*;
+use crate::client::format::SORT_FIELDS;
+use crate::client::request::{self, CartelClient};
 use crate::config;
 use anyhow::{anyhow, bail, Error, Result};
 use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
 use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// Sets the timeout of requests to the daemon (in seconds) when `--timeout`
+/// is not given.
+const DAEMON_TIMEOUT_ENV: &str = "CARTEL_DAEMON_TIMEOUT";
 
 pub struct ClientConfig {
     pub verbose: u64,
@@ -13,10 +23,55 @@ pub struct ClientConfig {
     pub follow_pager_cmd: Vec<String>,
     pub daemon_url: String,
     pub default_dir: Option<String>,
+    /// Print plain progress lines instead of rendering spinners.
+    pub no_spinner: bool,
+    /// The timeout of requests to the daemon, if overridden.
+    pub timeout: Option<Duration>,
+    /// The API key of the daemon, if it requires one.
+    pub api_key: Option<String>,
+}
+
+impl ClientConfig {
+    /// Returns a client for the daemon at `daemon_url`.
+    pub fn client(&self) -> CartelClient {
+        let client =
+            CartelClient::new(&self.daemon_url).api_key(self.api_key.clone());
+        match self.timeout {
+            Some(timeout) => client.timeout(timeout),
+            None => client,
+        }
+    }
 }
 
 pub fn cli_app() -> Result<()> {
-    let matches = App::new("cartel")
+    let mut args: Vec<_> = env::args_os().collect();
+    let mut matches = cli().get_matches_from(&args);
+    let defaults = match matches.subcommand() {
+        ("deploy", Some(deploy_cli_opts)) => cartel_opts_args(deploy_cli_opts)?,
+        _ => vec![],
+    };
+    if !defaults.is_empty() {
+        // Parsed again, so that clap validates them along with the arguments
+        args.extend(defaults.iter().map(OsString::from));
+        matches = cli().get_matches_from_safe(&args).unwrap_or_else(|e| {
+            twarn!(format!(
+                "{} added the options: {}",
+                CARTEL_OPTS_ENV,
+                defaults.join(" ")
+            ));
+            e.exit()
+        });
+    }
+
+    let cfg = cfg(&matches)?;
+    request::set_verbose(cfg.verbose > 0);
+    invoke_subcommand(&matches, &cfg)
+        .map_err(|e| handle_daemon_offline(e, cfg.verbose > 0))?;
+    Ok(())
+}
+
+fn cli<'a, 'b>() -> App<'a, 'b> {
+    App::new("cartel")
         .version(crate_version!())
         .about("Development workflow service orchestrator")
         .setting(AppSettings::SubcommandRequiredElseHelp)
@@ -52,90 +107,96 @@ pub fn cli_app() -> Result<()> {
                 .multiple(true)
                 .help("Sets the level of verbosity"),
         )
+        .arg(
+            Arg::with_name("no-spinner")
+                .long("no-spinner")
+                .help("Print plain progress lines instead of spinners"),
+        )
+        .arg(
+            Arg::with_name("timeout")
+                .long("timeout")
+                .value_name("SECS")
+                .takes_value(true)
+                .validator(|secs| {
+                    secs.parse::<u64>()
+                        .map(|_| ())
+                        .map_err(|_| String::from("Expected a number"))
+                })
+                .help("Timeout of requests to the daemon in seconds")
+                .long_help(
+                    "Timeout of requests to the daemon in seconds. \
+                    Defaults to the CARTEL_DAEMON_TIMEOUT environment \
+                    variable, or 30 seconds. Tasks use their own timeout.",
+                ),
+        )
+        .subcommand(deploy_subcommand())
         .subcommand(
-            SubCommand::with_name("deploy")
-                .about("Deploys a module (and it's dependencies)")
-                .visible_alias("d")
-                .arg(
-                    Arg::with_name("force")
-                        .help("Force deploy all modules")
-                        .short("f")
-                        .long("force"),
-                )
+            SubCommand::with_name("run")
+                .visible_alias("r")
+                .about("Runs a task (but NOT it's dependencies)")
                 .arg(
-                    Arg::with_name("env")
-                        .short("e")
-                        .long("env")
-                        .help("Environment set to activate")
-                        .takes_value(true)
-                        .require_delimiter(true)
-                        .value_delimiter("\0")
-                        .multiple(true)
-                        .long_help(
-                            "Override the env of each service by \
-                            activating environment sets \
-                            with the given name. In case of overlaps, \
-                            priority is given to the last defined.",
-                        ),
+                    Arg::with_name("task")
+                        .help("The task ro run")
+                        .multiple(false)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run-tasks")
+                .about(
+                    "Runs tasks through the daemon and reports their \
+                    results",
                 )
                 .arg(
-                    Arg::with_name("modules")
-                        .help("Modules to deploy")
+                    Arg::with_name("tasks")
+                        .help("The tasks to run")
+                        .required(true)
                         .multiple(true)
                         .takes_value(true),
                 )
                 .arg(
-                    Arg::with_name("skip_checks")
-                        .short("z")
-                        .long("no-checks")
-                        .help("Disables running checks"),
-                )
-                .arg(
-                    Arg::with_name("only_selected")
-                        .short("o")
-                        .long("only-selected")
-                        .help("Only deploy selected modules (no dependencies)"),
+                    Arg::with_name("parallel")
+                        .long("parallel")
+                        .help("Run the tasks at the same time")
+                        .takes_value(false),
                 )
                 .arg(
-                    Arg::with_name("wait")
-                        .short("w")
-                        .long("wait")
-                        .conflicts_with("skip_readiness_checks")
-                        .help("Waits for all readiness checks to complete"),
+                    Arg::with_name("max_concurrency")
+                        .long("max-concurrency")
+                        .value_name("N")
+                        .requires("parallel")
+                        .takes_value(true)
+                        .validator(|max| match max.parse::<usize>() {
+                            Ok(max) if max > 0 => Ok(()),
+                            _ => {
+                                Err(String::from("Expected a positive number"))
+                            }
+                        })
+                        .help("Run at most N tasks at the same time"),
                 )
                 .arg(
-                    Arg::with_name("serial")
-                        .short("k")
-                        .long("serial")
-                        .help("Deploy one module at a time"),
+                    Arg::with_name("env")
+                        .short("e")
+                        .long("env")
+                        .help("Environment set to activate")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
                 )
                 .arg(
-                    Arg::with_name("threads")
-                        .short("t")
-                        .long("threads")
-                        .conflicts_with("serial")
+                    Arg::with_name("namespace")
+                        .long("namespace")
+                        .value_name("NAME")
                         .takes_value(true)
-                        .help(
-                            "Set the number of threads \
-                            to use while deploying",
-                        ),
+                        .validator(validate_namespace)
+                        .help("Run the tasks in a separate namespace"),
                 )
                 .arg(
-                    Arg::with_name("skip_readiness_checks")
-                        .short("s")
-                        .long("no-readiness")
-                        .help("Disables running readiness checks"),
-                ),
-        )
-        .subcommand(
-            SubCommand::with_name("run")
-                .visible_alias("r")
-                .about("Runs a task (but NOT it's dependencies)")
-                .arg(
-                    Arg::with_name("task")
-                        .help("The task ro run")
-                        .multiple(false)
-                        .takes_value(true),
+                    Arg::with_name("module_log_level")
+                        .long("module-log-level")
+                        .value_name("LEVEL")
+                        .takes_value(true)
+                        .help("Override the log level of the tasks"),
                 ),
         )
         .subcommand(
@@ -157,6 +218,7 @@ pub fn cli_app() -> Result<()> {
         )
         .subcommand(
             SubCommand::with_name("ps")
+                .visible_alias("status")
                 .about("Print currently running services")
                 .arg(
                     Arg::with_name("no-color")
@@ -164,6 +226,81 @@ pub fn cli_app() -> Result<()> {
                         .long("no-color")
                         .help("Disable coloured output")
                         .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .short("v")
+                        .long("verbose")
+                        .help("Print additional details for each module")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("field")
+                        .long("field")
+                        .help("Print only the given field of each module")
+                        .takes_value(true)
+                        .possible_values(&PS_FIELDS),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .long("sort")
+                        .help("Sort the modules by the given field")
+                        .takes_value(true)
+                        .possible_values(&SORT_FIELDS),
+                )
+                .arg(
+                    Arg::with_name("sort-desc")
+                        .long("sort-desc")
+                        .requires("sort")
+                        .help("Sort in descending order")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("kind")
+                        .long("kind")
+                        .help("Only print modules of the given kind")
+                        .takes_value(true)
+                        .possible_values(&PS_KINDS),
+                )
+                .arg(
+                    Arg::with_name("namespace")
+                        .long("namespace")
+                        .value_name("NAME")
+                        .help("Only print modules in the given namespace")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("working-dir")
+                        .long("working-dir")
+                        .value_name("MODULE")
+                        .conflicts_with_all(&["field", "verbose", "modules"])
+                        .help("Print only the working directory of a module")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .conflicts_with_all(&["working-dir", "field"])
+                        .help("Keep polling and print each status change")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("on-change")
+                        .long("on-change")
+                        .value_name("COMMAND")
+                        .requires("watch")
+                        .help(
+                            "Run a shell command on each status change, \
+                            with CARTEL_MODULE_NAME, CARTEL_OLD_STATUS and \
+                            CARTEL_NEW_STATUS set",
+                        )
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("modules")
+                        .help("Only print the given modules")
+                        .multiple(true)
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -193,6 +330,51 @@ pub fn cli_app() -> Result<()> {
                         .help("Print the full logs")
                         .takes_value(false),
                 )
+                .arg(
+                    Arg::with_name("clear")
+                        .long("clear")
+                        .conflicts_with_all(&["follow", "all"])
+                        .help("Clear the logs instead of printing them")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("The output format")
+                        .takes_value(true)
+                        .default_value("text")
+                        .possible_values(&LOG_FORMATS),
+                )
+                .arg(
+                    Arg::with_name("all_modules")
+                        .long("all-modules")
+                        .conflicts_with_all(&[
+                            "service", "follow", "all", "clear",
+                        ])
+                        .help("Print the logs of all modules")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("tail")
+                        .long("tail")
+                        .value_name("N")
+                        .requires("all_modules")
+                        .takes_value(true)
+                        .validator(|lines| {
+                            lines
+                                .parse::<usize>()
+                                .map(|_| ())
+                                .map_err(|_| String::from("Expected a number"))
+                        })
+                        .help("Print only the last N lines of each module"),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .requires("clear")
+                        .help("Clear the logs even if the service is running")
+                        .takes_value(false),
+                )
                 .arg(
                     Arg::with_name("service")
                         .help("The service to print the logs of")
@@ -207,12 +389,36 @@ pub fn cli_app() -> Result<()> {
                     Arg::with_name("services")
                         .help("Services to stop")
                         .multiple(true)
-                        .takes_value(true),
+                        .takes_value(true)
+                        .required_unless("all")
+                        .conflicts_with("all"),
+                )
+                .arg(
+                    Arg::with_name("all")
+                        .long("all")
+                        .help("Stop all running services"),
+                )
+                .arg(
+                    Arg::with_name("except")
+                        .long("except")
+                        .value_name("NAME")
+                        .help("Keep this service running (with --all)")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .requires("all"),
                 ),
         )
         .subcommand(
             SubCommand::with_name("down").about("Stop all running services"),
         )
+        .subcommand(
+            SubCommand::with_name("unlock")
+                .about("Release the deploy lock of the daemon")
+                .arg(Arg::with_name("force").long("force").help(
+                    "Release the lock even though another deployment holds it",
+                )),
+        )
         .subcommand(
             SubCommand::with_name("restart")
                 .visible_alias("rr")
@@ -223,12 +429,156 @@ pub fn cli_app() -> Result<()> {
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("rollback")
+                .about("Redeploy a service with its previous definition")
+                .arg(
+                    Arg::with_name("module")
+                        .long("module")
+                        .value_name("NAME")
+                        .help("The service to roll back")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dependency-check")
+                .about("Check that the dependencies of a module are running")
+                .arg(
+                    Arg::with_name("module")
+                        .help("The module whose dependencies to check")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dependents")
+                .about("Print the deployed modules depending on a module")
+                .arg(
+                    Arg::with_name("transitive")
+                        .long("transitive")
+                        .help("Include modules depending on it indirectly")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("module")
+                        .help("The module whose dependents to print")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("reload")
+                .about("Signal a service to reload its configuration")
+                .arg(
+                    Arg::with_name("service")
+                        .help("Service to reload")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("pause")
+                .about("Suspend the processes of a running service")
+                .arg(
+                    Arg::with_name("service")
+                        .help("Service to pause")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("resume")
+                .about("Continue the processes of a paused service")
+                .arg(
+                    Arg::with_name("service")
+                        .help("Service to resume")
+                        .takes_value(true),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("daemon")
                 .about("Control the daemon")
+                .subcommand(
+                    SubCommand::with_name("start")
+                        .about("Start the daemon")
+                        .arg(
+                            Arg::with_name("background")
+                                .short("b")
+                                .long("background")
+                                .help(
+                                    "Run the daemon in the background and \
+                                    record its pid in the pidfile",
+                                )
+                                .takes_value(false),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("stop")
+                        .about("Stop a daemon started in the background"),
+                )
                 .subcommand(
                     SubCommand::with_name("restart")
                         .about("Restart the daemon"),
+                )
+                .subcommand(
+                    SubCommand::with_name("reload").about(
+                        "Re-read the daemon config without restarting",
+                    ),
+                )
+                .subcommand(
+                    SubCommand::with_name("ping")
+                        .about("Check the connection to the daemon")
+                        .arg(
+                            Arg::with_name("count")
+                                .short("c")
+                                .long("count")
+                                .value_name("N")
+                                .help("The number of requests to send")
+                                .takes_value(true)
+                                .default_value("4")
+                                .validator(|count| {
+                                    match count.parse::<usize>() {
+                                        Ok(count) if count > 0 => Ok(()),
+                                        _ => Err(String::from(
+                                            "Expected a positive number",
+                                        )),
+                                    }
+                                }),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Register services with the daemon without deploying")
+                .arg(
+                    Arg::with_name("env")
+                        .short("e")
+                        .long("env")
+                        .help("Environment set to activate")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("modules")
+                        .help("Services to import (all by default)")
+                        .multiple(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info").about(
+                "Print the version, uptime and platform of the daemon",
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("snapshot")
+                .about("Save the state of all modules to a file")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .help("The file to write the snapshot to")
+                        .takes_value(true),
                 ),
         )
         .subcommand(
@@ -310,14 +660,437 @@ pub fn cli_app() -> Result<()> {
                 )
                 .subcommand(SubCommand::with_name("view").about(
                     "View all currently set/unset configuration options",
-                )),
+                ))
+                .subcommand(
+                    SubCommand::with_name("lint")
+                        .about(
+                            "Warn about unknown or deprecated keys in the \
+                            module definitions",
+                        )
+                        .arg(
+                            Arg::with_name("fix")
+                                .long("fix")
+                                .help(
+                                    "Fix the auto-fixable issues and write \
+                                    the files back (unless they have \
+                                    comments or aliases)",
+                                )
+                                .takes_value(false),
+                        ),
+                ),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Print the dependency graph of modules")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("The output format")
+                        .takes_value(true)
+                        .default_value("text")
+                        .possible_values(&GRAPH_FORMATS),
+                )
+                .arg(
+                    Arg::with_name("layers")
+                        .long("layers")
+                        .help("Print the modules grouped by dependency depth")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("include-checks")
+                        .long("include-checks")
+                        .conflicts_with("layers")
+                        .help("Show the checks which run before the modules")
+                        .takes_value(false),
+                )
+                .arg(
+                    Arg::with_name("filter-kind")
+                        .long("filter-kind")
+                        .value_name("KIND")
+                        .help("Only show the modules of the given kind")
+                        .takes_value(true)
+                        .possible_values(&GRAPH_KINDS),
+                )
+                .arg(
+                    Arg::with_name("exclude")
+                        .long("exclude")
+                        .value_name("NAME")
+                        .help("Leave a module out of the graph")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("modules")
+                        .help("Modules to include (all by default)")
+                        .multiple(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("modules")
+                .about("Inspect the module definitions")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("validate-deps").about(
+                    "Check that all dependencies refer to known modules",
+                ))
+                .subcommand(SubCommand::with_name("list").about(
+                    "List the modules along with a summary of each",
+                ))
+                .subcommand(
+                    SubCommand::with_name("check-health")
+                        .about("Run the liveness probe of a module once")
+                        .arg(
+                            Arg::with_name("module")
+                                .help("Module to check")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Print the definition of a module")
+                        .arg(
+                            Arg::with_name("format")
+                                .long("format")
+                                .help("The output format")
+                                .takes_value(true)
+                                .default_value("yaml")
+                                .possible_values(&SHOW_FORMATS),
+                        )
+                        .arg(
+                            Arg::with_name("module")
+                                .help("Module to show")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rename")
+                        .about("Rename a module and all references to it")
+                        .arg(
+                            Arg::with_name("dry-run")
+                                .long("dry-run")
+                                .help("Print the changes without writing them")
+                                .takes_value(false),
+                        )
+                        .arg(
+                            Arg::with_name("old")
+                                .help("The current name of the module")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("new")
+                                .help("The new name of the module")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("copy")
+                        .about("Copy a module definition to a new module")
+                        .arg(
+                            Arg::with_name("src")
+                                .help("The module to copy")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("dst")
+                                .help("The name of the new module")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("diff")
+                        .about(
+                            "Compare the module definitions of two files \
+                            field by field",
+                        )
+                        .arg(
+                            Arg::with_name("old")
+                                .help("The original module definitions file")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("new")
+                                .help("The changed module definitions file")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                ),
+        )
+}
 
-    let cfg = cfg(&matches)?;
-    invoke_subcommand(&matches, &cfg)
-        .map_err(|e| handle_daemon_offline(e, cfg.verbose > 0))?;
-    Ok(())
+/// The `deploy` subcommand.
+///
+/// This is also used by [cartel_opts_args] to parse the options given
+/// through the `CARTEL_OPTS` environment variable.
+pub fn deploy_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("deploy")
+        .about("Deploys a module (and it's dependencies)")
+        .visible_alias("d")
+        .arg(
+            Arg::with_name("force")
+                .help("Force deploy all modules")
+                .short("f")
+                .long("force"),
+        )
+        .arg(
+            Arg::with_name("env")
+                .short("e")
+                .long("env")
+                .help("Environment set to activate")
+                .takes_value(true)
+                .require_delimiter(true)
+                .value_delimiter("\0")
+                .multiple(true)
+                .long_help(
+                    "Override the env of each service by \
+                    activating environment sets \
+                    with the given name. In case of overlaps, \
+                    priority is given to the last defined.",
+                ),
+        )
+        .arg(
+            Arg::with_name("modules")
+                .help("Modules to deploy")
+                .multiple(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("modules_from_stdin")
+                .long("modules-from-stdin")
+                .conflicts_with_all(&["modules", "deploy_order"])
+                .help("Read the modules to deploy from stdin, one per line"),
+        )
+        .arg(
+            Arg::with_name("skip_checks")
+                .short("z")
+                .long("no-checks")
+                .help("Disables running checks"),
+        )
+        .arg(
+            Arg::with_name("only_selected")
+                .short("o")
+                .long("only-selected")
+                .help("Only deploy selected modules (no dependencies)"),
+        )
+        .arg(
+            Arg::with_name("wait")
+                .short("w")
+                .long("wait")
+                .conflicts_with("skip_readiness_checks")
+                .help("Waits for all readiness checks to complete"),
+        )
+        .arg(
+            Arg::with_name("wait_all")
+                .long("wait-all")
+                .conflicts_with("skip_readiness_checks")
+                .help(
+                    "Waits for every service to be healthy, including \
+                    services which were already deployed",
+                ),
+        )
+        .arg(
+            Arg::with_name("serial")
+                .short("k")
+                .long("serial")
+                .help("Deploy one module at a time"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .conflicts_with("serial")
+                .takes_value(true)
+                .help(
+                    "Set the number of threads \
+                    to use while deploying",
+                ),
+        )
+        .arg(
+            Arg::with_name("skip_readiness_checks")
+                .short("s")
+                .long("no-readiness")
+                .help("Disables running readiness checks"),
+        )
+        .arg(
+            Arg::with_name("selector")
+                .long("selector")
+                .takes_value(true)
+                .conflicts_with("deploy_order")
+                .help("Deploy the modules matching an expression")
+                .long_help(
+                    "Deploy the modules matching the given \
+                    expression, eg. 'kind=Service AND \
+                    label.team=backend'. Supported predicates are \
+                    kind=<Kind>, label.<key>=<value> and \
+                    name=<glob>, combined with AND / OR. If modules \
+                    are also given, only those matching the \
+                    expression are deployed.",
+                ),
+        )
+        .arg(
+            Arg::with_name("deploy_order")
+                .long("deploy-order")
+                .takes_value(true)
+                .use_delimiter(true)
+                .multiple(true)
+                .conflicts_with_all(&["modules", "only_selected"])
+                .help("Deploy modules in exactly the given order")
+                .long_help(
+                    "Deploy the given comma separated modules one \
+                    at a time, in exactly the given order. This \
+                    overrides the order computed from the modules' \
+                    dependencies, and dependencies which are not \
+                    listed will not be deployed.",
+                ),
+        )
+        .arg(
+            Arg::with_name("save_plan")
+                .long("save-plan")
+                .value_name("FILE")
+                .takes_value(true)
+                .conflicts_with_all(&["deploy_order", "only_selected"])
+                .help("Save the dependency graph of the deployment as JSON")
+                .long_help(
+                    "Save the dependency graph of the deployment to \
+                    the given file, in the same JSON format as \
+                    `cartel graph --format json`.",
+                ),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .value_name("FILE")
+                .takes_value(true)
+                .help("Write a JSON report of the deployment to a file")
+                .long_help(
+                    "Write a JSON report of the deployment to the \
+                    given file once it completes (whether it \
+                    succeeded or not), including the status and \
+                    duration of each module.",
+                ),
+        )
+        .arg(
+            Arg::with_name("strategy")
+                .long("strategy")
+                .takes_value(true)
+                .possible_values(&DEPLOY_STRATEGIES)
+                .conflicts_with_all(&["deploy_order", "only_selected"])
+                .help("How the dependencies of the modules are deployed")
+                .long_help(
+                    "How the dependencies of the modules are \
+                    deployed. With 'eager' (the default) all \
+                    dependencies are deployed. With 'lazy' the \
+                    dependencies of a module are only deployed if \
+                    the module itself is going to be deployed, so \
+                    modules which are already running do not pull \
+                    in their dependencies.",
+                ),
+        )
+        .arg(
+            Arg::with_name("module_log_level")
+                .long("module-log-level")
+                .value_name("LEVEL")
+                .takes_value(true)
+                .help("Override the log level of the deployed modules")
+                .long_help(
+                    "Override the log_level of every deployed service and \
+                    task (eg. debug), given to them through the LOG_LEVEL \
+                    and RUST_LOG environment variables.",
+                ),
+        )
+        .arg(
+            Arg::with_name("namespace")
+                .long("namespace")
+                .value_name("NAME")
+                .takes_value(true)
+                .help("Deploy the modules in a separate namespace")
+                .validator(validate_namespace)
+                .long_help(
+                    "Deploy the modules in a separate namespace. The daemon \
+                    tracks modules in each namespace separately, so a \
+                    module can run in a namespace while it also runs \
+                    outside of it. Deployed modules are named \
+                    <module>@<namespace>.",
+                ),
+        )
+        .arg(
+            Arg::with_name("simulate")
+                .long("simulate")
+                .conflicts_with("namespace")
+                .help("Deploy the modules in the 'simulation' namespace"),
+        )
+        .arg(
+            Arg::with_name("lock")
+                .long("lock")
+                .help("Fail if another deployment holds the daemon lock")
+                .long_help(
+                    "Lock the daemon for the duration of the deployment. \
+                    The deployment fails (printing who holds the lock) if \
+                    another deployment with --lock is in progress.",
+                ),
+        )
+        .arg(
+            Arg::with_name("timing")
+                .long("timing")
+                .help("Print how long each step of the deployment took")
+                .long_help(
+                    "Print how long each step of the deployment took \
+                    once it completes. This is always printed when \
+                    running with --verbose.",
+                ),
+        )
+        .arg(
+            Arg::with_name("max_errors")
+                .long("max-errors")
+                .value_name("N")
+                .takes_value(true)
+                .validator(|max| {
+                    max.parse::<usize>()
+                        .map(|_| ())
+                        .map_err(|_| String::from("Expected a number"))
+                })
+                .help("Continue deploying past up to N failed modules")
+                .long_help(
+                    "Continue deploying past up to N failed modules. \
+                    The deployment is aborted once more than N \
+                    modules have failed, and all failures are \
+                    reported at the end. Defaults to 0, which aborts \
+                    on the first failure.",
+                ),
+        )
+        .arg(
+            Arg::with_name("healthcheck_timeout")
+                .long("healthcheck-timeout")
+                .value_name("SECS")
+                .takes_value(true)
+                .validator(|secs| {
+                    secs.parse::<u64>()
+                        .map(|_| ())
+                        .map_err(|_| String::from("Expected a number"))
+                })
+                .help("Give up waiting for a service to be healthy after SECS")
+                .long_help(
+                    "Give up waiting for a service to pass its readiness \
+                    probe after SECS seconds (including the grace period), \
+                    regardless of the retries of the probe.",
+                ),
+        )
+}
+
+/// Namespaces are separated from module names by '@' in the daemon.
+fn validate_namespace(namespace: String) -> Result<(), String> {
+    if namespace.is_empty() || namespace.contains('@') {
+        Err(String::from("Expected a name which does not contain '@'"))
+    } else {
+        Ok(())
+    }
 }
 
 fn cfg(matches: &ArgMatches) -> Result<ClientConfig> {
@@ -334,15 +1107,19 @@ fn cfg(matches: &ArgMatches) -> Result<ClientConfig> {
         .map(|port| format!("http://localhost:{}/api/v1", port));
 
     let default_dir = config::PERSISTED_CONFIG.client.default_dir.clone();
+    let api_key = config::PERSISTED_CONFIG.daemon.api_key.clone();
 
     Ok(ClientConfig {
         verbose: matches.occurrences_of("verbose"),
+        no_spinner: matches.is_present("no-spinner"),
+        timeout: parse_timeout(matches)?,
         module_file: matches.value_of("file").map(String::from),
         override_file: matches.value_of("override").map(String::from),
         default_pager_cmd,
         full_pager_cmd,
         follow_pager_cmd,
         default_dir,
+        api_key,
         daemon_url: daemon_url
             .unwrap_or_else(|| String::from("http://localhost:13754/api/v1")),
     })
@@ -364,13 +1141,49 @@ fn parse_cmd_from_env(env: &str, default: &str) -> Result<Vec<String>> {
     Ok(cmd)
 }
 
+/// Parses the request timeout from `--timeout`, falling back to the
+/// `CARTEL_DAEMON_TIMEOUT` environment variable.
+fn parse_timeout(matches: &ArgMatches) -> Result<Option<Duration>> {
+    let secs = match matches.value_of("timeout") {
+        Some(secs) => secs.to_string(),
+        None => match env::var(DAEMON_TIMEOUT_ENV) {
+            Ok(secs) => secs,
+            Err(_) => return Ok(None),
+        },
+    };
+    let secs = secs.trim().parse::<u64>().map_err(|_| {
+        anyhow!(
+            "Invalid timeout '{}', expected a number of seconds. \
+            Are you overriding {}?",
+            secs,
+            DAEMON_TIMEOUT_ENV
+        )
+    })?;
+    Ok(Some(Duration::from_secs(secs)))
+}
+
 fn invoke_subcommand(matches: &ArgMatches, cfg: &ClientConfig) -> Result<()> {
     match matches.subcommand() {
         ("deploy", Some(deploy_cli_opts)) => {
-            let modules_to_deploy = deploy_cli_opts
-                .values_of("modules")
-                .ok_or_else(|| anyhow!("Expected at least one module"))?
-                .collect();
+            let stdin_modules =
+                if deploy_cli_opts.is_present("modules_from_stdin") {
+                    read_module_names(io::stdin().lock())?
+                } else {
+                    vec![]
+                };
+            let modules_to_deploy = match deploy_cli_opts.values_of("modules")
+            {
+                Some(modules) => modules.collect(),
+                None if !stdin_modules.is_empty() => {
+                    stdin_modules.iter().map(String::as_str).collect()
+                }
+                None if deploy_cli_opts.is_present("deploy_order")
+                    || deploy_cli_opts.is_present("selector") =>
+                {
+                    vec![]
+                }
+                None => bail!("Expected at least one module"),
+            };
             let options = DeployOptions::from(deploy_cli_opts);
             deploy_cmd(modules_to_deploy, cfg, &options)?;
         }
@@ -380,10 +1193,32 @@ fn invoke_subcommand(matches: &ArgMatches, cfg: &ClientConfig) -> Result<()> {
                 .ok_or_else(|| anyhow!("Expected task name"))?;
             run_task_cmd(task_name, cfg)?;
         }
+        ("run-tasks", Some(run_tasks_cli_opts)) => {
+            let tasks: Vec<_> =
+                run_tasks_cli_opts.values_of("tasks").unwrap().collect();
+            // Without --parallel the tasks are run one after the other
+            let max_concurrency = if run_tasks_cli_opts.is_present("parallel") {
+                run_tasks_cli_opts
+                    .value_of("max_concurrency")
+                    .and_then(|max| max.parse::<usize>().ok())
+                    .unwrap_or_else(|| tasks.len())
+            } else {
+                1
+            };
+            let deploy_opts = DeployOptions::from(run_tasks_cli_opts);
+            run_tasks_cmd(&tasks, max_concurrency, &deploy_opts, cfg)?;
+        }
         ("ps", Some(ps_opts)) => {
             let opts = PsOpts::from(ps_opts);
             list_modules_cmd(&opts, cfg)?;
         }
+        ("stop", Some(stop_cli_opts)) if stop_cli_opts.is_present("all") => {
+            let except = match stop_cli_opts.values_of("except") {
+                Some(except) => except.collect(),
+                None => vec![],
+            };
+            stop_all_cmd(except, cfg)?;
+        }
         ("stop", Some(stop_cli_opts)) => {
             let modules_to_stop = stop_cli_opts
                 .values_of("services")
@@ -394,12 +1229,46 @@ fn invoke_subcommand(matches: &ArgMatches, cfg: &ClientConfig) -> Result<()> {
         ("down", Some(_down_cli_opts)) => {
             down_cmd(cfg)?;
         }
+        ("unlock", Some(unlock_cli_opts)) => {
+            unlock_cmd(unlock_cli_opts.is_present("force"), cfg)?;
+        }
         ("restart", Some(restart_cli_opts)) => {
             let module_to_restart = restart_cli_opts
                 .value_of("service")
                 .ok_or_else(|| anyhow!("Expected service name"))?;
             restart_module_cmd(module_to_restart, cfg)?;
         }
+        ("rollback", Some(rollback_cli_opts)) => {
+            let module = rollback_cli_opts.value_of("module").unwrap();
+            rollback_module_cmd(module, cfg)?;
+        }
+        ("dependency-check", Some(dependency_check_cli_opts)) => {
+            let module = dependency_check_cli_opts.value_of("module").unwrap();
+            dependency_check_cmd(module, cfg)?;
+        }
+        ("dependents", Some(dependents_cli_opts)) => {
+            let module = dependents_cli_opts.value_of("module").unwrap();
+            let transitive = dependents_cli_opts.is_present("transitive");
+            dependents_cmd(module, transitive, cfg)?;
+        }
+        ("reload", Some(reload_cli_opts)) => {
+            let module_to_reload = reload_cli_opts
+                .value_of("service")
+                .ok_or_else(|| anyhow!("Expected service name"))?;
+            reload_module_cmd(module_to_reload, cfg)?;
+        }
+        ("pause", Some(pause_cli_opts)) => {
+            let module_to_pause = pause_cli_opts
+                .value_of("service")
+                .ok_or_else(|| anyhow!("Expected service name"))?;
+            pause_module_cmd(module_to_pause, cfg)?;
+        }
+        ("resume", Some(resume_cli_opts)) => {
+            let module_to_resume = resume_cli_opts
+                .value_of("service")
+                .ok_or_else(|| anyhow!("Expected service name"))?;
+            resume_module_cmd(module_to_resume, cfg)?;
+        }
         ("shell", Some(shell_cli_opts)) => {
             let service_name = shell_cli_opts
                 .value_of("service")
@@ -408,24 +1277,72 @@ fn invoke_subcommand(matches: &ArgMatches, cfg: &ClientConfig) -> Result<()> {
             open_shell(service_name, shell_type, cfg)?;
         }
         ("logs", Some(logs_cli_opts)) => {
+            if logs_cli_opts.is_present("all_modules") {
+                let tail = logs_cli_opts
+                    .value_of("tail")
+                    .and_then(|lines| lines.parse::<usize>().ok());
+                let format = logs_cli_opts.value_of("format").unwrap();
+                return print_all_logs(tail, format, cfg);
+            }
             let module_name = logs_cli_opts
                 .value_of("service")
                 .ok_or_else(|| anyhow!("Expected service name"))?;
-            let follow = logs_cli_opts.is_present("follow");
-            let all = logs_cli_opts.is_present("all");
-
-            let mode = if follow {
-                LogMode::FOLLOW
-            } else if all {
-                LogMode::FULL
+            if logs_cli_opts.is_present("clear") {
+                let force = logs_cli_opts.is_present("force");
+                clear_logs(module_name, force, cfg)?;
             } else {
-                LogMode::DEFAULT
-            };
+                let follow = logs_cli_opts.is_present("follow");
+                let all = logs_cli_opts.is_present("all");
 
-            print_logs(module_name, mode, cfg)?;
+                let mode = if follow {
+                    LogMode::FOLLOW
+                } else if all {
+                    LogMode::FULL
+                } else {
+                    LogMode::DEFAULT
+                };
+
+                let format = logs_cli_opts.value_of("format").unwrap();
+                print_logs(module_name, mode, format, cfg)?;
+            }
+        }
+        ("daemon", Some(daemon_cli_opts)) => {
+            match daemon_cli_opts.subcommand() {
+                ("start", Some(opts)) => {
+                    start_daemon(opts.is_present("background"))?;
+                }
+                ("stop", _) => {
+                    stop_daemon()?;
+                }
+                ("reload", _) => {
+                    reload_daemon(cfg)?;
+                }
+                ("ping", Some(opts)) => {
+                    let count = opts.value_of("count").unwrap().parse()?;
+                    ping_cmd(count, cfg)?;
+                }
+                _ => {
+                    restart_daemon()?;
+                }
+            }
+        }
+        ("import", Some(import_cli_opts)) => {
+            let modules = match import_cli_opts.values_of("modules") {
+                Some(modules) => modules.collect(),
+                None => vec![],
+            };
+            let active_envs: Vec<String> =
+                match import_cli_opts.values_of("env") {
+                    Some(envs) => envs.map(String::from).collect(),
+                    None => vec![],
+                };
+            import_cmd(modules, &active_envs, cfg)?;
         }
-        ("daemon", _) => {
-            restart_daemon()?;
+        ("info", Some(_)) => {
+            info_cmd(cfg)?;
+        }
+        ("snapshot", Some(snapshot_cli_opts)) => {
+            snapshot_cmd(snapshot_cli_opts.value_of("output"), cfg)?;
         }
         ("exec", Some(exec_cli_opts)) => {
             let service = exec_cli_opts.value_of("service").unwrap();
@@ -458,6 +1375,64 @@ fn invoke_subcommand(matches: &ArgMatches, cfg: &ClientConfig) -> Result<()> {
                 ("view", _) => {
                     view_all_options()?;
                 }
+                ("lint", Some(opts)) => {
+                    lint_cmd(opts.is_present("fix"), cfg)?;
+                }
+                _ => {}
+            }
+        }
+        ("graph", Some(graph_cli_opts)) => {
+            let modules = match graph_cli_opts.values_of("modules") {
+                Some(modules) => modules.collect(),
+                None => vec![],
+            };
+            let exclude = match graph_cli_opts.values_of("exclude") {
+                Some(exclude) => exclude.collect(),
+                None => vec![],
+            };
+            let format = graph_cli_opts.value_of("format").unwrap();
+            let layers = graph_cli_opts.is_present("layers");
+            let include_checks = graph_cli_opts.is_present("include-checks");
+            let filter_kind = graph_cli_opts.value_of("filter-kind");
+            graph_cmd(
+                modules,
+                exclude,
+                filter_kind,
+                format,
+                layers,
+                include_checks,
+                cfg,
+            )?;
+        }
+        ("modules", Some(modules_cli_opts)) => {
+            match modules_cli_opts.subcommand() {
+                ("validate-deps", _) => validate_deps_cmd(cfg)?,
+                ("list", _) => list_cmd(cfg)?,
+                ("check-health", Some(health_cli_opts)) => {
+                    let module = health_cli_opts.value_of("module").unwrap();
+                    check_health_cmd(module, cfg)?;
+                }
+                ("show", Some(show_cli_opts)) => {
+                    let module = show_cli_opts.value_of("module").unwrap();
+                    let format = show_cli_opts.value_of("format").unwrap();
+                    show_cmd(module, format, cfg)?;
+                }
+                ("rename", Some(rename_cli_opts)) => {
+                    let old = rename_cli_opts.value_of("old").unwrap();
+                    let new = rename_cli_opts.value_of("new").unwrap();
+                    let dry_run = rename_cli_opts.is_present("dry-run");
+                    rename_cmd(old, new, dry_run, cfg)?;
+                }
+                ("copy", Some(copy_cli_opts)) => {
+                    let src = copy_cli_opts.value_of("src").unwrap();
+                    let dst = copy_cli_opts.value_of("dst").unwrap();
+                    copy_cmd(src, dst, cfg)?;
+                }
+                ("diff", Some(diff_cli_opts)) => {
+                    let old = diff_cli_opts.value_of("old").unwrap();
+                    let new = diff_cli_opts.value_of("new").unwrap();
+                    diff_cmd(Path::new(old), Path::new(new))?;
+                }
                 _ => {}
             }
         }
@@ -1,9 +1,71 @@
+use crate::client::cli::ClientConfig;
 use crate::client::emoji::{HOUR_GLASS, SUCCESS};
+use crate::client::request::CartelClientTrait;
+use crate::config::daemon_pidfile_path;
 use crate::shell::active_shell_path;
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use console::style;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::fs;
 use std::process::Command;
 
+/// Starts the daemon.
+///
+/// When `background` is set the daemon forks itself into the background and
+/// records its pid in the pidfile so it can later be stopped with
+/// `cartel daemon stop`. Otherwise the daemon runs attached to the terminal.
+pub fn start_daemon(background: bool) -> Result<()> {
+    let mut cmd = Command::new("cartel-daemon");
+    if !background {
+        cmd.spawn()?.wait()?;
+        return Ok(());
+    }
+
+    let pidfile = daemon_pidfile_path();
+    tprintstep!("Starting daemon...", 1, 2, HOUR_GLASS);
+    let status = cmd
+        .arg("--background")
+        .arg("--pidfile")
+        .arg(&pidfile)
+        .spawn()?
+        .wait()?;
+    if !status.success() {
+        bail!("Failed to start the daemon in the background");
+    }
+    tprintstep!(style("Daemon started").bold().green(), 2, 2, SUCCESS);
+    Ok(())
+}
+
+/// Stops a daemon started in the background by sending it SIGTERM.
+pub fn stop_daemon() -> Result<()> {
+    let pidfile = daemon_pidfile_path();
+    let content = fs::read_to_string(&pidfile).with_context(|| {
+        format!(
+            "Failed to read pidfile {}. Was the daemon started with \
+            `cartel daemon start --background`?",
+            pidfile.display()
+        )
+    })?;
+    let pid = Pid::from_raw(
+        content
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid pid in {}", pidfile.display()))?,
+    );
+
+    tprintstep!("Stopping daemon...", 1, 2, HOUR_GLASS);
+    if kill(pid, None).is_err() {
+        fs::remove_file(&pidfile)?;
+        bail!("Daemon with pid {} is not running, removed stale pidfile", pid);
+    }
+    kill(pid, Signal::SIGTERM)
+        .with_context(|| format!("Failed to signal daemon with pid {}", pid))?;
+    fs::remove_file(&pidfile)?;
+    tprintstep!(style("Daemon stopped").bold().green(), 2, 2, SUCCESS);
+    Ok(())
+}
+
 pub fn restart_daemon() -> Result<()> {
     let active_shell = if let Some(path) = active_shell_path() {
         path
@@ -29,3 +91,27 @@ pub fn restart_daemon() -> Result<()> {
     tprintstep!(style("Daemon restarted").bold().green(), 2, 2, SUCCESS);
     Ok(())
 }
+
+/// Asks the daemon to re-read its config file without restarting.
+pub fn reload_daemon(cfg: &ClientConfig) -> Result<()> {
+    let reload = cfg.client().reload_daemon_config()?;
+
+    if reload.applied.is_empty() && reload.requires_restart.is_empty() {
+        tprint!("Daemon config reloaded, no changes found");
+        return Ok(());
+    }
+
+    tprint!("{}", style("Daemon config reloaded").bold().green());
+    for setting in &reload.applied {
+        tiprint!(4, "{} (applied)", setting);
+    }
+    for setting in &reload.requires_restart {
+        tiprint!(
+            4,
+            "{} {}",
+            setting,
+            style("(requires `cartel daemon restart`)").yellow()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use crate::client::cli::ClientConfig;
+use crate::client::commands::format_status;
+use crate::client::definitions::read_module_definitions;
+use crate::client::module::{InnerDefinition, ModuleDefinition, ModuleKind};
+use crate::client::request::{CartelClientTrait, ModuleFilter};
+use crate::daemon::api::ApiModuleRunStatus;
+use anyhow::{anyhow, bail, Result};
+
+/// Checks whether the direct dependencies of a module are currently running.
+///
+/// Services must be running and one-shots must have completed. Tasks and
+/// groups are listed but not checked, since they have no state of their own.
+pub fn dependency_check_cmd(name: &str, cfg: &ClientConfig) -> Result<()> {
+    let module_defs = read_module_definitions(cfg)?;
+    let module = find_module(&module_defs, name)?;
+    let statuses = cfg.client().list_modules(&ModuleFilter::default())?.status;
+
+    let dependencies = direct_dependencies(module);
+    if dependencies.is_empty() {
+        tprint!("{}", cdim!(format!("'{}' has no dependencies", name)));
+        return Ok(());
+    }
+
+    let mut not_running = 0;
+    for dependency in dependencies {
+        let kind = &find_module(&module_defs, dependency)?.kind;
+        if *kind == ModuleKind::Task || *kind == ModuleKind::Group {
+            let kind = kind.to_string().to_lowercase();
+            tprint!("{} {} ({})", cdim!("-"), dependency, kind);
+            continue;
+        }
+
+        let status = statuses.iter().find(|s| s.name == dependency);
+        let is_up = status.map_or(false, |s| {
+            s.status == ApiModuleRunStatus::RUNNING
+                || s.status == ApiModuleRunStatus::COMPLETED
+        });
+        let formatted_status = match status {
+            Some(status) => format_status(status),
+            None => String::from("not deployed"),
+        };
+        let symbol = if is_up {
+            csuccess!("✓")
+        } else {
+            not_running += 1;
+            cfail!("✗")
+        };
+        tprint!("{} {} ({})", symbol, dependency, formatted_status);
+    }
+
+    if not_running > 0 {
+        bail!("{} dependencies of '{}' are not running", not_running, name);
+    }
+    Ok(())
+}
+
+fn find_module<'a>(
+    module_defs: &'a [ModuleDefinition],
+    name: &str,
+) -> Result<&'a ModuleDefinition> {
+    module_defs
+        .iter()
+        .find(|m| m.name == name)
+        .ok_or_else(|| anyhow!("The module '{}' does not exist", name))
+}
+
+/// Returns the modules a module directly depends on, in the order they are
+/// defined.
+fn direct_dependencies(module: &ModuleDefinition) -> Vec<&str> {
+    let dependencies: Vec<&String> = match &module.inner {
+        InnerDefinition::Group(group) => group.dependencies.iter().collect(),
+        InnerDefinition::Service(svc_or_task)
+        | InnerDefinition::Task(svc_or_task)
+        | InnerDefinition::OneShot(svc_or_task) => svc_or_task
+            .dependencies
+            .iter()
+            .chain(svc_or_task.ordered_dependencies.iter())
+            .collect(),
+        _ => vec![],
+    };
+    let mut names: Vec<&str> = vec![];
+    for dependency in dependencies {
+        if !names.contains(&dependency.as_str()) {
+            names.push(dependency);
+        }
+    }
+    names
+}
@@ -0,0 +1,24 @@
+use crate::client::cli::ClientConfig;
+use crate::client::request::CartelClientTrait;
+use anyhow::Result;
+
+/// Prints the deployed modules which depend on a module, ie. the modules
+/// affected by stopping it.
+pub fn dependents_cmd(
+    name: &str,
+    transitive: bool,
+    cfg: &ClientConfig,
+) -> Result<()> {
+    let client = cfg.client();
+    let name = client.expand_module_name(name)?;
+    let dependents = client.dependents(&name, transitive)?.dependents;
+
+    if dependents.is_empty() {
+        tprint!("{}", cdim!(format!("No modules depend on '{}'", name)));
+        return Ok(());
+    }
+    for dependent in dependents {
+        tprint!("{}", dependent);
+    }
+    Ok(())
+}
@@ -1,24 +1,44 @@
-use crate::client::cli::ClientConfig;
+use crate::client::cli::{deploy_subcommand, ClientConfig};
 use crate::client::commands::deployer::{
     Deployer, ModuleDeploymentPlan, ModuleToDeploy,
 };
+use crate::client::commands::report::{write_report, DeployRecorder};
+use crate::client::commands::strategy::{DeployStrategy, LazyDeployStrategy};
 use crate::client::definitions::read_module_definitions;
 use crate::client::emoji::{
     LINK, LOOKING_GLASS, SPIRAL_NOTEBOOK, SUCCESS, TEXTBOOK, VAN,
 };
 use crate::client::module::{
-    module_names_set, remove_checks, ModuleDefinition, ModuleMarker,
+    remove_checks, ModuleDefinition, ModuleKind, ModuleMarker,
+};
+use crate::client::progress::Cancelled;
+use crate::client::selector::Selector;
+use crate::client::validation::{
+    validate_group_members, validate_modules_selected,
 };
-use crate::client::validation::validate_modules_selected;
 use crate::dependency::{DependencyGraph, DependencyNode};
-use anyhow::Result;
+use crate::thread_control::{make_pair, Control};
+use anyhow::{bail, Context, Result};
+use chrono::Local;
 use clap::ArgMatches;
 use crossbeam_queue::ArrayQueue;
 use crossbeam_utils::thread;
-use indicatif::MultiProgress;
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use signal_hook::SIGINT;
 use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::env;
+use std::fs::File;
+use std::io::BufRead;
+use std::iter;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const CARTEL_OPTS_ENV: &str = "CARTEL_OPTS";
+
+/// The namespace modules are deployed in with `--simulate`.
+const SIMULATION_NAMESPACE: &str = "simulation";
 
 pub struct DeployOptions {
     pub force_deploy: bool,
@@ -28,6 +48,20 @@ pub struct DeployOptions {
     pub active_envs: Vec<String>,
     pub threads: u8,
     pub wait: bool,
+    pub wait_all: bool,
+    pub force_order: Option<Vec<String>>,
+    pub selector: Option<String>,
+    pub save_plan: Option<String>,
+    pub report: Option<String>,
+    pub max_errors: Option<usize>,
+    pub healthcheck_total_timeout_secs: Option<u64>,
+    pub timing: bool,
+    pub lock: bool,
+    /// Overrides the `log_level` of every deployed service and task.
+    pub module_log_level: Option<String>,
+    /// The namespace the modules are deployed in, if any.
+    pub namespace: Option<String>,
+    pub strategy: DeployStrategy,
 }
 
 impl DeployOptions {
@@ -36,7 +70,10 @@ impl DeployOptions {
         let skip_readiness_checks = opts.is_present("skip_readiness_checks");
         let skip_checks = opts.is_present("skip_checks");
         let wait = opts.is_present("wait");
+        let wait_all = opts.is_present("wait_all");
         let serial = opts.is_present("serial");
+        let timing = opts.is_present("timing");
+        let lock = opts.is_present("lock");
 
         let active_envs = if let Some(it) = opts.values_of("env") {
             it.map(String::from).collect()
@@ -53,6 +90,32 @@ impl DeployOptions {
                 .unwrap_or(4)
         };
 
+        let force_order = opts
+            .values_of("deploy_order")
+            .map(|it| it.map(String::from).collect());
+
+        let selector = opts.value_of("selector").map(String::from);
+        let save_plan = opts.value_of("save_plan").map(String::from);
+        let report = opts.value_of("report").map(String::from);
+        let module_log_level =
+            opts.value_of("module_log_level").map(String::from);
+        let namespace = if opts.is_present("simulate") {
+            Some(String::from(SIMULATION_NAMESPACE))
+        } else {
+            opts.value_of("namespace").map(String::from)
+        };
+        let max_errors = opts
+            .value_of("max_errors")
+            .and_then(|max| max.parse::<usize>().ok());
+        let healthcheck_total_timeout_secs = opts
+            .value_of("healthcheck_timeout")
+            .and_then(|secs| secs.parse::<u64>().ok());
+
+        let strategy = opts
+            .value_of("strategy")
+            .and_then(|strategy| strategy.parse().ok())
+            .unwrap_or_default();
+
         let only_selected = opts.is_present("only_selected");
         Self {
             force_deploy,
@@ -62,43 +125,287 @@ impl DeployOptions {
             active_envs,
             threads,
             wait,
+            wait_all,
+            force_order,
+            selector,
+            save_plan,
+            report,
+            max_errors,
+            healthcheck_total_timeout_secs,
+            timing,
+            lock,
+            module_log_level,
+            namespace,
+            strategy,
         }
     }
 }
 
+/// Returns the options of the `CARTEL_OPTS` environment variable, to be added
+/// to the arguments of `cartel deploy` so that clap validates them together
+/// (eg. `--deploy-order` conflicting with the modules given).
+///
+/// `CARTEL_OPTS` is split on whitespace and parsed the same way as the
+/// arguments to `cartel deploy` (eg. `CARTEL_OPTS="--force --no-checks"`).
+/// The options which are also given on the command line are left out, so
+/// that the command line takes precedence.
+pub fn cartel_opts_args(cli_opts: &ArgMatches) -> Result<Vec<String>> {
+    match env::var(CARTEL_OPTS_ENV) {
+        Ok(env_args) => default_args(&env_args, cli_opts),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+fn default_args(env_args: &str, cli_opts: &ArgMatches) -> Result<Vec<String>> {
+    let parse = |args: &[&str]| {
+        let args = iter::once("deploy").chain(args.iter().copied());
+        deploy_subcommand()
+            .get_matches_from_safe(args)
+            .with_context(|| format!("Failed to parse {}", CARTEL_OPTS_ENV))
+    };
+    let env_args: Vec<_> = env_args.split_whitespace().collect();
+    parse(&env_args)?;
+
+    // Each option starts a group, along with the values following it
+    let mut groups: Vec<Vec<&str>> = vec![];
+    for arg in env_args {
+        match groups.last_mut() {
+            Some(group) if !arg.starts_with('-') => group.push(arg),
+            _ => groups.push(vec![arg]),
+        }
+    }
+
+    let mut args = vec![];
+    for group in groups {
+        let matches = parse(&group)?;
+        if !matches.args.keys().any(|name| is_given(cli_opts, name)) {
+            args.extend(group.into_iter().map(String::from));
+        }
+    }
+    Ok(args)
+}
+
+/// Whether an option, or one which is exclusive with it, is given.
+fn is_given(opts: &ArgMatches, name: &str) -> bool {
+    let exclusive: &[&str] = match name {
+        "threads" | "serial" => &["threads", "serial"],
+        "namespace" | "simulate" => &["namespace", "simulate"],
+        _ => &[],
+    };
+    opts.is_present(name) || exclusive.iter().any(|name| opts.is_present(name))
+}
+
+/// Reads the names of modules to deploy, one per line (eg. from stdin with
+/// `--modules-from-stdin`). Surrounding whitespace and empty lines are
+/// ignored.
+pub fn read_module_names(reader: impl BufRead) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    for line in reader.lines() {
+        let line = line.context("Failed to read the module names")?;
+        let name = line.trim();
+        if !name.is_empty() {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
 pub fn deploy_cmd(
     modules_to_deploy: Vec<&str>,
     cfg: &ClientConfig,
     deploy_opts: &DeployOptions,
 ) -> Result<()> {
+    // Released once the deployment completes, whether it succeeded or not
+    let _lock = if deploy_opts.lock {
+        Some(DeployLockGuard::acquire(cfg)?)
+    } else {
+        None
+    };
+    let recorder = Arc::new(DeployRecorder::default());
+    let mut timer = StepTimer::new();
+    let result = deploy_modules(
+        modules_to_deploy,
+        cfg,
+        deploy_opts,
+        &recorder,
+        &mut timer,
+    );
+    let result = recorder.summarize(result);
+
+    if deploy_opts.timing || cfg.verbose > 0 {
+        timer.print_summary();
+    }
+
+    // The report is written whether or not the deployment succeeded
+    if let Some(path) = &deploy_opts.report {
+        write_report(&recorder.report(&result), path)?;
+    }
+    result
+}
+
+/// Holds the deploy lock of the daemon, releasing it when dropped.
+struct DeployLockGuard<'a> {
+    token: String,
+    cfg: &'a ClientConfig,
+}
+
+impl<'a> DeployLockGuard<'a> {
+    /// Acquires the deploy lock, failing if another client holds it.
+    fn acquire(cfg: &'a ClientConfig) -> Result<Self> {
+        let user = env::var("USER")
+            .or_else(|_| env::var("USERNAME"))
+            .unwrap_or_else(|_| String::from("unknown"));
+        let response = cfg.client().acquire_lock(&user)?;
+        match response.token {
+            Some(token) => Ok(Self { token, cfg }),
+            None => {
+                let holder = response.holder;
+                let now = u64::try_from(Local::now().timestamp()).unwrap();
+                let held_for = Duration::from_secs(
+                    now.saturating_sub(holder.acquired_at),
+                );
+                bail!(
+                    "Another deployment is in progress, the daemon was \
+                    locked by {} (pid {}) {}",
+                    holder.user,
+                    holder.pid,
+                    timeago::Formatter::new().convert(held_for)
+                )
+            }
+        }
+    }
+}
+
+impl Drop for DeployLockGuard<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.cfg.client().release_lock(&self.token) {
+            twarn!(format!("Failed to release the deploy lock: {}", e));
+        }
+    }
+}
+
+fn deploy_modules(
+    modules_to_deploy: Vec<&str>,
+    cfg: &ClientConfig,
+    deploy_opts: &DeployOptions,
+    recorder: &Arc<DeployRecorder>,
+    timer: &mut StepTimer,
+) -> Result<()> {
+    // Interrupted by Ctrl-C, so that checks stop retrying and the deploy lock
+    // is still released.
+    let (_flag, cancel) = make_pair();
+    cancel.interrupt_on(SIGINT)?;
+
+    timer.start(1, "config read");
     tprintstep!("Looking for module definitions...", 1, 6, LOOKING_GLASS);
     let mut module_defs = read_module_definitions(cfg)?;
     let checks_map = remove_checks(&mut module_defs);
-    let module_names = module_names_set(&module_defs);
+    validate_modules_selected(&module_defs, &modules_to_deploy)?;
+
+    let modules_to_deploy = match &deploy_opts.selector {
+        Some(expr) => select_modules(&module_defs, expr, &modules_to_deploy)?,
+        None => modules_to_deploy,
+    };
 
-    validate_modules_selected(&module_names, &modules_to_deploy)?;
+    let deployed: Vec<_> = if let Some(force_order) = &deploy_opts.force_order
+    {
+        let order: Vec<&str> = force_order.iter().map(String::as_str).collect();
+        validate_modules_selected(&module_defs, &order)?;
+        twarn!(
+            "Dependency order is overridden by --deploy-order, modules will \
+            be deployed one at a time in the given order"
+        );
+        tprintskipped!("Resolving dependencies...", 2, 6, LINK);
 
-    let deployed: Vec<_> = if !deploy_opts.only_selected {
+        let selected: Vec<_> = order
+            .iter()
+            .filter_map(|name| module_defs.iter().find(|m| m.name == *name))
+            .collect();
+        validate_group_members(&selected)?;
+
+        if deploy_opts.skip_checks {
+            tprintskipped!("Running checks...", 3, 6, TEXTBOOK);
+        } else {
+            timer.start(3, "checks");
+            tprintstep!("Running checks...", 3, 6, TEXTBOOK);
+            Deployer::run_checks(
+                checks_map,
+                &selected,
+                &cancel,
+                cfg,
+                deploy_opts,
+                recorder,
+            )?;
+        }
+        tprintskipped!("Obtaining plan...", 4, 6, SPIRAL_NOTEBOOK);
+        timer.start(5, "deploy");
+        tprintstep!("Deploying...", 5, 6, VAN);
+        deploy_in_order(
+            &selected,
+            &module_defs,
+            &cancel,
+            cfg,
+            deploy_opts,
+            recorder,
+        )?;
+        selected.iter().map(|m| m.name.clone()).collect()
+    } else if !deploy_opts.only_selected {
+        timer.start(2, "dependency resolution");
         tprintstep!("Resolving dependencies...", 2, 6, LINK);
         let graph = DependencyGraph::from(&module_defs, &modules_to_deploy);
-        let dependencies = resolve_dependencies(&graph)?;
+        let mut dependencies = resolve_dependencies(&graph)?;
+        if let Some(path) = &deploy_opts.save_plan {
+            save_plan(&graph, path)?;
+        }
+        if cfg.verbose > 0 {
+            for node in &dependencies.all {
+                tiprint!(
+                    4,
+                    "{} {}",
+                    cdim!(format!("[depth={}]", node.depth())),
+                    node.key
+                );
+            }
+        }
 
         if deploy_opts.skip_checks {
             tprintskipped!("Running checks...", 3, 6, TEXTBOOK);
         } else {
+            timer.start(3, "checks");
             tprintstep!("Running checks...", 3, 6, TEXTBOOK);
-            Deployer::run_checks(checks_map, &dependencies.all)?;
+            Deployer::run_checks(
+                checks_map,
+                &dependencies.all,
+                &cancel,
+                cfg,
+                deploy_opts,
+                recorder,
+            )?;
         }
 
+        timer.start(4, "plan");
         tprintstep!("Obtaining plan...", 4, 6, SPIRAL_NOTEBOOK);
         let deployment_plan =
             Deployer::obtain_plan(&dependencies.all, cfg, deploy_opts)?;
+        if deploy_opts.strategy == DeployStrategy::Lazy {
+            let included = LazyDeployStrategy::new(
+                &graph,
+                &deployment_plan,
+                deploy_opts.force_deploy,
+            )
+            .resolve(&modules_to_deploy);
+            dependencies.retain(|name| included.contains(name));
+        }
+        timer.start(5, "deploy");
         tprintstep!("Deploying...", 5, 6, VAN);
         deploy_with_dependencies(
             &dependencies.groupped,
             deployment_plan,
+            &module_defs,
+            &cancel,
             cfg,
             deploy_opts,
+            recorder,
         )?;
         dependencies.all.iter().map(|d| d.key.clone()).collect()
     } else {
@@ -110,6 +417,7 @@ pub fn deploy_cmd(
             .iter()
             .filter(|m| modules_to_deploy_set.contains(m.name.as_str()))
             .collect();
+        validate_group_members(&selected)?;
 
         let modules_to_deploy: Vec<ModuleToDeploy> =
             selected.iter().map(|m| ModuleToDeploy::from(*m)).collect();
@@ -117,12 +425,28 @@ pub fn deploy_cmd(
         if deploy_opts.skip_checks {
             tprintskipped!("Running checks...", 3, 6, TEXTBOOK);
         } else {
+            timer.start(3, "checks");
             tprintstep!("Running checks...", 3, 6, TEXTBOOK);
-            Deployer::run_checks(checks_map, &selected)?;
+            Deployer::run_checks(
+                checks_map,
+                &selected,
+                &cancel,
+                cfg,
+                deploy_opts,
+                recorder,
+            )?;
         }
         tprintskipped!("Obtaining plan...", 4, 6, SPIRAL_NOTEBOOK);
+        timer.start(5, "deploy");
         tprintstep!("Deploying...", 5, 6, VAN);
-        deploy_without_dependencies(&modules_to_deploy, cfg, deploy_opts)?;
+        deploy_without_dependencies(
+            &modules_to_deploy,
+            &module_defs,
+            &cancel,
+            cfg,
+            deploy_opts,
+            recorder,
+        )?;
         selected.iter().map(|m| m.name.clone()).collect()
     };
 
@@ -132,20 +456,123 @@ pub fn deploy_cmd(
     Ok(())
 }
 
+/// Measures how long each step of a deployment takes (see
+/// `deploy --timing`).
+struct StepTimer {
+    started_at: Instant,
+    current: Option<(u8, &'static str, Instant)>,
+    steps: Vec<(u8, &'static str, Duration)>,
+}
+
+impl StepTimer {
+    fn new() -> StepTimer {
+        StepTimer {
+            started_at: Instant::now(),
+            current: None,
+            steps: vec![],
+        }
+    }
+
+    /// Starts timing the given step, which finishes the current one.
+    fn start(&mut self, step: u8, name: &'static str) {
+        self.finish();
+        self.current = Some((step, name, Instant::now()));
+    }
+
+    fn finish(&mut self) {
+        if let Some((step, name, started_at)) = self.current.take() {
+            self.steps.push((step, name, started_at.elapsed()));
+        }
+    }
+
+    /// Prints the duration of each step which ran, along with the total.
+    fn print_summary(&mut self) {
+        self.finish();
+        tprint!("{}", cbold!("Timings:"));
+        for (step, name, duration) in &self.steps {
+            let step = format!("Step {} ({})", step, name);
+            tiprint!(4, "{:<30} {}ms", step, duration.as_millis());
+        }
+        let total = self.started_at.elapsed().as_millis();
+        tiprint!(4, "{:<30} {}ms", "Total", total);
+    }
+}
+
+/// Selects the modules matching the selector expression.
+///
+/// If any modules were explicitly given then only those are considered,
+/// otherwise all deployable modules are.
+fn select_modules<'a>(
+    module_defs: &'a [ModuleDefinition],
+    expr: &str,
+    given: &[&str],
+) -> Result<Vec<&'a str>> {
+    let selector = Selector::parse(expr)?;
+    let selected: Vec<_> = module_defs
+        .iter()
+        .filter(|m| m.kind != ModuleKind::Shell)
+        .filter(|m| given.is_empty() || given.contains(&m.name.as_str()))
+        .filter(|m| selector.matches(m))
+        .map(|m| m.name.as_str())
+        .collect();
+
+    if selected.is_empty() {
+        bail!("No modules match the selector '{}'", expr);
+    }
+    Ok(selected)
+}
+
+/// Writes the dependency graph of a deployment to a file as JSON.
+fn save_plan(
+    graph: &DependencyGraph<ModuleDefinition, ModuleMarker>,
+    path: &str,
+) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create plan file {}", path))?;
+    serde_json::to_writer_pretty(file, &graph.to_json())
+        .with_context(|| format!("Failed to write plan file {}", path))?;
+    tiprint!(4, "{}", cdim!(format!("Plan saved to {}", path)));
+    Ok(())
+}
+
 struct DeploymentGraph<'a> {
     groupped: Vec<Vec<ModuleToDeploy<'a>>>,
     all: Vec<&'a DependencyNode<&'a ModuleDefinition, ModuleMarker>>,
 }
 
+impl DeploymentGraph<'_> {
+    /// Keeps only the modules whose name matches the predicate.
+    fn retain(&mut self, keep: impl Fn(&str) -> bool) {
+        for grp in &mut self.groupped {
+            grp.retain(|m| keep(&m.definition.name));
+        }
+        self.groupped.retain(|grp| !grp.is_empty());
+        self.all.retain(|node| keep(&node.key));
+    }
+}
+
 fn resolve_dependencies<'a>(
     graph: &'a DependencyGraph<ModuleDefinition, ModuleMarker>,
 ) -> Result<DeploymentGraph<'a>> {
     let sort_result = graph.group_sort()?;
-    let groupped: Vec<Vec<_>> = sort_result
+    let (mut groupped, oneshots): (Vec<Vec<_>>, Vec<Vec<_>>) = sort_result
         .groups
         .iter()
-        .map(|grp| grp.iter().map(|m| ModuleToDeploy::from(*m)).collect())
-        .collect();
+        .map(|grp| {
+            let mut grp: Vec<_> =
+                grp.iter().map(|m| ModuleToDeploy::from(*m)).collect();
+            // Modules within a group do not depend on each other, so they are
+            // ordered by their priority instead (the sort is stable).
+            grp.sort_by_key(|m| m.definition.priority());
+            grp.into_iter().partition(|m| !m.definition.is_oneshot())
+        })
+        .unzip();
+
+    // One-shots run after all other modules have been deployed, while still
+    // keeping their dependency order amongst themselves.
+    groupped.extend(oneshots);
+    groupped.retain(|grp| !grp.is_empty());
+
     Ok(DeploymentGraph {
         groupped,
         all: sort_result.flat,
@@ -155,10 +582,16 @@ fn resolve_dependencies<'a>(
 fn deploy(
     modules: &[ModuleToDeploy],
     deployment_plan: Option<Arc<ModuleDeploymentPlan>>,
+    module_defs: &[ModuleDefinition],
+    cancel: &Control,
     cfg: &ClientConfig,
     deploy_opts: &DeployOptions,
+    recorder: &Arc<DeployRecorder>,
 ) -> Result<()> {
     let multiprogress = Arc::new(MultiProgress::new());
+    if cfg.no_spinner {
+        multiprogress.set_draw_target(ProgressDrawTarget::hidden());
+    }
     let sync_point = Arc::new(AtomicBool::new(false));
 
     // Maintain a queue of modules that need to be deployed. The queue
@@ -187,6 +620,11 @@ fn deploy(
                     multiprogress.clone(),
                     queue.clone(),
                     deployment_plan.clone(),
+                    // The deployers cancel each other on failure through the
+                    // control, which is also interrupted by Ctrl-C.
+                    cancel.clone(),
+                    Arc::clone(recorder),
+                    module_defs,
                 );
                 deployer.do_work(modules, cfg, deploy_opts)?;
                 Ok(())
@@ -207,21 +645,34 @@ fn deploy(
             }
         });
 
-        for worker_thread in worker_threads {
-            worker_thread.join().unwrap()?;
-        }
+        let mut errors: Vec<_> = worker_threads
+            .into_iter()
+            .filter_map(|worker_thread| worker_thread.join().unwrap().err())
+            .collect();
         // Once all the deployer threads have finished we can set the
         // synchronization point to true, so that the above loop can finish.
         sync_point.clone().store(true, Ordering::SeqCst);
         progress_sync
             .join()
             .expect("Failed to join progress sync thread");
-        Ok(())
+
+        // Report the failure which caused the others to be cancelled, rather
+        // than one of the cancellations (the sort is stable).
+        errors.sort_by_key(|e| e.is::<Cancelled>());
+        match errors.into_iter().next() {
+            Some(e) => Err(Box::new(e)),
+            None => Ok(()),
+        }
     });
 
     if let Err(e) = result.unwrap() {
         return Err(*e);
     }
+    // The deployers stop picking up modules once interrupted, even if none of
+    // them was waiting at the time.
+    if cancel.is_interrupted() {
+        return Err(Cancelled.into());
+    }
 
     Ok(())
 }
@@ -229,21 +680,171 @@ fn deploy(
 fn deploy_with_dependencies(
     groups: &[Vec<ModuleToDeploy>],
     deployment_plan: ModuleDeploymentPlan,
+    module_defs: &[ModuleDefinition],
+    cancel: &Control,
     cfg: &ClientConfig,
     deploy_opts: &DeployOptions,
+    recorder: &Arc<DeployRecorder>,
 ) -> Result<()> {
     let deployment_plan = Arc::new(deployment_plan);
     for group in groups {
-        deploy(group, Some(Arc::clone(&deployment_plan)), cfg, deploy_opts)?;
+        let plan = Some(Arc::clone(&deployment_plan));
+        deploy(group, plan, module_defs, cancel, cfg, deploy_opts, recorder)?;
     }
     Ok(())
 }
 
 fn deploy_without_dependencies(
     sorted: &[ModuleToDeploy],
+    module_defs: &[ModuleDefinition],
+    cancel: &Control,
+    cfg: &ClientConfig,
+    deploy_opts: &DeployOptions,
+    recorder: &Arc<DeployRecorder>,
+) -> Result<()> {
+    deploy(sorted, None, module_defs, cancel, cfg, deploy_opts, recorder)?;
+    Ok(())
+}
+
+/// Deploys the given modules one by one in the exact order given, without
+/// taking their dependencies into account.
+fn deploy_in_order(
+    ordered: &[&ModuleDefinition],
+    module_defs: &[ModuleDefinition],
+    cancel: &Control,
     cfg: &ClientConfig,
     deploy_opts: &DeployOptions,
+    recorder: &Arc<DeployRecorder>,
 ) -> Result<()> {
-    deploy(sorted, None, cfg, deploy_opts)?;
+    for module in ordered {
+        let modules = [ModuleToDeploy::from(*module)];
+        deploy(
+            &modules,
+            None,
+            module_defs,
+            cancel,
+            cfg,
+            deploy_opts,
+            recorder,
+        )?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Parses deploy options the same way as `cartel deploy <args>`.
+    fn options(args: &[&str]) -> DeployOptions {
+        let args = iter::once("deploy").chain(args.iter().copied());
+        DeployOptions::from(&deploy_subcommand().get_matches_from(args))
+    }
+
+    /// Parses deploy options the same way as `cartel deploy <cli>` with
+    /// `CARTEL_OPTS` set to `env`.
+    fn combined(env: &str, cli: &[&str]) -> Result<DeployOptions> {
+        let cli: Vec<_> =
+            iter::once("deploy").chain(cli.iter().copied()).collect();
+        let cli_opts = deploy_subcommand().get_matches_from(&cli);
+        let args = cli
+            .into_iter()
+            .map(String::from)
+            .chain(default_args(env, &cli_opts)?);
+        let matches = deploy_subcommand().get_matches_from_safe(args)?;
+        Ok(DeployOptions::from(&matches))
+    }
+
+    #[test]
+    fn test_read_module_names() {
+        let input = "backend\n  frontend  \n\n\tdatabase\n";
+        let names = read_module_names(input.as_bytes()).unwrap();
+        assert_eq!(names, vec!["backend", "frontend", "database"]);
+    }
+
+    #[test]
+    fn test_cartel_opts_flags() {
+        let flags = [
+            "--force",
+            "--no-checks",
+            "--only-selected",
+            "--no-readiness",
+            "--timing",
+            "--lock",
+        ];
+        for &(in_env, in_cli) in
+            &[(false, false), (false, true), (true, false), (true, true)]
+        {
+            let env = if in_env { flags.join(" ") } else { String::new() };
+            let mut cli = vec!["module"];
+            if in_cli {
+                cli.extend(&flags);
+            }
+            let opts = combined(&env, &cli).unwrap();
+            let expected = in_env || in_cli;
+            assert_eq!(opts.force_deploy, expected);
+            assert_eq!(opts.skip_checks, expected);
+            assert_eq!(opts.only_selected, expected);
+            assert_eq!(opts.skip_readiness_checks, expected);
+            assert_eq!(opts.timing, expected);
+            assert_eq!(opts.lock, expected);
+        }
+    }
+
+    #[test]
+    fn test_cartel_opts_values() {
+        let env = "--selector=kind=Service --report=env.json --max-errors=1 \
+            --healthcheck-timeout=10 --module-log-level=info --namespace=env";
+
+        let opts = combined(env, &["module"]).unwrap();
+        assert_eq!(opts.report.as_deref(), Some("env.json"));
+        assert_eq!(opts.max_errors, Some(1));
+        assert_eq!(opts.module_log_level.as_deref(), Some("info"));
+        assert_eq!(opts.namespace.as_deref(), Some("env"));
+        assert!(opts.selector.is_some());
+
+        let cli = [
+            "--report=cli.json",
+            "--max-errors=2",
+            "--healthcheck-timeout=20",
+            "--module-log-level=debug",
+            "--namespace=cli",
+            "module",
+        ];
+        let opts = combined(env, &cli).unwrap();
+        let expected = options(&cli);
+        assert_eq!(opts.report, expected.report);
+        assert_eq!(opts.max_errors, expected.max_errors);
+        assert_eq!(
+            opts.healthcheck_total_timeout_secs,
+            expected.healthcheck_total_timeout_secs
+        );
+        assert_eq!(opts.module_log_level, expected.module_log_level);
+        assert_eq!(opts.namespace, expected.namespace);
+        // Not given on the command line
+        assert!(opts.selector.is_some());
+    }
+
+    #[test]
+    fn test_cartel_opts_replaced_values() {
+        let env = "--env=base1 --env=base2 --threads=8";
+
+        let opts = combined(env, &["module"]).unwrap();
+        assert_eq!(opts.active_envs, vec!["base1", "base2"]);
+
+        let cli = ["--env=cli", "--serial", "--strategy=lazy", "module"];
+        let opts = combined(env, &cli).unwrap();
+        assert_eq!(opts.active_envs, vec!["cli"]);
+        assert_eq!(opts.threads, options(&cli).threads);
+        assert_eq!(opts.strategy, DeployStrategy::Lazy);
+    }
+
+    #[test]
+    fn test_cartel_opts_conflicts() {
+        // Conflicts with the modules given on the command line
+        assert!(combined("--deploy-order=a,b", &["module"]).is_err());
+        assert!(combined("--wait", &["--no-readiness", "module"]).is_err());
+        assert!(combined("--force", &["--force", "module"]).is_ok());
+        assert!(combined("--unknown", &["module"]).is_err());
+    }
+}
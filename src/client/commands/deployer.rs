@@ -1,32 +1,48 @@
+use crate::client::check_executor::{execute_checks, Environment};
 use crate::client::cli::ClientConfig;
+use crate::client::commands::report::{
+    DeployRecorder, ModuleOutcome, ModuleReport, ModuleReportStatus,
+};
 use crate::client::commands::DeployOptions;
 use crate::client::emoji::{HOUR_GLASS, SUCCESS, YELLOW_NOTEBOOK};
 use crate::client::module::{
-    CheckDefinition, GroupDefinition, InnerDefinition, ModuleDefinition,
-    ModuleMarker, ServiceOrTaskDefinition, SuggestedFixDefinition,
+    module_by_name, CheckDefinition, GroupDefinition, InnerDefinition,
+    ModuleDefinition, ModuleKind, ModuleMarker, ServiceOrTaskDefinition,
+    SuggestedFixDefinition, WaitForDefinition,
 };
 use crate::client::process::{apply_suggested_fix, run_check};
 use crate::client::progress::{
-    SpinnerOptions, WaitResult, WaitSpin, WaitUntil,
+    Cancelled, SpinnerOptions, WaitResult, WaitSpin, WaitUntil,
+};
+use crate::client::request::{
+    build_env_arg, CartelClientTrait, PollBackoff,
 };
-use crate::client::request;
-use crate::client::request::get_plan;
 use crate::daemon::api::{
-    ApiGetPlanResponse, ApiPlannedAction, ApiProbeStatus,
+    ApiDeploymentResponse, ApiGetPlanResponse, ApiPlannedAction,
+    ApiProbeStatus,
 };
-use crate::dependency::DependencyNode;
-use anyhow::{anyhow, bail, Result};
+use crate::dependency::{DependencyGraph, DependencyNode};
+use crate::thread_control::Control;
+use anyhow::{bail, Result};
+use console::style;
 use crossbeam_queue::ArrayQueue;
 use indicatif::{MultiProgress, ProgressBar};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use text_io::read;
 
-pub struct Deployer {
+pub struct Deployer<'a> {
     multiprogress: Arc<MultiProgress>,
     queue: Arc<ArrayQueue<usize>>,
     deployment_plan: Option<Arc<ModuleDeploymentPlan>>,
+    // Shared between all deployers of a deployment, and interrupted as soon
+    // as any of them fails so that the others stop waiting.
+    cancel: Control,
+    recorder: Arc<DeployRecorder>,
+    /// Every module definition read for the deployment, including the ones
+    /// which are not deployed (eg. the targets of wait-for modules).
+    module_defs: &'a [ModuleDefinition],
 }
 
 pub struct ModuleDeploymentPlan {
@@ -38,16 +54,22 @@ pub struct ModuleToDeploy<'a> {
     pub marker: Option<ModuleMarker>,
 }
 
-impl Deployer {
+impl<'a> Deployer<'a> {
     pub fn new(
         multiprogress: Arc<MultiProgress>,
         queue: Arc<ArrayQueue<usize>>,
         deployment_plan: Option<Arc<ModuleDeploymentPlan>>,
+        cancel: Control,
+        recorder: Arc<DeployRecorder>,
+        module_defs: &'a [ModuleDefinition],
     ) -> Self {
         Self {
             multiprogress,
             queue,
             deployment_plan,
+            cancel,
+            recorder,
+            module_defs,
         }
     }
 
@@ -57,40 +79,155 @@ impl Deployer {
         cfg: &ClientConfig,
         deploy_opts: &DeployOptions,
     ) -> Result<()> {
-        // Consume modules from the shared queue and deploy them.
-        while !self.queue.is_empty() {
+        // Consume modules from the shared queue and deploy them, until the
+        // queue is empty or another deployer has failed.
+        while !self.queue.is_empty() && !self.cancel.is_interrupted() {
             if let Some(idx) = self.queue.pop() {
                 let module_to_deploy = &modules[idx];
-                self.deploy_module(module_to_deploy, cfg, deploy_opts)?;
+                let name = &module_to_deploy.definition.name;
+                if let Some(failed) = self.recorder.failed_dependency(name) {
+                    self.skip_module(module_to_deploy, &failed, cfg)?;
+                    continue;
+                }
+                let started = Instant::now();
+                let result =
+                    self.deploy_module(module_to_deploy, cfg, deploy_opts);
+                self.record(module_to_deploy, &result, started);
+                if let Err(e) = result {
+                    if self.should_abort(module_to_deploy, &e, deploy_opts) {
+                        self.cancel.interrupt();
+                        return Err(e);
+                    }
+                    self.block_dependents(module_to_deploy);
+                }
             }
         }
         Ok(())
     }
 
+    /// Whether the deployment should be aborted after a module failed.
+    ///
+    /// The deployment continues until more than `--max-errors` modules have
+    /// failed, skipping the modules which depend on the failed ones.
+    fn should_abort(
+        &self,
+        module: &ModuleToDeploy,
+        error: &anyhow::Error,
+        deploy_opts: &DeployOptions,
+    ) -> bool {
+        if error.is::<Cancelled>() {
+            return true;
+        }
+        let name = &module.definition.name;
+        let failures = self.recorder.record_failure(name, error);
+        failures > deploy_opts.max_errors.unwrap_or(0)
+    }
+
+    /// Marks the modules which depend on a failed module, directly or
+    /// through other modules, to be skipped instead of deployed.
+    fn block_dependents(&self, module: &ModuleToDeploy) {
+        let names: Vec<_> = self
+            .module_defs
+            .iter()
+            .filter(|m| {
+                m.kind != ModuleKind::Check && m.kind != ModuleKind::Shell
+            })
+            .map(|m| m.name.as_str())
+            .collect();
+        let graph = DependencyGraph::<_, ModuleMarker>::from(
+            self.module_defs,
+            &names,
+        );
+        let name = &module.definition.name;
+        let dependents = graph.dependents_of(name, true);
+        self.recorder.block_dependents(name, &dependents);
+    }
+
+    /// Skips a module because a module it depends on failed to deploy.
+    fn skip_module(
+        &self,
+        module: &ModuleToDeploy,
+        failed_dependency: &str,
+        cfg: &ClientConfig,
+    ) -> Result<()> {
+        let name = &module.definition.name;
+        let message = format!("Skipping {}", cbold!(name));
+        let spin_opt = SpinnerOptions::new(message).no_spinner(cfg.no_spinner);
+        let pb = self.multiprogress.add(ProgressBar::new(std::u64::MAX));
+        let wu = WaitUntil::new_multi(&spin_opt, pb);
+        wu.spin_until_status(|| {
+            let status = format!("(Skipped, {} failed)", failed_dependency);
+            Ok(WaitResult::from((), cdim!(status).to_string()))
+        })?;
+        self.recorder.record(ModuleReport {
+            name: name.clone(),
+            status: ModuleReportStatus::Skipped,
+            duration_ms: 0,
+            checks_passed: self.recorder.checks_passed(name),
+            healthcheck_duration_ms: None,
+        });
+        Ok(())
+    }
+
+    /// Records the outcome of deploying a module in the deploy report.
+    fn record(
+        &self,
+        module: &ModuleToDeploy,
+        result: &Result<ModuleOutcome>,
+        started: Instant,
+    ) {
+        let (status, healthcheck_duration) = match result {
+            Ok(outcome) => (outcome.status, outcome.healthcheck_duration),
+            Err(e) if e.is::<Cancelled>() => {
+                (ModuleReportStatus::Cancelled, None)
+            }
+            Err(_) => (ModuleReportStatus::Failed, None),
+        };
+        let name = &module.definition.name;
+        self.recorder.record(ModuleReport {
+            name: name.clone(),
+            status,
+            duration_ms: started.elapsed().as_millis() as u64,
+            checks_passed: self.recorder.checks_passed(name),
+            healthcheck_duration_ms: healthcheck_duration
+                .map(|d| d.as_millis() as u64),
+        });
+    }
+
     fn deploy_and_maybe_wait_service(
         &self,
         service: &ServiceOrTaskDefinition,
         marker: Option<ModuleMarker>,
         cfg: &ClientConfig,
         deploy_opts: &DeployOptions,
-    ) -> Result<()> {
-        let monitor_handle = self.deploy_service(service, cfg, deploy_opts)?;
+    ) -> Result<ModuleOutcome> {
+        let response = self.deploy_service(service, cfg, deploy_opts)?;
         let node_marked = marker == Some(ModuleMarker::WaitProbe);
+        let mut outcome = ModuleOutcome::from(if response.deployed {
+            ModuleReportStatus::Deployed
+        } else {
+            ModuleReportStatus::AlreadyDeployed
+        });
 
-        if let Some(handle) = monitor_handle {
+        if let Some(handle) = response.monitor {
             if (node_marked
                 || service.always_await_readiness_probe
-                || deploy_opts.wait)
+                || deploy_opts.wait
+                || deploy_opts.wait_all)
                 && !deploy_opts.skip_readiness_checks
             {
+                let started = Instant::now();
                 self.wait_until_healthy(
-                    service.name.as_str(),
+                    service,
                     handle.as_str(),
+                    deploy_opts.healthcheck_total_timeout_secs,
+                    "--healthcheck-timeout",
                     cfg,
                 )?;
+                outcome.healthcheck_duration = Some(started.elapsed());
             }
         }
-        Ok(())
+        Ok(outcome)
     }
 
     fn deploy_module(
@@ -98,11 +235,14 @@ impl Deployer {
         module: &ModuleToDeploy,
         cfg: &ClientConfig,
         deploy_opts: &DeployOptions,
-    ) -> Result<()> {
+    ) -> Result<ModuleOutcome> {
         match module.definition.inner {
-            InnerDefinition::Task(ref task) => {
-                self.deploy_task(task, deploy_opts, cfg)
-            }
+            InnerDefinition::Task(ref task) => self
+                .deploy_task(task, deploy_opts, cfg)
+                .map(ModuleOutcome::from),
+            InnerDefinition::OneShot(ref oneshot) => self
+                .deploy_oneshot(oneshot, deploy_opts, cfg)
+                .map(ModuleOutcome::from),
             InnerDefinition::Service(ref service) => self
                 .deploy_and_maybe_wait_service(
                     service,
@@ -111,13 +251,59 @@ impl Deployer {
                     deploy_opts,
                 ),
             InnerDefinition::Group(ref group) => {
-                self.deploy_group(group);
-                Ok(())
+                self.deploy_group(group, cfg);
+                Ok(ModuleOutcome::from(ModuleReportStatus::Deployed))
             }
-            InnerDefinition::Check(_) => Ok(()),
-            InnerDefinition::Shell(_) => Ok(()),
-        }?;
-        Ok(())
+            InnerDefinition::WaitFor(ref wait_for) => {
+                self.deploy_wait_for(wait_for, cfg, deploy_opts)
+            }
+            InnerDefinition::Check(_) | InnerDefinition::Shell(_) => {
+                Ok(ModuleOutcome::from(ModuleReportStatus::Skipped))
+            }
+        }
+    }
+
+    /// Waits until the target of a wait-for module is healthy. The module has
+    /// no process of its own, so it is completed once the wait is over.
+    fn deploy_wait_for(
+        &self,
+        wait_for: &WaitForDefinition,
+        cfg: &ClientConfig,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ModuleOutcome> {
+        let mut outcome = ModuleOutcome::from(ModuleReportStatus::Completed);
+        if deploy_opts.skip_readiness_checks {
+            return Ok(outcome);
+        }
+
+        // The target is deployed before this module, by an earlier group
+        let target = match module_by_name(&wait_for.target, self.module_defs) {
+            Some(ModuleDefinition {
+                inner: InnerDefinition::Service(target),
+                ..
+            }) => target,
+            _ => bail!(
+                "The target '{}' of {} is not a service",
+                wait_for.target,
+                wait_for.name
+            ),
+        };
+
+        let response = cfg.client().watch_readiness(target, deploy_opts)?;
+        if let Some(handle) = response.monitor {
+            let started = Instant::now();
+            let timeout_setting =
+                format!("the timeout_secs of {}", wait_for.name);
+            self.wait_until_healthy(
+                target,
+                handle.as_str(),
+                Some(wait_for.timeout_secs),
+                &timeout_setting,
+                cfg,
+            )?;
+            outcome.healthcheck_duration = Some(started.elapsed());
+        }
+        Ok(outcome)
     }
 
     fn deploy_service(
@@ -125,15 +311,15 @@ impl Deployer {
         module: &ServiceOrTaskDefinition,
         cfg: &ClientConfig,
         deploy_opts: &DeployOptions,
-    ) -> Result<Option<String>> {
+    ) -> Result<ApiDeploymentResponse> {
         let message = format!("Deploying {}", cbold!(&module.name));
-        let spin_opt = SpinnerOptions::new(message);
+        let spin_opt =
+            SpinnerOptions::new(message).no_spinner(cfg.no_spinner);
 
         let pb = self.multiprogress.add(ProgressBar::new(std::u64::MAX));
-        let wu = WaitUntil::new_multi(&spin_opt, pb);
+        let wu = WaitUntil::new_multi(&spin_opt, pb.clone());
         let deploy_result = wu.spin_until_status(|| {
-            let result =
-                request::deploy_module(module, deploy_opts, &cfg.daemon_url)?;
+            let result = cfg.client().deploy_module(module, deploy_opts)?;
 
             let deploy_status = if result.deployed {
                 csuccess!("(Deployed)")
@@ -143,93 +329,224 @@ impl Deployer {
             Ok(WaitResult::from(result, deploy_status.to_string()))
         })?;
 
-        let monitor_handle = deploy_result.monitor;
-        Ok(monitor_handle)
+        for warning in &deploy_result.warnings {
+            Self::println(
+                &pb,
+                format!("{} {}", style("Warning:").bold().yellow(), warning),
+                cfg,
+            );
+        }
+
+        Ok(deploy_result)
     }
 
+    /// Waits until the readiness probe of the service succeeds, for at most
+    /// `total_timeout_secs`, which is named by `timeout_setting` when the
+    /// wait times out.
     fn wait_until_healthy(
         &self,
-        module_name: &str,
+        service: &ServiceOrTaskDefinition,
         monitor_handle: &str,
+        total_timeout_secs: Option<u64>,
+        timeout_setting: &str,
         cfg: &ClientConfig,
     ) -> Result<()> {
-        let message = format!("Waiting {} to be healthy", cbold!(module_name));
-        let spin_opt = SpinnerOptions::new(message);
+        let message =
+            format!("Waiting {} to be healthy", cbold!(&service.name));
+        let spin_opt =
+            SpinnerOptions::new(message).no_spinner(cfg.no_spinner);
         let pb = self.multiprogress.add(ProgressBar::new(std::u64::MAX));
         let wu = WaitUntil::new_multi(&spin_opt, pb);
-
-        wu.spin_until_status(|| loop {
-            let status = csuccess!("(Done)").to_string();
-            match request::poll_health(monitor_handle, &cfg.daemon_url)?
-                .probe_status
-            {
-                Some(ApiProbeStatus::Successful) => {
-                    break Ok(WaitResult::from((), status))
-                }
-                Some(ApiProbeStatus::RetriesExceeded) => {
-                    bail!(
-                        "The service did not complete its readiness probe checks in time.\n\
-                        Check the logs for more details."
-                    )
-                }
-                Some(ApiProbeStatus::Error) => {
-                    bail!(
-                        "An error occured while waiting for the service \
-                        readiness probe to complete.\nThis is usually a mistake in \
-                        the probe configuration, ensure the command or \
-                        condition is correct."
-                    )
-                }
-                _ => {
-                    std::thread::sleep(Duration::from_secs(2));
+        // Give the service some time to start up before the first poll
+        let mut delay =
+            Duration::from_secs(service.healthcheck_grace_period_secs);
+        let mut backoff = PollBackoff::new();
+        // Bounds the wait in case the daemon never reports the probe as
+        // finished, independently of the retries of the probe itself.
+        let deadline = total_timeout_secs
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+        wu.spin_with_cancel(
+            || {
+                if let Some(deadline) = deadline {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        bail!(
+                            "Gave up waiting for the service to be healthy \
+                            after {} seconds ({}).",
+                            total_timeout_secs.unwrap_or_default(),
+                            timeout_setting
+                        )
+                    }
+                    delay = delay.min(deadline - now);
                 }
-        }
-    })?;
+                std::thread::sleep(delay);
+                let result =
+                    Self::poll_health_once(monitor_handle, cfg, &mut backoff);
+                delay = backoff.interval();
+                result
+            },
+            &self.cancel,
+        )?;
 
         Ok(())
     }
 
+    /// Polls the readiness of a service once, returning `None` if it is still
+    /// pending.
+    ///
+    /// The status of the probe is given to `backoff`, which determines how
+    /// long to wait until the next poll.
+    pub(crate) fn poll_health_once(
+        monitor_handle: &str,
+        cfg: &ClientConfig,
+        backoff: &mut PollBackoff,
+    ) -> Result<Option<WaitResult<()>>> {
+        let status = csuccess!("(Done)").to_string();
+        let probe_status =
+            cfg.client().poll_health(monitor_handle)?.probe_status;
+        backoff.observe(probe_status);
+        match probe_status {
+            // The probe has not run yet, which is the same as still pending
+            Some(ApiProbeStatus::Starting) | Some(ApiProbeStatus::Pending) => {
+                Ok(None)
+            }
+            Some(ApiProbeStatus::Ready) => {
+                Ok(Some(WaitResult::from((), status)))
+            }
+            Some(ApiProbeStatus::RetriesExceeded) => {
+                bail!(
+                    "The service did not complete its readiness probe checks in time.\n\
+                    Check the logs for more details."
+                )
+            }
+            Some(ApiProbeStatus::Error) => {
+                bail!(
+                    "An error occured while waiting for the service \
+                    readiness probe to complete.\nThis is usually a mistake in \
+                    the probe configuration, ensure the command or \
+                    condition is correct."
+                )
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn deploy_task(
         &self,
         module: &ServiceOrTaskDefinition,
         deploy_opts: &DeployOptions,
         cfg: &ClientConfig,
-    ) -> Result<()> {
+    ) -> Result<ModuleReportStatus> {
         let message = format!("Running task {}", cbold!(&module.name));
-        let spin_opt = SpinnerOptions::new(message);
+        // If the task has a timeout then show how close it is to timing out
+        let spin_opt = match module.timeout {
+            Some(timeout) => {
+                SpinnerOptions::new(message).with_progress(timeout)
+            }
+            None => SpinnerOptions::new(message),
+        }
+        .no_spinner(cfg.no_spinner);
 
         let pb = self.multiprogress.add(ProgressBar::new(std::u64::MAX));
-        let wu = WaitUntil::new_multi(&spin_opt, pb);
+        let wu = WaitUntil::new_multi(&spin_opt, pb.clone());
         let force = deploy_opts.force_deploy;
 
         // If none of this tasks services will be deployed then skip deploying
         // this task also.
         let skipped_by_plan = !self.should_deploy(module.name.as_str(), force);
-        wu.spin_until_status(|| {
+        let output = wu.spin_until_progress(|progress| {
             if skipped_by_plan {
                 return Ok(WaitResult::from(
-                    false,
+                    None,
                     cdim!("(Skipping)").to_string(),
                 ));
             }
-            let result =
-                request::deploy_task(module, deploy_opts, &cfg.daemon_url)?;
+            let result = progress.track_elapsed(|| {
+                cfg.client().deploy_task(module, deploy_opts)
+            })?;
             let status = csuccess!("(Done)").to_string();
-            Ok(WaitResult::from(result.success, status))
+            Ok(WaitResult::from(result.output, status))
         })?;
 
-        Ok(())
+        if let Some(output) = output {
+            Self::println(&pb, output.trim_end(), cfg);
+        }
+
+        if skipped_by_plan {
+            Ok(ModuleReportStatus::Skipped)
+        } else {
+            Ok(ModuleReportStatus::Deployed)
+        }
+    }
+
+    fn deploy_oneshot(
+        &self,
+        module: &ServiceOrTaskDefinition,
+        deploy_opts: &DeployOptions,
+        cfg: &ClientConfig,
+    ) -> Result<ModuleReportStatus> {
+        let message = format!("Running one-shot {}", cbold!(&module.name));
+        let spin_opt =
+            SpinnerOptions::new(message).no_spinner(cfg.no_spinner);
+
+        // Lets the daemon forget the one-shots removed from the definitions
+        let defined_oneshots: Vec<_> = self
+            .module_defs
+            .iter()
+            .filter(|m| m.is_oneshot())
+            .map(|m| m.name.clone())
+            .collect();
+
+        let pb = self.multiprogress.add(ProgressBar::new(std::u64::MAX));
+        let wu = WaitUntil::new_multi(&spin_opt, pb.clone());
+        let (skipped, output) = wu.spin_until_status(|| {
+            let result = cfg.client().deploy_oneshot(
+                module,
+                defined_oneshots.clone(),
+                deploy_opts,
+            )?;
+            // One-shots only ever run once for the lifetime of the daemon
+            let status = if result.skipped {
+                cdim!("(Already ran)").to_string()
+            } else {
+                csuccess!("(Done)").to_string()
+            };
+            Ok(WaitResult::from((result.skipped, result.output), status))
+        })?;
+
+        if let Some(output) = output {
+            Self::println(&pb, output.trim_end(), cfg);
+        }
+
+        if skipped {
+            Ok(ModuleReportStatus::AlreadyDeployed)
+        } else {
+            Ok(ModuleReportStatus::Deployed)
+        }
     }
 
-    fn deploy_group(&self, module: &GroupDefinition) {
+    fn deploy_group(&self, module: &GroupDefinition, cfg: &ClientConfig) {
         let message = format!("Group {}", cbold!(&module.name));
-        let spin_opt = SpinnerOptions::new(message);
+        let spin_opt =
+            SpinnerOptions::new(message).no_spinner(cfg.no_spinner);
 
         let pb = self.multiprogress.add(ProgressBar::new(std::u64::MAX));
         let mut ws = WaitSpin::from(&spin_opt, pb);
         ws.stop_with_status(csuccess!("(Done)").to_string());
     }
 
+    /// Prints a line above the progress bars of the deployment.
+    ///
+    /// The progress bars are hidden when the spinners are disabled, in which
+    /// case the line is printed as is.
+    fn println<S: AsRef<str>>(pb: &ProgressBar, line: S, cfg: &ClientConfig) {
+        if cfg.no_spinner {
+            println!("{}", line.as_ref());
+        } else {
+            pb.println(line);
+        }
+    }
+
     fn should_deploy(&self, module_name: &str, force: bool) -> bool {
         if force {
             true
@@ -243,33 +560,56 @@ impl Deployer {
         }
     }
 
-    pub fn perform_check(check_def: &CheckDefinition) -> Result<()> {
+    /// Runs a check, retrying it up to its `retry_count` if it fails.
+    ///
+    /// The delay between the attempts is cut short, failing with [Cancelled],
+    /// once `cancel` is interrupted.
+    pub fn perform_check(
+        check_def: &CheckDefinition,
+        environment: &HashMap<String, String>,
+        pb: Option<ProgressBar>,
+        cancel: &Control,
+        cfg: &ClientConfig,
+    ) -> Result<()> {
         let message =
             format!("Check {} ({})", cbold!(&check_def.about), check_def.name);
-        let spin_opt = SpinnerOptions::new(message);
-        let wu = WaitUntil::new(&spin_opt);
-
-        let check_result = wu.spin_until_status(|| {
-            let check_result = run_check(check_def)?;
-            let status = if check_result.success() {
-                csuccess!("(OK)")
+        let spin_opt = SpinnerOptions::new(message)
+            .with_failure_message(check_def.help.trim())
+            .no_spinner(cfg.no_spinner);
+        let wu = match pb {
+            Some(pb) => WaitUntil::new_multi(&spin_opt, pb),
+            None => WaitUntil::new(&spin_opt),
+        };
+
+        let check_result = wu.spin_until_progress(|progress| {
+            let attempts = check_def.retry_count + 1;
+            let mut check_result = run_check(check_def, environment)?;
+            for attempt in 2..=attempts {
+                if check_result.success() {
+                    break;
+                }
+                let status = format!("(Attempt {}/{})", attempt, attempts);
+                progress.set_status(&cdim!(status).to_string());
+                let delay = Duration::from_millis(check_def.retry_delay_ms);
+                if !cancel.sleep(delay) {
+                    return Err(Cancelled.into());
+                }
+                check_result = run_check(check_def, environment)?;
+            }
+            if check_result.success() {
+                let status = csuccess!("(OK)").to_string();
+                Ok(WaitResult::from(check_result, status))
             } else {
-                cfail!("(FAIL)")
-            };
-            Ok(WaitResult::from(check_result, status.to_string()))
+                // Shows the help of the check in place of the spinner line
+                Ok(WaitResult::failure(check_result))
+            }
         })?;
 
         if !check_result.success() {
             if let Some(suggested_fix) = &check_def.suggested_fix {
                 Self::ask_to_apply_suggested_fix(check_def, suggested_fix);
             } else {
-                bail!(
-                    "The {} check has failed\n\
-                    {}: {}",
-                    cbold!(&check_def.about),
-                    cbold!("Message"),
-                    check_def.help
-                )
+                bail!("The {} check has failed", cbold!(&check_def.about))
             }
         }
         Ok(())
@@ -280,11 +620,9 @@ impl Deployer {
         suggested_fix: &SuggestedFixDefinition,
     ) {
         tprint!(
-            "{} The {} check has failed\n {}: {}\n",
+            "{} The {} check has failed\n",
             cfail!("Error:"),
-            cbold!(&check_def.about),
-            cbold!("Message"),
-            check_def.help
+            cbold!(&check_def.about)
         );
 
         tprint!(
@@ -318,31 +656,114 @@ impl Deployer {
         }
     }
 
+    /// Runs the checks of the given modules.
+    ///
+    /// Checks are run in the order imposed by their `depends_on`, the checks
+    /// which don't depend on each other being run concurrently (see
+    /// `execute_checks`). A check is skipped if any of the checks it depends on
+    /// did not pass, and is not run at all if it is only required by modules
+    /// excluded by its `only_for_kind`. All checks
+    /// which don't depend on a failed check are still attempted, and the
+    /// failures are reported together at the end.
+    ///
+    /// Whether the checks of each module passed is given to `recorder`. Once
+    /// `cancel` is interrupted no more checks are started and a [Cancelled]
+    /// error is returned.
     pub fn run_checks<T: AsRef<ModuleDefinition>>(
         checks_map: HashMap<String, CheckDefinition>,
         modules: &[T],
+        cancel: &Control,
+        cfg: &ClientConfig,
+        deploy_opts: &DeployOptions,
+        recorder: &DeployRecorder,
     ) -> Result<()> {
-        let mut already_performed = HashSet::new();
+        let mut selected = Vec::new();
+        let mut module_checks = Vec::new();
+        // The distinct environments of the modules requiring each check, for
+        // checks which run in them.
+        let mut environments: HashMap<String, Vec<Environment>> =
+            HashMap::new();
         for m in modules {
-            let checks = match &m.as_ref().inner {
-                InnerDefinition::Group(grp) => grp.checks.as_slice(),
-                InnerDefinition::Service(srvc) => srvc.checks.as_slice(),
-                InnerDefinition::Task(tsk) => tsk.checks.as_slice(),
-                _ => &[],
+            let kind = &m.as_ref().kind;
+            let (checks, environment) = match &m.as_ref().inner {
+                InnerDefinition::Group(grp) => (grp.checks.as_slice(), None),
+                InnerDefinition::Service(srvc)
+                | InnerDefinition::Task(srvc)
+                | InnerDefinition::OneShot(srvc) => {
+                    // The same environment the module is deployed with
+                    let environment = build_env_arg(
+                        srvc,
+                        &deploy_opts.active_envs,
+                        deploy_opts.module_log_level.as_deref(),
+                    );
+                    (srvc.checks.as_slice(), Some(environment))
+                }
+                _ => (&[][..], None),
             };
 
+            let mut applicable = Vec::new();
             for check in checks {
-                let check = checks_map
-                    .get(check)
-                    .ok_or_else(|| anyhow!("Check '{}' not defined", check))?;
-
-                if !already_performed.contains(&check.name) {
-                    Self::perform_check(check)?;
-                    already_performed.insert(check.name.clone());
+                let check_def = match checks_map.get(check) {
+                    Some(check_def) => check_def,
+                    None => bail!("Check '{}' not defined", check),
+                };
+                if !check_def.applies_to(kind) {
+                    continue;
+                }
+                applicable.push(check.as_str());
+                if !selected.contains(&check.as_str()) {
+                    selected.push(check.as_str());
+                }
+                if let Some(environment) = &environment {
+                    if check_def.exec_in_module_env {
+                        let envs =
+                            environments.entry(check.clone()).or_default();
+                        if !envs.contains(environment) {
+                            envs.push(environment.clone());
+                        }
+                    }
                 }
             }
+            if !applicable.is_empty() {
+                module_checks.push((m.as_ref().name.as_str(), applicable));
+            }
         }
-        Ok(())
+
+        let checks: Vec<_> = checks_map.into_iter().map(|(_, c)| c).collect();
+        let graph = DependencyGraph::<_, ()>::from(&checks, &selected);
+        let results = execute_checks(&graph, &environments, cancel, cfg)?;
+        if cancel.is_interrupted() {
+            return Err(Cancelled.into());
+        }
+        for (module_name, checks) in module_checks {
+            let passed = checks
+                .iter()
+                .all(|check| !results.not_passed.contains(*check));
+            recorder.record_checks(module_name, passed);
+        }
+
+        let mut failures = results.failures;
+
+        if failures.len() > 1 {
+            let messages: Vec<_> =
+                failures.iter().map(|e| format!("{:#}", e)).collect();
+            bail!("{}", messages.join("\n\n"));
+        }
+        failures.pop().map_or(Ok(()), Err)
+    }
+
+    pub fn skip_check(
+        check_def: &CheckDefinition,
+        failed_dependency: &str,
+        cfg: &ClientConfig,
+    ) {
+        let message =
+            format!("Check {} ({})", cbold!(&check_def.about), check_def.name);
+        let spin_opt =
+            SpinnerOptions::new(message).no_spinner(cfg.no_spinner);
+        let mut ws = WaitSpin::new(&spin_opt);
+        let status = format!("(Skipped, {} did not pass)", failed_dependency);
+        ws.stop_with_status(cdim!(status).to_string());
     }
 
     fn is_going_to_deploy(
@@ -365,7 +786,7 @@ impl Deployer {
         deploy_opts: &DeployOptions,
     ) -> Result<ModuleDeploymentPlan> {
         let module_defs: Vec<_> = modules.iter().map(|m| m.value).collect();
-        let plan = get_plan(&module_defs, deploy_opts, &cfg.daemon_url)?;
+        let plan = cfg.client().get_plan(&module_defs, deploy_opts)?;
 
         let should_deploy = modules
             .iter()
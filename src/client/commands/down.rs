@@ -1,12 +1,12 @@
 use crate::client::cli::ClientConfig;
 use crate::client::emoji::{HOUR_GLASS, SUCCESS};
-use crate::client::request;
+use crate::client::request::CartelClientTrait;
 use anyhow::Result;
 use console::style;
 
 pub fn down_cmd(cfg: &ClientConfig) -> Result<()> {
     tprintstep!("Stopping all service(s)...", 1, 2, HOUR_GLASS);
-    request::stop_all(&cfg.daemon_url)?;
+    cfg.client().stop_all()?;
     tprintstep!(style("Service(s) stopped").bold().green(), 2, 2, SUCCESS);
     Ok(())
 }
@@ -0,0 +1,131 @@
+use crate::client::cli::ClientConfig;
+use crate::client::definitions::read_module_definitions;
+use crate::client::module::{remove_checks, ModuleKind};
+use crate::client::validation::validate_modules_selected;
+use crate::dependency::DependencyGraph;
+use anyhow::Result;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use tabwriter::TabWriter;
+
+pub const GRAPH_FORMATS: [&str; 2] = ["text", "json"];
+/// The kinds of modules the graph can be filtered by.
+pub const GRAPH_KINDS: [&str; 5] =
+    ["Service", "Task", "OneShot", "Group", "WaitFor"];
+
+/// Prints the dependency graph of the given modules (or of all modules if none
+/// are given).
+///
+/// The `json` format is produced by [DependencyGraph::to_json] and is stable,
+/// so that it can be consumed by external tooling.
+///
+/// With `layers` the modules are instead grouped by their depth in the graph,
+/// modules of the same layer could be deployed in parallel.
+///
+/// The `exclude` modules are removed from the graph, see
+/// [DependencyGraph::without].
+///
+/// With `filter_kind` only the modules of that kind are shown, along with the
+/// dependencies between them through modules of other kinds, see
+/// [DependencyGraph::filter_kind].
+///
+/// With `include_checks` the checks which run before the modules are deployed
+/// are shown as well, each pointing to the modules it runs before. In the
+/// `json` format they are added to the `nodes` (with a `Check` kind) and
+/// listed in a separate `check_edges` array, leaving `edges` unchanged.
+pub fn graph_cmd(
+    modules: Vec<&str>,
+    exclude: Vec<&str>,
+    filter_kind: Option<&str>,
+    format: &str,
+    layers: bool,
+    include_checks: bool,
+    cfg: &ClientConfig,
+) -> Result<()> {
+    let mut module_defs = read_module_definitions(cfg)?;
+    let checks_map = remove_checks(&mut module_defs);
+    module_defs.retain(|m| m.kind != ModuleKind::Shell);
+
+    let selected: Vec<&str> = if modules.is_empty() {
+        module_defs.iter().map(|m| m.name.as_str()).collect()
+    } else {
+        validate_modules_selected(&module_defs, &modules)?;
+        modules
+    };
+
+    validate_modules_selected(&module_defs, &exclude)?;
+
+    let graph = DependencyGraph::from(&module_defs, &selected);
+    let mut graph = graph.without(&exclude)?;
+    if let Some(kind) = filter_kind {
+        graph = graph.filter_kind(&kind);
+    }
+    let sorted = graph.dependency_sort()?;
+
+    if layers {
+        let mut tw = TabWriter::new(io::stdout()).minwidth(8);
+        writeln!(&mut tw, "{}", cbold!("Layer\tModules"))?;
+        for layer in 0..graph.layer_count() {
+            let modules = graph.nodes_at_layer(layer).join(", ");
+            writeln!(&mut tw, "{}\t{}", layer, modules)?;
+        }
+        tw.flush()?;
+        return Ok(());
+    }
+
+    // The modules each check runs before, for the checks of the graph
+    let mut check_edges: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    if include_checks {
+        for node in &sorted {
+            for check in node.value.checks() {
+                let applies = checks_map
+                    .get(check)
+                    .map_or(true, |c| c.applies_to(&node.value.kind));
+                if applies {
+                    check_edges
+                        .entry(check.as_str())
+                        .or_default()
+                        .push(node.key.as_str());
+                }
+            }
+        }
+    }
+
+    if format == "json" {
+        let mut json = graph.to_json();
+        if include_checks {
+            for check in check_edges.keys() {
+                json["nodes"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(json!({ "name": check, "kind": "Check" }));
+            }
+            json["check_edges"] = check_edges
+                .iter()
+                .flat_map(|(check, modules)| {
+                    modules
+                        .iter()
+                        .map(move |to| json!({ "from": check, "to": to }))
+                })
+                .collect();
+        }
+        tprint!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+
+    for (check, modules) in &check_edges {
+        let kind = cdim!("(Check)");
+        tprint!("[{}] {} => {}", check, kind, modules.join(", "));
+    }
+    for node in sorted {
+        let dependencies = graph.dependencies_of(&node.key);
+        let kind = cdim!(format!("({})", node.value.kind));
+        if dependencies.is_empty() {
+            tprint!("{} {}", node.key, kind);
+        } else {
+            tprint!("{} {} -> {}", node.key, kind, dependencies.join(", "));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use crate::client::cli::ClientConfig;
+use crate::client::request::CartelClientTrait;
+use crate::daemon::api::ApiProbeStatus;
+use anyhow::{bail, Result};
+
+/// Runs the liveness probe of a running module once, rather than waiting for
+/// the daemon to poll it, and prints the result.
+///
+/// Fails if the module is not healthy.
+pub fn check_health_cmd(name: &str, cfg: &ClientConfig) -> Result<()> {
+    let client = cfg.client();
+    let name = client.expand_module_name(name)?;
+    let response = client.check_health(&name)?;
+
+    match response.probe_status {
+        ApiProbeStatus::Successful => {
+            tprint!("{} is {}", cbold!(&name), csuccess!("healthy"));
+            Ok(())
+        }
+        ApiProbeStatus::Error => {
+            bail!("The liveness probe of {} failed to run", cbold!(&name))
+        }
+        _ => bail!("{} is {}", cbold!(&name), cfail!("unhealthy")),
+    }
+}
@@ -0,0 +1,48 @@
+use crate::client::cli::ClientConfig;
+use crate::client::definitions::read_module_definitions;
+use crate::client::module::{InnerDefinition, ServiceOrTaskDefinition};
+use crate::client::request::CartelClientTrait;
+use crate::client::validation::validate_modules_selected;
+use anyhow::{bail, Result};
+
+/// Registers services with the daemon without deploying them, so that they
+/// show up as waiting until they are deployed with `cartel deploy`.
+///
+/// All services are imported when no modules are given. Services which are
+/// already known to the daemon are left untouched.
+pub fn import_cmd(
+    modules: Vec<&str>,
+    active_envs: &[String],
+    cfg: &ClientConfig,
+) -> Result<()> {
+    let module_defs = read_module_definitions(cfg)?;
+    validate_modules_selected(&module_defs, &modules)?;
+
+    let mut services: Vec<&ServiceOrTaskDefinition> = vec![];
+    for module in &module_defs {
+        if !modules.is_empty() && !modules.contains(&module.name.as_str()) {
+            continue;
+        }
+        match &module.inner {
+            InnerDefinition::Service(svc) => services.push(svc),
+            _ if modules.is_empty() => {}
+            _ => bail!(
+                "Only services can be imported, '{}' is a {}",
+                module.name,
+                module.kind.to_string().to_lowercase()
+            ),
+        }
+    }
+    if services.is_empty() {
+        bail!("There are no services to import");
+    }
+
+    let response = cfg.client().import_modules(&services, active_envs)?;
+    for name in &response.imported {
+        tprint!("{} {}", csuccess!("✓"), name);
+    }
+    for name in &response.skipped {
+        tprint!("{} {} {}", cdim!("-"), name, cdim!("(already known)"));
+    }
+    Ok(())
+}
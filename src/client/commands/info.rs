@@ -0,0 +1,93 @@
+use crate::client::cli::ClientConfig;
+use crate::client::request::CartelClientTrait;
+use anyhow::Result;
+use std::time::Duration;
+
+/// The versions of the client and of the daemon it is connected to.
+pub struct CartelVersion {
+    pub client: String,
+    pub daemon: String,
+}
+
+impl CartelVersion {
+    /// Whether the client and the daemon have different major versions. Before
+    /// 1.0 the minor version is compared instead, since it is the one bumped on
+    /// breaking changes.
+    pub fn is_mismatched(&self) -> bool {
+        breaking_version(&self.client) != breaking_version(&self.daemon)
+    }
+}
+
+/// Returns the components of a version which change on breaking changes.
+fn breaking_version(version: &str) -> Vec<&str> {
+    let components: Vec<&str> = version.split('.').collect();
+    match components.as_slice() {
+        ["0", minor, ..] => vec!["0", minor],
+        [major, ..] => vec![major],
+        [] => vec![],
+    }
+}
+
+/// Prints the version, uptime, platform and capabilities of the daemon.
+pub fn info_cmd(cfg: &ClientConfig) -> Result<()> {
+    let info = cfg.client().daemon_info()?;
+    let version = CartelVersion {
+        client: String::from(env!("CARGO_PKG_VERSION")),
+        daemon: info.version.clone(),
+    };
+
+    let time_formatter = timeago::Formatter::new();
+    let started = time_formatter.convert(Duration::from_secs(info.uptime_secs));
+    let features = if info.features.is_empty() {
+        String::from("none")
+    } else {
+        info.features.join(", ")
+    };
+
+    tprint!("{}", cbold!("Daemon"));
+    tiprint!(
+        4,
+        "Version:  {} (client {})",
+        version.daemon,
+        version.client
+    );
+    tiprint!(4, "PID:      {}", info.pid);
+    tiprint!(4, "Started:  {}", started);
+    tiprint!(4, "Platform: {}/{}", info.os, info.arch);
+    tiprint!(4, "Features: {}", features);
+    tiprint!(
+        4,
+        "Modules:  {} ({} running)",
+        info.modules_total,
+        info.modules_running
+    );
+
+    if version.is_mismatched() {
+        twarn!(format!(
+            "The client ({}) and daemon ({}) versions differ, restart the \
+            daemon with `cartel daemon restart`",
+            version.client, version.daemon
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(client: &str, daemon: &str) -> CartelVersion {
+        CartelVersion {
+            client: client.to_string(),
+            daemon: daemon.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        assert!(!version("0.11.1", "0.11.0").is_mismatched());
+        assert!(version("0.11.1", "0.10.4").is_mismatched());
+        assert!(!version("1.2.0", "1.4.1").is_mismatched());
+        assert!(version("2.0.0", "1.4.1").is_mismatched());
+    }
+}
@@ -0,0 +1,610 @@
+use crate::client::cli::ClientConfig;
+use crate::client::definitions::{open_module_file, open_override_file};
+use anyhow::{Context, Result};
+use phf::{phf_map, phf_set};
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ops::AddAssign;
+use std::path::Path;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+static SERVICE_OR_TASK_KEYS: phf::Set<&'static str> = phf_set! {
+    "kind",
+    "name",
+    "labels",
+    "documentation",
+    "command",
+    "shell",
+    "termination_signal",
+    "environment",
+    "environment_sets",
+    "log_file_path",
+    "dependencies",
+    "ordered_dependencies",
+    "after",
+    "post_up",
+    "post",
+    "working_dir",
+    "checks",
+    "always_await_readiness_probe",
+    "readiness_probe",
+    "readiness_command",
+    "health_check_endpoint",
+    "liveness_probe",
+    "timeout",
+    "interactive_shell",
+    "capture_output",
+    "priority",
+    "healthcheck_grace_period_secs",
+    "max_memory_mb",
+    "cpu_quota_percent",
+    "network_namespace",
+    "shutdown_command",
+    "shutdown_timeout_secs",
+    "inherit_env",
+    "ports",
+    "log_level",
+    "umask",
+    "stdout_filter",
+    "version",
+    "socket_activation",
+};
+
+static CHECK_KEYS: phf::Set<&'static str> = phf_set! {
+    "kind",
+    "name",
+    "labels",
+    "documentation",
+    "about",
+    "command",
+    "shell",
+    "suggested_fix",
+    "working_dir",
+    "help",
+    "retry_count",
+    "retry_delay_ms",
+    "depends_on",
+    "exec_in_module_env",
+    "only_for_kind",
+};
+
+static GROUP_KEYS: phf::Set<&'static str> = phf_set! {
+    "kind",
+    "name",
+    "labels",
+    "documentation",
+    "description",
+    "dependencies",
+    "checks",
+    "require_all",
+};
+
+static SHELL_KEYS: phf::Set<&'static str> = phf_set! {
+    "kind",
+    "name",
+    "labels",
+    "documentation",
+    "service",
+    "type",
+    "command",
+    "shell",
+    "environment",
+    "working_dir",
+};
+
+static WAIT_FOR_KEYS: phf::Set<&'static str> = phf_set! {
+    "kind",
+    "name",
+    "labels",
+    "documentation",
+    "target",
+    "timeout_secs",
+};
+
+/// How to migrate away from a deprecated key.
+enum Migration {
+    /// The key was only renamed, so it is auto-fixable by renaming it.
+    Renamed(&'static str),
+    /// The key needs to be migrated by hand, following the instructions.
+    Manual(&'static str),
+}
+
+/// Keys which are no longer supported, along with how to migrate away from
+/// them.
+static DEPRECATED_KEYS: phf::Map<&'static str, Migration> = phf_map! {
+    "healthcheck" => Migration::Renamed("readiness_probe"),
+};
+
+/// The number of issues found in module definitions, and how many of them
+/// were fixed.
+#[derive(Default)]
+struct LintSummary {
+    issues: usize,
+    fixed: usize,
+}
+
+impl AddAssign for LintSummary {
+    fn add_assign(&mut self, other: Self) {
+        self.issues += other.issues;
+        self.fixed += other.fixed;
+    }
+}
+
+/// Warns about unknown or deprecated keys in the module definitions, about
+/// string values with trailing whitespace, about keys defined more than once
+/// in an `environment` and about anchors which are never used.
+///
+/// Unknown keys are otherwise silently ignored when parsing the module
+/// definitions, which makes typos and stale configuration hard to spot.
+///
+/// With `fix` the auto-fixable issues (all of the above except unknown keys
+/// and deprecated keys which were not only renamed) are corrected, and the
+/// files are written back from their parsed form. A file with comments or
+/// aliases is not fixed, since they would be lost when writing it back.
+pub fn lint_cmd(fix: bool, cfg: &ClientConfig) -> Result<()> {
+    let (_, path) = open_module_file(&cfg.module_file, &cfg.default_dir)?;
+    let mut summary = lint_file(&path, fix)?;
+
+    if let Some((_, override_path)) = open_override_file(&path, cfg)? {
+        summary += lint_file(&override_path, fix)?;
+    }
+
+    if summary.issues == 0 {
+        tprint!("{}", csuccess!("No issues found"));
+    } else if fix {
+        tprint!(
+            "Found {} issue(s), fixed {}",
+            cbold!(summary.issues),
+            cbold!(summary.fixed)
+        );
+    } else {
+        tprint!("Found {} issue(s)", cbold!(summary.issues));
+    }
+    Ok(())
+}
+
+/// Lints a single module definitions file, fixing the auto-fixable issues
+/// with `fix`.
+fn lint_file(path: &Path, fix: bool) -> Result<LintSummary> {
+    let buffer = fs::read_to_string(path)
+        .with_context(|| "While reading module definition file")?;
+    let unpreserved = unpreserved_content(&buffer)
+        .with_context(|| format!("Failed to parse {:?}", path))?;
+    let fix = match unpreserved {
+        Some(content) if fix => {
+            twarn!(format!(
+                "Not fixing {}, since writing it back would lose its {}",
+                path.display(),
+                content
+            ));
+            false
+        }
+        _ => fix,
+    };
+
+    let (summary, fixed) = lint_str(&buffer, fix)
+        .with_context(|| format!("Failed to parse {:?}", path))?;
+    if let Some(output) = fixed {
+        fs::write(path, output)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(summary)
+}
+
+/// Lints the module definitions, returning them fixed with `fix` if any
+/// issue was fixed.
+fn lint_str(buffer: &str, fix: bool) -> Result<(LintSummary, Option<String>)> {
+    let mut documents = serde_yaml::Deserializer::from_str(buffer)
+        .map(Value::deserialize)
+        .collect::<Result<Vec<_>, _>>()?;
+    let scan = YamlScan::of(buffer)?;
+
+    let mut summary = LintSummary::default();
+    for (idx, document) in documents.iter_mut().enumerate() {
+        let name = describe_module(document, idx);
+        for (key, line) in scan.duplicate_env_keys(idx) {
+            summary += report(
+                fix,
+                format!(
+                    "Removed the duplicate key '{}' (line {}) from the \
+                    environment of module {}",
+                    key, line, name
+                ),
+                format!(
+                    "Key '{}' (line {}) is defined more than once in the \
+                    environment of module {}, only its last value is used",
+                    key, line, name
+                ),
+            );
+        }
+        for line in scan.unused_anchors(idx) {
+            summary += report(
+                fix,
+                format!("Removed the unused anchor in module {}", name),
+                format!(
+                    "Anchor in module {} (near line {}) is never used",
+                    name, line
+                ),
+            );
+        }
+        summary += lint_document(document, &name, fix);
+    }
+
+    if summary.fixed == 0 {
+        return Ok((summary, None));
+    }
+    // Parsing dropped the duplicate keys and unused anchors already
+    let mut output = String::new();
+    for document in &documents {
+        output.push_str(&serde_yaml::to_string(document)?);
+    }
+    Ok((summary, Some(output)))
+}
+
+/// Returns a description of what the module definitions contain that would
+/// be lost by writing them back from their parsed form, if anything.
+pub(crate) fn unpreserved_content(
+    buffer: &str,
+) -> Result<Option<&'static str>> {
+    let comments = buffer.lines().any(has_comment);
+    let aliases = YamlScan::of(buffer)?.has_aliases();
+    Ok(match (comments, aliases) {
+        (true, true) => Some("comments and aliases"),
+        (true, false) => Some("comments"),
+        (false, true) => Some("aliases"),
+        (false, false) => None,
+    })
+}
+
+/// Whether the line has a comment, ie. a `#` at its start or after a space,
+/// outside of quoted strings.
+///
+/// Quotes are only tracked within the line and escaped quotes are not
+/// recognized, so this may find comments which aren't (eg. in multi-line
+/// strings), which only prevents fixing the file.
+fn has_comment(line: &str) -> bool {
+    let mut quote = None;
+    let mut previous = ' ';
+    for c in line.chars() {
+        match (quote, c) {
+            (None, '#') if previous.is_whitespace() => return true,
+            (None, '"') | (None, '\'')
+                if previous.is_whitespace() || "[{,".contains(previous) =>
+            {
+                quote = Some(c)
+            }
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+        previous = c;
+    }
+    false
+}
+
+/// Prints an issue which is fixed with `fix`, as either of the messages.
+fn report(fix: bool, fixed: String, warning: String) -> LintSummary {
+    if fix {
+        tprint!("{} {}", console::style("Fixed:").bold().green(), fixed);
+    } else {
+        tprint!("{} {}", console::style("Warning:").bold().yellow(), warning);
+    }
+    LintSummary {
+        issues: 1,
+        fixed: usize::from(fix),
+    }
+}
+
+fn describe_module(document: &Value, idx: usize) -> String {
+    match document.get("name").and_then(Value::as_str) {
+        Some(name) => format!("'{}'", name),
+        None => format!("with index {}", idx),
+    }
+}
+
+/// Lints a single module definition, fixing the auto-fixable issues with
+/// `fix`.
+fn lint_document(
+    document: &mut Value,
+    module_name: &str,
+    fix: bool,
+) -> LintSummary {
+    let mut summary = LintSummary::default();
+    let known_keys = match document.get("kind").and_then(Value::as_str) {
+        Some("Service") | Some("Task") | Some("OneShot") => {
+            &SERVICE_OR_TASK_KEYS
+        }
+        Some("Check") => &CHECK_KEYS,
+        Some("Group") => &GROUP_KEYS,
+        Some("Shell") => &SHELL_KEYS,
+        Some("WaitFor") => &WAIT_FOR_KEYS,
+        _ => return summary,
+    };
+    let mapping = match document.as_mapping_mut() {
+        Some(mapping) => mapping,
+        None => return summary,
+    };
+
+    let keys: Vec<String> = mapping
+        .keys()
+        .filter_map(Value::as_str)
+        .map(String::from)
+        .collect();
+    for key in &keys {
+        let key_value = Value::String(key.clone());
+        if let Some(migration) = DEPRECATED_KEYS.get(key.as_str()) {
+            summary.issues += 1;
+            let instructions = match migration {
+                Migration::Renamed(new_key) => {
+                    let new_key = Value::String(new_key.to_string());
+                    if fix && !mapping.contains_key(&new_key) {
+                        let value = mapping.remove(&key_value).unwrap();
+                        tprint!(
+                            "{} Renamed key '{}' in module {} to '{}'",
+                            console::style("Fixed:").bold().green(),
+                            key,
+                            module_name,
+                            new_key.as_str().unwrap()
+                        );
+                        mapping.insert(new_key, value);
+                        summary.fixed += 1;
+                        continue;
+                    }
+                    format!("Renamed to `{}`", new_key.as_str().unwrap())
+                }
+                Migration::Manual(instructions) => instructions.to_string(),
+            };
+            tprint!(
+                "{} Key '{}' in module {} is deprecated. {}",
+                console::style("Deprecated:").bold().magenta(),
+                key,
+                module_name,
+                instructions
+            );
+        } else if !known_keys.contains(key.as_str()) {
+            summary.issues += 1;
+            tprint!(
+                "{} Unknown key '{}' in module {}",
+                console::style("Warning:").bold().yellow(),
+                key,
+                module_name
+            );
+        }
+
+        let value = match mapping.get_mut(&key_value) {
+            Some(Value::String(value)) => value,
+            _ => continue,
+        };
+        let trimmed_len =
+            value.trim_end_matches(|c| c == ' ' || c == '\t').len();
+        if trimmed_len == value.len() {
+            continue;
+        }
+        summary.issues += 1;
+        if fix {
+            value.truncate(trimmed_len);
+            summary.fixed += 1;
+            tprint!(
+                "{} Removed trailing whitespace from key '{}' in module {}",
+                console::style("Fixed:").bold().green(),
+                key,
+                module_name
+            );
+        } else {
+            tprint!(
+                "{} Value of key '{}' in module {} has trailing whitespace",
+                console::style("Warning:").bold().yellow(),
+                key,
+                module_name
+            );
+        }
+    }
+    summary
+}
+
+/// What parsing the module definitions into values does not retain: the
+/// anchors and aliases, and the keys of a mapping which are defined more
+/// than once (of which only the last one is kept).
+#[derive(Default)]
+struct YamlScan {
+    /// The index of the current document.
+    document: usize,
+    /// The mappings and sequences the current node is in.
+    stack: Vec<Frame>,
+    /// The document and line each anchor is defined in, by anchor id.
+    anchors: HashMap<usize, (usize, usize)>,
+    aliased: HashSet<usize>,
+    /// The document, key and line of each duplicate key of an environment.
+    duplicate_env_keys: Vec<(usize, String, usize)>,
+}
+
+enum Frame {
+    Mapping {
+        environment: bool,
+        /// The last key, or `None` if the next node is a key.
+        key: Option<String>,
+        keys: HashSet<String>,
+    },
+    Sequence,
+}
+
+impl YamlScan {
+    fn of(buffer: &str) -> Result<YamlScan> {
+        let mut scan = YamlScan::default();
+        Parser::new(buffer.chars()).load(&mut scan, true)?;
+        Ok(scan)
+    }
+
+    fn has_aliases(&self) -> bool {
+        !self.aliased.is_empty()
+    }
+
+    /// Returns the key and line of each duplicate key in the environment of
+    /// the document.
+    fn duplicate_env_keys(&self, document: usize) -> Vec<(&str, usize)> {
+        self.duplicate_env_keys
+            .iter()
+            .filter(|(doc, _, _)| *doc == document)
+            .map(|(_, key, line)| (key.as_str(), *line))
+            .collect()
+    }
+
+    /// Returns the line of each anchor of the document which is never used.
+    fn unused_anchors(&self, document: usize) -> Vec<usize> {
+        let mut lines: Vec<_> = self
+            .anchors
+            .iter()
+            .filter(|(id, (doc, _))| {
+                *doc == document && !self.aliased.contains(id)
+            })
+            .map(|(_, (_, line))| *line)
+            .collect();
+        lines.sort_unstable();
+        lines
+    }
+
+    fn define_anchor(&mut self, anchor_id: usize, mark: Marker) {
+        // Nodes without an anchor have an id of 0
+        if anchor_id > 0 {
+            self.anchors.insert(anchor_id, (self.document, mark.line()));
+        }
+    }
+
+    /// Records a complete node of the current mapping or sequence, which is
+    /// given for scalars.
+    fn complete_node(&mut self, scalar: Option<String>, mark: Marker) {
+        let document = self.document;
+        if let Some(Frame::Mapping {
+            environment,
+            key,
+            keys,
+        }) = self.stack.last_mut()
+        {
+            if key.is_some() {
+                // The node is the value of the key
+                *key = None;
+                return;
+            }
+            let scalar = scalar.unwrap_or_default();
+            if *environment && !keys.insert(scalar.clone()) {
+                self.duplicate_env_keys.push((
+                    document,
+                    scalar.clone(),
+                    mark.line(),
+                ));
+            }
+            *key = Some(scalar);
+        }
+    }
+}
+
+impl MarkedEventReceiver for YamlScan {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::DocumentEnd => self.document += 1,
+            Event::MappingStart(anchor_id) => {
+                self.define_anchor(anchor_id, mark);
+                let environment = matches!(
+                    self.stack.last(),
+                    Some(Frame::Mapping { key: Some(key), .. })
+                        if key == "environment"
+                );
+                self.stack.push(Frame::Mapping {
+                    environment,
+                    key: None,
+                    keys: HashSet::new(),
+                });
+            }
+            Event::SequenceStart(anchor_id) => {
+                self.define_anchor(anchor_id, mark);
+                self.stack.push(Frame::Sequence);
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                self.complete_node(None, mark);
+            }
+            Event::Scalar(value, _, anchor_id, _) => {
+                self.define_anchor(anchor_id, mark);
+                self.complete_node(Some(value), mark);
+            }
+            Event::Alias(anchor_id) => {
+                self.aliased.insert(anchor_id);
+                self.complete_node(None, mark);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lint(yaml: &str) -> usize {
+        let mut document = serde_yaml::from_str(yaml).unwrap();
+        lint_document(&mut document, "'test'", false).issues
+    }
+
+    #[test]
+    fn test_labels_are_known_for_every_kind() {
+        for kind in &["Service", "Task", "Check", "Group", "Shell", "WaitFor"] {
+            let yaml = format!("kind: {}\nlabels:\n  team: backend\n", kind);
+            assert_eq!(lint(&yaml), 0, "labels reported for {}", kind);
+        }
+    }
+
+    #[test]
+    fn test_unknown_key_is_reported() {
+        assert_eq!(lint("kind: Check\nabuot: typo\n"), 1);
+    }
+
+    #[test]
+    fn test_deprecated_key_is_renamed() {
+        let yaml = "kind: Service\nname: api\nhealthcheck: {}\n";
+
+        let (summary, fixed) = lint_str(yaml, true).unwrap();
+        assert_eq!((summary.issues, summary.fixed), (1, 1));
+        let fixed: Value = serde_yaml::from_str(&fixed.unwrap()).unwrap();
+        assert!(fixed.get("readiness_probe").is_some());
+        assert!(fixed.get("healthcheck").is_none());
+    }
+
+    #[test]
+    fn test_duplicate_env_keys_are_removed() {
+        let yaml = "kind: Service\nname: api\nenvironment:\n  PORT: 80\n  \
+                    HOST: local\n  PORT: 8080\nlabels:\n  PORT: a\n";
+
+        let (summary, _) = lint_str(yaml, false).unwrap();
+        assert_eq!((summary.issues, summary.fixed), (1, 0));
+
+        let (summary, fixed) = lint_str(yaml, true).unwrap();
+        assert_eq!((summary.issues, summary.fixed), (1, 1));
+        let fixed: Value = serde_yaml::from_str(&fixed.unwrap()).unwrap();
+        assert_eq!(fixed["environment"]["PORT"].as_u64(), Some(8080));
+    }
+
+    #[test]
+    fn test_unused_anchors_are_reported() {
+        let yaml = "kind: Task\nname: a\nenvironment: &env\n  A: b\n\
+                    labels: *env\n---\nkind: Task\nname: &other b\n";
+
+        let scan = YamlScan::of(yaml).unwrap();
+        assert!(scan.unused_anchors(0).is_empty());
+        assert_eq!(scan.unused_anchors(1), vec![8]);
+        assert!(scan.has_aliases());
+    }
+
+    #[test]
+    fn test_comments_and_aliases_are_not_preserved() {
+        let commented = "kind: Task # the kind\nname: 'a # b'\n";
+        let aliased = "kind: Task\nname: &name a\ncommand: [*name]\n";
+        let plain = "kind: Task\nname: \"#a\"\nshell: echo a#b\n";
+
+        let content = unpreserved_content(commented).unwrap();
+        assert_eq!(content, Some("comments"));
+        assert_eq!(unpreserved_content(aliased).unwrap(), Some("aliases"));
+        assert_eq!(unpreserved_content(plain).unwrap(), None);
+    }
+}
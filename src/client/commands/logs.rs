@@ -1,13 +1,27 @@
 use crate::client::cli::ClientConfig;
 use crate::client::definitions::get_module_by_name;
 use crate::client::module::{InnerDefinition, ModuleKind};
-use crate::client::request;
+use crate::client::request::CartelClientTrait;
 use anyhow::{bail, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde_json::{json, Value};
 use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
 #[cfg(unix)]
 use std::os::unix::process::CommandExt;
 use std::path::Path;
 use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+pub const LOG_FORMATS: [&str; 2] = ["text", "json"];
+
+/// The number of lines printed in the default mode, same as the default
+/// pager.
+const DEFAULT_TAIL_LINES: usize = 30;
+/// How often the log file is checked for new lines when following it.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 pub enum LogMode {
     FULL,
@@ -15,12 +29,28 @@ pub enum LogMode {
     DEFAULT,
 }
 
+/// Expands a partial module name into a full one.
+///
+/// Module names defined in the module definitions are used as is, otherwise
+/// the daemon is asked to expand the name.
+fn expand_module_name(
+    module_name: &str,
+    cfg: &ClientConfig,
+) -> Result<String> {
+    if get_module_by_name(module_name, cfg)?.is_some() {
+        return Ok(module_name.to_string());
+    }
+    cfg.client().expand_module_name(module_name)
+}
+
 fn get_log_file(module_name: &str, cfg: &ClientConfig) -> Result<OsString> {
     let module = get_module_by_name(module_name, cfg)?;
     // If it is a task with a custom path then use that. Since tasks are
     // stateless there is no reason to contact the daemon.
     if let Some(ref m) = module {
-        if let InnerDefinition::Task(tsk) = &m.inner {
+        if let InnerDefinition::Task(tsk) | InnerDefinition::OneShot(tsk) =
+            &m.inner
+        {
             if let Some(path) = &tsk.log_file_path {
                 return Ok(OsString::from(path));
             }
@@ -35,23 +65,65 @@ fn get_log_file(module_name: &str, cfg: &ClientConfig) -> Result<OsString> {
         ModuleKind::Service
     };
 
-    let path =
-        request::log_file_path(module_name, &module_kind, &cfg.daemon_url)?
-            .log_file_path;
+    let path = cfg
+        .client()
+        .log_file_path(module_name, &module_kind)?
+        .log_file_path;
     Ok(path)
 }
 
+/// Truncates the log file of a module.
+///
+/// The log file of a running service is only cleared if `force` is given.
+pub fn clear_logs(
+    module_name: &str,
+    force: bool,
+    cfg: &ClientConfig,
+) -> Result<()> {
+    let module_name = &expand_module_name(module_name, cfg)?;
+    let module = get_module_by_name(module_name, cfg)?;
+    // Tasks with a custom path are cleared directly, same as when reading
+    // their logs.
+    if let Some(ref m) = module {
+        if let InnerDefinition::Task(tsk) | InnerDefinition::OneShot(tsk) =
+            &m.inner
+        {
+            if let Some(path) = &tsk.log_file_path {
+                File::create(path)?;
+                tprint!("Cleared logs for {}", cbold!(module_name));
+                return Ok(());
+            }
+        }
+    }
+
+    let module_kind = match module {
+        Some(m) => m.kind,
+        None => ModuleKind::Service,
+    };
+
+    cfg.client()
+        .clear_log_file(module_name, &module_kind, force)?;
+    tprint!("Cleared logs for {}", cbold!(module_name));
+    Ok(())
+}
+
 pub fn print_logs(
     module_name: &str,
     log_mode: LogMode,
+    format: &str,
     cfg: &ClientConfig,
 ) -> Result<()> {
+    let module_name = &expand_module_name(module_name, cfg)?;
     let log_file = get_log_file(module_name, cfg)?;
 
     if !Path::new(&log_file).exists() {
         bail!("Log file not found for module {}", module_name);
     }
 
+    if format == "json" {
+        return print_json_logs(Path::new(&log_file), log_mode);
+    }
+
     // This might fail on systems like Windows since paths may not be UTF-8
     // encoded there. Since we are using 'less' to page the logs and we don't
     // support Windows this is not currently an issue, but worth revisiting
@@ -86,3 +158,198 @@ pub fn print_logs(
 
     Ok(())
 }
+
+/// Prints the logs of all the modules known to the daemon, one module after
+/// the other in name order, instead of paging them.
+///
+/// Only the last `tail` lines of each module are printed, if given. In the
+/// text format the lines of each module follow a `==> module <==` header,
+/// while in the JSON format each line also has the `module` it belongs to.
+pub fn print_all_logs(
+    tail: Option<usize>,
+    format: &str,
+    cfg: &ClientConfig,
+) -> Result<()> {
+    let mut logs: Vec<_> = cfg.client().get_all_logs()?.into_iter().collect();
+    logs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for (module_name, log) in logs {
+        let log_file = Path::new(&log.log_file_path);
+        // Modules which haven't been deployed yet have no log file
+        if !log_file.exists() {
+            continue;
+        }
+        let lines = read_lines_lossy(log_file)?;
+        let skip = tail.map_or(0, |tail| lines.len().saturating_sub(tail));
+
+        if format == "json" {
+            let modified = modified_time(log_file)?;
+            for line in &lines[skip..] {
+                let mut entry = json_entry(line, &modified);
+                entry["module"] = json!(module_name);
+                writeln!(out, "{}", entry)?;
+            }
+        } else {
+            writeln!(out, "==> {} <==", module_name)?;
+            for line in &lines[skip..] {
+                writeln!(out, "{}", line)?;
+            }
+            writeln!(out)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads the lines of a log file, replacing invalid UTF-8 (eg. binary output
+/// of a module) instead of failing.
+fn read_lines_lossy(log_file: &Path) -> io::Result<Vec<String>> {
+    let contents = fs::read(log_file)?;
+    Ok(String::from_utf8_lossy(&contents)
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+/// Prints each line of the log file as a JSON object with its `line` and
+/// `timestamp`, instead of paging the log file.
+///
+/// The modes behave the same as with the default pagers: the default mode
+/// prints the last few lines and follows the file, the full mode prints all
+/// lines and the follow mode prints all lines and follows the file.
+fn print_json_logs(log_file: &Path, log_mode: LogMode) -> Result<()> {
+    let mut reader = BufReader::new(File::open(log_file)?);
+    let mut lines = vec![];
+    let mut line = vec![];
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        lines.push(std::mem::take(&mut line));
+    }
+
+    let follow = !matches!(log_mode, LogMode::FULL);
+    // An incomplete last line is printed once it is complete
+    if follow && lines.last().map_or(false, |l| !l.ends_with(b"\n")) {
+        line = lines.pop().unwrap();
+    }
+    let skip = match log_mode {
+        LogMode::DEFAULT => lines.len().saturating_sub(DEFAULT_TAIL_LINES),
+        _ => 0,
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let modified = modified_time(log_file)?;
+    for line in &lines[skip..] {
+        print_json_line(&mut out, &String::from_utf8_lossy(line), &modified)?;
+    }
+    out.flush()?;
+
+    while follow {
+        if reader.read_until(b'\n', &mut line)? == 0
+            || !line.ends_with(b"\n")
+        {
+            // Start over if the log file was cleared
+            let position = reader.seek(SeekFrom::Current(0))?;
+            if fs::metadata(log_file)?.len() < position {
+                reader.seek(SeekFrom::Start(0))?;
+                line.clear();
+            }
+            thread::sleep(FOLLOW_POLL_INTERVAL);
+            continue;
+        }
+        let modified = modified_time(log_file)?;
+        print_json_line(&mut out, &String::from_utf8_lossy(&line), &modified)?;
+        out.flush()?;
+        line.clear();
+    }
+    Ok(())
+}
+
+fn print_json_line(
+    out: &mut impl Write,
+    line: &str,
+    modified: &str,
+) -> Result<()> {
+    writeln!(out, "{}", json_entry(line, modified))?;
+    Ok(())
+}
+
+fn json_entry(line: &str, modified: &str) -> Value {
+    let line = line.trim_end_matches(|c| c == '\n' || c == '\r');
+    let timestamp =
+        embedded_timestamp(line).unwrap_or_else(|| modified.to_string());
+    json!({ "timestamp": timestamp, "line": line })
+}
+
+/// Returns the time the file was last modified at, in RFC 3339 format.
+fn modified_time(path: &Path) -> Result<String> {
+    let modified: DateTime<Local> = fs::metadata(path)?.modified()?.into();
+    Ok(modified.to_rfc3339())
+}
+
+/// Returns the timestamp a log line starts with (if any), in RFC 3339 format.
+///
+/// Both RFC 3339 timestamps (eg. `2021-06-01T12:00:00Z`) and local date
+/// times (eg. `2021-06-01 12:00:00.123`) are recognised, optionally within
+/// square brackets.
+fn embedded_timestamp(line: &str) -> Option<String> {
+    let mut tokens = line
+        .split_whitespace()
+        .map(|token| token.trim_matches(|c| c == '[' || c == ']'));
+    let date = tokens.next()?;
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(date) {
+        return Some(timestamp.to_rfc3339());
+    }
+
+    let date_time = format!("{} {}", date, tokens.next()?);
+    let naive =
+        NaiveDateTime::parse_from_str(&date_time, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .single()
+        .map(|timestamp| timestamp.to_rfc3339())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_embedded_timestamp() {
+        assert_eq!(
+            embedded_timestamp("2021-06-01T12:00:00Z Started"),
+            Some(String::from("2021-06-01T12:00:00+00:00"))
+        );
+        assert_eq!(
+            embedded_timestamp("[2021-06-01T12:00:00+02:00] Started"),
+            Some(String::from("2021-06-01T12:00:00+02:00"))
+        );
+
+        let naive = NaiveDateTime::parse_from_str(
+            "2021-06-01 12:00:00.123",
+            "%Y-%m-%d %H:%M:%S%.f",
+        )
+        .unwrap();
+        let local = Local.from_local_datetime(&naive).single().unwrap();
+        assert_eq!(
+            embedded_timestamp("2021-06-01 12:00:00.123 INFO Started"),
+            Some(local.to_rfc3339())
+        );
+    }
+
+    #[test]
+    fn test_embedded_timestamp_absent() {
+        assert_eq!(embedded_timestamp("Started the server"), None);
+        assert_eq!(embedded_timestamp(""), None);
+        assert_eq!(embedded_timestamp("2021-06-01"), None);
+    }
+
+    #[test]
+    fn test_embedded_timestamp_malformed() {
+        assert_eq!(embedded_timestamp("2021-13-01T12:00:00Z Started"), None);
+        assert_eq!(embedded_timestamp("2021-06-01 25:00:00 Started"), None);
+        assert_eq!(embedded_timestamp("[2021-06-01T12:00] Started"), None);
+    }
+}
@@ -1,25 +1,56 @@
 mod config;
 mod convert;
 mod daemon;
+mod dependency_check;
+mod dependents;
 mod deploy;
-mod deployer;
+pub(crate) mod deployer;
 mod down;
 mod exec;
+mod graph;
+mod health;
+mod import;
+mod info;
+mod lint;
 mod logs;
+mod modules;
+mod pause;
+mod ping;
 mod ps;
+mod reload;
+mod report;
 mod restart;
+mod rollback;
 mod run;
 mod shell;
+mod snapshot;
 mod stop;
+mod strategy;
+mod unlock;
 
 pub use self::config::*;
 pub use self::daemon::*;
+pub use self::dependency_check::*;
+pub use self::dependents::*;
 pub use self::deploy::*;
 pub use self::down::*;
 pub use self::exec::*;
+pub use self::graph::*;
+pub use self::health::*;
+pub use self::import::*;
+pub use self::info::*;
+pub use self::lint::*;
 pub use self::logs::*;
+pub use self::modules::*;
+pub use self::pause::*;
+pub use self::ping::*;
 pub use self::ps::*;
+pub use self::reload::*;
 pub use self::restart::*;
+pub use self::rollback::*;
 pub use self::run::*;
 pub use self::shell::*;
+pub use self::snapshot::*;
 pub use self::stop::*;
+pub use self::strategy::{DeployStrategy, DEPLOY_STRATEGIES};
+pub use self::unlock::*;
@@ -0,0 +1,651 @@
+use crate::client::cli::ClientConfig;
+use crate::client::commands::lint::unpreserved_content;
+use crate::client::definitions::{
+    open_module_file, open_override_file, parse_raw_from_yaml_str,
+    read_module_definitions, read_module_definitions_unvalidated,
+};
+use crate::client::module::{InnerDefinition, ModuleDefinition};
+use crate::client::validation::validate_dependency_names;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use serde_yaml::Value as YamlValue;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+pub const SHOW_FORMATS: [&str; 3] = ["yaml", "json", "table"];
+
+/// The fields of a module definition which refer to a module by name (the
+/// `target` of wait-for modules is a single name, unlike the reference
+/// fields below).
+const NAME_FIELDS: [&str; 3] = ["name", "service", "target"];
+/// The fields of a module definition which list other modules by name.
+const REFERENCE_FIELDS: [&str; 7] = [
+    "dependencies",
+    "ordered_dependencies",
+    "after",
+    "post_up",
+    "post",
+    "checks",
+    "depends_on",
+];
+
+/// Checks that every dependency in the module definitions refers to a known
+/// module, listing all the unknown ones.
+pub fn validate_deps_cmd(cfg: &ClientConfig) -> Result<()> {
+    let module_defs = read_module_definitions_unvalidated(cfg)?;
+    let invalid = validate_dependency_names(&module_defs);
+
+    if invalid.is_empty() {
+        tprint!("{}", csuccess!("All dependencies refer to known modules"));
+        return Ok(());
+    }
+
+    for name in &invalid {
+        tprint!("{} {}", cfail!("✗"), name);
+    }
+    bail!("Found {} unknown dependencies", invalid.len())
+}
+
+/// Prints the definition of a single module, as read from the module
+/// definitions file (after overrides are applied).
+///
+/// The documentation of the module is rendered below its definition, except
+/// in the JSON format where it is kept in the definition.
+///
+/// This does not contact the daemon.
+pub fn show_cmd(name: &str, format: &str, cfg: &ClientConfig) -> Result<()> {
+    let module_defs = read_module_definitions(cfg)?;
+    let mut module = module_defs
+        .into_iter()
+        .find(|m| m.name == name)
+        .ok_or_else(|| anyhow!("The module '{}' does not exist", name))?;
+
+    if format == "json" {
+        tprint!("{}", serde_json::to_string_pretty(&module)?);
+        return Ok(());
+    }
+
+    let documentation = module.documentation.take();
+    match format {
+        "table" => print_table(serde_json::to_value(&module)?),
+        _ => tprint!("{}", serde_yaml::to_string(&module)?),
+    }
+    if let Some(documentation) = documentation {
+        print_documentation(&documentation);
+    }
+    Ok(())
+}
+
+/// Lists the modules in the module definitions, along with their kind and
+/// the first line of their documentation (or the description of groups).
+///
+/// This does not contact the daemon.
+pub fn list_cmd(cfg: &ClientConfig) -> Result<()> {
+    let module_defs = read_module_definitions(cfg)?;
+    let width = module_defs.iter().map(|m| m.name.len()).max().unwrap_or(0);
+    for module in &module_defs {
+        let name = format!("{:width$}", module.name, width = width);
+        let kind = format!("{:7}", module.kind.to_string());
+        // Groups are described by their description, if they have one
+        let description = match &module.inner {
+            InnerDefinition::Group(group) => group.description.as_deref(),
+            _ => None,
+        };
+        let summary = description.or_else(|| module.summary());
+        tprint!(
+            "{}  {}  {}",
+            cbold!(name),
+            kind,
+            cdim!(summary.unwrap_or_default())
+        );
+    }
+    Ok(())
+}
+
+/// Prints the Markdown documentation of a module. Headings are printed in
+/// bold and list items with a bullet, other lines are printed as they are.
+fn print_documentation(documentation: &str) {
+    tprint!("\n{}", cbold!("Documentation"));
+    for line in documentation.trim_end().lines() {
+        let trimmed = line.trim_start();
+        let item = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "));
+        if trimmed.starts_with('#') {
+            let heading = trimmed.trim_start_matches('#').trim();
+            tiprint!(4, "{}", cbold!(heading));
+        } else if let Some(item) = item {
+            tiprint!(4, "• {}", item);
+        } else {
+            tiprint!(4, "{}", line);
+        }
+    }
+}
+
+/// Prints the fields of a module definition as a two column table, leaving
+/// out the ones which are not set.
+fn print_table(module: Value) {
+    let fields = match module {
+        Value::Object(fields) => fields,
+        _ => return,
+    };
+    let width = fields.keys().map(String::len).max().unwrap_or(0);
+    for (key, value) in fields {
+        let value = match value {
+            Value::Null => continue,
+            Value::String(string) => string,
+            other => other.to_string(),
+        };
+        let key = format!("{:width$}", key, width = width);
+        tprint!("{} {}", cbold!(key), value);
+    }
+}
+
+/// Renames a module, along with every reference to it from other modules.
+///
+/// Both the module definitions file and its overrides file are rewritten, and
+/// each change is printed. With `dry_run` the changes are only printed.
+///
+/// The files are written back from their parsed form, so formatting is not
+/// preserved. Files with comments or aliases are not renamed in, since they
+/// would be lost, and none of the files is written then.
+pub fn rename_cmd(
+    old: &str,
+    new: &str,
+    dry_run: bool,
+    cfg: &ClientConfig,
+) -> Result<()> {
+    let module_defs = read_module_definitions_unvalidated(cfg)?;
+    if !module_defs.iter().any(|m| m.name == old) {
+        bail!("The module '{}' does not exist", old);
+    }
+    if module_defs.iter().any(|m| m.name == new) {
+        bail!("The module '{}' already exists", new);
+    }
+
+    let (_, path) = open_module_file(&cfg.module_file, &cfg.default_dir)?;
+    let mut files = vec![path.clone()];
+    if let Some((_, override_path)) = open_override_file(&path, cfg)? {
+        files.push(override_path);
+    }
+
+    if !dry_run {
+        for file in &files {
+            let source = fs::read_to_string(file).with_context(|| {
+                format!("Failed to read {}", file.display())
+            })?;
+            if !rename_in_str(&source, old, new)?.0.is_empty() {
+                ensure_preserved(file, &source)?;
+            }
+        }
+    }
+    for file in files {
+        rename_in_file(&file, old, new, dry_run)?;
+    }
+    if dry_run {
+        tprint!("{}", cdim!("Dry run, no files were written"));
+    } else {
+        tprint!("{}", csuccess!(format!("Renamed '{}' to '{}'", old, new)));
+    }
+    Ok(())
+}
+
+/// Copies the definition of a module to a new module named `dst`.
+///
+/// The copy is appended to the module definitions file without its
+/// `dependencies`, which rarely apply to it, so that only the differences
+/// need to be edited. The rest of the file is left untouched.
+pub fn copy_cmd(src: &str, dst: &str, cfg: &ClientConfig) -> Result<()> {
+    let module_defs = read_module_definitions_unvalidated(cfg)?;
+    if !module_defs.iter().any(|m| m.name == src) {
+        bail!("The module '{}' does not exist", src);
+    }
+    if module_defs.iter().any(|m| m.name == dst) {
+        bail!("The module '{}' already exists", dst);
+    }
+
+    let (_, path) = open_module_file(&cfg.module_file, &cfg.default_dir)?;
+    let mut source = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut copy = serde_yaml::Deserializer::from_str(&source)
+        .map(YamlValue::deserialize)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse {}", path.display()))?
+        .into_iter()
+        .find(|document| {
+            document.get("name").and_then(YamlValue::as_str) == Some(src)
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "The module '{}' is not defined in {}",
+                src,
+                path.display()
+            )
+        })?;
+
+    if let Some(fields) = copy.as_mapping_mut() {
+        let name = YamlValue::String(String::from("name"));
+        fields.insert(name, YamlValue::String(dst.to_string()));
+        fields.remove(&YamlValue::String(String::from("dependencies")));
+    }
+
+    if !source.is_empty() && !source.ends_with('\n') {
+        source.push('\n');
+    }
+    source.push_str(&serde_yaml::to_string(&copy)?);
+    fs::write(&path, source)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    tprint!(
+        "{}",
+        csuccess!(format!(
+            "Copied '{}' to '{}' in {}",
+            src,
+            dst,
+            path.display()
+        ))
+    );
+    Ok(())
+}
+
+/// Compares the module definitions of two files, printing the modules which
+/// were added or removed and the fields which changed in the other modules.
+///
+/// The definitions are compared after they are parsed, so differences in
+/// formatting or in the order of modules and fields are ignored. They are
+/// compared as written, so relative paths (eg. a `working_dir`) are not
+/// resolved against the directory of each file. Overrides files are not
+/// applied.
+///
+/// This does not contact the daemon.
+pub fn diff_cmd(old: &Path, new: &Path) -> Result<()> {
+    let old = definitions_by_name(old)?;
+    let new = definitions_by_name(new)?;
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for (name, diff) in diff_definitions(&old, &new) {
+        match diff {
+            ModuleDiff::Removed => {
+                removed += 1;
+                tprint!("{}", cfail!(format!("- {}", name)));
+            }
+            ModuleDiff::Added => {
+                added += 1;
+                tprint!("{}", csuccess!(format!("+ {}", name)));
+            }
+            ModuleDiff::Changed(fields) => {
+                changed += 1;
+                tprint!("{}", cbold!(format!("~ {}", name)));
+                for field in fields {
+                    tiprint!(
+                        4,
+                        "{} {} → {}",
+                        cdim!(format!("{}:", field.name)),
+                        cfail!(format_field_value(&field.old)),
+                        csuccess!(format_field_value(&field.new))
+                    );
+                }
+            }
+        }
+    }
+
+    if added + removed + changed == 0 {
+        tprint!("{}", csuccess!("No differences found"));
+    } else {
+        tprint!(
+            "\n{} added, {} removed, {} changed",
+            cbold!(added),
+            cbold!(removed),
+            cbold!(changed)
+        );
+    }
+    Ok(())
+}
+
+/// How a module differs between two sets of module definitions.
+#[derive(Debug, PartialEq)]
+enum ModuleDiff {
+    Added,
+    Removed,
+    /// The fields which differ, in alphabetical order.
+    Changed(Vec<FieldChange>),
+}
+
+/// A field which differs between two definitions of a module. A field which
+/// is missing from a definition is `null`.
+#[derive(Debug, PartialEq)]
+struct FieldChange {
+    name: String,
+    old: Value,
+    new: Value,
+}
+
+/// Reads the module definitions of a file as they are written, keyed by
+/// module name.
+fn definitions_by_name(path: &Path) -> Result<BTreeMap<String, Value>> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let modules = parse_raw_from_yaml_str(&source)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    index_by_name(modules)
+        .with_context(|| format!("Failed to compare {}", path.display()))
+}
+
+/// Fails if a module is defined more than once, since only one of its
+/// definitions could be compared.
+fn index_by_name(
+    modules: Vec<ModuleDefinition>,
+) -> Result<BTreeMap<String, Value>> {
+    let mut definitions = BTreeMap::new();
+    for module in modules {
+        let name = module.name.clone();
+        let definition = serde_json::to_value(module)?;
+        if definitions.insert(name.clone(), definition).is_some() {
+            bail!("The module '{}' is defined more than once", name);
+        }
+    }
+    Ok(definitions)
+}
+
+/// Compares two sets of module definitions keyed by name, returning how each
+/// module which differs does, in alphabetical order.
+fn diff_definitions(
+    old: &BTreeMap<String, Value>,
+    new: &BTreeMap<String, Value>,
+) -> Vec<(String, ModuleDiff)> {
+    let names: BTreeSet<_> = old.keys().chain(new.keys()).collect();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let diff = match (old.get(name), new.get(name)) {
+                (Some(_), None) => ModuleDiff::Removed,
+                (None, Some(_)) => ModuleDiff::Added,
+                (Some(old), Some(new)) if old != new => {
+                    ModuleDiff::Changed(field_changes(old, new))
+                }
+                _ => return None,
+            };
+            Some((name.clone(), diff))
+        })
+        .collect()
+}
+
+/// Returns the fields which differ between two definitions of a module.
+fn field_changes(old: &Value, new: &Value) -> Vec<FieldChange> {
+    let no_fields = serde_json::Map::new();
+    let old = old.as_object().unwrap_or(&no_fields);
+    let new = new.as_object().unwrap_or(&no_fields);
+    let fields: BTreeSet<_> = old.keys().chain(new.keys()).collect();
+
+    fields
+        .into_iter()
+        .filter_map(|field| {
+            let old_value = old.get(field).unwrap_or(&Value::Null);
+            let new_value = new.get(field).unwrap_or(&Value::Null);
+            if old_value == new_value {
+                return None;
+            }
+            Some(FieldChange {
+                name: field.clone(),
+                old: old_value.clone(),
+                new: new_value.clone(),
+            })
+        })
+        .collect()
+}
+
+fn format_field_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("(none)"),
+        Value::String(string) => string.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Renames a module in a single module definitions file, printing the
+/// changes made to it.
+fn rename_in_file(
+    path: &Path,
+    old: &str,
+    new: &str,
+    dry_run: bool,
+) -> Result<()> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let (changes, output) = rename_in_str(&source, old, new)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    tprint!("{}", cbold!(path.display().to_string()));
+    for (module, field) in changes {
+        let location = format!("{} ({})", module, field);
+        tiprint!(2, "{}", cdim!(location));
+        tiprint!(4, "{}", cfail!(format!("- {}", old)));
+        tiprint!(4, "{}", csuccess!(format!("+ {}", new)));
+    }
+
+    if !dry_run {
+        fs::write(path, output)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Fails if writing the module definitions back from their parsed form
+/// would lose some of their content.
+fn ensure_preserved(path: &Path, source: &str) -> Result<()> {
+    let unpreserved = unpreserved_content(source)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    if let Some(content) = unpreserved {
+        bail!(
+            "Not renaming in {}, since writing it back would lose its {}. \
+            Rename the module there by hand instead.",
+            path.display(),
+            content
+        );
+    }
+    Ok(())
+}
+
+/// Renames a module in the content of a module definitions file, returning
+/// the module and field of each change along with the renamed content.
+fn rename_in_str(
+    source: &str,
+    old: &str,
+    new: &str,
+) -> Result<(Vec<(String, &'static str)>, String)> {
+    let mut documents = serde_yaml::Deserializer::from_str(source)
+        .map(YamlValue::deserialize)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut changes = vec![];
+    for document in &mut documents {
+        let fields = rename_in_document(document, old, new);
+        let module = document.get("name").and_then(YamlValue::as_str);
+        let module = module.unwrap_or("-").to_string();
+        changes.extend(fields.into_iter().map(|f| (module.clone(), f)));
+    }
+
+    let mut output = String::new();
+    for document in &documents {
+        output.push_str(&serde_yaml::to_string(document)?);
+    }
+    Ok((changes, output))
+}
+
+/// Renames a module within a single module definition, returning the fields
+/// which were changed.
+fn rename_in_document(
+    document: &mut YamlValue,
+    old: &str,
+    new: &str,
+) -> Vec<&'static str> {
+    let mut changed = vec![];
+    let fields = match document.as_mapping_mut() {
+        Some(fields) => fields,
+        None => return changed,
+    };
+
+    for field in NAME_FIELDS.iter() {
+        let key = YamlValue::String(field.to_string());
+        if let Some(value) = fields.get_mut(&key) {
+            if value.as_str() == Some(old) {
+                *value = YamlValue::String(new.to_string());
+                changed.push(*field);
+            }
+        }
+    }
+
+    for field in REFERENCE_FIELDS.iter() {
+        let key = YamlValue::String(field.to_string());
+        if let Some(YamlValue::Sequence(names)) = fields.get_mut(&key) {
+            let mut renamed = false;
+            for name in names.iter_mut().filter(|n| n.as_str() == Some(old)) {
+                *name = YamlValue::String(new.to_string());
+                renamed = true;
+            }
+            if renamed {
+                changed.push(*field);
+            }
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::definitions::parse_from_yaml_str;
+    use serde_json::json;
+
+    fn definitions(modules: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        modules
+            .iter()
+            .map(|(name, definition)| (name.to_string(), definition.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_definitions() {
+        let old = definitions(&[
+            ("api", json!({ "command": ["api"], "timeout": 10 })),
+            ("db", json!({ "command": ["db"] })),
+            ("worker", json!({ "command": ["worker"] })),
+        ]);
+        let new = definitions(&[
+            ("api", json!({ "command": ["api", "-v"], "priority": 1 })),
+            ("cache", json!({ "command": ["cache"] })),
+            ("worker", json!({ "command": ["worker"] })),
+        ]);
+
+        let diff = diff_definitions(&old, &new);
+        let changes = vec![
+            FieldChange {
+                name: String::from("command"),
+                old: json!(["api"]),
+                new: json!(["api", "-v"]),
+            },
+            FieldChange {
+                name: String::from("priority"),
+                old: Value::Null,
+                new: json!(1),
+            },
+            FieldChange {
+                name: String::from("timeout"),
+                old: json!(10),
+                new: Value::Null,
+            },
+        ];
+        assert_eq!(
+            diff,
+            vec![
+                (String::from("api"), ModuleDiff::Changed(changes)),
+                (String::from("cache"), ModuleDiff::Added),
+                (String::from("db"), ModuleDiff::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_modules_are_rejected() {
+        let yaml = "kind: Task\nname: migrate\ncommand: [a]\n---\n\
+                    kind: Task\nname: migrate\ncommand: [b]\n";
+        let modules = parse_from_yaml_str(yaml, Path::new(".")).unwrap();
+
+        assert!(index_by_name(modules).is_err());
+    }
+
+    #[test]
+    fn test_diff_files_in_different_directories() {
+        let dir = std::env::temp_dir()
+            .join(format!("cartel-diff-{}", std::process::id()));
+        let (old_dir, new_dir) = (dir.join("old"), dir.join("new"));
+        fs::create_dir_all(&old_dir).unwrap();
+        fs::create_dir_all(&new_dir).unwrap();
+        let old_path = old_dir.join("modules.yaml");
+        let new_path = new_dir.join("modules.yaml");
+        let modules_yaml = |command: &str| {
+            format!(
+                "kind: Service\nname: api\ncommand: [api]\n\
+                working_dir: ./api\n---\n\
+                kind: Task\nname: migrate\ncommand: {}\n",
+                command
+            )
+        };
+        fs::write(&old_path, modules_yaml("[migrate]")).unwrap();
+        fs::write(&new_path, modules_yaml("[migrate, -v]")).unwrap();
+
+        let old = definitions_by_name(&old_path).unwrap();
+        let new = definitions_by_name(&new_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The same relative working_dir is not a difference
+        let diff = diff_definitions(&old, &new);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].0, "migrate");
+        match &diff[0].1 {
+            ModuleDiff::Changed(fields) => {
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].name, "command");
+            }
+            other => panic!("Unexpected diff: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rename_in_str() {
+        let source = "name: api\ncommand: [api]\n---\nname: web\n\
+            dependencies: [db, api]\n";
+        let (changes, output) =
+            rename_in_str(source, "api", "backend").unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                (String::from("backend"), "name"),
+                (String::from("web"), "dependencies"),
+            ]
+        );
+        assert!(output.contains("name: backend"));
+        assert!(output.contains("- backend"));
+        assert!(!output.contains("api"));
+    }
+
+    #[test]
+    fn test_rename_refuses_to_lose_content() {
+        let path = Path::new("modules.yaml");
+        let commented = "name: api # The API\ncommand: [api]\n";
+        let aliased = "name: api\nenvironment: &env {A: 1}\n---\n\
+            name: web\nenvironment: *env\ndependencies: [api]\n";
+        let plain = "name: api\ncommand: [api]\n";
+
+        let error = ensure_preserved(path, commented).unwrap_err();
+        assert!(error.to_string().contains("comments"));
+        let error = ensure_preserved(path, aliased).unwrap_err();
+        assert!(error.to_string().contains("aliases"));
+        assert!(ensure_preserved(path, plain).is_ok());
+    }
+}
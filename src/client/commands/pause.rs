@@ -0,0 +1,21 @@
+use crate::client::cli::ClientConfig;
+use crate::client::emoji::{HOUR_GLASS, SUCCESS};
+use crate::client::request::CartelClientTrait;
+use anyhow::Result;
+use console::style;
+
+pub fn pause_module_cmd(module: &str, cfg: &ClientConfig) -> Result<()> {
+    let module = &cfg.client().expand_module_name(module)?;
+    tprintstep!(format!("Pausing service '{}'...", module), 1, 2, HOUR_GLASS);
+    cfg.client().pause_module(module)?;
+    tprintstep!(style("Service paused").bold().green(), 2, 2, SUCCESS);
+    Ok(())
+}
+
+pub fn resume_module_cmd(module: &str, cfg: &ClientConfig) -> Result<()> {
+    let module = &cfg.client().expand_module_name(module)?;
+    tprintstep!(format!("Resuming service '{}'...", module), 1, 2, HOUR_GLASS);
+    cfg.client().resume_module(module)?;
+    tprintstep!(style("Service resumed").bold().green(), 2, 2, SUCCESS);
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use crate::client::cli::ClientConfig;
+use crate::client::request::CartelClientTrait;
+use anyhow::{bail, Result};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The pause between two requests, same as ping.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sends `count` requests to the daemon, printing the round-trip time of each
+/// of them and then their minimum, average and maximum.
+///
+/// Fails if any of the requests failed.
+pub fn ping_cmd(count: usize, cfg: &ClientConfig) -> Result<()> {
+    let client = cfg.client();
+    let mut times = vec![];
+    for seq in 1..=count {
+        if seq > 1 {
+            thread::sleep(PING_INTERVAL);
+        }
+        let started = Instant::now();
+        match client.ping() {
+            Ok(()) => {
+                let time = started.elapsed().as_secs_f64() * 1000.0;
+                tprint!("Reply from daemon: seq={} time={:.2} ms", seq, time);
+                times.push(time);
+            }
+            Err(e) => tprint!("{} seq={} {}", cfail!("No reply:"), seq, e),
+        }
+    }
+
+    let failed = count - times.len();
+    tprint!(
+        "\n{} requests sent, {} replies received",
+        count,
+        times.len()
+    );
+    if !times.is_empty() {
+        let min = times.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = times.iter().cloned().fold(0.0, f64::max);
+        let avg = times.iter().sum::<f64>() / times.len() as f64;
+        tprint!(
+            "round-trip min/avg/max = {:.2}/{:.2}/{:.2} ms",
+            min,
+            avg,
+            max
+        );
+    }
+
+    if failed > 0 {
+        bail!("{} of {} requests failed", failed, count);
+    }
+    Ok(())
+}
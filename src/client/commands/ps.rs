@@ -1,24 +1,81 @@
 use crate::client::cli::ClientConfig;
-use crate::client::request;
-use crate::daemon::api::{ApiModuleRunStatus, ApiProbeStatus};
-use anyhow::Result;
+use crate::client::cmd::shell_to_cmd;
+use crate::client::format::{sort_modules, SortField};
+use crate::client::module::ModuleKind;
+use crate::client::request::{CartelClientTrait, ModuleFilter};
+use crate::daemon::api::{
+    ApiModuleRunStatus, ApiModuleStatus, ApiProbeStatus,
+};
+use anyhow::{anyhow, Result};
 use chrono::Local;
 use clap::ArgMatches;
 use console::Style;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::io;
 use std::io::Write;
+use std::process::Command;
+use std::thread;
 use std::time::Duration;
 use tabwriter::TabWriter;
 
+/// How often the status of the modules is polled with `--watch`.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The kinds of modules which can be listed on their own with `--kind`.
+pub const PS_KINDS: [&str; 3] = ["Service", "Task", "OneShot"];
+
+/// The fields which can be printed on their own with `--field`.
+pub const PS_FIELDS: [&str; 6] = [
+    "pid",
+    "name",
+    "status",
+    "log_file_path",
+    "working_dir",
+    "command",
+];
+
 pub struct PsOpts {
     pub color: bool,
+    pub verbose: bool,
+    pub field: Option<String>,
+    pub modules: Vec<String>,
+    pub sort: Option<SortField>,
+    pub sort_desc: bool,
+    /// Print only the working directory of this module.
+    pub working_dir: Option<String>,
+    /// Only list modules of this kind.
+    pub kind: Option<ModuleKind>,
+    /// Only list modules deployed in this namespace.
+    pub namespace: Option<String>,
+    /// Keep polling the modules and print their status changes.
+    pub watch: bool,
+    /// A shell command to run on each status change while watching.
+    pub on_change: Option<String>,
 }
 
 impl PsOpts {
     pub fn from(matches: &ArgMatches) -> Self {
+        let modules = match matches.values_of("modules") {
+            Some(modules) => modules.map(String::from).collect(),
+            None => vec![],
+        };
         Self {
             color: !matches.is_present("no-color"),
+            verbose: matches.is_present("verbose"),
+            field: matches.value_of("field").map(String::from),
+            modules,
+            sort: matches.value_of("sort").and_then(|f| f.parse().ok()),
+            sort_desc: matches.is_present("sort-desc"),
+            working_dir: matches.value_of("working-dir").map(String::from),
+            kind: matches.value_of("kind").map(|kind| match kind {
+                "Task" => ModuleKind::Task,
+                "OneShot" => ModuleKind::OneShot,
+                _ => ModuleKind::Service,
+            }),
+            namespace: matches.value_of("namespace").map(String::from),
+            watch: matches.is_present("watch"),
+            on_change: matches.value_of("on-change").map(String::from),
         }
     }
 }
@@ -42,8 +99,11 @@ fn get_line_style(
         {
             console::Style::new()
         } else if (run_status == ApiModuleRunStatus::STOPPED
-            || run_status == ApiModuleRunStatus::WAITING)
-            && (probe_status == ApiProbeStatus::Pending
+            || run_status == ApiModuleRunStatus::PAUSED
+            || run_status == ApiModuleRunStatus::WAITING
+            || run_status == ApiModuleRunStatus::COMPLETED)
+            && (probe_status == ApiProbeStatus::Starting
+                || probe_status == ApiProbeStatus::Pending
                 || probe_status == ApiProbeStatus::Successful)
         {
             console::Style::new().dim()
@@ -55,25 +115,119 @@ fn get_line_style(
     }
 }
 
+pub(crate) fn format_status(mod_status: &ApiModuleStatus) -> String {
+    match mod_status.status {
+        ApiModuleRunStatus::RUNNING => "running".to_string(),
+        ApiModuleRunStatus::STOPPED => "stopped".to_string(),
+        ApiModuleRunStatus::PAUSED => "paused".to_string(),
+        ApiModuleRunStatus::WAITING => "waiting".to_string(),
+        ApiModuleRunStatus::EXITED => "exited".to_string(),
+        ApiModuleRunStatus::COMPLETED => "completed".to_string(),
+        ApiModuleRunStatus::FAILED => match mod_status.exit_code {
+            Some(code) => format!("failed ({})", code),
+            None => "failed".to_string(),
+        },
+    }
+}
+
+fn format_command(mod_status: &ApiModuleStatus) -> String {
+    match &mod_status.command {
+        Some(command) => command.join(" "),
+        None => String::from("-"),
+    }
+}
+
+/// Formats the ports a module exposes, eg. `8080:80/tcp, 5432:5432/tcp`.
+fn format_ports(mod_status: &ApiModuleStatus) -> String {
+    if mod_status.ports.is_empty() {
+        return String::from("-");
+    }
+    mod_status
+        .ports
+        .iter()
+        .map(|port| {
+            format!(
+                "{}:{}/{}",
+                port.host_port, port.container_port, port.protocol
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Prints a single field of each module, one per line.
+fn print_field(field: &str, statuses: &[&ApiModuleStatus]) {
+    for mod_status in statuses {
+        let value = match field {
+            "pid" => mod_status.pid.to_string(),
+            "name" => mod_status.name.clone(),
+            "status" => format_status(mod_status),
+            "log_file_path" => mod_status
+                .log_file_path
+                .clone()
+                .unwrap_or_else(|| String::from("-")),
+            "working_dir" => mod_status
+                .working_dir
+                .clone()
+                .unwrap_or_else(|| String::from("-")),
+            _ => format_command(mod_status),
+        };
+        println!("{}", value);
+    }
+}
+
+/// Prints the working directory of a module, failing if it isn't known.
+fn print_working_dir(name: &str, statuses: &[ApiModuleStatus]) -> Result<()> {
+    let mod_status = statuses
+        .iter()
+        .find(|m| m.name == name)
+        .ok_or_else(|| anyhow!("Module '{}' has not been deployed", name))?;
+    let working_dir = mod_status.working_dir.as_ref().ok_or_else(|| {
+        anyhow!("The working directory of '{}' is not known yet", name)
+    })?;
+    println!("{}", working_dir);
+    Ok(())
+}
+
 pub fn list_modules_cmd(ps_opts: &PsOpts, cfg: &ClientConfig) -> Result<()> {
-    let module_status = request::list_modules(&cfg.daemon_url)?;
+    let filter = ModuleFilter {
+        kind: ps_opts.kind.clone(),
+        status: None,
+        namespace: ps_opts.namespace.clone(),
+    };
+    let mut module_status = cfg.client().list_modules(&filter)?;
+    if let Some(name) = &ps_opts.working_dir {
+        return print_working_dir(name, &module_status.status);
+    }
+    if let Some(field) = ps_opts.sort {
+        sort_modules(&mut module_status.status, field, ps_opts.sort_desc);
+    }
+    let selected: Vec<_> = module_status
+        .status
+        .iter()
+        .filter(|m| {
+            ps_opts.modules.is_empty() || ps_opts.modules.contains(&m.name)
+        })
+        .collect();
+
+    if let Some(field) = &ps_opts.field {
+        print_field(field, &selected);
+        return Ok(());
+    }
+
     let mut tw = TabWriter::new(io::stdout()).minwidth(8);
 
-    writeln!(
-        &mut tw,
-        "{}",
-        get_header_style(ps_opts)
-            .apply_to("pid\tname\tliveness\tstatus\tsince")
-    )?;
-
-    module_status.status.iter().try_for_each(|mod_status| {
-        let formatted_status = match mod_status.status {
-            ApiModuleRunStatus::RUNNING => "running",
-            ApiModuleRunStatus::STOPPED => "stopped",
-            ApiModuleRunStatus::WAITING => "waiting",
-            ApiModuleRunStatus::EXITED => "exited",
-        };
+    let header = if ps_opts.verbose {
+        "pid\tname\tliveness\tstatus\tsince\trestarts\tports\tversion\tcommand"
+    } else {
+        "pid\tname\tliveness\tstatus\tsince"
+    };
+    writeln!(&mut tw, "{}", get_header_style(ps_opts).apply_to(header))?;
+
+    selected.iter().try_for_each(|mod_status| {
+        let formatted_status = format_status(mod_status);
         let formatted_liveness_status = match mod_status.liveness_status {
+            Some(ApiProbeStatus::Starting) => "starting",
             Some(ApiProbeStatus::Pending) => "pending",
             Some(ApiProbeStatus::Successful) => "healthy",
             Some(ApiProbeStatus::Failing) => "failing",
@@ -96,20 +250,185 @@ pub fn list_modules_cmd(ps_opts: &PsOpts, cfg: &ClientConfig) -> Result<()> {
             .liveness_status
             .unwrap_or(ApiProbeStatus::Successful);
 
+        let mut line = format!(
+            "{}\t{}\t{}\t{}\t{}",
+            mod_status.pid,
+            mod_status.name,
+            formatted_liveness_status,
+            formatted_status,
+            formatted_time,
+        );
+        if ps_opts.verbose {
+            line = format!(
+                "{}\t{}\t{}\t{}\t{}",
+                line,
+                mod_status.restarts,
+                format_ports(mod_status),
+                mod_status.version.as_deref().unwrap_or("-"),
+                format_command(mod_status)
+            );
+        }
+
         writeln!(
             &mut tw,
             "{}",
             get_line_style(ps_opts, mod_status.status, liveness_status)
-                .apply_to(format!(
-                    "{}\t{}\t{}\t{}\t{}",
-                    mod_status.pid,
-                    mod_status.name,
-                    formatted_liveness_status,
-                    formatted_status,
-                    formatted_time,
-                ))
+                .apply_to(line)
         )
     })?;
     tw.flush()?;
+
+    if ps_opts.verbose {
+        let now = u64::try_from(Local::now().timestamp()).unwrap();
+        let age = now.saturating_sub(module_status.snapshot_time);
+        tprint!("{}", cdim!(format!("Last updated: {}s ago", age)));
+    }
+
+    if ps_opts.watch {
+        return watch_modules(ps_opts, &filter, cfg);
+    }
     Ok(())
 }
+
+/// The status of each selected module, by module name.
+fn statuses_by_name(
+    statuses: &[ApiModuleStatus],
+    ps_opts: &PsOpts,
+) -> BTreeMap<String, String> {
+    statuses
+        .iter()
+        .filter(|m| {
+            ps_opts.modules.is_empty() || ps_opts.modules.contains(&m.name)
+        })
+        .map(|m| (m.name.clone(), format_status(m)))
+        .collect()
+}
+
+/// Polls the status of the modules until interrupted, printing each status
+/// change and running the `--on-change` command for it.
+///
+/// Modules which appear or disappear between polls are reported with a `-`
+/// status. A failed poll is reported and retried on the next one.
+fn watch_modules(
+    ps_opts: &PsOpts,
+    filter: &ModuleFilter,
+    cfg: &ClientConfig,
+) -> Result<()> {
+    let client = cfg.client();
+    let status = client.list_modules(filter)?.status;
+    let mut previous = statuses_by_name(&status, ps_opts);
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let status = match client.list_modules(filter) {
+            Ok(response) => response.status,
+            Err(e) => {
+                twarn!(format!("Failed to poll the module statuses: {}", e));
+                continue;
+            }
+        };
+        let current = statuses_by_name(&status, ps_opts);
+        for (name, old_status, new_status) in
+            status_changes(&previous, &current)
+        {
+            on_status_change(name, old_status, new_status, ps_opts);
+        }
+        previous = current;
+    }
+}
+
+/// Returns the modules whose status changed between two polls, along with
+/// their old and new status. Modules which only appear in one of the polls
+/// have a `-` status in the other.
+fn status_changes<'a>(
+    previous: &'a BTreeMap<String, String>,
+    current: &'a BTreeMap<String, String>,
+) -> Vec<(&'a str, &'a str, &'a str)> {
+    let mut changes = vec![];
+    for (name, new_status) in current {
+        let old_status = previous.get(name).map_or("-", String::as_str);
+        if old_status != new_status {
+            changes.push((name.as_str(), old_status, new_status.as_str()));
+        }
+    }
+    for (name, old_status) in previous {
+        if !current.contains_key(name) {
+            changes.push((name.as_str(), old_status.as_str(), "-"));
+        }
+    }
+    changes
+}
+
+/// Prints a status change, and runs the `--on-change` command with the
+/// change in its environment.
+fn on_status_change(
+    name: &str,
+    old_status: &str,
+    new_status: &str,
+    ps_opts: &PsOpts,
+) {
+    let time = Local::now().format("%H:%M:%S");
+    tprint!(
+        "{} {}: {} -> {}",
+        cdim!(time),
+        cbold!(name),
+        old_status,
+        new_status
+    );
+
+    let command = match &ps_opts.on_change {
+        Some(command) => shell_to_cmd(command),
+        None => return,
+    };
+    let result = Command::new(&command[0])
+        .args(&command[1..])
+        .env("CARTEL_MODULE_NAME", name)
+        .env("CARTEL_OLD_STATUS", old_status)
+        .env("CARTEL_NEW_STATUS", new_status)
+        .status();
+    match result {
+        Ok(exit_status) if exit_status.success() => {}
+        Ok(exit_status) => twarn!(format!(
+            "The --on-change command failed for {} ({})",
+            name, exit_status
+        )),
+        Err(e) => twarn!(format!(
+            "Failed to run the --on-change command for {}: {}",
+            name, e
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn statuses(statuses: &[(&str, &str)]) -> BTreeMap<String, String> {
+        statuses
+            .iter()
+            .map(|(name, status)| (name.to_string(), status.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_status_changes() {
+        let previous = statuses(&[("api", "running"), ("db", "running")]);
+        let current = statuses(&[("api", "exited"), ("db", "running")]);
+
+        assert_eq!(
+            status_changes(&previous, &current),
+            vec![("api", "running", "exited")]
+        );
+        assert!(status_changes(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_status_changes_of_added_and_removed_modules() {
+        let previous = statuses(&[("api", "running"), ("db", "running")]);
+        let current = statuses(&[("db", "running"), ("web", "waiting")]);
+
+        assert_eq!(
+            status_changes(&previous, &current),
+            vec![("web", "-", "waiting"), ("api", "running", "-")]
+        );
+    }
+}
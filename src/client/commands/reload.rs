@@ -0,0 +1,13 @@
+use crate::client::cli::ClientConfig;
+use crate::client::emoji::{HOUR_GLASS, SUCCESS};
+use crate::client::request::CartelClientTrait;
+use anyhow::Result;
+use console::style;
+
+pub fn reload_module_cmd(module: &str, cfg: &ClientConfig) -> Result<()> {
+    let module = &cfg.client().expand_module_name(module)?;
+    tprintstep!(format!("Reloading service '{}'...", module), 1, 2, HOUR_GLASS);
+    cfg.client().reload_module(module)?;
+    tprintstep!(style("Service signalled").bold().green(), 2, 2, SUCCESS);
+    Ok(())
+}
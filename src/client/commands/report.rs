@@ -0,0 +1,200 @@
+use anyhow::{bail, Context, Error, Result};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::time::Duration;
+
+/// A machine readable summary of a deployment, written by
+/// `cartel deploy --report`.
+#[derive(Serialize, Debug)]
+pub struct DeployReport {
+    /// When the report was written (RFC 3339).
+    pub timestamp: String,
+    pub success: bool,
+    /// The error the deployment failed with, if any.
+    pub error: Option<String>,
+    pub modules: Vec<ModuleReport>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ModuleReport {
+    pub name: String,
+    pub status: ModuleReportStatus,
+    pub duration_ms: u64,
+    /// Whether the checks of the module passed. Not set if the module has no
+    /// checks, or if checks were skipped.
+    pub checks_passed: Option<bool>,
+    /// How long it took for the readiness probe to pass. Not set if the
+    /// readiness of the module was not waited on.
+    pub healthcheck_duration_ms: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModuleReportStatus {
+    Deployed,
+    AlreadyDeployed,
+    Skipped,
+    /// The module has no process of its own and finished its work (eg. a
+    /// `WaitFor` whose target became healthy).
+    Completed,
+    Failed,
+    /// The deployment of the module was interrupted because another module
+    /// failed.
+    Cancelled,
+}
+
+/// The outcome of deploying a single module.
+pub struct ModuleOutcome {
+    pub status: ModuleReportStatus,
+    pub healthcheck_duration: Option<Duration>,
+}
+
+impl From<ModuleReportStatus> for ModuleOutcome {
+    fn from(status: ModuleReportStatus) -> Self {
+        Self {
+            status,
+            healthcheck_duration: None,
+        }
+    }
+}
+
+/// Collects the reports and failures of the modules of a deployment.
+///
+/// Shared between the deployers of each thread.
+#[derive(Default)]
+pub struct DeployRecorder {
+    modules: Mutex<Vec<ModuleReport>>,
+    failures: Mutex<Vec<String>>,
+    /// Whether the checks of each module passed, for the modules which have
+    /// checks that were run.
+    checks_passed: Mutex<HashMap<String, bool>>,
+    /// The modules which are skipped because a module they depend on failed,
+    /// along with the name of that module.
+    blocked: Mutex<HashMap<String, String>>,
+}
+
+impl DeployRecorder {
+    pub fn record(&self, report: ModuleReport) {
+        self.modules.lock().push(report);
+    }
+
+    /// Records whether the checks of a module passed.
+    ///
+    /// A module whose checks did not pass is not deployed, so it is reported
+    /// as failed right away.
+    pub fn record_checks(&self, module_name: &str, passed: bool) {
+        self.checks_passed
+            .lock()
+            .insert(module_name.to_string(), passed);
+        if !passed {
+            self.record(ModuleReport {
+                name: module_name.to_string(),
+                status: ModuleReportStatus::Failed,
+                duration_ms: 0,
+                checks_passed: Some(false),
+                healthcheck_duration_ms: None,
+            });
+        }
+    }
+
+    /// Returns whether the checks of a module passed, or `None` if no checks
+    /// were run for it.
+    pub fn checks_passed(&self, module_name: &str) -> Option<bool> {
+        self.checks_passed.lock().get(module_name).copied()
+    }
+
+    /// Records a module which failed to deploy, returning the number of
+    /// failures so far.
+    pub fn record_failure(&self, module_name: &str, error: &Error) -> usize {
+        let mut failures = self.failures.lock();
+        failures.push(format!("{}: {:#}", module_name, error));
+        failures.len()
+    }
+
+    /// Marks the dependents of a failed module to be skipped.
+    pub fn block_dependents(&self, failed: &str, dependents: &[&str]) {
+        let mut blocked = self.blocked.lock();
+        for name in dependents {
+            blocked
+                .entry(name.to_string())
+                .or_insert_with(|| failed.to_string());
+        }
+    }
+
+    /// Returns the failed module which the given module depends on, if the
+    /// module is to be skipped.
+    pub fn failed_dependency(&self, module_name: &str) -> Option<String> {
+        self.blocked.lock().get(module_name).cloned()
+    }
+
+    /// Combines the result of the deployment with the failures which did not
+    /// abort it (see `deploy --max-errors`).
+    ///
+    /// A single failure is returned as is, while multiple failures are
+    /// summarized together.
+    pub fn summarize(&self, result: Result<()>) -> Result<()> {
+        let failures = self.failures.lock();
+        match (result, failures.len()) {
+            (result, 0) | (result @ Err(_), 1) => result,
+            (_, count) => bail!(
+                "Failed to deploy {} module(s)\n\n{}",
+                count,
+                failures.join("\n")
+            ),
+        }
+    }
+
+    /// Builds the report of the deployment given its result.
+    pub fn report(&self, result: &Result<()>) -> DeployReport {
+        DeployReport {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            modules: self.modules.lock().clone(),
+        }
+    }
+}
+
+/// Writes the deploy report to the given file as JSON.
+pub fn write_report(report: &DeployReport, path: &str) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create report file {}", path))?;
+    serde_json::to_writer_pretty(file, report)
+        .with_context(|| format!("Failed to write report file {}", path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_records_checks_passed() {
+        let recorder = DeployRecorder::default();
+        recorder.record_checks("api", true);
+        recorder.record_checks("db", false);
+
+        assert_eq!(recorder.checks_passed("api"), Some(true));
+        assert_eq!(recorder.checks_passed("db"), Some(false));
+        assert_eq!(recorder.checks_passed("cache"), None);
+
+        let report = recorder.report(&Ok(()));
+        assert_eq!(report.modules.len(), 1);
+        assert_eq!(report.modules[0].name, "db");
+        assert_eq!(report.modules[0].status, ModuleReportStatus::Failed);
+        assert_eq!(report.modules[0].checks_passed, Some(false));
+    }
+
+    #[test]
+    fn test_blocks_dependents_of_failed_modules() {
+        let recorder = DeployRecorder::default();
+        recorder.block_dependents("db", &["api", "web"]);
+        recorder.block_dependents("cache", &["web"]);
+
+        assert_eq!(recorder.failed_dependency("api").as_deref(), Some("db"));
+        // The first failure is kept
+        assert_eq!(recorder.failed_dependency("web").as_deref(), Some("db"));
+        assert_eq!(recorder.failed_dependency("db"), None);
+    }
+}
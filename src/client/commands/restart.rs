@@ -1,13 +1,14 @@
 use crate::client::cli::ClientConfig;
 use crate::client::emoji::{HOUR_GLASS, SUCCESS};
-use crate::client::request;
+use crate::client::request::CartelClientTrait;
 use anyhow::Result;
 use console::style;
 
 pub fn restart_module_cmd(module: &str, cfg: &ClientConfig) -> Result<()> {
+    let module = &cfg.client().expand_module_name(module)?;
     #[rustfmt::skip]
     tprintstep!(format!("Restarting service '{}'...", module), 1, 2, HOUR_GLASS);
-    request::restart_module(module, &cfg.daemon_url)?;
+    cfg.client().restart_module(module)?;
     tprintstep!(style("Service restarted").bold().green(), 2, 2, SUCCESS);
     Ok(())
 }
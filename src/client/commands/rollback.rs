@@ -0,0 +1,51 @@
+use crate::client::cli::ClientConfig;
+use crate::client::commands::deployer::Deployer;
+use crate::client::emoji::{SUCCESS, VAN};
+use crate::client::progress::{SpinnerOptions, WaitResult, WaitUntil};
+use crate::client::request::{CartelClientTrait, PollBackoff};
+use anyhow::Result;
+use std::thread;
+
+/// Redeploys a service with the definition it was deployed with before its
+/// current one, and waits for it to be healthy.
+///
+/// The daemon keeps a few definitions of each service (see the
+/// `max_snapshots` daemon setting), so a service can be rolled back several
+/// times in a row.
+pub fn rollback_module_cmd(module: &str, cfg: &ClientConfig) -> Result<()> {
+    let client = cfg.client();
+    let module = &client.expand_module_name(module)?;
+    tprintstep!("Rolling back...", 1, 3, VAN);
+    let response = client.rollback(module)?;
+    let definition = &response.definition;
+    tiprint!(4, "Command:     {}", definition.command.join(" "));
+    if let Some(working_dir) = &definition.working_dir {
+        tiprint!(4, "Working dir: {}", working_dir);
+    }
+
+    let message = format!("Waiting {} to be healthy", cbold!(module));
+    let spin_opt = SpinnerOptions::new(message)
+        .no_spinner(cfg.no_spinner)
+        .step(2, 3);
+    WaitUntil::new(&spin_opt).spin_until_status(|| {
+        let monitor_handle = match &response.monitor {
+            Some(monitor_handle) => monitor_handle,
+            None => {
+                let status = csuccess!("(Done)").to_string();
+                return Ok(WaitResult::from((), status));
+            }
+        };
+        let mut backoff = PollBackoff::new();
+        loop {
+            thread::sleep(backoff.interval());
+            if let Some(wait_result) =
+                Deployer::poll_health_once(monitor_handle, cfg, &mut backoff)?
+            {
+                return Ok(wait_result);
+            }
+        }
+    })?;
+
+    tprintstep!(csuccess!("Service rolled back"), 3, 3, SUCCESS);
+    Ok(())
+}
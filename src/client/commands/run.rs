@@ -1,7 +1,9 @@
 use crate::client::cli::ClientConfig;
+use crate::client::commands::DeployOptions;
 use crate::client::definitions::read_module_definitions;
 use crate::client::module::{module_by_name, InnerDefinition};
 use crate::client::process::run_task;
+use crate::client::request::CartelClientTrait;
 use anyhow::{anyhow, bail, Result};
 
 pub fn run_task_cmd(task_name: &str, cfg: &ClientConfig) -> Result<()> {
@@ -17,3 +19,70 @@ pub fn run_task_cmd(task_name: &str, cfg: &ClientConfig) -> Result<()> {
         bail!("Module provided is a {}, not a task", module_def.kind)
     }
 }
+
+/// Runs tasks through the daemon, up to `max_concurrency` of them at the same
+/// time, and prints the outcome of each of them once they have all completed.
+///
+/// The environment sets, namespace and log level of the tasks are taken from
+/// the deploy options.
+///
+/// Fails if any of the tasks failed.
+pub fn run_tasks_cmd(
+    task_names: &[&str],
+    max_concurrency: usize,
+    deploy_opts: &DeployOptions,
+    cfg: &ClientConfig,
+) -> Result<()> {
+    let module_defs = read_module_definitions(cfg)?;
+    let mut tasks = vec![];
+    for task_name in task_names {
+        let module_def =
+            module_by_name(task_name, &module_defs).ok_or_else(|| {
+                anyhow!("Failed to find task with name '{}'", task_name)
+            })?;
+        match &module_def.inner {
+            InnerDefinition::Task(task) => tasks.push(task),
+            _ => bail!("Module {} is not a task", task_name),
+        }
+    }
+
+    let response =
+        cfg.client()
+            .deploy_task_batch(&tasks, max_concurrency, deploy_opts)?;
+
+    let mut failed = 0;
+    for result in &response.results {
+        let name = &result.name;
+        let duration = cdim!(format!("({} ms)", result.duration_ms));
+        match (result.exit_code, &result.error) {
+            (Some(0), _) => {
+                tprint!("{} {} {}", csuccess!("✓"), name, duration);
+            }
+            (_, Some(error)) => {
+                failed += 1;
+                tprint!("{} {} {}", cfail!("✗"), name, duration);
+                tiprint!(4, "{}", error);
+            }
+            (exit_code, None) => {
+                failed += 1;
+                let code = exit_code
+                    .map_or_else(|| String::from("-"), |c| c.to_string());
+                tprint!(
+                    "{} {} exited with code {} {}",
+                    cfail!("✗"),
+                    name,
+                    code,
+                    duration
+                );
+            }
+        }
+        if let Some(output) = &result.output {
+            tiprint!(4, "{}", output.trim_end());
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} of {} tasks failed", failed, response.results.len());
+    }
+    Ok(())
+}
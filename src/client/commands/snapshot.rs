@@ -0,0 +1,24 @@
+use crate::client::cli::ClientConfig;
+use crate::client::emoji::{HOUR_GLASS, SUCCESS};
+use crate::client::request::CartelClientTrait;
+use anyhow::Result;
+use std::env;
+
+/// Asks the daemon to write a snapshot of all modules to `output`.
+///
+/// A relative `output` is resolved against the current directory, since the
+/// daemon might be running from a different one.
+pub fn snapshot_cmd(output: Option<&str>, cfg: &ClientConfig) -> Result<()> {
+    tprintstep!("Taking snapshot...", 1, 2, HOUR_GLASS);
+    let output = match output {
+        Some(path) => {
+            Some(env::current_dir()?.join(path).to_string_lossy().to_string())
+        }
+        None => None,
+    };
+
+    let response = cfg.client().snapshot(output.as_deref())?;
+    let message = format!("Snapshot written to {}", response.path);
+    tprintstep!(csuccess!(message), 2, 2, SUCCESS);
+    Ok(())
+}
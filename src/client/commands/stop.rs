@@ -1,29 +1,150 @@
 use crate::client::cli::ClientConfig;
+use crate::client::definitions::read_module_definitions;
 use crate::client::emoji::{HOUR_GLASS, SUCCESS};
+use crate::client::module::{module_names_set, remove_checks, ModuleKind};
 use crate::client::progress::{SpinnerOptions, WaitResult, WaitUntil};
-use crate::client::request;
-use anyhow::Result;
+use crate::client::request::{CartelClientTrait, ModuleFilter};
+use crate::daemon::api::ApiModuleRunStatus;
+use crate::dependency::DependencyGraph;
+use anyhow::{bail, Result};
 use console::style;
 
 pub fn stop_service_cmd(services: Vec<&str>, cfg: &ClientConfig) -> Result<()> {
     tprintstep!("Stopping service(s)...", 1, 2, HOUR_GLASS);
-    for service in services {
+    let client = cfg.client();
+    let services = services
+        .iter()
+        .map(|s| client.expand_module_name(s))
+        .collect::<Result<Vec<_>>>()?;
+    let services: Vec<_> = services.iter().map(String::as_str).collect();
+
+    if services.len() > 1 {
+        stop_services(&services, cfg)?;
+    } else {
+        for service in services {
+            stop_service(service, cfg)?;
+        }
+    }
+    tprintstep!(style("Service(s) stopped").bold().green(), 2, 2, SUCCESS);
+    Ok(())
+}
+
+/// Stops all running (or paused) services except the given ones.
+///
+/// Services are stopped one at a time, dependent services before their
+/// dependencies, so that e.g. a database is not stopped from under the
+/// applications using it.
+pub fn stop_all_cmd(except: Vec<&str>, cfg: &ClientConfig) -> Result<()> {
+    tprintstep!("Stopping service(s)...", 1, 2, HOUR_GLASS);
+    let client = cfg.client();
+    let except = except
+        .iter()
+        .map(|s| client.expand_module_name(s))
+        .collect::<Result<Vec<_>>>()?;
+
+    let filter = ModuleFilter {
+        kind: Some(ModuleKind::Service),
+        status: None,
+        namespace: None,
+    };
+    let running: Vec<String> = client
+        .list_modules(&filter)?
+        .status
+        .into_iter()
+        .filter(|s| {
+            matches!(
+                s.status,
+                ApiModuleRunStatus::RUNNING | ApiModuleRunStatus::PAUSED
+            )
+        })
+        .map(|s| s.name)
+        .filter(|name| !except.contains(name))
+        .collect();
+
+    for service in stop_order(&running, cfg)? {
         stop_service(service, cfg)?;
     }
     tprintstep!(style("Service(s) stopped").bold().green(), 2, 2, SUCCESS);
     Ok(())
 }
 
+/// Orders services so that each is stopped before the services it depends
+/// on (reverse topological order).
+///
+/// Services which are not in the module definitions are stopped first, in the
+/// order they were given.
+fn stop_order<'a>(
+    services: &'a [String],
+    cfg: &ClientConfig,
+) -> Result<Vec<&'a str>> {
+    let mut module_defs = read_module_definitions(cfg)?;
+    remove_checks(&mut module_defs);
+    let known = module_names_set(&module_defs);
+
+    let (defined, mut order): (Vec<&str>, Vec<&str>) = services
+        .iter()
+        .map(String::as_str)
+        .partition(|name| known.contains(name));
+
+    let graph = DependencyGraph::from(&module_defs, &defined);
+    for node in graph.dependency_sort()?.into_iter().rev() {
+        if let Some(name) = defined.iter().find(|n| **n == node.value.name) {
+            order.push(*name);
+        }
+    }
+    Ok(order)
+}
+
 fn stop_service(service: &str, cfg: &ClientConfig) -> Result<()> {
     let message = format!("Stopping {}", style(service).white().bold());
-    let spin_opt = SpinnerOptions::new(message);
+    let spin_opt =
+        SpinnerOptions::new(message).no_spinner(cfg.no_spinner);
 
     let wu = WaitUntil::new(&spin_opt);
     wu.spin_until_status(|| {
         let status = style("(Stopped)").white().dim().bold().to_string();
-        request::stop_module(service, &cfg.daemon_url)?;
+        cfg.client().stop_module(service)?;
         Ok(WaitResult::from((), status))
     })?;
 
     Ok(())
 }
+
+/// Stops multiple services at once.
+fn stop_services(services: &[&str], cfg: &ClientConfig) -> Result<()> {
+    let message = format!(
+        "Stopping {}",
+        style(services.join(", ")).white().bold()
+    );
+    let spin_opt =
+        SpinnerOptions::new(message).no_spinner(cfg.no_spinner);
+
+    let wu = WaitUntil::new(&spin_opt);
+    let response = wu.spin_until_status(|| {
+        let response = cfg.client().stop_modules(services)?;
+        let status = if response.results.iter().all(|r| r.success) {
+            style("(Stopped)").white().dim().bold().to_string()
+        } else {
+            cfail!("(Failed)").to_string()
+        };
+        Ok(WaitResult::from(response, status))
+    })?;
+
+    let failed: Vec<_> =
+        response.results.iter().filter(|r| !r.success).collect();
+    if !failed.is_empty() {
+        let errors: Vec<_> = failed
+            .iter()
+            .map(|r| {
+                format!(
+                    "{}: {}",
+                    r.name,
+                    r.message.as_deref().unwrap_or("Unknown error")
+                )
+            })
+            .collect();
+        bail!("Failed to stop some services\n{}", errors.join("\n"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use crate::client::commands::deployer::ModuleDeploymentPlan;
+use crate::client::module::{ModuleDefinition, ModuleMarker};
+use crate::dependency::DependencyGraph;
+use anyhow::{bail, Error, Result};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+pub const DEPLOY_STRATEGIES: [&str; 2] = ["eager", "lazy"];
+
+/// How the dependencies of the selected modules are deployed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DeployStrategy {
+    /// Deploy all dependencies of the selected modules.
+    Eager,
+    /// Only deploy the dependencies of modules which are going to be
+    /// deployed, see [LazyDeployStrategy].
+    Lazy,
+}
+
+impl Default for DeployStrategy {
+    fn default() -> Self {
+        DeployStrategy::Eager
+    }
+}
+
+impl FromStr for DeployStrategy {
+    type Err = Error;
+
+    fn from_str(strategy: &str) -> Result<Self> {
+        match strategy {
+            "eager" => Ok(DeployStrategy::Eager),
+            "lazy" => Ok(DeployStrategy::Lazy),
+            _ => bail!("Unknown deploy strategy '{}'", strategy),
+        }
+    }
+}
+
+/// Builds the set of modules to deploy incrementally, starting from the
+/// selected modules.
+///
+/// The dependencies of a module are only included if the module itself is
+/// going to be deployed according to the plan. Modules which are already
+/// deployed are kept (and skipped as usual), but do not pull in their own
+/// dependencies.
+pub struct LazyDeployStrategy<'a> {
+    graph: &'a DependencyGraph<'a, ModuleDefinition, ModuleMarker>,
+    plan: &'a ModuleDeploymentPlan,
+    force: bool,
+}
+
+impl<'a> LazyDeployStrategy<'a> {
+    pub fn new(
+        graph: &'a DependencyGraph<'a, ModuleDefinition, ModuleMarker>,
+        plan: &'a ModuleDeploymentPlan,
+        force: bool,
+    ) -> Self {
+        Self { graph, plan, force }
+    }
+
+    /// Returns the names of the modules to deploy in order to deploy the
+    /// given modules.
+    pub fn resolve(&self, targets: &[&str]) -> HashSet<String> {
+        let mut included = HashSet::new();
+        let mut pending: Vec<&str> = targets.to_vec();
+        while let Some(name) = pending.pop() {
+            if !included.insert(name.to_string()) {
+                continue;
+            }
+            if self.will_deploy(name) {
+                pending.extend(self.graph.dependencies_of(name));
+            }
+        }
+        included
+    }
+
+    fn will_deploy(&self, name: &str) -> bool {
+        self.force
+            || self.plan.should_deploy.get(name).copied().unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::definitions::parse_from_yaml_str;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    const MODULES: &str = "
+kind: Service
+name: web
+command: [web]
+dependencies: [api]
+---
+kind: Service
+name: api
+command: [api]
+dependencies: [db]
+---
+kind: Service
+name: db
+command: [db]
+";
+
+    fn resolve(deployed: &[&str], force: bool) -> Vec<String> {
+        let modules = parse_from_yaml_str(MODULES, Path::new(".")).unwrap();
+        let selected = ["web"];
+        let graph = DependencyGraph::from(&modules, &selected);
+        let should_deploy: HashMap<_, _> = deployed
+            .iter()
+            .map(|name| (name.to_string(), false))
+            .collect();
+        let plan = ModuleDeploymentPlan { should_deploy };
+
+        let mut included: Vec<_> = LazyDeployStrategy::new(&graph, &plan, force)
+            .resolve(&selected)
+            .into_iter()
+            .collect();
+        included.sort();
+        included
+    }
+
+    #[test]
+    fn test_deploys_dependencies_of_modules_to_deploy() {
+        assert_eq!(resolve(&[], false), vec!["api", "db", "web"]);
+    }
+
+    #[test]
+    fn test_skips_dependencies_of_deployed_modules() {
+        assert_eq!(resolve(&["web"], false), vec!["web"]);
+        assert_eq!(resolve(&["api"], false), vec!["api", "web"]);
+    }
+
+    #[test]
+    fn test_force_deploys_all_dependencies() {
+        assert_eq!(
+            resolve(&["web", "api", "db"], true),
+            vec!["api", "db", "web"]
+        );
+    }
+}
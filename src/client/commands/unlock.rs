@@ -0,0 +1,22 @@
+use crate::client::cli::ClientConfig;
+use crate::client::request::CartelClientTrait;
+use anyhow::{bail, Result};
+
+/// Releases the deploy lock of the daemon, eg. when a deployment with
+/// `--lock` was killed before it could release the lock.
+pub fn unlock_cmd(force: bool, cfg: &ClientConfig) -> Result<()> {
+    if !force {
+        bail!(
+            "The deploy lock is released by the deployment holding it, use \
+            --force to release it regardless"
+        );
+    }
+    match cfg.client().force_release_lock()?.holder {
+        Some(holder) => println!(
+            "Released the deploy lock held by {} (pid {})",
+            holder.user, holder.pid
+        ),
+        None => println!("The deploy lock was not held"),
+    }
+    Ok(())
+}
@@ -1,7 +1,10 @@
-use crate::client::module::{ModuleKind, Probe, TermSignal};
+use crate::client::module::{
+    ModuleKind, PortMapping, Probe, SocketActivationConfig, TermSignal,
+};
 use crate::daemon::api::{
-    ApiExeProbe, ApiLogLineProbe, ApiModuleKind, ApiNetworkProbe, ApiProbe,
-    ApiProbeStatus, ApiTermSignal,
+    ApiExeProbe, ApiHttpProbe, ApiLogLineProbe, ApiModuleKind, ApiNetworkProbe,
+    ApiPortMapping, ApiProbe, ApiProbeStatus, ApiSocketActivation,
+    ApiTermSignal,
 };
 use crate::daemon::planner::MonitorStatus;
 
@@ -10,9 +13,30 @@ impl From<&ModuleKind> for ApiModuleKind {
         match kind {
             ModuleKind::Service => ApiModuleKind::Service,
             ModuleKind::Task => ApiModuleKind::Task,
+            ModuleKind::OneShot => ApiModuleKind::OneShot,
             ModuleKind::Check => ApiModuleKind::Task,
             ModuleKind::Group => ApiModuleKind::Task,
             ModuleKind::Shell => ApiModuleKind::Task,
+            ModuleKind::WaitFor => ApiModuleKind::Task,
+        }
+    }
+}
+
+impl From<&PortMapping> for ApiPortMapping {
+    fn from(port: &PortMapping) -> ApiPortMapping {
+        ApiPortMapping {
+            host_port: port.host_port,
+            container_port: port.container_port,
+            protocol: port.protocol.clone(),
+        }
+    }
+}
+
+impl From<&SocketActivationConfig> for ApiSocketActivation {
+    fn from(socket: &SocketActivationConfig) -> ApiSocketActivation {
+        ApiSocketActivation {
+            path: socket.path.clone(),
+            port: socket.port,
         }
     }
 }
@@ -35,6 +59,8 @@ impl From<&MonitorStatus> for ApiProbeStatus {
             MonitorStatus::RetriesExceeded => Self::RetriesExceeded,
             MonitorStatus::Successful => Self::Successful,
             MonitorStatus::Pending => Self::Pending,
+            MonitorStatus::Starting => Self::Starting,
+            MonitorStatus::Ready => Self::Ready,
         }
     }
 }
@@ -56,6 +82,10 @@ impl From<&Probe> for ApiProbe {
                 hostname: net.host.clone(),
                 port: net.port,
             }),
+            Probe::Http(http) => ApiProbe::Http(ApiHttpProbe {
+                retries: http.retries,
+                url: http.url.clone(),
+            }),
         }
     }
 }
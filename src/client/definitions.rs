@@ -16,19 +16,11 @@ use std::io::Read;
 use std::option::Option;
 use std::path::{Path, PathBuf};
 
-/// Parse one or more modules from the given string.
-///
-/// Parses module definitions in YAML format from the given string. One or more
-/// definitions may be provided by separating with three dashes (---).
+/// Parse one or more modules from the given string, as they are written.
 ///
-/// # Arguments
-/// * `source` - The source string to parse from. It may contain
-/// one or more modules separated by '---'
-/// * `path` - The path to the *directory* of the module definitions file.
-pub fn parse_from_yaml_str(
-    source: &str,
-    path: &Path,
-) -> Result<Vec<ModuleDefinition>> {
+/// Unlike [parse_from_yaml_str] the paths of the modules (eg. their
+/// `working_dir`) are not resolved, nor is their kind set.
+pub fn parse_raw_from_yaml_str(source: &str) -> Result<Vec<ModuleDefinition>> {
     let mut parsed: Vec<ModuleDefinition> = vec![];
     for (idx, document) in
         serde_yaml::Deserializer::from_str(source).enumerate()
@@ -55,6 +47,23 @@ pub fn parse_from_yaml_str(
 
         parsed.push(module);
     }
+    Ok(parsed)
+}
+
+/// Parse one or more modules from the given string.
+///
+/// Parses module definitions in YAML format from the given string. One or more
+/// definitions may be provided by separating with three dashes (---).
+///
+/// # Arguments
+/// * `source` - The source string to parse from. It may contain
+/// one or more modules separated by '---'
+/// * `path` - The path to the *directory* of the module definitions file.
+pub fn parse_from_yaml_str(
+    source: &str,
+    path: &Path,
+) -> Result<Vec<ModuleDefinition>> {
+    let mut parsed = parse_raw_from_yaml_str(source)?;
     for mut m in parsed.iter_mut() {
         match &mut m.inner {
             InnerDefinition::Service(ref mut def) => {
@@ -73,6 +82,11 @@ pub fn parse_from_yaml_str(
                     update_path(&mut exec.working_dir, path)?;
                 }
             }
+            InnerDefinition::OneShot(def) => {
+                m.kind = ModuleKind::OneShot;
+                def.name = m.name.clone();
+                update_path(&mut def.working_dir, path)?;
+            }
             InnerDefinition::Check(def) => {
                 m.kind = ModuleKind::Check;
                 def.name = m.name.clone();
@@ -87,6 +101,10 @@ pub fn parse_from_yaml_str(
                 update_path(&mut def.working_dir, path)?;
                 def.name = format!("{}-service-shell", def.service);
             }
+            InnerDefinition::WaitFor(def) => {
+                m.kind = ModuleKind::WaitFor;
+                def.name = m.name.clone();
+            }
         }
     }
     Ok(parsed)
@@ -256,7 +274,7 @@ fn locate_override_file(
 /// Open the given override module definitions file.
 ///
 /// If no file is given, an attempt to locate the file is done instead.
-fn open_override_file(
+pub fn open_override_file(
     mod_def_file: &Path,
     cfg: &ClientConfig,
 ) -> Result<Option<(File, PathBuf)>> {
@@ -286,7 +304,6 @@ fn parse_module_def_file(
             .with_context(|| "Failed to read module definitions")?;
 
     validate_modules_unique(&module_defs)?;
-    validate_dependencies_exist(&module_defs)?;
     validate_fields(&module_defs)?;
 
     Ok(module_defs)
@@ -328,13 +345,28 @@ fn merge_module_definitions(
 /// The search for the module definitions file begins at the current directory,
 /// and walks upwards until a file is found. In case of a file not located then
 /// the default directory from the client config is used.
+///
+/// Dependencies are validated after merging, so modules in the override file
+/// may depend on modules from the main file.
 pub fn read_module_definitions(
     cfg: &ClientConfig,
+) -> Result<Vec<ModuleDefinition>> {
+    let module_defs = read_module_definitions_unvalidated(cfg)?;
+    validate_dependencies_exist(&module_defs)?;
+    Ok(module_defs)
+}
+
+/// Read module definitions from the filesystem, without validating that their
+/// dependencies exist.
+///
+/// See [`read_module_definitions`].
+pub fn read_module_definitions_unvalidated(
+    cfg: &ClientConfig,
 ) -> Result<Vec<ModuleDefinition>> {
     let (mod_def_file, path) =
         open_module_file(&cfg.module_file, &cfg.default_dir)?;
 
-    let module_defs = parse_module_def_file(mod_def_file, path.as_path())?;
+    let mut module_defs = parse_module_def_file(mod_def_file, path.as_path())?;
 
     if let Some((override_file, override_file_path)) =
         open_override_file(path.as_path(), cfg)?
@@ -342,7 +374,8 @@ pub fn read_module_definitions(
         let override_module_defs =
             parse_module_def_file(override_file, override_file_path.as_path())
                 .context("Failed while parsing overrides file")?;
-        return Ok(merge_module_definitions(module_defs, override_module_defs));
+        module_defs =
+            merge_module_definitions(module_defs, override_module_defs);
     }
 
     Ok(module_defs)
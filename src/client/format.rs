@@ -0,0 +1,121 @@
+use crate::daemon::api::{ApiModuleRunStatus, ApiModuleStatus};
+use anyhow::{bail, Error, Result};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+pub const SORT_FIELDS: [&str; 4] = ["name", "status", "uptime", "restarts"];
+
+/// The field by which module statuses are sorted.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortField {
+    Name,
+    /// Running modules first, followed by modules which are not.
+    Status,
+    /// The time since the module's current status, shortest first.
+    Uptime,
+    Restarts,
+}
+
+impl FromStr for SortField {
+    type Err = Error;
+
+    fn from_str(field: &str) -> Result<Self> {
+        match field {
+            "name" => Ok(SortField::Name),
+            "status" => Ok(SortField::Status),
+            "uptime" => Ok(SortField::Uptime),
+            "restarts" => Ok(SortField::Restarts),
+            _ => bail!("Unknown sort field '{}'", field),
+        }
+    }
+}
+
+/// Sorts module statuses by the given field, in descending order if `desc`
+/// is set. Modules which are equal on the field are sorted by name.
+pub fn sort_modules(
+    modules: &mut [ApiModuleStatus],
+    field: SortField,
+    desc: bool,
+) {
+    modules.sort_by(|a, b| {
+        let ordering = compare_by(a, b, field);
+        let ordering = if desc { ordering.reverse() } else { ordering };
+        ordering.then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+fn compare_by(
+    a: &ApiModuleStatus,
+    b: &ApiModuleStatus,
+    field: SortField,
+) -> Ordering {
+    match field {
+        SortField::Name => a.name.cmp(&b.name),
+        SortField::Status => status_rank(a.status).cmp(&status_rank(b.status)),
+        // A later status change means a shorter uptime
+        SortField::Uptime => b.time_since_status.cmp(&a.time_since_status),
+        SortField::Restarts => a.restarts.cmp(&b.restarts),
+    }
+}
+
+fn status_rank(status: ApiModuleRunStatus) -> u8 {
+    match status {
+        ApiModuleRunStatus::RUNNING => 0,
+        ApiModuleRunStatus::PAUSED => 1,
+        ApiModuleRunStatus::WAITING => 2,
+        ApiModuleRunStatus::STOPPED => 3,
+        ApiModuleRunStatus::EXITED => 4,
+        ApiModuleRunStatus::COMPLETED => 5,
+        ApiModuleRunStatus::FAILED => 6,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn status(
+        name: &str,
+        status: ApiModuleRunStatus,
+        restarts: u32,
+    ) -> ApiModuleStatus {
+        ApiModuleStatus {
+            name: name.to_string(),
+            pid: 0,
+            status,
+            liveness_status: None,
+            exit_code: None,
+            time_since_status: 0,
+            command: None,
+            log_file_path: None,
+            restarts,
+            kind: None,
+            working_dir: None,
+            namespace: None,
+            ports: vec![],
+            version: None,
+        }
+    }
+
+    fn names(modules: &[ApiModuleStatus]) -> Vec<&str> {
+        modules.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_modules() {
+        let mut modules = vec![
+            status("c", ApiModuleRunStatus::STOPPED, 1),
+            status("a", ApiModuleRunStatus::RUNNING, 0),
+            status("b", ApiModuleRunStatus::RUNNING, 1),
+        ];
+
+        sort_modules(&mut modules, SortField::Name, false);
+        assert_eq!(names(&modules), vec!["a", "b", "c"]);
+
+        sort_modules(&mut modules, SortField::Status, true);
+        assert_eq!(names(&modules), vec!["c", "a", "b"]);
+
+        sort_modules(&mut modules, SortField::Restarts, true);
+        assert_eq!(names(&modules), vec!["b", "c", "a"]);
+    }
+}
@@ -1,13 +1,16 @@
 #[macro_use]
 pub mod terminal;
+pub mod check_executor;
 pub mod cli;
 pub mod cmd;
 pub mod commands;
 pub mod convert;
 pub mod definitions;
 pub mod emoji;
+pub mod format;
 pub mod module;
 pub mod process;
 pub mod progress;
 pub mod request;
+pub mod selector;
 pub mod validation;
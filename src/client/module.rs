@@ -1,33 +1,66 @@
 use crate::client::cmd::{shell_to_cmd, shell_to_cmd_interactive};
 use crate::dependency::{
     DependencyEdge, DependencyNode, EdgeDirection, WithDependencies, WithKey,
+    WithKind,
 };
-use serde::Deserialize;
+use serde::de::{Error, Unexpected};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::{fmt, iter};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct ModuleDefinition {
     pub name: String,
-    #[serde(skip_deserializing)]
+    #[serde(skip)]
     pub kind: ModuleKind,
+    /// Arbitrary key/value pairs used to select modules (eg. in
+    /// `deploy --selector`).
+    #[serde(default = "HashMap::new")]
+    pub labels: HashMap<String, String>,
+    /// Markdown documentation of the module (eg. what it does, who owns it
+    /// and how to debug it), shown by `modules show`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub documentation: Option<String>,
     #[serde(flatten)]
     pub inner: InnerDefinition,
 }
 
-#[derive(Deserialize, Debug)]
+/// The definition of a module, depending on its kind.
+///
+/// New kinds of modules may be added, so matches outside of this crate must
+/// have a wildcard arm:
+///
+/// ```compile_fail
+/// use cartel::client::module::InnerDefinition;
+///
+/// fn is_runnable(inner: &InnerDefinition) -> bool {
+///     match inner {
+///         InnerDefinition::Task(_)
+///         | InnerDefinition::Service(_)
+///         | InnerDefinition::OneShot(_) => true,
+///         InnerDefinition::Check(_)
+///         | InnerDefinition::Group(_)
+///         | InnerDefinition::Shell(_)
+///         | InnerDefinition::WaitFor(_) => false,
+///     }
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug)]
 #[serde(tag = "kind")]
+#[non_exhaustive]
 pub enum InnerDefinition {
     Task(ServiceOrTaskDefinition),
     Service(ServiceOrTaskDefinition),
+    OneShot(ServiceOrTaskDefinition),
     Check(CheckDefinition),
     Group(GroupDefinition),
     Shell(ShellDefinition),
+    WaitFor(WaitForDefinition),
 }
 
 /// The type of the module.
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub enum ModuleKind {
     /// A task is a module with a limited lifetime, used to perform some
     /// temporary operation or some setup.
@@ -35,6 +68,9 @@ pub enum ModuleKind {
     /// A service is a longer running module. It's lifetime will be managed and
     /// can be started, stopped independently.
     Service,
+    /// A one-shot is a task which runs at most once for the lifetime of the
+    /// daemon, after all other modules of a deployment are deployed.
+    OneShot,
     /// A check is a module which defines some condition which must evaluate to
     /// true before some service can be operated.
     Check,
@@ -43,12 +79,15 @@ pub enum ModuleKind {
     Group,
     /// A shell is a module which allows for opening a shell to some service.
     Shell,
+    /// A wait-for is a module without a process of its own, which blocks the
+    /// deployment until another service is healthy.
+    WaitFor,
 }
 
 /// The choice of terminating signal to use when terminating the process.
 ///
 /// Note: Only implemented for Unix based systems.
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub enum TermSignal {
     /// Translates to SIGKILL on Unix based systems.
     KILL,
@@ -75,17 +114,19 @@ impl fmt::Display for ModuleKind {
         match self {
             Self::Task => write!(f, "Task"),
             Self::Service => write!(f, "Service"),
+            Self::OneShot => write!(f, "OneShot"),
             Self::Check => write!(f, "Check"),
             Self::Group => write!(f, "Group"),
             Self::Shell => write!(f, "Shell"),
+            Self::WaitFor => write!(f, "WaitFor"),
         }
     }
 }
 
 /// A definition of a module for version 1 (V1) of the daemon.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ServiceOrTaskDefinition {
-    #[serde(default = "String::default")]
+    #[serde(default = "String::default", skip_serializing)]
     pub name: String,
     /// The command used to run the service / task.
     #[serde(default = "Vec::new")]
@@ -110,8 +151,10 @@ pub struct ServiceOrTaskDefinition {
     /// sequentially.
     #[serde(default = "Vec::new")]
     pub ordered_dependencies: Vec<String>,
-    /// A list of dependencies of the service / task that must be deployed
-    /// _after_ this service/task deploys.
+    /// A list of modules this service / task should be deployed _after_.
+    ///
+    /// Unlike `dependencies` these only affect the ordering when the modules
+    /// are part of the same deployment, and are never deployed because of it.
     #[serde(default = "Vec::new")]
     pub after: Vec<String>,
     /// A list of tasks to perform after the services readiness probe has passed.
@@ -134,6 +177,12 @@ pub struct ServiceOrTaskDefinition {
     pub always_await_readiness_probe: bool,
     /// Definition of a readiness probe for the service.
     pub readiness_probe: Option<Probe>,
+    /// A command which exits with zero once the service is ready. This is a
+    /// shorthand for an executable readiness probe run from `working_dir`.
+    pub readiness_command: Option<Vec<String>>,
+    /// An HTTP URL which responds with a 2xx status once the service is
+    /// ready. This is a shorthand for an HTTP readiness probe.
+    pub health_check_endpoint: Option<String>,
     /// Definition of a liveness probe for the service.
     pub liveness_probe: Option<Probe>,
     /// Duration in seconds before a task is considered as failed (currently
@@ -143,12 +192,87 @@ pub struct ServiceOrTaskDefinition {
     /// in an interactive shell based on the one the client is running on.
     #[serde(default = "default_interactive_shell")]
     pub interactive_shell: bool,
+    /// If enabled the stdout of a task is captured by the daemon and printed
+    /// once the task completes (only for tasks).
+    #[serde(default = "default_capture_output")]
+    pub capture_output: bool,
+    /// The deployment priority of the service / task amongst modules which
+    /// are deployed at the same time. Lower values are deployed first.
+    #[serde(default = "default_priority")]
+    pub priority: i32,
+    /// Number of seconds to wait after the service is deployed before its
+    /// readiness is polled (only for services).
+    #[serde(default = "default_healthcheck_grace_period_secs")]
+    pub healthcheck_grace_period_secs: u64,
+    /// The maximum amount of memory in megabytes the service / task may use.
+    /// Only enforced on Linux (through cgroups v2).
+    pub max_memory_mb: Option<u64>,
+    /// The maximum share of a single CPU the service / task may use, in
+    /// percent (1-100). Only enforced on Linux (through cgroups v2).
+    pub cpu_quota_percent: Option<u32>,
+    /// The path of a network namespace (eg. `/var/run/netns/myns`) to run the
+    /// service / task in. Only supported on Linux.
+    pub network_namespace: Option<String>,
+    /// A command to run in order to stop the service gracefully, instead of
+    /// sending it the `termination_signal` (only for services).
+    pub shutdown_command: Option<Vec<String>>,
+    /// Number of seconds to wait for the service to exit after running its
+    /// `shutdown_command`, before escalating to `SIGTERM` and then `SIGKILL`.
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+    /// Whether the service / task inherits the environment of the daemon.
+    /// When disabled it only gets the variables given in `environment`.
+    #[serde(default = "default_inherit_env")]
+    pub inherit_env: bool,
+    /// The log level of the service / task (eg. `debug`), given to it through
+    /// the `LOG_LEVEL` and `RUST_LOG` environment variables.
+    pub log_level: Option<String>,
+    /// The ports the module exposes. Only informative, the daemon does not
+    /// enforce them.
+    #[serde(default = "Vec::new")]
+    pub ports: Vec<PortMapping>,
+    /// The file creation mask of the service / task process, written in
+    /// octal (eg. `0o027` or `"027"`).
+    #[serde(default, deserialize_with = "umask_from_octal")]
+    pub umask: Option<u32>,
+    /// Strings which are replaced with `[REDACTED]` in the stdout and stderr
+    /// of the service / task before they are written to the log file.
+    #[serde(default = "Vec::new")]
+    pub stdout_filter: Vec<String>,
+    /// The version of the definition (eg. `1.2.0`), shown by `cartel ps
+    /// --verbose` once deployed. Free-form, but semver is recommended.
+    pub version: Option<String>,
+    /// A socket the daemon listens on and passes to the service when starting
+    /// it, following the systemd socket activation convention (only for
+    /// services).
+    pub socket_activation: Option<SocketActivationConfig>,
 }
 
-#[derive(Debug, Deserialize)]
+/// A port exposed by a module, eg. `8080:80/tcp`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PortMapping {
+    /// The port reachable from the host.
+    pub host_port: u16,
+    /// The port the module listens on.
+    pub container_port: u16,
+    #[serde(default = "default_port_protocol")]
+    pub protocol: String,
+}
+
+/// The socket of a socket activated service. Exactly one of `path` (for a
+/// Unix socket) or `port` (for a TCP socket on `127.0.0.1`) must be given.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct SocketActivationConfig {
+    /// The path of the Unix socket.
+    pub path: Option<String>,
+    /// The TCP port to listen on.
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 /// A definition of a command which spawns a shell
 pub struct ShellDefinition {
-    #[serde(default = "String::default")]
+    #[serde(default = "String::default", skip_serializing)]
     pub name: String,
     /// The service this shell is for
     pub service: String,
@@ -168,15 +292,16 @@ pub struct ShellDefinition {
     pub working_dir: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Probe {
     Exec(ExecutableProbe),
     LogLine(LogLineProbe),
     Net(NetworkProbe),
+    Http(HttpProbe),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ExecutableProbe {
     /// Number of retries before the probe is considered failed.
     #[serde(default = "default_probe_retries")]
@@ -191,7 +316,7 @@ pub struct ExecutableProbe {
     pub working_dir: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct LogLineProbe {
     /// Number of retries before the probe is considered failed.
     #[serde(default = "default_probe_retries")]
@@ -200,7 +325,7 @@ pub struct LogLineProbe {
     pub line_regex: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct NetworkProbe {
     /// Number of retries before the probe is considered failed.
     #[serde(default = "default_probe_retries")]
@@ -211,21 +336,52 @@ pub struct NetworkProbe {
     pub port: u16,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HttpProbe {
+    /// Number of retries before the probe is considered failed.
+    #[serde(default = "default_probe_retries")]
+    pub retries: u32,
+    /// The URL to send a GET request to. A 2xx response is considered
+    /// healthy. Only plain `http://` URLs are supported.
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GroupDefinition {
-    #[serde(default = "String::default")]
+    #[serde(default = "String::default", skip_serializing)]
     pub name: String,
+    /// A short description of the group, shown next to its name by
+    /// `cartel modules list`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     /// A list of dependencies of the group.
     #[serde(default = "Vec::new")]
     pub dependencies: Vec<String>,
     /// A list of checks to perform.
     #[serde(default = "Vec::new")]
     pub checks: Vec<String>,
+    /// Whether all the dependencies of the group must be deployed along with
+    /// it (eg. when deploying with `--only-selected`).
+    #[serde(default)]
+    pub require_all: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WaitForDefinition {
+    #[serde(default = "String::default", skip_serializing)]
+    pub name: String,
+    /// The service to wait for. It is deployed before this module, which
+    /// then waits until its readiness probe succeeds.
+    pub target: String,
+    /// How long to wait for the target to be healthy before failing the
+    /// deployment.
+    #[serde(default = "default_wait_for_timeout_secs")]
+    pub timeout_secs: u64,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CheckDefinition {
-    #[serde(default = "String::default")]
+    #[serde(default = "String::default", skip_serializing)]
     pub name: String,
     /// A short description of the check checks for.
     pub about: String,
@@ -242,9 +398,25 @@ pub struct CheckDefinition {
     /// An detailed error message to display the user instructing how to fix the
     /// issue the check is concerned with.
     pub help: String,
+    /// Number of times to retry the check before it is considered failed.
+    #[serde(default = "default_check_retry_count")]
+    pub retry_count: u32,
+    /// Delay in milliseconds between each retry of the check.
+    #[serde(default = "default_check_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+    /// Other checks which must pass before this check is attempted.
+    #[serde(default = "Vec::new")]
+    pub depends_on: Vec<String>,
+    /// Whether to run the check with the `environment` of the module which
+    /// requires it.
+    #[serde(default)]
+    pub exec_in_module_env: bool,
+    /// Only run the check for modules of these kinds (eg. only for services).
+    /// The check runs for modules of any kind if not given.
+    pub only_for_kind: Option<Vec<ModuleKind>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SuggestedFixDefinition {
     /// A message shown to the user before the choose to accept the fix.
     pub message: String,
@@ -276,9 +448,26 @@ impl ServiceOrTaskDefinition {
         termination_signal: TermSignal,
         always_await_readiness_probe: bool,
         readiness_probe: Option<Probe>,
+        readiness_command: Option<Vec<String>>,
+        health_check_endpoint: Option<String>,
         liveness_probe: Option<Probe>,
         timeout: Option<u64>,
         interactive_shell: bool,
+        capture_output: bool,
+        priority: i32,
+        healthcheck_grace_period_secs: u64,
+        max_memory_mb: Option<u64>,
+        cpu_quota_percent: Option<u32>,
+        network_namespace: Option<String>,
+        shutdown_command: Option<Vec<String>>,
+        shutdown_timeout_secs: u64,
+        inherit_env: bool,
+        log_level: Option<String>,
+        ports: Vec<PortMapping>,
+        umask: Option<u32>,
+        stdout_filter: Vec<String>,
+        version: Option<String>,
+        socket_activation: Option<SocketActivationConfig>,
     ) -> ServiceOrTaskDefinition {
         ServiceOrTaskDefinition {
             name,
@@ -297,9 +486,26 @@ impl ServiceOrTaskDefinition {
             checks,
             always_await_readiness_probe,
             readiness_probe,
+            readiness_command,
+            health_check_endpoint,
             liveness_probe,
             timeout,
             interactive_shell,
+            capture_output,
+            priority,
+            healthcheck_grace_period_secs,
+            max_memory_mb,
+            cpu_quota_percent,
+            network_namespace,
+            shutdown_command,
+            shutdown_timeout_secs,
+            inherit_env,
+            log_level,
+            ports,
+            umask,
+            stdout_filter,
+            version,
+            socket_activation,
         }
     }
 
@@ -318,9 +524,39 @@ impl ServiceOrTaskDefinition {
             self.command.clone()
         }
     }
+
+    /// Get the probe used to determine the readiness of this service.
+    ///
+    /// A `readiness_command` is turned into an executable probe and a
+    /// `health_check_endpoint` into an HTTP probe, otherwise the
+    /// `readiness_probe` is used as is.
+    pub fn readiness(&self) -> Option<Probe> {
+        if let Some(command) = &self.readiness_command {
+            return Some(Probe::Exec(ExecutableProbe {
+                retries: default_probe_retries(),
+                command: command.clone(),
+                shell: None,
+                working_dir: self.working_dir.clone(),
+            }));
+        }
+        match &self.health_check_endpoint {
+            Some(url) => Some(Probe::Http(HttpProbe {
+                retries: default_probe_retries(),
+                url: url.clone(),
+            })),
+            None => self.readiness_probe.clone(),
+        }
+    }
 }
 
 impl CheckDefinition {
+    /// Whether the check should run for a module of the given kind.
+    pub fn applies_to(&self, kind: &ModuleKind) -> bool {
+        self.only_for_kind
+            .as_ref()
+            .map_or(true, |kinds| kinds.contains(kind))
+    }
+
     /// Get the execution command of this check.
     ///
     /// If no command was provided then the `shell` field is used to get an
@@ -376,6 +612,46 @@ impl ShellDefinition {
     }
 }
 
+impl ModuleDefinition {
+    /// Get the deployment priority of this module.
+    ///
+    /// Only services and tasks can set a priority, all other modules have the
+    /// default priority of zero.
+    pub fn priority(&self) -> i32 {
+        match &self.inner {
+            InnerDefinition::Service(svc_or_task)
+            | InnerDefinition::Task(svc_or_task)
+            | InnerDefinition::OneShot(svc_or_task) => svc_or_task.priority,
+            _ => default_priority(),
+        }
+    }
+
+    /// The checks which must pass before this module is deployed.
+    pub fn checks(&self) -> &[String] {
+        match &self.inner {
+            InnerDefinition::Group(group) => &group.checks,
+            InnerDefinition::Service(svc_or_task)
+            | InnerDefinition::Task(svc_or_task)
+            | InnerDefinition::OneShot(svc_or_task) => &svc_or_task.checks,
+            _ => &[],
+        }
+    }
+
+    /// The first line of the documentation of this module, if any.
+    pub fn summary(&self) -> Option<&str> {
+        self.documentation
+            .as_deref()?
+            .lines()
+            .map(|line| line.trim_start_matches('#').trim())
+            .find(|line| !line.is_empty())
+    }
+
+    /// Whether this module is a one-shot.
+    pub fn is_oneshot(&self) -> bool {
+        self.kind == ModuleKind::OneShot
+    }
+}
+
 impl Hash for ModuleDefinition {
     fn hash<S: Hasher>(&self, state: &mut S) {
         self.name.hash(state);
@@ -455,6 +731,19 @@ impl EdgeList for GroupDefinition {
     }
 }
 
+impl EdgeList for WaitForDefinition {
+    fn edges(&self) -> Vec<DependencyEdge<ModuleMarker>> {
+        // The target is waited on by this module, rather than when deployed
+        vec![DependencyEdge {
+            edge_src: self.name.clone(),
+            edge_dst: self.target.clone(),
+            is_weak: false,
+            direction: EdgeDirection::To,
+            marker: ModuleMarker::Instant,
+        }]
+    }
+}
+
 impl EdgeList for ServiceOrTaskDefinition {
     fn edges(&self) -> Vec<DependencyEdge<ModuleMarker>> {
         let edges: Vec<DependencyEdge<ModuleMarker>> = self
@@ -528,6 +817,8 @@ impl WithDependencies<ModuleMarker> for ModuleDefinition {
             InnerDefinition::Group(group) => group.edges(),
             InnerDefinition::Task(task) => task.edges(),
             InnerDefinition::Service(service) => service.edges(),
+            InnerDefinition::OneShot(oneshot) => oneshot.edges(),
+            InnerDefinition::WaitFor(wait_for) => wait_for.edges(),
             InnerDefinition::Check(_) => panic!("Check used as dependency"),
             InnerDefinition::Shell(_) => panic!("Shell used as dependency"),
         }
@@ -538,6 +829,55 @@ impl WithDependencies<ModuleMarker> for ModuleDefinition {
     }
 }
 
+impl WithKind for ModuleDefinition {
+    fn kind_name(&self) -> String {
+        self.kind.to_string()
+    }
+}
+
+impl Hash for CheckDefinition {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        self.name.hash(state);
+    }
+}
+
+impl PartialEq for CheckDefinition {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for CheckDefinition {}
+
+impl WithDependencies<()> for CheckDefinition {
+    fn dependencies(&self) -> Vec<DependencyEdge<()>> {
+        self.depends_on
+            .iter()
+            .map(|key| DependencyEdge {
+                edge_src: self.name.clone(),
+                edge_dst: key.clone(),
+                is_weak: false,
+                direction: EdgeDirection::To,
+                marker: (),
+            })
+            .collect()
+    }
+
+    fn is_group(&self) -> bool {
+        false
+    }
+}
+
+impl WithKey for CheckDefinition {
+    fn key(&self) -> String {
+        self.name.clone()
+    }
+
+    fn key_ref(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
 impl WithKey for ModuleDefinition {
     fn key(&self) -> String {
         self.name.clone()
@@ -560,6 +900,76 @@ fn default_interactive_shell() -> bool {
     false
 }
 
+fn default_capture_output() -> bool {
+    false
+}
+
+fn default_priority() -> i32 {
+    0
+}
+
+fn default_healthcheck_grace_period_secs() -> u64 {
+    0
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    10
+}
+
+fn default_inherit_env() -> bool {
+    true
+}
+
+fn default_port_protocol() -> String {
+    String::from("tcp")
+}
+
+fn default_wait_for_timeout_secs() -> u64 {
+    60
+}
+
+/// Deserializes a file creation mask, either from an octal number (eg.
+/// `0o027`) or from a string of octal digits (eg. `"027"` or `"0o027"`).
+fn umask_from_octal<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Umask {
+        Number(u32),
+        Octal(String),
+    }
+
+    let umask = match Umask::deserialize(deserializer)? {
+        Umask::Number(umask) => umask,
+        Umask::Octal(octal) => {
+            let digits = octal.strip_prefix("0o").unwrap_or(&octal);
+            u32::from_str_radix(digits, 8).map_err(|_| {
+                Error::invalid_value(
+                    Unexpected::Str(&octal),
+                    &"octal digits (eg. \"027\")",
+                )
+            })?
+        }
+    };
+    if umask > 0o777 {
+        return Err(Error::invalid_value(
+            Unexpected::Unsigned(umask.into()),
+            &"a mask between 0o000 and 0o777",
+        ));
+    }
+    Ok(Some(umask))
+}
+
+fn default_check_retry_count() -> u32 {
+    0
+}
+
+fn default_check_retry_delay_ms() -> u64 {
+    1000
+}
+
 pub fn module_names(modules: &[ModuleDefinition]) -> Vec<&str> {
     modules.iter().map(|m| m.name.as_str()).collect()
 }
@@ -634,3 +1044,34 @@ pub fn shell_for_service<'a>(
         _ => false,
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct WithUmask {
+        #[serde(default, deserialize_with = "umask_from_octal")]
+        umask: Option<u32>,
+    }
+
+    fn parse_umask(yaml: &str) -> Result<Option<u32>, serde_yaml::Error> {
+        serde_yaml::from_str::<WithUmask>(yaml).map(|m| m.umask)
+    }
+
+    #[test]
+    fn test_umask_from_octal() {
+        assert_eq!(parse_umask("umask: 0o027").unwrap(), Some(0o027));
+        assert_eq!(parse_umask("umask: \"027\"").unwrap(), Some(0o027));
+        assert_eq!(parse_umask("umask: \"0o022\"").unwrap(), Some(0o022));
+        assert_eq!(parse_umask("other: 1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_umask_from_octal_is_validated() {
+        assert!(parse_umask("umask: \"089\"").is_err());
+        assert!(parse_umask("umask: \"0o1000\"").is_err());
+        assert!(parse_umask("umask: 0o1000").is_err());
+        assert!(parse_umask("umask: \"\"").is_err());
+    }
+}
@@ -5,6 +5,7 @@ use crate::client::module::{
 use crate::command_builder::CommandBuilder;
 use crate::path;
 use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
 use std::process::ExitStatus;
 
 pub fn run_task(task_definition: &ServiceOrTaskDefinition) -> Result<()> {
@@ -29,7 +30,12 @@ pub fn run_task(task_definition: &ServiceOrTaskDefinition) -> Result<()> {
     Ok(())
 }
 
-pub fn run_check(check_definition: &CheckDefinition) -> Result<ExitStatus> {
+/// Runs a check, with the given environment variables in addition to the ones
+/// of the client.
+pub fn run_check(
+    check_definition: &CheckDefinition,
+    environment: &HashMap<String, String>,
+) -> Result<ExitStatus> {
     let working_dir = check_definition
         .working_dir
         .as_deref()
@@ -38,7 +44,8 @@ pub fn run_check(check_definition: &CheckDefinition) -> Result<ExitStatus> {
     let cmd_line = check_definition.cmd_line();
     let mut cmd = CommandBuilder::new(&cmd_line);
 
-    cmd.stdout_null()
+    cmd.env(environment)
+        .stdout_null()
         .stderr_null()
         .work_dir(working_dir.as_deref());
 
@@ -1,12 +1,29 @@
+use crate::thread_control::Control;
 use anyhow::Result;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// The error returned when a spinner was cancelled through its [Control].
+#[derive(Error, Debug)]
+#[error("cancelled")]
+pub struct Cancelled;
 
 #[derive(Clone)]
 pub struct SpinnerOptions {
     pub style: ProgressStyle,
     pub message: String,
     pub failure_message: String,
+    /// A line which replaces the whole line of the spinner when the
+    /// operation fails, instead of the failure message.
+    pub failure_line: Option<String>,
     pub step: Option<(u64, u64)>,
+    pub progress_total: Option<u64>,
+    /// Print plain lines when the operation starts and ends, instead of
+    /// rendering a spinner.
+    pub no_spinner: bool,
 }
 
 impl SpinnerOptions {
@@ -30,7 +47,10 @@ impl SpinnerOptions {
                 .red()
                 .bold()
                 .to_string(),
+            failure_line: None,
             step: None,
+            progress_total: None,
+            no_spinner: false,
         }
     }
 
@@ -46,11 +66,97 @@ impl SpinnerOptions {
         self
     }
 
+    /// Replaces the whole spinner line with the given message, rendered in
+    /// red, when the operation fails.
+    pub fn with_failure_message(mut self, msg: &str) -> SpinnerOptions {
+        self.failure_line = Some(console::style(msg).red().to_string());
+        self
+    }
+
+    /// Disables the spinner, in favour of printing plain lines when the
+    /// operation starts and ends (eg. for terminals which do not handle the
+    /// spinner's control sequences).
+    pub fn no_spinner(mut self, no_spinner: bool) -> SpinnerOptions {
+        self.no_spinner = no_spinner;
+        self
+    }
+
+    /// Creates a progress bar for the spinner, which is hidden if the spinner
+    /// is disabled.
+    ///
+    /// Progress bars added to a `MultiProgress` are instead hidden along with
+    /// it.
+    pub fn progress_bar(&self) -> ProgressBar {
+        if self.no_spinner {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new(std::u64::MAX)
+        }
+    }
+
     /// Set the progress bar style.
     pub fn style(mut self, style: ProgressStyle) -> SpinnerOptions {
         self.style = style;
         self
     }
+
+    /// Render a progress bar next to the spinner.
+    ///
+    /// The progress is reported through the [ProgressHandle] given to
+    /// [WaitUntil::spin_until_progress].
+    pub fn with_progress(mut self, total: u64) -> SpinnerOptions {
+        self.style = ProgressStyle::default_bar()
+            .template("{prefix:.bold.dim} {spinner} {wide_msg} [{bar:25}]");
+        self.progress_total = Some(total);
+        self
+    }
+}
+
+/// A handle used to report the progress of an operation.
+#[derive(Clone)]
+pub struct ProgressHandle {
+    pb: ProgressBar,
+    message: String,
+}
+
+impl ProgressHandle {
+    /// Shows a status next to the message of the spinner while the operation
+    /// is still in progress (eg. the current attempt).
+    pub fn set_status(&self, status: &str) {
+        self.pb.set_message(format!("{} {}", self.message, status));
+    }
+
+    /// Sets the current progress.
+    ///
+    /// This only renders if the spinner was created with
+    /// [SpinnerOptions::with_progress].
+    pub fn set_progress(&self, current: u64) {
+        self.pb.set_position(current);
+    }
+
+    /// Reports the number of seconds elapsed as the progress while the closure
+    /// runs.
+    pub fn track_elapsed<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let done = Arc::new(AtomicBool::new(false));
+        let ticker = {
+            let done = Arc::clone(&done);
+            let handle = self.clone();
+            let start = Instant::now();
+            std::thread::spawn(move || {
+                while !done.load(Ordering::SeqCst) {
+                    handle.set_progress(start.elapsed().as_secs());
+                    std::thread::sleep(Duration::from_millis(250));
+                }
+            })
+        };
+        let result = f();
+        done.store(true, Ordering::SeqCst);
+        ticker.join().expect("Failed to join progress ticker thread");
+        result
+    }
 }
 /// Creates and renders a 'wait' spinner.
 ///
@@ -72,7 +178,14 @@ pub struct WaitSpin<'a> {
 
 impl<'a> WaitSpin<'a> {
     pub fn new(options: &'a SpinnerOptions) -> WaitSpin {
-        Self::from(options, ProgressBar::new(std::u64::MAX))
+        Self::from(options, options.progress_bar())
+    }
+
+    fn prefix(options: &SpinnerOptions) -> String {
+        match options.step {
+            Some((step, of)) => format!("[{}/{}]  ", step, of),
+            None => String::from("     "),
+        }
     }
 
     pub fn from(options: &'a SpinnerOptions, mut pb: ProgressBar) -> WaitSpin {
@@ -81,12 +194,11 @@ impl<'a> WaitSpin<'a> {
     }
 
     fn init(pb: &mut ProgressBar, options: SpinnerOptions) {
+        pb.set_prefix(Self::prefix(&options));
         pb.set_style(options.style);
-        if let Some((step, of)) = options.step {
-            pb.set_prefix(format!("[{}/{}]  ", step, of));
-        } else {
-            pb.set_prefix("     ");
-        };
+        if let Some(total) = options.progress_total {
+            pb.set_length(total);
+        }
         pb.set_message(options.message);
     }
 
@@ -94,7 +206,11 @@ impl<'a> WaitSpin<'a> {
     ///
     /// The spinner will keep spinning until `stop` is called.
     pub fn start(&mut self) {
-        self.pb.enable_steady_tick(100);
+        if self.options.no_spinner {
+            self.print_line(None);
+        } else {
+            self.pb.enable_steady_tick(100);
+        }
     }
 
     /// Stops the spinner.
@@ -109,19 +225,38 @@ impl<'a> WaitSpin<'a> {
 
     /// Stops the spinner and updates the status of the last line.
     pub fn stop_with_status(&mut self, status: String) {
+        if self.options.no_spinner {
+            self.print_line(Some(&status));
+        }
         self.pb.finish_with_message(format!(
             "{} {}",
             self.options.message, status
         ));
     }
 
-    /// Stops the spinner and sets the status to error.
+    /// Stops the spinner and sets the status to error, or replaces the line
+    /// with the failure line if one was set.
     pub fn stop_with_error(&mut self) {
+        if let Some(line) = &self.options.failure_line {
+            if self.options.no_spinner {
+                println!("{}{}", Self::prefix(self.options), line);
+            }
+            self.pb.finish_with_message(format!("  {}", line));
+            return;
+        }
         let status = self.options.failure_message.clone();
-        self.pb.finish_with_message(format!(
-            "{} {}",
-            self.options.message, status
-        ));
+        self.stop_with_status(status);
+    }
+
+    /// Prints the message (and status) of the spinner as a plain line.
+    fn print_line(&self, status: Option<&str>) {
+        let prefix = Self::prefix(self.options);
+        match status {
+            Some(status) => {
+                println!("{}{} {}", prefix, self.options.message, status)
+            }
+            None => println!("{}{}", prefix, self.options.message),
+        }
     }
 }
 
@@ -133,11 +268,29 @@ pub struct WaitUntil<'a> {
 pub struct WaitResult<T> {
     result: T,
     status: String,
+    /// Whether the operation completed but failed, in which case the spinner
+    /// stops as it does on errors.
+    failed: bool,
 }
 
 impl<T> WaitResult<T> {
     pub fn from(result: T, status: String) -> WaitResult<T> {
-        WaitResult { result, status }
+        WaitResult {
+            result,
+            status,
+            failed: false,
+        }
+    }
+
+    /// An operation which completed but failed (eg. a check which did not
+    /// pass). The spinner renders it with the failure message or line of its
+    /// [SpinnerOptions], while the result is still returned.
+    pub fn failure(result: T) -> WaitResult<T> {
+        WaitResult {
+            result,
+            status: String::new(),
+            failed: true,
+        }
     }
 }
 
@@ -165,7 +318,7 @@ impl<'a> WaitUntil<'a> {
 
     pub fn new(options: &'a SpinnerOptions) -> WaitUntil {
         WaitUntil {
-            wait_spin: WaitSpin::from(options, ProgressBar::new(std::u64::MAX)),
+            wait_spin: WaitSpin::new(options),
         }
     }
 
@@ -214,21 +367,97 @@ impl<'a> WaitUntil<'a> {
     ///     Ok(WaitResult::from((), String::from("(Done)")))
     /// });
     /// ```
-    pub fn spin_until_status<F, T>(mut self, f: F) -> Result<T>
+    pub fn spin_until_status<F, T>(self, f: F) -> Result<T>
     where
         F: FnOnce() -> Result<WaitResult<T>>,
     {
+        self.spin_until_progress(|_| f())
+    }
+
+    /// Renders a spinner until the closure completes and updates the status.
+    ///
+    /// This is the same as [spin_until_status], but the closure is also given
+    /// a [ProgressHandle] with which it can report its progress.
+    pub fn spin_until_progress<F, T>(mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&ProgressHandle) -> Result<WaitResult<T>>,
+    {
+        let handle = ProgressHandle {
+            pb: self.wait_spin.pb.clone(),
+            message: self.wait_spin.options.message.clone(),
+        };
         self.wait_spin.start();
-        let wait_result = f();
+        let wait_result = f(&handle);
         match wait_result {
+            Ok(w) if w.failed => {
+                self.wait_spin.stop_with_error();
+                Ok(w.result)
+            }
             Ok(w) => {
                 self.wait_spin.stop_with_status(w.status);
                 Ok(w.result)
             }
+            Err(e) if e.is::<Cancelled>() => {
+                let status = cdim!("(Cancelled)").to_string();
+                self.wait_spin.stop_with_status(status);
+                Err(e)
+            }
             Err(e) => {
                 self.wait_spin.stop_with_error();
                 Err(e)
             }
         }
     }
+
+    /// Renders a spinner while repeatedly calling the closure, until it
+    /// completes or the operation is cancelled.
+    ///
+    /// The closure returns `None` while the operation is still in progress,
+    /// and is called again unless `cancel` has been stopped or interrupted in
+    /// the meantime. In that case the spinner stops and a [Cancelled] error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// let (flag, control) = make_pair();
+    /// let spin_opts = SpinnerOptions::new(String::from("Waiting..."));
+    /// let wu = WaitUntil::new(&spin_opts);
+    /// wu.spin_with_cancel(|| {
+    ///     std::thread::sleep(500);
+    ///     Ok(Some(WaitResult::from((), String::from("(Done)"))))
+    /// }, &control);
+    /// ```
+    pub fn spin_with_cancel<F, T>(
+        mut self,
+        mut f: F,
+        cancel: &Control,
+    ) -> Result<T>
+    where
+        F: FnMut() -> Result<Option<WaitResult<T>>>,
+    {
+        self.wait_spin.start();
+        loop {
+            if cancel.is_done() || cancel.is_interrupted() {
+                let status = cdim!("(Cancelled)").to_string();
+                self.wait_spin.stop_with_status(status);
+                return Err(Cancelled.into());
+            }
+            match f() {
+                Ok(Some(w)) if w.failed => {
+                    self.wait_spin.stop_with_error();
+                    return Ok(w.result);
+                }
+                Ok(Some(w)) => {
+                    self.wait_spin.stop_with_status(w.status);
+                    return Ok(w.result);
+                }
+                Ok(None) => continue,
+                Err(e) => {
+                    self.wait_spin.stop_with_error();
+                    return Err(e);
+                }
+            }
+        }
+    }
 }
@@ -3,12 +3,17 @@ use crate::client::module::{
     merge_env, InnerDefinition, ModuleDefinition, ModuleKind,
     ServiceOrTaskDefinition,
 };
+use crate::constants::{API_KEY_HEADER, REQUEST_ID_HEADER};
 use crate::daemon::api::*;
 use anyhow::{anyhow, bail, Result};
 use core::convert::Into;
-use reqwest::blocking::Client;
+use percent_encoding::{utf8_percent_encode, AsciiSet, PercentEncode};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::{HeaderMap, HeaderValue};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
+use uuid::Uuid;
 
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +24,41 @@ pub enum DeploymentResponse {
     Err(ErrorResponse),
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ModuleStatusResponse {
+    Ok(ApiModuleStatusResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum LockResponse {
+    Ok(ApiLockResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ForceUnlockResponse {
+    Ok(ApiForceUnlockResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ImportResponse {
+    Ok(ApiImportResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum InfoResponse {
+    Ok(ApiInfoResponse),
+    Err(ErrorResponse),
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum TaskDeploymentResponse {
@@ -26,6 +66,13 @@ pub enum TaskDeploymentResponse {
     Err(ErrorResponse),
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum TaskBatchResponse {
+    Ok(ApiTaskBatchResponse),
+    Err(ErrorResponse),
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum OperationResponse {
@@ -33,6 +80,41 @@ pub enum OperationResponse {
     Err(ErrorResponse),
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum BulkOperationResponse {
+    Ok(ApiBulkOperationResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum FindModuleResponse {
+    Ok(ApiFindModuleResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum RollbackResponse {
+    Ok(ApiRollbackResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum HealthCheckResponse {
+    Ok(ApiHealthCheckResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum DependentsResponse {
+    Ok(ApiDependentsResponse),
+    Err(ErrorResponse),
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum LogInfoResponse {
@@ -40,66 +122,165 @@ pub enum LogInfoResponse {
     Err(ErrorResponse),
 }
 
-fn client(timeout: &Option<u64>) -> Client {
-    reqwest::blocking::Client::builder()
-        .timeout(Duration::from_secs(timeout.unwrap_or(180)))
-        .build()
-        .unwrap()
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum AllLogsResponse {
+    Ok(ApiAllLogsResponse),
+    Err(ErrorResponse),
 }
 
-fn build_env_arg(
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum ReloadResponse {
+    Ok(ApiReloadResponse),
+    Err(ErrorResponse),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum SnapshotResponse {
+    Ok(ApiSnapshotResponse),
+    Err(ErrorResponse),
+}
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Enables printing the ID of each request sent to the daemon.
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+trait WithRequestId {
+    fn with_request_id(self) -> Self;
+}
+
+impl WithRequestId for RequestBuilder {
+    /// Tags the request with a unique ID, which the daemon logs alongside the
+    /// request so that the two can be correlated.
+    fn with_request_id(self) -> Self {
+        let request_id = Uuid::new_v4().to_string();
+        if VERBOSE.load(Ordering::Relaxed) {
+            eprintln!("{}", cdim!(format!("Request ID: {}", request_id)));
+        }
+        self.header(REQUEST_ID_HEADER, request_id)
+    }
+}
+
+/// The environment variables the log level of a module is given through.
+const LOG_LEVEL_VARS: [&str; 2] = ["LOG_LEVEL", "RUST_LOG"];
+
+/// Builds the environment of a module from its `environment`, its active
+/// environment sets and its log level.
+///
+/// The `log_level` of the module does not override variables set explicitly,
+/// while a `log_level_override` (from `--module-log-level`) does.
+pub(crate) fn build_env_arg(
     svc: &ServiceOrTaskDefinition,
-    opts: &DeployOptions,
+    active_envs: &[String],
+    log_level_override: Option<&str>,
 ) -> HashMap<String, String> {
     let mut base_env = svc.environment.clone();
-    opts.active_envs.iter().for_each(|key| {
+    active_envs.iter().for_each(|key| {
         if svc.environment_sets.contains_key(key) {
             let env_set = svc.environment_sets.get(key).unwrap();
             merge_env(&mut base_env, env_set);
         }
     });
+
+    if let Some(level) = log_level_override {
+        for var in LOG_LEVEL_VARS.iter() {
+            base_env.insert(var.to_string(), level.to_string());
+        }
+    } else if let Some(level) = &svc.log_level {
+        for var in LOG_LEVEL_VARS.iter() {
+            base_env
+                .entry(var.to_string())
+                .or_insert_with(|| level.clone());
+        }
+    }
     base_env
 }
 
 fn build_svc_module_definition(
     module_definition: &ServiceOrTaskDefinition,
-    opts: &DeployOptions,
+    active_envs: &[String],
+    log_level_override: Option<&str>,
 ) -> ApiModuleDefinition {
     ApiModuleDefinition {
         kind: ApiModuleKind::Service,
         name: module_definition.name.clone(),
         command: module_definition.cmd_line(),
-        environment: build_env_arg(module_definition, opts),
+        environment: build_env_arg(
+            module_definition,
+            active_envs,
+            log_level_override,
+        ),
         log_file_path: module_definition.log_file_path.clone(),
         dependencies: module_definition.dependencies.clone(),
         working_dir: module_definition.working_dir.clone(),
         termination_signal: (&module_definition.termination_signal).into(),
         readiness_probe: module_definition
-            .readiness_probe
+            .readiness()
             .as_ref()
             .map(Into::into),
         liveness_probe: module_definition
             .liveness_probe
             .as_ref()
             .map(Into::into),
+        capture_output: false,
+        max_memory_mb: module_definition.max_memory_mb,
+        cpu_quota_percent: module_definition.cpu_quota_percent,
+        network_namespace: module_definition.network_namespace.clone(),
+        shutdown_command: module_definition.shutdown_command.clone(),
+        shutdown_timeout_secs: module_definition.shutdown_timeout_secs,
+        inherit_env: module_definition.inherit_env,
+        namespace: None,
+        ports: module_definition.ports.iter().map(Into::into).collect(),
+        umask: module_definition.umask,
+        stdout_filter: module_definition.stdout_filter.clone(),
+        version: module_definition.version.clone(),
+        socket_activation: module_definition
+            .socket_activation
+            .as_ref()
+            .map(Into::into),
     }
 }
 
 fn build_task_module_definition(
     task_definition: &ServiceOrTaskDefinition,
-    opts: &DeployOptions,
+    kind: ApiModuleKind,
+    active_envs: &[String],
+    log_level_override: Option<&str>,
+    namespace: Option<String>,
 ) -> ApiModuleDefinition {
     ApiModuleDefinition {
-        kind: ApiModuleKind::Task,
+        kind,
         name: task_definition.name.clone(),
         command: task_definition.cmd_line(),
-        environment: build_env_arg(task_definition, opts),
+        environment: build_env_arg(
+            task_definition,
+            active_envs,
+            log_level_override,
+        ),
         log_file_path: task_definition.log_file_path.clone(),
         dependencies: task_definition.dependencies.clone(),
         working_dir: task_definition.working_dir.clone(),
         termination_signal: ApiTermSignal::KILL,
         readiness_probe: None,
         liveness_probe: None,
+        capture_output: task_definition.capture_output,
+        max_memory_mb: task_definition.max_memory_mb,
+        cpu_quota_percent: task_definition.cpu_quota_percent,
+        network_namespace: task_definition.network_namespace.clone(),
+        shutdown_command: task_definition.shutdown_command.clone(),
+        shutdown_timeout_secs: task_definition.shutdown_timeout_secs,
+        inherit_env: task_definition.inherit_env,
+        namespace,
+        ports: task_definition.ports.iter().map(Into::into).collect(),
+        umask: task_definition.umask,
+        stdout_filter: task_definition.stdout_filter.clone(),
+        version: task_definition.version.clone(),
+        socket_activation: None,
     }
 }
 
@@ -108,17 +289,33 @@ fn build_deploy_command(
     opts: &DeployOptions,
 ) -> ApiDeploymentCommand {
     ApiDeploymentCommand {
-        module_definition: build_svc_module_definition(module_definition, opts),
+        module_definition: ApiModuleDefinition {
+            namespace: opts.namespace.clone(),
+            ..build_svc_module_definition(
+                module_definition,
+                &opts.active_envs,
+                opts.module_log_level.as_deref(),
+            )
+        },
         force: opts.force_deploy,
+        await_readiness: opts.wait_all,
     }
 }
 
 fn build_task_deploy_command(
     task_definition: &ServiceOrTaskDefinition,
+    kind: ApiModuleKind,
     opts: &DeployOptions,
 ) -> ApiTaskDeploymentCommand {
     ApiTaskDeploymentCommand {
-        task_definition: build_task_module_definition(task_definition, opts),
+        task_definition: build_task_module_definition(
+            task_definition,
+            kind,
+            &opts.active_envs,
+            opts.module_log_level.as_deref(),
+            opts.namespace.clone(),
+        ),
+        defined_oneshots: None,
     }
 }
 
@@ -130,9 +327,14 @@ fn build_get_plan_request(
         .iter()
         .filter(|m| m.kind == ModuleKind::Service)
         .map(|m| match &m.inner {
-            InnerDefinition::Service(svc) => {
-                build_svc_module_definition(svc, opts)
-            }
+            InnerDefinition::Service(svc) => ApiModuleDefinition {
+                namespace: opts.namespace.clone(),
+                ..build_svc_module_definition(
+                    svc,
+                    &opts.active_envs,
+                    opts.module_log_level.as_deref(),
+                )
+            },
             _ => unreachable!(),
         })
         .collect();
@@ -149,166 +351,857 @@ fn build_get_log_file_request(
     }
 }
 
-pub fn deploy_module(
-    module_definition: &ServiceOrTaskDefinition,
-    deploy_opts: &DeployOptions,
-    daemon_url: &str,
-) -> Result<ApiDeploymentResponse> {
-    let client = reqwest::blocking::Client::new();
-    let command = build_deploy_command(module_definition, deploy_opts);
+/// The timeout of requests to the daemon, unless the client is created with
+/// another one. Matches the default of `reqwest`.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Restricts the modules returned by [CartelClientTrait::list_modules]. The
+/// filtering is done by the daemon.
+#[derive(Default)]
+pub struct ModuleFilter {
+    pub kind: Option<ModuleKind>,
+    pub status: Option<ApiModuleRunStatus>,
+    pub namespace: Option<String>,
+}
+
+/// The requests the client can send to the daemon.
+///
+/// Commands should depend on this trait rather than on [CartelClient]
+/// directly where they are to be tested without a running daemon.
+pub trait CartelClientTrait {
+    fn deploy_module(
+        &self,
+        module_definition: &ServiceOrTaskDefinition,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiDeploymentResponse>;
+
+    /// Requests a readiness monitor for a service, which is only deployed if
+    /// it is not deployed already.
+    fn watch_readiness(
+        &self,
+        module_definition: &ServiceOrTaskDefinition,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiDeploymentResponse>;
+
+    fn deploy_task(
+        &self,
+        task_definition: &ServiceOrTaskDefinition,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiTaskDeploymentResponse>;
+
+    /// Runs a one-shot module, unless it already ran since the daemon started
+    /// in which case the response is marked as skipped.
+    ///
+    /// `defined_oneshots` are the names of all the one-shots in the module
+    /// definitions, the daemon forgetting the other one-shots it recorded.
+    fn deploy_oneshot(
+        &self,
+        oneshot_definition: &ServiceOrTaskDefinition,
+        defined_oneshots: Vec<String>,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiTaskDeploymentResponse>;
+
+    /// Runs many tasks, up to `max_concurrency` of them at the same time,
+    /// and returns the result of each of them.
+    fn deploy_task_batch(
+        &self,
+        task_definitions: &[&ServiceOrTaskDefinition],
+        max_concurrency: usize,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiTaskBatchResponse>;
+
+    fn stop_module(&self, module_name: &str) -> Result<ApiOperationResponse>;
+
+    fn stop_modules(
+        &self,
+        module_names: &[&str],
+    ) -> Result<ApiBulkOperationResponse>;
+
+    fn stop_all(&self) -> Result<ApiOperationResponse>;
+
+    fn reload_module(&self, module_name: &str) -> Result<ApiOperationResponse>;
+
+    fn pause_module(&self, module_name: &str) -> Result<ApiOperationResponse>;
+
+    fn resume_module(&self, module_name: &str) -> Result<ApiOperationResponse>;
+
+    fn restart_module(&self, module_name: &str)
+        -> Result<ApiOperationResponse>;
+
+    fn list_modules(
+        &self,
+        filter: &ModuleFilter,
+    ) -> Result<ApiModuleStatusResponse>;
+
+    fn log_file_path(
+        &self,
+        module_name: &str,
+        module_kind: &ModuleKind,
+    ) -> Result<ApiLogResponse>;
+
+    /// Returns the log file of every module known to the daemon, by module
+    /// name.
+    fn get_all_logs(&self) -> Result<HashMap<String, ApiLogResponse>>;
 
-    let deployment_result: DeploymentResponse = client
-        .post(&(daemon_url.to_owned() + "/deploy"))
-        .json(&command)
-        .send()?
-        .json()?;
+    fn clear_log_file(
+        &self,
+        module_name: &str,
+        module_kind: &ModuleKind,
+        force: bool,
+    ) -> Result<ApiOperationResponse>;
 
-    match deployment_result {
-        DeploymentResponse::Ok(r) => Ok(r),
-        DeploymentResponse::Err(e) => bail!(e.message),
+    fn find_module_by_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<ApiFindModuleResponse>;
+
+    /// Registers services with the daemon without deploying them.
+    fn import_modules(
+        &self,
+        services: &[&ServiceOrTaskDefinition],
+        active_envs: &[String],
+    ) -> Result<ApiImportResponse>;
+
+    /// Redeploys a service with the definition it was deployed with before
+    /// its current one, returning the definition rolled back to.
+    fn rollback(&self, module_name: &str) -> Result<ApiRollbackResponse>;
+
+    /// Runs the liveness probe of a running module once and returns its
+    /// result.
+    fn check_health(
+        &self,
+        module_name: &str,
+    ) -> Result<ApiHealthCheckResponse>;
+
+    /// Returns the modules known to the daemon which directly depend on the
+    /// given module, or all modules which depend on it with `transitive`.
+    fn dependents(
+        &self,
+        module_name: &str,
+        transitive: bool,
+    ) -> Result<ApiDependentsResponse>;
+
+    /// Attempts to acquire the deploy lock of the daemon.
+    ///
+    /// The response only contains a token if the lock was acquired, otherwise
+    /// it contains the client already holding it.
+    fn acquire_lock(&self, user: &str) -> Result<ApiLockResponse>;
+
+    /// Releases the deploy lock acquired with the given token.
+    fn release_lock(&self, token: &str) -> Result<()>;
+
+    /// Releases the deploy lock regardless of which client holds it.
+    fn force_release_lock(&self) -> Result<ApiForceUnlockResponse>;
+
+    /// Expands a (possibly partial) module name to the full name of a module
+    /// known to the daemon.
+    ///
+    /// The name is returned unchanged if no module starts with it, so that
+    /// the caller can report the missing module as usual.
+    fn expand_module_name(&self, prefix: &str) -> Result<String> {
+        let module_name = self.find_module_by_prefix(prefix)?.module_name;
+        Ok(module_name.unwrap_or_else(|| prefix.to_string()))
     }
+
+    fn reload_daemon_config(&self) -> Result<ApiReloadResponse>;
+
+    /// Returns the version, uptime and platform of the daemon.
+    fn daemon_info(&self) -> Result<ApiInfoResponse>;
+
+    fn snapshot(&self, path: Option<&str>) -> Result<ApiSnapshotResponse>;
+
+    fn poll_health(&self, monitor_handle: &str) -> Result<ApiHealthResponse>;
+
+    /// Sends a request which the daemon answers without doing any work, to
+    /// check that it is reachable.
+    fn ping(&self) -> Result<()>;
+
+    fn get_plan(
+        &self,
+        modules: &[&ModuleDefinition],
+        opts: &DeployOptions,
+    ) -> Result<ApiGetPlanResponse>;
 }
 
-pub fn deploy_task(
-    task_definition: &ServiceOrTaskDefinition,
-    deploy_opts: &DeployOptions,
-    daemon_url: &str,
-) -> Result<ApiTaskDeploymentResponse> {
-    let client = client(&task_definition.timeout);
-    let command = build_task_deploy_command(task_definition, deploy_opts);
-
-    let deployment_result: TaskDeploymentResponse = client
-        .post(&(daemon_url.to_owned() + "/tasks/deploy"))
-        .json(&command)
-        .send()
-        .map_err(|e| {
-            if e.is_timeout() {
-                anyhow!(task_took_too_long_msg(&task_definition.name))
-            } else {
-                e.into()
-            }
-        })?
-        .json()?;
-
-    match deployment_result {
-        TaskDeploymentResponse::Ok(r) => Ok(r),
-        TaskDeploymentResponse::Err(e) => bail!(e.message),
-    }
-}
-
-pub fn stop_module(
-    module_name: &str,
-    daemon_url: &str,
-) -> Result<ApiOperationResponse> {
-    let client = reqwest::blocking::Client::new();
-    let command = ApiOperationCommand {
-        operation: ApiModuleOperation::STOP,
-        module_name: module_name.to_string(),
-    };
+/// The characters which are percent-encoded in a segment of a request path:
+/// the ones which would end the segment or be misread in it, on top of the
+/// control characters.
+const PATH_SEGMENT: &AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
 
-    let operation_result: OperationResponse = client
-        .post(&(daemon_url.to_owned() + "/operation"))
-        .json(&command)
-        .send()?
-        .json()?;
+/// Percent-encodes a value (such as a module name) to be used as a segment of
+/// a request path.
+fn path_segment(value: &str) -> PercentEncode<'_> {
+    utf8_percent_encode(value, PATH_SEGMENT)
+}
 
-    match operation_result {
-        OperationResponse::Ok(r) => Ok(r),
-        OperationResponse::Err(e) => bail!(e.message),
-    }
+/// Sends requests to the daemon listening at `daemon_url`.
+pub struct CartelClient {
+    daemon_url: String,
+    timeout: Duration,
+    api_key: Option<String>,
 }
 
-pub fn stop_all(daemon_url: &str) -> Result<ApiOperationResponse> {
-    let client = reqwest::blocking::Client::new();
+impl CartelClient {
+    pub fn new(daemon_url: &str) -> CartelClient {
+        CartelClient {
+            daemon_url: daemon_url.to_string(),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            api_key: None,
+        }
+    }
 
-    let operation_result: OperationResponse = client
-        .post(&(daemon_url.to_owned() + "/stop_all"))
-        .send()?
-        .json()?;
+    /// Sets the API key sent along with every request, for daemons which
+    /// require one.
+    pub fn api_key(mut self, api_key: Option<String>) -> CartelClient {
+        self.api_key = api_key;
+        self
+    }
 
-    match operation_result {
-        OperationResponse::Ok(r) => Ok(r),
-        OperationResponse::Err(e) => bail!(e.message),
+    /// Sets the timeout of requests. Tasks are always given their own
+    /// timeout.
+    pub fn timeout(mut self, timeout: Duration) -> CartelClient {
+        self.timeout = timeout;
+        self
     }
-}
 
-pub fn restart_module(
-    module_name: &str,
-    daemon_url: &str,
-) -> Result<ApiOperationResponse> {
-    let client = reqwest::blocking::Client::new();
-    let command = ApiOperationCommand {
-        operation: ApiModuleOperation::RESTART,
-        module_name: module_name.to_string(),
-    };
+    fn http_client(&self, timeout: Duration) -> Client {
+        let mut headers = HeaderMap::new();
+        if let Some(api_key) = &self.api_key {
+            let value = HeaderValue::from_str(api_key)
+                .expect("The daemon API key is not a valid header value");
+            headers.insert(API_KEY_HEADER, value);
+        }
+        reqwest::blocking::Client::builder()
+            .timeout(timeout)
+            .default_headers(headers)
+            .build()
+            .unwrap()
+    }
+
+    fn url(&self, path: &str) -> String {
+        self.daemon_url.clone() + path
+    }
 
-    let operation_result: OperationResponse = client
-        .post(&(daemon_url.to_owned() + "/operation"))
-        .json(&command)
-        .send()?
-        .json()?;
+    fn send_deploy_command(
+        &self,
+        command: &ApiDeploymentCommand,
+    ) -> Result<ApiDeploymentResponse> {
+        let client = self.http_client(self.timeout);
+        let deployment_result: DeploymentResponse = client
+            .post(&self.url("/deploy"))
+            .with_request_id()
+            .json(command)
+            .send()?
+            .json()?;
 
-    match operation_result {
-        OperationResponse::Ok(r) => Ok(r),
-        OperationResponse::Err(e) => bail!(e.message),
+        match deployment_result {
+            DeploymentResponse::Ok(r) => Ok(r),
+            DeploymentResponse::Err(e) => bail!(e.message),
+        }
     }
-}
 
-pub fn list_modules(daemon_url: &str) -> Result<ApiModuleStatusResponse> {
-    let client = reqwest::blocking::Client::new();
-    let status = client
-        .get(&(daemon_url.to_owned() + "/status"))
-        .send()?
-        .json()?;
+    fn run_task(
+        &self,
+        task_definition: &ServiceOrTaskDefinition,
+        command: &ApiTaskDeploymentCommand,
+    ) -> Result<ApiTaskDeploymentResponse> {
+        let timeout = task_definition.timeout.unwrap_or(180);
+        let client = self.http_client(Duration::from_secs(timeout));
+
+        let deployment_result: TaskDeploymentResponse = client
+            .post(&self.url("/tasks/deploy"))
+            .with_request_id()
+            .json(command)
+            .send()
+            .map_err(|e| {
+                if e.is_timeout() {
+                    anyhow!(task_took_too_long_msg(&task_definition.name))
+                } else {
+                    e.into()
+                }
+            })?
+            .json()?;
+
+        match deployment_result {
+            TaskDeploymentResponse::Ok(r) => Ok(r),
+            TaskDeploymentResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn module_operation(
+        &self,
+        module_name: &str,
+        operation: ApiModuleOperation,
+    ) -> Result<ApiOperationResponse> {
+        let client = self.http_client(self.timeout);
+        let command = ApiOperationCommand {
+            operation,
+            module_name: module_name.to_string(),
+        };
+
+        let operation_result: OperationResponse = client
+            .post(&self.url("/operation"))
+            .with_request_id()
+            .json(&command)
+            .send()?
+            .json()?;
 
-    Ok(status)
+        match operation_result {
+            OperationResponse::Ok(r) => Ok(r),
+            OperationResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    /// Performs an action on a single module through its own endpoint.
+    fn module_action(
+        &self,
+        module_name: &str,
+        action: &str,
+    ) -> Result<ApiOperationResponse> {
+        let client = self.http_client(self.timeout);
+        let module_name = path_segment(module_name);
+        let url = self.url(&format!("/modules/{}/{}", module_name, action));
+
+        let operation_result: OperationResponse =
+            client.post(&url).with_request_id().send()?.json()?;
+
+        match operation_result {
+            OperationResponse::Ok(r) => Ok(r),
+            OperationResponse::Err(e) => bail!(e.message),
+        }
+    }
 }
 
-pub fn log_file_path(
-    module_name: &str,
-    module_kind: &ModuleKind,
-    daemon_url: &str,
-) -> Result<ApiLogResponse> {
-    let client = reqwest::blocking::Client::new();
-    let request = build_get_log_file_request(module_name, module_kind);
-    let status: LogInfoResponse = client
-        .post(&(daemon_url.to_owned() + "/log_file"))
-        .json(&request)
-        .send()?
-        .json()?;
+impl CartelClientTrait for CartelClient {
+    fn deploy_module(
+        &self,
+        module_definition: &ServiceOrTaskDefinition,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiDeploymentResponse> {
+        let command = build_deploy_command(module_definition, deploy_opts);
+        self.send_deploy_command(&command)
+    }
+
+    fn watch_readiness(
+        &self,
+        module_definition: &ServiceOrTaskDefinition,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiDeploymentResponse> {
+        let command = ApiDeploymentCommand {
+            force: false,
+            await_readiness: true,
+            ..build_deploy_command(module_definition, deploy_opts)
+        };
+        self.send_deploy_command(&command)
+    }
+
+    fn deploy_task(
+        &self,
+        task_definition: &ServiceOrTaskDefinition,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiTaskDeploymentResponse> {
+        let command = build_task_deploy_command(
+            task_definition,
+            ApiModuleKind::Task,
+            deploy_opts,
+        );
+        self.run_task(task_definition, &command)
+    }
+
+    fn deploy_task_batch(
+        &self,
+        task_definitions: &[&ServiceOrTaskDefinition],
+        max_concurrency: usize,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiTaskBatchResponse> {
+        // The tasks may all run one after the other
+        let timeout: u64 = task_definitions
+            .iter()
+            .map(|task| task.timeout.unwrap_or(180))
+            .sum();
+        let client = self.http_client(Duration::from_secs(timeout));
+        let command = ApiTaskBatchCommand {
+            tasks: task_definitions
+                .iter()
+                .map(|task| {
+                    build_task_deploy_command(
+                        task,
+                        ApiModuleKind::Task,
+                        deploy_opts,
+                    )
+                    .task_definition
+                })
+                .collect(),
+            max_concurrency,
+        };
+
+        let batch_result: TaskBatchResponse = client
+            .post(&self.url("/tasks/batch"))
+            .with_request_id()
+            .json(&command)
+            .send()
+            .map_err(|e| {
+                if e.is_timeout() {
+                    anyhow!("The tasks took too long to finish")
+                } else {
+                    e.into()
+                }
+            })?
+            .json()?;
+
+        match batch_result {
+            TaskBatchResponse::Ok(r) => Ok(r),
+            TaskBatchResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn deploy_oneshot(
+        &self,
+        oneshot_definition: &ServiceOrTaskDefinition,
+        defined_oneshots: Vec<String>,
+        deploy_opts: &DeployOptions,
+    ) -> Result<ApiTaskDeploymentResponse> {
+        let command = ApiTaskDeploymentCommand {
+            defined_oneshots: Some(defined_oneshots),
+            ..build_task_deploy_command(
+                oneshot_definition,
+                ApiModuleKind::OneShot,
+                deploy_opts,
+            )
+        };
+        self.run_task(oneshot_definition, &command)
+    }
+
+    fn stop_module(&self, module_name: &str) -> Result<ApiOperationResponse> {
+        self.module_operation(module_name, ApiModuleOperation::STOP)
+    }
+
+    fn stop_modules(
+        &self,
+        module_names: &[&str],
+    ) -> Result<ApiBulkOperationResponse> {
+        let client = self.http_client(self.timeout);
+        let command = ApiBulkOperationCommand {
+            names: module_names.iter().map(|n| n.to_string()).collect(),
+            operation: ApiModuleOperation::STOP,
+        };
+
+        let operation_result: BulkOperationResponse = client
+            .post(&self.url("/operation/bulk"))
+            .with_request_id()
+            .json(&command)
+            .send()?
+            .json()?;
+
+        match operation_result {
+            BulkOperationResponse::Ok(r) => Ok(r),
+            BulkOperationResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn stop_all(&self) -> Result<ApiOperationResponse> {
+        let client = self.http_client(self.timeout);
+
+        let operation_result: OperationResponse = client
+            .post(&self.url("/stop_all"))
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match operation_result {
+            OperationResponse::Ok(r) => Ok(r),
+            OperationResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn reload_module(&self, module_name: &str) -> Result<ApiOperationResponse> {
+        self.module_operation(module_name, ApiModuleOperation::RELOAD)
+    }
+
+    fn pause_module(&self, module_name: &str) -> Result<ApiOperationResponse> {
+        self.module_action(module_name, "pause")
+    }
+
+    fn resume_module(&self, module_name: &str) -> Result<ApiOperationResponse> {
+        self.module_action(module_name, "resume")
+    }
+
+    fn restart_module(
+        &self,
+        module_name: &str,
+    ) -> Result<ApiOperationResponse> {
+        self.module_operation(module_name, ApiModuleOperation::RESTART)
+    }
 
-    match status {
-        LogInfoResponse::Ok(r) => Ok(r),
-        LogInfoResponse::Err(e) => bail!(e.message),
+    fn list_modules(
+        &self,
+        filter: &ModuleFilter,
+    ) -> Result<ApiModuleStatusResponse> {
+        let client = self.http_client(self.timeout);
+        let mut request = client.get(&self.url("/status"));
+        if let Some(kind) = &filter.kind {
+            request = request.query(&[("kind", kind.to_string())]);
+        }
+        if let Some(status) = &filter.status {
+            request = request.query(&[("status", format!("{:?}", status))]);
+        }
+        if let Some(namespace) = &filter.namespace {
+            request = request.query(&[("namespace", namespace)]);
+        }
+        let status_result: ModuleStatusResponse =
+            request.with_request_id().send()?.json()?;
+
+        match status_result {
+            ModuleStatusResponse::Ok(r) => Ok(r),
+            ModuleStatusResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn log_file_path(
+        &self,
+        module_name: &str,
+        module_kind: &ModuleKind,
+    ) -> Result<ApiLogResponse> {
+        let client = self.http_client(self.timeout);
+        let request = build_get_log_file_request(module_name, module_kind);
+        let status: LogInfoResponse = client
+            .post(&self.url("/log_file"))
+            .with_request_id()
+            .json(&request)
+            .send()?
+            .json()?;
+
+        match status {
+            LogInfoResponse::Ok(r) => Ok(r),
+            LogInfoResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn get_all_logs(&self) -> Result<HashMap<String, ApiLogResponse>> {
+        let client = self.http_client(self.timeout);
+        let logs: AllLogsResponse = client
+            .get(&self.url("/logs/all"))
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match logs {
+            AllLogsResponse::Ok(r) => Ok(r.logs),
+            AllLogsResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn clear_log_file(
+        &self,
+        module_name: &str,
+        module_kind: &ModuleKind,
+        force: bool,
+    ) -> Result<ApiOperationResponse> {
+        let client = self.http_client(self.timeout);
+        let request = ApiClearLogRequest {
+            module_name: module_name.to_string(),
+            module_kind: module_kind.into(),
+            force,
+        };
+        let operation_result: OperationResponse = client
+            .post(&self.url("/log_file/clear"))
+            .with_request_id()
+            .json(&request)
+            .send()?
+            .json()?;
+
+        match operation_result {
+            OperationResponse::Ok(r) => Ok(r),
+            OperationResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn find_module_by_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<ApiFindModuleResponse> {
+        let client = self.http_client(self.timeout);
+        let find_result: FindModuleResponse = client
+            .get(&self.url(&format!("/modules/find/{}", path_segment(prefix))))
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match find_result {
+            FindModuleResponse::Ok(r) => Ok(r),
+            FindModuleResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn import_modules(
+        &self,
+        services: &[&ServiceOrTaskDefinition],
+        active_envs: &[String],
+    ) -> Result<ApiImportResponse> {
+        let client = self.http_client(self.timeout);
+        let command = ApiImportCommand {
+            modules: services
+                .iter()
+                .map(|svc| build_svc_module_definition(svc, active_envs, None))
+                .collect(),
+        };
+        let import_result: ImportResponse = client
+            .post(&self.url("/modules/import"))
+            .with_request_id()
+            .json(&command)
+            .send()?
+            .json()?;
+
+        match import_result {
+            ImportResponse::Ok(r) => Ok(r),
+            ImportResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn rollback(&self, module_name: &str) -> Result<ApiRollbackResponse> {
+        let client = self.http_client(self.timeout);
+        let module_name = path_segment(module_name);
+        let url = self.url(&format!("/modules/{}/rollback", module_name));
+
+        let rollback_result: RollbackResponse =
+            client.post(&url).with_request_id().send()?.json()?;
+
+        match rollback_result {
+            RollbackResponse::Ok(r) => Ok(r),
+            RollbackResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn check_health(
+        &self,
+        module_name: &str,
+    ) -> Result<ApiHealthCheckResponse> {
+        let client = self.http_client(self.timeout);
+        let module_name = path_segment(module_name);
+        let url = self.url(&format!("/modules/{}/healthcheck", module_name));
+
+        let health_check_result: HealthCheckResponse =
+            client.post(&url).with_request_id().send()?.json()?;
+
+        match health_check_result {
+            HealthCheckResponse::Ok(r) => Ok(r),
+            HealthCheckResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn dependents(
+        &self,
+        module_name: &str,
+        transitive: bool,
+    ) -> Result<ApiDependentsResponse> {
+        let client = self.http_client(self.timeout);
+        let module_name = path_segment(module_name);
+        let url = self.url(&format!("/modules/{}/children", module_name));
+
+        let dependents_result: DependentsResponse = client
+            .get(&url)
+            .query(&[("transitive", transitive)])
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match dependents_result {
+            DependentsResponse::Ok(r) => Ok(r),
+            DependentsResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn acquire_lock(&self, user: &str) -> Result<ApiLockResponse> {
+        let client = self.http_client(self.timeout);
+        let command = ApiLockCommand {
+            user: user.to_string(),
+            pid: std::process::id(),
+        };
+        // A conflict still responds with the holder of the lock
+        let lock_result: LockResponse = client
+            .post(&self.url("/lock"))
+            .with_request_id()
+            .json(&command)
+            .send()?
+            .json()?;
+
+        match lock_result {
+            LockResponse::Ok(r) => Ok(r),
+            LockResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn release_lock(&self, token: &str) -> Result<()> {
+        let client = self.http_client(self.timeout);
+        let release_result: OperationResponse = client
+            .delete(&self.url("/lock"))
+            .query(&[("token", token)])
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match release_result {
+            OperationResponse::Ok(_) => Ok(()),
+            OperationResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn force_release_lock(&self) -> Result<ApiForceUnlockResponse> {
+        let client = self.http_client(self.timeout);
+        let unlock_result: ForceUnlockResponse = client
+            .delete(&self.url("/lock/force"))
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match unlock_result {
+            ForceUnlockResponse::Ok(r) => Ok(r),
+            ForceUnlockResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn reload_daemon_config(&self) -> Result<ApiReloadResponse> {
+        let client = self.http_client(self.timeout);
+        let reload_result: ReloadResponse = client
+            .post(&self.url("/reload"))
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match reload_result {
+            ReloadResponse::Ok(r) => Ok(r),
+            ReloadResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn daemon_info(&self) -> Result<ApiInfoResponse> {
+        let client = self.http_client(self.timeout);
+        let info_result: InfoResponse = client
+            .get(&self.url("/info"))
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match info_result {
+            InfoResponse::Ok(r) => Ok(r),
+            InfoResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn snapshot(&self, path: Option<&str>) -> Result<ApiSnapshotResponse> {
+        let client = self.http_client(self.timeout);
+        let mut request = client.post(&self.url("/snapshot"));
+        if let Some(path) = path {
+            request = request.query(&[("path", path)]);
+        }
+        let snapshot_result: SnapshotResponse =
+            request.with_request_id().send()?.json()?;
+
+        match snapshot_result {
+            SnapshotResponse::Ok(r) => Ok(r),
+            SnapshotResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn poll_health(&self, monitor_handle: &str) -> Result<ApiHealthResponse> {
+        let client = self.http_client(self.timeout);
+        let monitor_handle = path_segment(monitor_handle);
+        let health = client
+            .get(&self.url(&format!("/health/{}", monitor_handle)))
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        Ok(health)
+    }
+
+    fn ping(&self) -> Result<()> {
+        let client = self.http_client(self.timeout);
+        let ping_result: OperationResponse = client
+            .get(&self.url("/health"))
+            .with_request_id()
+            .send()?
+            .json()?;
+
+        match ping_result {
+            OperationResponse::Ok(_) => Ok(()),
+            OperationResponse::Err(e) => bail!(e.message),
+        }
+    }
+
+    fn get_plan(
+        &self,
+        modules: &[&ModuleDefinition],
+        opts: &DeployOptions,
+    ) -> Result<ApiGetPlanResponse> {
+        let client = self.http_client(self.timeout);
+        let request = build_get_plan_request(modules, opts);
+        let get_plan_result = client
+            .post(&self.url("/get_plan"))
+            .with_request_id()
+            .json(&request)
+            .send()?
+            .json()?;
+        Ok(get_plan_result)
     }
 }
 
-pub fn poll_health(
-    monitor_handle: &str,
-    daemon_url: &str,
-) -> Result<ApiHealthResponse> {
-    let client = reqwest::blocking::Client::new();
-    let health = client
-        .get(&(daemon_url.to_owned() + "/health/" + monitor_handle))
-        .send()?
-        .json()?;
+/// The interval between polls of a readiness probe before its status
+/// changes.
+const INITIAL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// The longest interval between polls of a readiness probe.
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(10);
 
-    Ok(health)
+/// Determines how long to wait between polls of a readiness probe.
+///
+/// The interval starts at [INITIAL_POLL_INTERVAL] and doubles (up to
+/// [MAX_POLL_INTERVAL]) every time the probe reports the same status again.
+/// It is reset whenever the status changes, so that services which are quick
+/// to start are still picked up quickly.
+pub struct PollBackoff {
+    interval: Duration,
+    last_status: Option<ApiProbeStatus>,
 }
 
-pub fn get_plan(
-    modules: &[&ModuleDefinition],
-    opts: &DeployOptions,
-    daemon_url: &str,
-) -> Result<ApiGetPlanResponse> {
-    let client = reqwest::blocking::Client::new();
-    let request = build_get_plan_request(modules, opts);
-    let get_plan_result = client
-        .post(&(daemon_url.to_owned() + "/get_plan"))
-        .json(&request)
-        .send()?
-        .json()?;
-    Ok(get_plan_result)
+impl PollBackoff {
+    pub fn new() -> PollBackoff {
+        PollBackoff {
+            interval: INITIAL_POLL_INTERVAL,
+            last_status: None,
+        }
+    }
+
+    /// Records the status returned by a poll.
+    pub fn observe(&mut self, status: Option<ApiProbeStatus>) {
+        if status == self.last_status {
+            self.interval = (self.interval * 2).min(MAX_POLL_INTERVAL);
+        } else {
+            self.interval = INITIAL_POLL_INTERVAL;
+            self.last_status = status;
+        }
+    }
+
+    /// Returns how long to wait until the next poll.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+}
+
+impl Default for PollBackoff {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 fn task_took_too_long_msg(task_name: &str) -> String {
@@ -322,3 +1215,34 @@ fn task_took_too_long_msg(task_name: &str) -> String {
         cbold!("Note:")
     );
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_poll_backoff() {
+        let mut backoff = PollBackoff::new();
+        assert_eq!(backoff.interval(), INITIAL_POLL_INTERVAL);
+
+        backoff.observe(Some(ApiProbeStatus::Pending));
+        assert_eq!(backoff.interval(), INITIAL_POLL_INTERVAL);
+        backoff.observe(Some(ApiProbeStatus::Pending));
+        assert_eq!(backoff.interval(), Duration::from_secs(1));
+        for _ in 0..10 {
+            backoff.observe(Some(ApiProbeStatus::Pending));
+        }
+        assert_eq!(backoff.interval(), MAX_POLL_INTERVAL);
+
+        backoff.observe(Some(ApiProbeStatus::Failing));
+        assert_eq!(backoff.interval(), INITIAL_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn test_path_segment() {
+        assert_eq!(path_segment("auth-service").to_string(), "auth-service");
+        assert_eq!(path_segment("auth@test").to_string(), "auth@test");
+        assert_eq!(path_segment("a/b c").to_string(), "a%2Fb%20c");
+        assert_eq!(path_segment("db?#%").to_string(), "db%3F%23%25");
+    }
+}
@@ -0,0 +1,185 @@
+use crate::client::module::{ModuleDefinition, ModuleKind};
+use anyhow::{bail, Result};
+use std::iter::Peekable;
+
+/// A predicate over module definitions used to select a subset of them.
+///
+/// Selectors are parsed from expressions such as
+/// `kind=Service AND label.team=backend`. The supported predicates are:
+/// * `kind=<Kind>` - Modules of the given kind.
+/// * `label.<key>=<value>` - Modules with the given label.
+/// * `name=<glob>` - Modules with a name matching the glob (`*` matches any
+/// sequence of characters and `?` any single character).
+///
+/// Predicates can be combined with `AND` and `OR`, where `AND` binds tighter
+/// than `OR`.
+#[derive(Debug)]
+pub enum Selector {
+    Kind(ModuleKind),
+    Label(String, String),
+    Name(String),
+    And(Box<Selector>, Box<Selector>),
+    Or(Box<Selector>, Box<Selector>),
+}
+
+impl Selector {
+    /// Parses a selector from the given expression.
+    pub fn parse(expr: &str) -> Result<Selector> {
+        let mut tokens = expr.split_whitespace().peekable();
+        let selector = Self::parse_or(&mut tokens)?;
+        if let Some(token) = tokens.next() {
+            bail!("Unexpected '{}' in selector", token);
+        }
+        Ok(selector)
+    }
+
+    /// Whether the module matches the selector.
+    pub fn matches(&self, module: &ModuleDefinition) -> bool {
+        match self {
+            Self::Kind(kind) => module.kind == *kind,
+            Self::Label(key, value) => module.labels.get(key) == Some(value),
+            Self::Name(pattern) => glob_match(pattern, &module.name),
+            Self::And(lhs, rhs) => lhs.matches(module) && rhs.matches(module),
+            Self::Or(lhs, rhs) => lhs.matches(module) || rhs.matches(module),
+        }
+    }
+
+    fn parse_or<'a, I>(tokens: &mut Peekable<I>) -> Result<Selector>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let mut selector = Self::parse_and(tokens)?;
+        while tokens.peek() == Some(&"OR") {
+            tokens.next();
+            let rhs = Self::parse_and(tokens)?;
+            selector = Self::Or(Box::new(selector), Box::new(rhs));
+        }
+        Ok(selector)
+    }
+
+    fn parse_and<'a, I>(tokens: &mut Peekable<I>) -> Result<Selector>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let mut selector = Self::parse_predicate(tokens)?;
+        while tokens.peek() == Some(&"AND") {
+            tokens.next();
+            let rhs = Self::parse_predicate(tokens)?;
+            selector = Self::And(Box::new(selector), Box::new(rhs));
+        }
+        Ok(selector)
+    }
+
+    fn parse_predicate<'a, I>(tokens: &mut Peekable<I>) -> Result<Selector>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let token = match tokens.next() {
+            Some(token) => token,
+            None => bail!("Expected a predicate at the end of the selector"),
+        };
+        let (key, value) = match token.split_once('=') {
+            Some((key, value)) if !value.is_empty() => (key, value),
+            _ => bail!(
+                "Invalid predicate '{}' in selector, expected <key>=<value>",
+                token
+            ),
+        };
+
+        match key {
+            "kind" => Ok(Self::Kind(parse_kind(value)?)),
+            "name" => Ok(Self::Name(value.to_string())),
+            _ => match key.strip_prefix("label.") {
+                Some(label) if !label.is_empty() => {
+                    Ok(Self::Label(label.to_string(), value.to_string()))
+                }
+                _ => bail!(
+                    "Unknown predicate '{}' in selector, expected one of \
+                    'kind', 'name' or 'label.<key>'",
+                    key
+                ),
+            },
+        }
+    }
+}
+
+fn parse_kind(kind: &str) -> Result<ModuleKind> {
+    match kind.to_lowercase().as_str() {
+        "task" => Ok(ModuleKind::Task),
+        "service" => Ok(ModuleKind::Service),
+        "oneshot" => Ok(ModuleKind::OneShot),
+        "check" => Ok(ModuleKind::Check),
+        "group" => Ok(ModuleKind::Group),
+        "shell" => Ok(ModuleKind::Shell),
+        "waitfor" => Ok(ModuleKind::WaitFor),
+        _ => bail!("Unknown module kind '{}' in selector", kind),
+    }
+}
+
+/// Matches a name against a glob pattern, where `*` matches any sequence of
+/// characters and `?` matches any single character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` seen, and the position in the name it was
+    // matched up to.
+    let mut last_star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            last_star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = last_star {
+            // Let the last `*` consume one more character and retry
+            p = star_p + 1;
+            n = star_n + 1;
+            last_star = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("backend-*", "backend-api"));
+        assert!(glob_match("*-api", "backend-api"));
+        assert!(glob_match("b?ck*d-*", "backend-api"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("backend-?", "backend-api"));
+        assert!(!glob_match("frontend-*", "backend-api"));
+    }
+
+    #[test]
+    fn test_selector_precedence() {
+        let selector =
+            Selector::parse("kind=Task OR kind=Service AND label.team=api")
+                .unwrap();
+
+        match selector {
+            Selector::Or(lhs, rhs) => {
+                assert!(matches!(*lhs, Selector::Kind(ModuleKind::Task)));
+                assert!(matches!(*rhs, Selector::And(_, _)));
+            }
+            _ => panic!("Expected OR at the top level"),
+        }
+    }
+
+    #[test]
+    fn test_selector_parse_errors() {
+        assert!(Selector::parse("").is_err());
+        assert!(Selector::parse("kind=Service AND").is_err());
+        assert!(Selector::parse("kind=Unknown").is_err());
+        assert!(Selector::parse("team=api").is_err());
+        assert!(Selector::parse("kind=Service name=api").is_err());
+    }
+}
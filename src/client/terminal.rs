@@ -19,6 +19,13 @@ macro_rules! teprint {
     };
 }
 
+#[macro_export]
+macro_rules! twarn {
+    ( $x:expr ) => {
+        eprintln!("{} {:}", console::style("Warning:").bold().yellow(), $x);
+    };
+}
+
 #[macro_export]
 macro_rules! texit {
     ($x:expr) => {{
@@ -1,5 +1,7 @@
-use crate::client::module::{InnerDefinition, ModuleDefinition, Probe};
-use anyhow::{bail, Context, Result};
+use crate::client::module::{
+    module_names_set, InnerDefinition, ModuleDefinition, ModuleKind, Probe,
+};
+use anyhow::{bail, Result};
 use std::collections::HashSet;
 
 pub fn non_existant_modules<'a>(
@@ -16,14 +18,45 @@ pub fn non_existant_modules<'a>(
     non_existent
 }
 
+/// Fails if some of the selected modules do not exist, or if the dependencies
+/// of the module definitions refer to unknown modules.
 pub fn validate_modules_selected(
-    module_names: &HashSet<&str>,
+    module_defs: &[ModuleDefinition],
     to_validate: &[&str],
 ) -> Result<()> {
-    let non_existant = non_existant_modules(module_names, to_validate);
+    let module_names = module_names_set(module_defs);
+    let non_existant = non_existant_modules(&module_names, to_validate);
     if !non_existant.is_empty() {
         bail!("The following modules do not exist: {:?}", non_existant)
     }
+    validate_dependencies_exist(module_defs)
+}
+
+/// Fails if a group which requires all of its dependencies (`require_all`) is
+/// selected without some of them.
+pub fn validate_group_members(selected: &[&ModuleDefinition]) -> Result<()> {
+    let selected_names: HashSet<_> =
+        selected.iter().map(|m| m.name.as_str()).collect();
+    for module in selected {
+        if let InnerDefinition::Group(group) = &module.inner {
+            if !group.require_all {
+                continue;
+            }
+            let missing: Vec<_> = group
+                .dependencies
+                .iter()
+                .filter(|d| !selected_names.contains(d.as_str()))
+                .collect();
+            if !missing.is_empty() {
+                bail!(
+                    "Group '{}' requires all of its members to be deployed, \
+                    but the following were not selected: {:?}",
+                    module.name,
+                    missing
+                );
+            }
+        }
+    }
     Ok(())
 }
 
@@ -55,9 +88,14 @@ pub fn validate_module_names_exist(
 
 pub fn validate_fields(modules: &[ModuleDefinition]) -> Result<()> {
     for module in modules {
+        // Separates the name of a module from its namespace in the daemon
+        if module.name.contains('@') {
+            bail!("The name of module '{}' cannot contain '@'", module.name);
+        }
         match &module.inner {
             InnerDefinition::Service(svc_or_task)
-            | InnerDefinition::Task(svc_or_task) => {
+            | InnerDefinition::Task(svc_or_task)
+            | InnerDefinition::OneShot(svc_or_task) => {
                 if svc_or_task.shell.is_some()
                     && !svc_or_task.command.is_empty()
                 {
@@ -91,6 +129,98 @@ pub fn validate_fields(modules: &[ModuleDefinition]) -> Result<()> {
                         );
                     }
                 }
+                if let Some(command) = &svc_or_task.shutdown_command {
+                    if command.is_empty() {
+                        bail!(
+                            "The 'shutdown_command' of {} cannot be empty",
+                            svc_or_task.name
+                        );
+                    }
+                }
+                if svc_or_task.max_memory_mb == Some(0) {
+                    bail!(
+                        "The 'max_memory_mb' of {} must be greater than zero",
+                        svc_or_task.name
+                    );
+                }
+                if let Some(percent) = svc_or_task.cpu_quota_percent {
+                    if !(1..=100).contains(&percent) {
+                        bail!(
+                            "The 'cpu_quota_percent' of {} must be between 1 \
+                            and 100",
+                            svc_or_task.name
+                        );
+                    }
+                }
+                if let Some(umask) = svc_or_task.umask {
+                    if umask > 0o777 {
+                        bail!(
+                            "The 'umask' of {} must be between 0o000 and \
+                            0o777",
+                            svc_or_task.name
+                        );
+                    }
+                }
+                if svc_or_task.stdout_filter.iter().any(String::is_empty) {
+                    bail!(
+                        "The 'stdout_filter' of {} cannot contain empty \
+                        strings",
+                        svc_or_task.name
+                    );
+                }
+                if let Some(socket) = &svc_or_task.socket_activation {
+                    if module.kind != ModuleKind::Service {
+                        bail!(
+                            "Module {} cannot use 'socket_activation', since \
+                            only services can be socket activated",
+                            svc_or_task.name
+                        );
+                    }
+                    if socket.path.is_some() == socket.port.is_some() {
+                        bail!(
+                            "The 'socket_activation' of {} must define \
+                            exactly one of 'path' or 'port'",
+                            svc_or_task.name
+                        );
+                    }
+                }
+                if let Some(command) = &svc_or_task.readiness_command {
+                    if svc_or_task.readiness_probe.is_some() {
+                        bail!(
+                            "Module {} cannot have both a \
+                            'readiness_command' and a 'readiness_probe'",
+                            svc_or_task.name
+                        );
+                    }
+                    if command.is_empty() {
+                        bail!(
+                            "The 'readiness_command' of {} cannot be empty",
+                            svc_or_task.name
+                        );
+                    }
+                }
+                if let Some(url) = &svc_or_task.health_check_endpoint {
+                    if svc_or_task.readiness_command.is_some()
+                        || svc_or_task.readiness_probe.is_some()
+                    {
+                        bail!(
+                            "Module {} cannot have a 'health_check_endpoint' \
+                            along with a 'readiness_command' or a \
+                            'readiness_probe'",
+                            svc_or_task.name
+                        );
+                    }
+                    validate_http_url(url, &svc_or_task.name)?;
+                }
+                for probe in svc_or_task
+                    .readiness_probe
+                    .iter()
+                    .chain(svc_or_task.liveness_probe.iter())
+                {
+                    if let Probe::Http(http) = probe {
+                        validate_http_url(&http.url, &svc_or_task.name)?;
+                    }
+                }
                 if let Some(Probe::Exec(probe)) = &svc_or_task.readiness_probe {
                     if probe.shell.is_some() && !probe.command.is_empty() {
                         bail!(
@@ -153,78 +283,106 @@ pub fn validate_fields(modules: &[ModuleDefinition]) -> Result<()> {
                     );
                 }
             }
+            InnerDefinition::WaitFor(wait_for) => {
+                let target_kind = modules
+                    .iter()
+                    .find(|m| m.name == wait_for.target)
+                    .map(|m| &m.kind);
+                if target_kind.map_or(false, |k| *k != ModuleKind::Service) {
+                    bail!(
+                        "The target of {} must be a service",
+                        wait_for.name
+                    );
+                }
+            }
             _ => {}
         }
     }
     Ok(())
 }
 
-pub fn validate_dependencies_exist(modules: &[ModuleDefinition]) -> Result<()> {
+/// HTTP probes only support plain `http://` URLs.
+fn validate_http_url(url: &str, module_name: &str) -> Result<()> {
+    if !url.starts_with("http://") {
+        bail!(
+            "The health check URL '{}' of {} must start with 'http://'",
+            url,
+            module_name
+        );
+    }
+    Ok(())
+}
+
+/// Returns every dependency name which does not refer to a known module.
+///
+/// Each entry describes the unknown name along with the module and field it
+/// was found in, eg. `'typo-service' (dependencies of 'api')`.
+pub fn validate_dependency_names(defs: &[ModuleDefinition]) -> Vec<String> {
     let module_names: HashSet<_> =
-        modules.iter().map(|m| m.name.clone()).collect();
-    for module in modules {
-        match module.inner {
-            InnerDefinition::Group(ref grp) => {
-                validate_module_names_exist(&module_names, &grp.dependencies)
-                    .with_context(|| {
-                        format!(
-                            "Failed resolving dependencies of group '{}'",
-                            module.name
-                        )
-                    })?
-            }
-            InnerDefinition::Shell(ref shell) => {
-                let service_name = vec![shell.service.clone()];
-                validate_module_names_exist(&module_names, &service_name)
-                    .with_context(|| {
-                        format!(
-                            "Failed resolving service of shell '{}'",
-                            module.name
-                        )
-                    })?
-            }
-            InnerDefinition::Service(ref svc_or_task)
-            | InnerDefinition::Task(ref svc_or_task) => {
-                validate_module_names_exist(
-                    &module_names,
-                    &svc_or_task.dependencies,
-                )
-                .with_context(|| {
-                    format!(
-                        "Failed resolving dependencies of service/task '{}'",
-                        module.name
-                    )
-                })?;
-                validate_module_names_exist(
-                    &module_names,
-                    &svc_or_task.ordered_dependencies,
-                )
-                .with_context(|| {
-                    format!(
-                        "Failed resolving ordered_dependencies of service/task '{}'",
-                        module.name
-                    )
-                })?;
-                validate_module_names_exist(
-                    &module_names,
-                    &svc_or_task.post_up,
-                )
-                .with_context(|| {
-                    format!(
-                        "Failed resolving post_up dependencies of service/task '{}'",
-                        module.name
-                    )
-                })?;
-                validate_module_names_exist(&module_names, &svc_or_task.post)
-                    .with_context(|| {
-                    format!(
-                        "Failed resolving post dependencies of service/task '{}'",
-                        module.name
-                    )
-                })?;
+        defs.iter().map(|m| m.name.as_str()).collect();
+    let check_names: HashSet<_> = defs
+        .iter()
+        .filter(|m| m.kind == ModuleKind::Check)
+        .map(|m| m.name.as_str())
+        .collect();
+
+    let mut invalid = Vec::new();
+    for module in defs {
+        let (known, lists) = match &module.inner {
+            InnerDefinition::Group(grp) => (
+                &module_names,
+                vec![("dependencies", grp.dependencies.as_slice())],
+            ),
+            InnerDefinition::Shell(shell) => (
+                &module_names,
+                vec![("service", std::slice::from_ref(&shell.service))],
+            ),
+            InnerDefinition::WaitFor(wait_for) => (
+                &module_names,
+                vec![("target", std::slice::from_ref(&wait_for.target))],
+            ),
+            InnerDefinition::Service(svc_or_task)
+            | InnerDefinition::Task(svc_or_task)
+            | InnerDefinition::OneShot(svc_or_task) => (
+                &module_names,
+                vec![
+                    ("dependencies", svc_or_task.dependencies.as_slice()),
+                    (
+                        "ordered_dependencies",
+                        svc_or_task.ordered_dependencies.as_slice(),
+                    ),
+                    ("post_up", svc_or_task.post_up.as_slice()),
+                    ("post", svc_or_task.post.as_slice()),
+                ],
+            ),
+            InnerDefinition::Check(check) => (
+                &check_names,
+                vec![("depends_on", check.depends_on.as_slice())],
+            ),
+        };
+
+        for (field, names) in lists {
+            for name in names {
+                if !known.contains(name.as_str()) {
+                    invalid.push(format!(
+                        "'{}' ({} of '{}')",
+                        name, field, module.name
+                    ));
+                }
             }
-            _ => {}
         }
     }
+    invalid
+}
+
+pub fn validate_dependencies_exist(modules: &[ModuleDefinition]) -> Result<()> {
+    let invalid = validate_dependency_names(modules);
+    if !invalid.is_empty() {
+        bail!(
+            "The following dependencies refer to modules which do not \
+            exist:\n{}",
+            invalid.join("\n")
+        );
+    }
     Ok(())
 }
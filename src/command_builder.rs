@@ -29,6 +29,16 @@ impl CommandBuilder {
         CommandBuilder { command }
     }
 
+    /// Set whether the process inherits the environment of the current
+    /// process. Must be called before [CommandBuilder::env], since disabling
+    /// it also clears any variables set so far.
+    pub fn inherit_env(&mut self, inherit: bool) -> &mut Self {
+        if !inherit {
+            self.command.env_clear();
+        }
+        self
+    }
+
     /// Set the process's environment variables.
     pub fn env<'c>(
         &'c mut self,
@@ -96,3 +106,29 @@ impl CommandBuilder {
         self.command
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_inherit_env() {
+        env::set_var("CARTEL_TEST_PARENT_VAR", "parent");
+        let mut env = HashMap::new();
+        env.insert("CARTEL_TEST_VAR".to_string(), "module".to_string());
+        let printenv = |inherit: bool| {
+            let mut cmd = CommandBuilder::new(&["/usr/bin/env".to_string()]);
+            cmd.inherit_env(inherit).env(&env);
+            let output = cmd.build().output().unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        let inherited = printenv(true);
+        assert!(inherited.contains("CARTEL_TEST_PARENT_VAR=parent"));
+        assert!(inherited.contains("CARTEL_TEST_VAR=module"));
+
+        let clean = printenv(false);
+        assert_eq!(clean.trim_end(), "CARTEL_TEST_VAR=module");
+    }
+}
@@ -1,4 +1,5 @@
 use crate::constants::PROJECT_DIR;
+use crate::path::from_user_str;
 use anyhow::{bail, Context, Result};
 use lazy_static::lazy_static;
 use phf::phf_map;
@@ -21,6 +22,36 @@ pub struct DaemonConfig {
     #[serde(default)]
     #[serde(deserialize_with = "bool_from_enabled_disabled")]
     pub use_env_grabber: Option<bool>,
+    /// The pidfile written by the daemon when started in the background.
+    pub pidfile: Option<String>,
+    /// The address the daemon API listens on.
+    pub bind_addr: Option<String>,
+    /// The maximum level of the daemon logs (eg. "info", "debug").
+    pub log_level: Option<String>,
+    /// The format of the daemon logs ("pretty" or "json").
+    pub log_format: Option<String>,
+    /// The number of definitions kept per service for rollbacks.
+    pub max_snapshots: Option<String>,
+    /// A key which clients must send along with every request. Requests are
+    /// not authenticated when unset.
+    pub api_key: Option<String>,
+}
+
+impl DaemonConfig {
+    /// Returns the path of the daemon pidfile.
+    ///
+    /// Uses the `pidfile` option when set, otherwise defaults to `daemon.pid`
+    /// inside the project directory.
+    pub fn pidfile_path(&self) -> PathBuf {
+        if let Some(path) = self.pidfile.as_ref().and_then(from_user_str) {
+            return path;
+        }
+        let mut pidfile_path =
+            dirs::home_dir().expect("Failed to locate users home dir");
+        pidfile_path.push(PROJECT_DIR);
+        pidfile_path.push("daemon.pid");
+        pidfile_path
+    }
 }
 
 fn bool_from_enabled_disabled<'de, D>(
@@ -78,7 +109,8 @@ impl Default for PersistedConfig {
     }
 }
 
-fn default_config_file_path() -> PathBuf {
+/// Returns the path of the config file inside the project directory.
+pub fn default_config_file_path() -> PathBuf {
     let mut config_path =
         dirs::home_dir().expect("Failed to locate users home dir");
     config_path.push(PROJECT_DIR);
@@ -86,6 +118,12 @@ fn default_config_file_path() -> PathBuf {
     config_path
 }
 
+/// Returns the path of the daemon pidfile, as configured in the default config
+/// file.
+pub fn daemon_pidfile_path() -> PathBuf {
+    PERSISTED_CONFIG.daemon.pidfile_path()
+}
+
 pub fn create_config_if_not_exists() -> Result<()> {
     let path = default_config_file_path();
 
@@ -168,6 +206,12 @@ pub struct EditableConfig {
 static KEY_TO_PATH: phf::Map<&'static str, [&'static str; 2]> = phf_map! {
     "daemon.port" => ["daemon", "port"],
     "daemon.use_env_grabber" => ["daemon", "use_env_grabber"],
+    "daemon.pidfile" => ["daemon", "pidfile"],
+    "daemon.bind_addr" => ["daemon", "bind_addr"],
+    "daemon.log_level" => ["daemon", "log_level"],
+    "daemon.log_format" => ["daemon", "log_format"],
+    "daemon.max_snapshots" => ["daemon", "max_snapshots"],
+    "daemon.api_key" => ["daemon", "api_key"],
     "client.default_dir" => ["client", "default_dir"],
     "client.use_current_shell" => ["client", "use_current_shell"],
 };
@@ -1 +1,7 @@
 pub const PROJECT_DIR: &str = ".cartel";
+
+/// The header used to correlate client requests with the daemon logs.
+pub const REQUEST_ID_HEADER: &str = "X-Request-ID";
+
+/// The header carrying the API key of the daemon, if it requires one.
+pub const API_KEY_HEADER: &str = "X-Cartel-Api-Key";
@@ -0,0 +1,133 @@
+use crossbeam_queue::SegQueue;
+use crossbeam_utils::thread;
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashSet;
+
+/// Runs `run` on each item, on up to `max_concurrency` threads at the same
+/// time, and waits for all of them to complete.
+///
+/// The items are started in the order given, each with the index its result
+/// is returned at. An item is only run once the items at the indices given
+/// by `depends_on` have finished, so each item must come after the items it
+/// depends on.
+pub fn run_batch<T, R>(
+    items: Vec<(usize, T)>,
+    max_concurrency: usize,
+    depends_on: impl Fn(&T) -> Vec<usize> + Sync,
+    run: impl Fn(&T) -> R + Sync,
+) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+{
+    let queue = SegQueue::new();
+    for item in items {
+        queue.push(item);
+    }
+    let workers = max_concurrency.max(1).min(queue.len());
+    // The indices of the items which finished
+    let finished = Mutex::new(HashSet::new());
+    let item_finished = Condvar::new();
+
+    let mut results: Vec<_> = thread::scope(|s| {
+        let queue = &queue;
+        let depends_on = &depends_on;
+        let run = &run;
+        let finished = &finished;
+        let item_finished = &item_finished;
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                s.spawn(move |_| {
+                    let mut results = vec![];
+                    while let Some((idx, item)) = queue.pop() {
+                        let dependencies = depends_on(&item);
+                        let mut done = finished.lock();
+                        while !dependencies.iter().all(|d| done.contains(d)) {
+                            item_finished.wait(&mut done);
+                        }
+                        drop(done);
+
+                        results.push((idx, run(&item)));
+                        finished.lock().insert(idx);
+                        item_finished.notify_all();
+                    }
+                    results
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("Task thread panicked"))
+            .collect()
+    })
+    .expect("Failed to join task threads");
+
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn test_results_are_in_index_order() {
+        let items = vec![(2, "c"), (0, "a"), (3, "d"), (1, "b")];
+
+        let results =
+            run_batch(items, 2, |_| vec![], |item| item.to_uppercase());
+        assert_eq!(results, vec!["A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn test_max_concurrency() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let items: Vec<_> = (0..6).map(|idx| (idx, idx)).collect();
+
+        let results = run_batch(items, 2, |_| vec![], |item| {
+            let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now_running, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            running.fetch_sub(1, Ordering::SeqCst);
+            *item
+        });
+
+        assert_eq!(results, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_items_run_one_at_a_time_in_order() {
+        let started = parking_lot::Mutex::new(vec![]);
+        let items = vec![(1, "seed"), (0, "migrate")];
+
+        run_batch(items, 1, |_| vec![], |item| started.lock().push(*item));
+        assert_eq!(*started.lock(), vec!["seed", "migrate"]);
+    }
+
+    #[test]
+    fn test_items_wait_for_their_dependencies() {
+        let finished = parking_lot::Mutex::new(vec![]);
+        // seed (1) depends on migrate (0), which takes a while
+        let items = vec![(0, "migrate"), (1, "seed"), (2, "other")];
+        let depends_on = |item: &&str| match *item {
+            "seed" => vec![0],
+            _ => vec![],
+        };
+
+        run_batch(items, 3, depends_on, |item| {
+            if *item == "migrate" {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            finished.lock().push(*item);
+        });
+        let finished = finished.into_inner();
+        let position = |name| finished.iter().position(|i| *i == name);
+        assert!(position("migrate") < position("seed"));
+        assert_eq!(position("other"), Some(0));
+    }
+}
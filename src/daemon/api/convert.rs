@@ -1,18 +1,70 @@
 use crate::daemon::api::handlers::*;
+use crate::daemon::api::lock::LockHolder;
 use crate::daemon::executor::RunStatus;
 use crate::daemon::logs::log_file_module;
-use crate::daemon::module::{ModuleDefinition, ModuleKind, TermSignal};
+use crate::daemon::module::{
+    ModuleDefinition, ModuleKind, PortMapping, SocketActivationConfig,
+    TermSignal,
+};
 use crate::daemon::monitor::{
-    ExecMonitor, LogLineMonitor, Monitor, MonitorTask, NetMonitor,
+    ExecMonitor, HttpMonitor, LogLineMonitor, Monitor, MonitorTask,
+    NetMonitor,
 };
-use crate::daemon::planner::{Plan, PlannedAction};
+use crate::daemon::planner::{Plan, PlannedAction, PsStatus};
 use crate::path;
-use anyhow::Result;
+use anyhow::{bail, Error, Result};
 use std::path::Path;
+use std::str::FromStr;
+
+/// Separates the name of a module from its namespace, eg. `auth@test`.
+const NAMESPACE_SEPARATOR: char = '@';
+
+/// Returns the name the daemon tracks a module by in the given namespace.
+pub fn qualified_name(name: &str, namespace: Option<&str>) -> String {
+    match namespace {
+        Some(namespace) => {
+            format!("{}{}{}", name, NAMESPACE_SEPARATOR, namespace)
+        }
+        None => name.to_string(),
+    }
+}
+
+/// Returns the path of a log file given in a definition, qualified with the
+/// namespace of the module so that each namespace logs to its own file (eg.
+/// `logs/auth.log` becomes `logs/auth@test.log`).
+fn namespaced_log_file(path: &str, namespace: &str) -> String {
+    let path = Path::new(path);
+    let mut file_name = path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!("{}{}", NAMESPACE_SEPARATOR, namespace));
+    if let Some(extension) = path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
+
+/// Qualifies the name, dependencies and log file of a definition with its
+/// namespace, so modules only depend on modules in the same namespace.
+fn into_namespace(mut src: ApiModuleDefinition) -> ApiModuleDefinition {
+    if let Some(namespace) = src.namespace.as_deref() {
+        src.name = qualified_name(&src.name, Some(namespace));
+        src.dependencies = src
+            .dependencies
+            .iter()
+            .map(|dep| qualified_name(dep, Some(namespace)))
+            .collect();
+        src.log_file_path = src
+            .log_file_path
+            .as_deref()
+            .map(|path| namespaced_log_file(path, namespace));
+    }
+    src
+}
 
 pub fn from_task(src: ApiModuleDefinition) -> ModuleDefinition {
+    let src = into_namespace(src);
     ModuleDefinition::new(
-        ModuleKind::Task,
+        src.kind.into(),
         src.name,
         src.command,
         src.environment,
@@ -21,12 +73,26 @@ pub fn from_task(src: ApiModuleDefinition) -> ModuleDefinition {
         src.working_dir.and_then(path::from_user_str),
         TermSignal::KILL,
         None,
+        src.capture_output,
+        src.max_memory_mb,
+        src.cpu_quota_percent,
+        src.network_namespace,
+        src.shutdown_command,
+        src.shutdown_timeout_secs,
+        src.inherit_env,
+        src.ports.into_iter().map(Into::into).collect(),
+        src.umask,
+        src.stdout_filter,
+        src.version,
+        src.socket_activation.map(Into::into),
+        src.namespace,
     )
 }
 
 pub fn from_service_with_monitor(
-    mut src: ApiModuleDefinition,
+    src: ApiModuleDefinition,
 ) -> Result<(ModuleDefinition, Option<Monitor>)> {
+    let mut src = into_namespace(src);
     let readiness_probe = src.readiness_probe.take();
     let liveness_probe = src.liveness_probe.take();
 
@@ -40,6 +106,19 @@ pub fn from_service_with_monitor(
         src.working_dir.and_then(path::from_user_str),
         src.termination_signal.into(),
         None, // assigned below
+        false,
+        src.max_memory_mb,
+        src.cpu_quota_percent,
+        src.network_namespace,
+        src.shutdown_command,
+        src.shutdown_timeout_secs,
+        src.inherit_env,
+        src.ports.into_iter().map(Into::into).collect(),
+        src.umask,
+        src.stdout_filter,
+        src.version,
+        src.socket_activation.map(Into::into),
+        src.namespace,
     );
 
     let log_file_path = log_file_module(&module_definition)?;
@@ -56,6 +135,7 @@ pub fn from_service_with_monitor(
 }
 
 pub fn from_task_or_service(src: ApiModuleDefinition) -> ModuleDefinition {
+    let src = into_namespace(src);
     ModuleDefinition::new(
         src.kind.into(),
         src.name,
@@ -66,6 +146,19 @@ pub fn from_task_or_service(src: ApiModuleDefinition) -> ModuleDefinition {
         src.working_dir.and_then(path::from_user_str),
         src.termination_signal.into(),
         None, // assumed not needed in any code using this
+        src.capture_output,
+        src.max_memory_mb,
+        src.cpu_quota_percent,
+        src.network_namespace,
+        src.shutdown_command,
+        src.shutdown_timeout_secs,
+        src.inherit_env,
+        src.ports.into_iter().map(Into::into).collect(),
+        src.umask,
+        src.stdout_filter,
+        src.version,
+        src.socket_activation.map(Into::into),
+        src.namespace,
     )
 }
 
@@ -74,8 +167,103 @@ impl From<RunStatus> for ApiModuleRunStatus {
         match r {
             RunStatus::RUNNING => ApiModuleRunStatus::RUNNING,
             RunStatus::STOPPED => ApiModuleRunStatus::STOPPED,
+            RunStatus::PAUSED => ApiModuleRunStatus::PAUSED,
             RunStatus::WAITING => ApiModuleRunStatus::WAITING,
             RunStatus::EXITED => ApiModuleRunStatus::EXITED,
+            RunStatus::COMPLETED => ApiModuleRunStatus::COMPLETED,
+            RunStatus::FAILED => ApiModuleRunStatus::FAILED,
+        }
+    }
+}
+
+impl From<PsStatus> for ApiModuleStatus {
+    fn from(m: PsStatus) -> ApiModuleStatus {
+        ApiModuleStatus {
+            name: m.name,
+            pid: m.pid,
+            time_since_status: m.time_since_status,
+            exit_code: m.exit_code,
+            liveness_status: m.liveness_status.as_ref().map(|s| s.into()),
+            status: ApiModuleRunStatus::from(m.status),
+            command: Some(m.command),
+            log_file_path: Some(m.log_file_path.to_string_lossy().to_string()),
+            restarts: m.restarts,
+            kind: Some((&m.kind).into()),
+            working_dir: m
+                .working_dir
+                .map(|dir| dir.to_string_lossy().to_string()),
+            namespace: m.namespace,
+            ports: m.ports.iter().map(Into::into).collect(),
+            version: m.version,
+        }
+    }
+}
+
+impl From<ApiPortMapping> for PortMapping {
+    fn from(port: ApiPortMapping) -> PortMapping {
+        PortMapping {
+            host_port: port.host_port,
+            container_port: port.container_port,
+            protocol: port.protocol,
+        }
+    }
+}
+
+impl From<ApiSocketActivation> for SocketActivationConfig {
+    fn from(socket: ApiSocketActivation) -> SocketActivationConfig {
+        SocketActivationConfig {
+            path: socket.path,
+            port: socket.port,
+        }
+    }
+}
+
+impl From<&PortMapping> for ApiPortMapping {
+    fn from(port: &PortMapping) -> ApiPortMapping {
+        ApiPortMapping {
+            host_port: port.host_port,
+            container_port: port.container_port,
+            protocol: port.protocol.clone(),
+        }
+    }
+}
+
+impl FromStr for ApiModuleKind {
+    type Err = Error;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        match kind {
+            "Service" => Ok(ApiModuleKind::Service),
+            "Task" => Ok(ApiModuleKind::Task),
+            "OneShot" => Ok(ApiModuleKind::OneShot),
+            _ => bail!("Unknown module kind '{}'", kind),
+        }
+    }
+}
+
+impl FromStr for ApiModuleRunStatus {
+    type Err = Error;
+
+    fn from_str(status: &str) -> Result<Self> {
+        match status.to_uppercase().as_str() {
+            "RUNNING" => Ok(ApiModuleRunStatus::RUNNING),
+            "WAITING" => Ok(ApiModuleRunStatus::WAITING),
+            "STOPPED" => Ok(ApiModuleRunStatus::STOPPED),
+            "PAUSED" => Ok(ApiModuleRunStatus::PAUSED),
+            "EXITED" => Ok(ApiModuleRunStatus::EXITED),
+            "COMPLETED" => Ok(ApiModuleRunStatus::COMPLETED),
+            "FAILED" => Ok(ApiModuleRunStatus::FAILED),
+            _ => bail!("Unknown module status '{}'", status),
+        }
+    }
+}
+
+impl From<&ModuleKind> for ApiModuleKind {
+    fn from(src: &ModuleKind) -> Self {
+        match src {
+            ModuleKind::Service => ApiModuleKind::Service,
+            ModuleKind::Task => ApiModuleKind::Task,
+            ModuleKind::OneShot => ApiModuleKind::OneShot,
         }
     }
 }
@@ -85,6 +273,7 @@ impl From<ApiModuleKind> for ModuleKind {
         match src {
             ApiModuleKind::Service => ModuleKind::Service,
             ApiModuleKind::Task => ModuleKind::Task,
+            ApiModuleKind::OneShot => ModuleKind::OneShot,
         }
     }
 }
@@ -107,6 +296,16 @@ impl From<Plan> for ApiGetPlanResponse {
     }
 }
 
+impl From<LockHolder> for ApiLockHolder {
+    fn from(src: LockHolder) -> Self {
+        ApiLockHolder {
+            user: src.user,
+            pid: src.pid,
+            acquired_at: src.acquired_at,
+        }
+    }
+}
+
 impl From<ApiTermSignal> for TermSignal {
     fn from(signal: ApiTermSignal) -> TermSignal {
         match signal {
@@ -122,6 +321,7 @@ pub fn from_probe(probe: ApiProbe, log_file_path: &Path) -> Monitor {
         ApiProbe::Executable(exe) => exe.into(),
         ApiProbe::LogLine(log) => from_log_line_probe(log, log_file_path),
         ApiProbe::Net(net) => net.into(),
+        ApiProbe::Http(http) => http.into(),
     }
 }
 
@@ -146,6 +346,15 @@ impl From<ApiNetworkProbe> for Monitor {
     }
 }
 
+impl From<ApiHttpProbe> for Monitor {
+    fn from(http: ApiHttpProbe) -> Monitor {
+        Monitor {
+            retries: http.retries,
+            task: MonitorTask::Http(HttpMonitor::from(http.url)),
+        }
+    }
+}
+
 pub fn from_log_line_probe(
     log_line: ApiLogLineProbe,
     log_file_path: &Path,
@@ -158,3 +367,62 @@ pub fn from_log_line_probe(
         )),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn task(namespace: Option<&str>) -> ApiModuleDefinition {
+        serde_json::from_value(serde_json::json!({
+            "kind": "Task",
+            "name": "migrate",
+            "command": ["migrate"],
+            "environment": {},
+            "log_file_path": "logs/migrate.log",
+            "dependencies": ["db"],
+            "working_dir": null,
+            "termination_signal": "KILL",
+            "readiness_probe": null,
+            "liveness_probe": null,
+            "capture_output": false,
+            "max_memory_mb": null,
+            "cpu_quota_percent": null,
+            "network_namespace": null,
+            "shutdown_command": null,
+            "shutdown_timeout_secs": 10,
+            "inherit_env": true,
+            "namespace": namespace,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_qualifies_module_with_namespace() {
+        let module = from_task(task(Some("test")));
+
+        assert_eq!(module.name, "migrate@test");
+        assert_eq!(module.namespace.as_deref(), Some("test"));
+        assert_eq!(module.dependencies, vec!["db@test"]);
+        assert_eq!(
+            module.log_file_path.as_deref(),
+            Some("logs/migrate@test.log")
+        );
+    }
+
+    #[test]
+    fn test_module_without_namespace_is_unchanged() {
+        let module = from_task(task(None));
+
+        assert_eq!(module.name, "migrate");
+        assert_eq!(module.namespace, None);
+        assert_eq!(module.dependencies, vec!["db"]);
+        assert_eq!(module.log_file_path.as_deref(), Some("logs/migrate.log"));
+    }
+
+    #[test]
+    fn test_namespaced_log_file() {
+        let log_file = namespaced_log_file("/tmp/auth.log", "a");
+        assert_eq!(log_file, "/tmp/auth@a.log");
+        assert_eq!(namespaced_log_file("auth", "a"), "auth@a");
+    }
+}
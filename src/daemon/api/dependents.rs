@@ -0,0 +1,134 @@
+use crate::daemon::module::ModuleDefinition;
+use crate::dependency::{
+    DependencyEdge, DependencyGraph, EdgeDirection, WithDependencies, WithKey,
+};
+use anyhow::Result;
+use std::sync::Arc;
+
+/// A module registered with the daemon, as a node of the dependency graph.
+///
+/// Only dependencies which are registered as well are kept, since modules
+/// may depend on modules the daemon doesn't know about (eg. groups).
+#[derive(PartialEq, Eq, Hash)]
+struct RegisteredModule {
+    name: String,
+    dependencies: Vec<String>,
+}
+
+impl WithKey for RegisteredModule {
+    fn key(&self) -> String {
+        self.name.clone()
+    }
+
+    fn key_ref(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl WithDependencies<()> for RegisteredModule {
+    fn dependencies(&self) -> Vec<DependencyEdge<()>> {
+        self.dependencies
+            .iter()
+            .map(|dependency| DependencyEdge {
+                edge_src: self.name.clone(),
+                edge_dst: dependency.clone(),
+                is_weak: false,
+                direction: EdgeDirection::To,
+                marker: (),
+            })
+            .collect()
+    }
+
+    fn is_group(&self) -> bool {
+        false
+    }
+}
+
+/// Returns the names of the registered modules which depend on the named
+/// module, directly or (with `transitive`) through other modules.
+pub fn dependents(
+    modules: &[Arc<ModuleDefinition>],
+    name: &str,
+    transitive: bool,
+) -> Vec<String> {
+    let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+    let nodes = registered_modules(modules, &names);
+
+    let graph = DependencyGraph::from(&nodes, &names);
+    graph
+        .dependents_of(name, transitive)
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the names of the registered modules in the order they should be
+/// deployed in, each module coming after the modules it depends on.
+///
+/// Fails if the registered modules depend on each other in a cycle.
+pub fn dependency_order(
+    modules: &[Arc<ModuleDefinition>],
+) -> Result<Vec<String>> {
+    let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+    let nodes = registered_modules(modules, &names);
+
+    let graph = DependencyGraph::<_, ()>::from(&nodes, &names);
+    let order = graph
+        .dependency_sort()?
+        .into_iter()
+        .map(|node| node.key.clone())
+        .collect();
+    Ok(order)
+}
+
+/// Returns the nodes of the registered modules, keeping only the dependencies
+/// among the given names.
+fn registered_modules(
+    modules: &[Arc<ModuleDefinition>],
+    names: &[&str],
+) -> Vec<RegisteredModule> {
+    modules
+        .iter()
+        .map(|m| RegisteredModule {
+            name: m.name.clone(),
+            dependencies: m
+                .dependencies
+                .iter()
+                .filter(|dependency| names.contains(&dependency.as_str()))
+                .cloned()
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn module(name: &str, dependencies: &[&str]) -> Arc<ModuleDefinition> {
+        let mut module = ModuleDefinition::test_service(name);
+        module.dependencies =
+            dependencies.iter().map(|d| d.to_string()).collect();
+        Arc::new(module)
+    }
+
+    #[test]
+    fn test_dependency_order() {
+        let modules = vec![
+            module("seed", &["migrate"]),
+            module("migrate", &["db"]),
+            module("db", &[]),
+        ];
+
+        let order = dependency_order(&modules).unwrap();
+        assert_eq!(order, vec!["db", "migrate", "seed"]);
+    }
+
+    #[test]
+    fn test_dependency_order_ignores_unknown_dependencies() {
+        let modules = vec![module("seed", &["migrate"]), module("db", &[])];
+
+        let order = dependency_order(&modules).unwrap();
+        assert_eq!(order.len(), 2);
+    }
+}
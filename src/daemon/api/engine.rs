@@ -1,5 +1,12 @@
+use crate::daemon::api::dependents::dependency_order;
+use crate::daemon::api::fairing::{ApiKeyCheck, RequestLogger};
 use crate::daemon::api::handlers;
+use crate::daemon::api::history::DefinitionHistory;
+use crate::daemon::api::lock::DeployLock;
+use crate::daemon::api::oneshots::{self, OneShotStore};
+use crate::daemon::config;
 use crate::daemon::Core;
+use anyhow::{anyhow, Result};
 use log::info;
 use rocket::config::{Environment, LoggingLevel};
 use rocket::Config;
@@ -7,42 +14,95 @@ use std::sync::Arc;
 
 pub struct CoreState {
     pub core: Arc<Core>,
+    /// The previously deployed definitions of services, used for rollbacks.
+    pub history: DefinitionHistory,
+    /// Held by the client deploying with `--lock`, if any.
+    pub lock: DeployLock,
+    /// The one-shots to run again when the daemon starts.
+    pub oneshots: Arc<OneShotStore>,
+    /// Whether requests must carry the daemon API key.
+    pub auth: bool,
 }
 
-pub fn start(core: &Arc<Core>) {
+impl CoreState {
+    /// Returns the names of all the registered modules in the order they
+    /// should be deployed in, so that the daemon can order modules itself
+    /// instead of relying on the order the client sent them in.
+    pub fn module_dependency_order(&self) -> Result<Vec<String>> {
+        let modules: Vec<_> = self
+            .core
+            .planner()
+            .module_definitions()
+            .into_iter()
+            .map(|(definition, _)| definition)
+            .collect();
+        dependency_order(&modules)
+    }
+}
+
+pub fn start(core: &Arc<Core>) -> Result<()> {
     let config = core.config();
-    let port: u16 = config
-        .daemon
-        .port
-        .as_ref()
-        .and_then(|p| p.parse::<u16>().ok())
-        .unwrap_or(13754);
+    let port = config::port(&config.daemon)?;
+    let max_snapshots = config::max_snapshots(&config.daemon)?;
 
     let cfg = Config::build(Environment::Production)
-        .address("127.0.0.1")
+        .address(config::bind_addr(&config.daemon))
         .port(port)
         .log_level(LoggingLevel::Normal)
         .workers(4)
-        .unwrap();
+        .finalize()
+        .map_err(|e| anyhow!("Invalid daemon API configuration: {}", e))?;
+
+    let oneshot_store =
+        Arc::new(OneShotStore::new(OneShotStore::default_path()));
+    oneshots::run_at_startup(core, &oneshot_store)?;
 
     info!("Starting API listener");
     rocket::custom(cfg)
         .manage(CoreState {
             core: Arc::clone(core),
+            history: DefinitionHistory::new(max_snapshots),
+            lock: DeployLock::default(),
+            oneshots: oneshot_store,
+            auth: config.daemon.api_key.is_some(),
+        })
+        .attach(ApiKeyCheck {
+            api_key: config.daemon.api_key.clone(),
         })
+        .attach(RequestLogger)
         .mount(
             "/",
             routes![
                 handlers::index,
                 handlers::health,
+                handlers::daemon_health,
+                handlers::unauthorized,
+                handlers::info,
                 handlers::deploy,
                 handlers::deploy_task,
+                handlers::deploy_task_batch,
                 handlers::status,
                 handlers::stop_all,
                 handlers::module_operation,
+                handlers::bulk_module_operation,
+                handlers::pause_module,
+                handlers::resume_module,
+                handlers::check_module_health,
+                handlers::rollback_module,
+                handlers::module_children,
+                handlers::import_modules,
+                handlers::acquire_lock,
+                handlers::release_lock,
+                handlers::force_release_lock,
                 handlers::log_file,
+                handlers::all_log_files,
+                handlers::clear_log_file,
+                handlers::find_module,
+                handlers::reload_config,
+                handlers::snapshot,
                 handlers::get_plan
             ],
         )
         .launch();
+    Ok(())
 }
@@ -0,0 +1,68 @@
+use crate::constants::{API_KEY_HEADER, REQUEST_ID_HEADER};
+use log::{info, warn};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::Method;
+use rocket::{Data, Request, Response};
+
+/// The route requests without a valid API key are redirected to.
+pub const UNAUTHORIZED_PATH: &str = "/api/v1/unauthorized";
+
+/// Logs every handled request along with the request ID set by the client.
+pub struct RequestLogger;
+
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request logger",
+            kind: Kind::Response,
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let request_id =
+            request.headers().get_one(REQUEST_ID_HEADER).unwrap_or("-");
+        info!(
+            "[{}] {} {} => {}",
+            request_id,
+            request.method(),
+            request.uri(),
+            response.status()
+        );
+    }
+}
+
+/// Rejects requests which do not carry the API key of the daemon, when one is
+/// configured.
+///
+/// Fairings cannot respond to a request themselves, so rejected requests are
+/// rerouted to [UNAUTHORIZED_PATH] before any handler runs.
+pub struct ApiKeyCheck {
+    pub api_key: Option<String>,
+}
+
+impl Fairing for ApiKeyCheck {
+    fn info(&self) -> Info {
+        Info {
+            name: "API key check",
+            kind: Kind::Request,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        let api_key = match &self.api_key {
+            Some(api_key) => api_key,
+            None => return,
+        };
+        if request.headers().get_one(API_KEY_HEADER) == Some(api_key.as_str()) {
+            return;
+        }
+        warn!(
+            "Rejected {} {} without a valid API key",
+            request.method(),
+            request.uri()
+        );
+        request.set_method(Method::Get);
+        request.set_uri(Origin::parse(UNAUTHORIZED_PATH).unwrap());
+    }
+}
@@ -1,18 +1,43 @@
+use crate::constants::PROJECT_DIR;
+use crate::daemon::api::batch::run_batch;
 use crate::daemon::api::convert::*;
+use crate::daemon::api::dependents::{dependency_order, dependents};
 use crate::daemon::api::engine::CoreState;
 use crate::daemon::api::error::*;
+use crate::daemon::error::DaemonError;
+use crate::daemon::executor::RunStatus;
+use crate::daemon::logs::tail_lines;
+use crate::daemon::module::{ModuleDefinition, ModuleKind};
 use crate::daemon::monitor::MonitorType;
-use crate::daemon::planner::MonitorStatus;
+use crate::daemon::planner::{MonitorStatus, Planner};
+use crate::daemon::time::epoch_now;
+use anyhow::{anyhow, Context, Result};
+use crossbeam_utils::thread;
+use log::warn;
+use rocket::http::Status;
+use rocket::response::status;
 use rocket::State;
 use rocket_contrib::json::Json;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::ffi::OsString;
+use std::fs::{File, OpenOptions, Permissions};
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The file snapshots are written to by default, inside the project directory.
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SNAPSHOT_LOG_LINES: usize = 100;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum ApiModuleKind {
     Task,
     Service,
+    OneShot,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -27,6 +52,48 @@ pub struct ApiModuleDefinition {
     pub termination_signal: ApiTermSignal,
     pub readiness_probe: Option<ApiProbe>,
     pub liveness_probe: Option<ApiProbe>,
+    pub capture_output: bool,
+    pub max_memory_mb: Option<u64>,
+    pub cpu_quota_percent: Option<u32>,
+    pub network_namespace: Option<String>,
+    pub shutdown_command: Option<Vec<String>>,
+    pub shutdown_timeout_secs: u64,
+    pub inherit_env: bool,
+    /// Modules in different namespaces are tracked separately, so the same
+    /// module can be deployed once per namespace.
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub ports: Vec<ApiPortMapping>,
+    #[serde(default)]
+    pub umask: Option<u32>,
+    #[serde(default)]
+    pub stdout_filter: Vec<String>,
+    /// The version of the definition, as given by the user.
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub socket_activation: Option<ApiSocketActivation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiPortMapping {
+    pub host_port: u16,
+    pub container_port: u16,
+    pub protocol: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiSocketActivation {
+    pub path: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl ApiModuleDefinition {
+    /// The name the daemon tracks the module by, including its namespace.
+    pub fn qualified_name(&self) -> String {
+        qualified_name(&self.name, self.namespace.as_deref())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -35,6 +102,7 @@ pub enum ApiProbe {
     Executable(ApiExeProbe),
     LogLine(ApiLogLineProbe),
     Net(ApiNetworkProbe),
+    Http(ApiHttpProbe),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -57,6 +125,12 @@ pub struct ApiNetworkProbe {
     pub port: u16,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiHttpProbe {
+    pub retries: u32,
+    pub url: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ApiTermSignal {
     KILL,
@@ -68,16 +142,52 @@ pub enum ApiTermSignal {
 pub struct ApiDeploymentCommand {
     pub module_definition: ApiModuleDefinition,
     pub force: bool,
+    /// Whether to monitor the readiness of the module even if it was already
+    /// deployed.
+    #[serde(default)]
+    pub await_readiness: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApiTaskDeploymentCommand {
     pub task_definition: ApiModuleDefinition,
+    /// The names of all the one-shots defined along with a one-shot, so that
+    /// the daemon forgets the one-shots which are no longer defined.
+    #[serde(default)]
+    pub defined_oneshots: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApiTaskDeploymentResponse {
     pub success: bool,
+    pub output: Option<String>,
+    /// Set when a one-shot was not run because it already ran before.
+    #[serde(default)]
+    pub skipped: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiTaskBatchCommand {
+    pub tasks: Vec<ApiModuleDefinition>,
+    /// How many of the tasks are run at the same time at most.
+    pub max_concurrency: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiTaskResult {
+    pub name: String,
+    /// Not set if the task could not be run, or was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub output: Option<String>,
+    /// Set when the task could not be run.
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// The results of a batch of tasks, in the order the tasks were given in.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiTaskBatchResponse {
+    pub results: Vec<ApiTaskResult>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -91,16 +201,39 @@ pub struct ApiOperationResponse {
     pub success: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiBulkOperationCommand {
+    pub names: Vec<String>,
+    pub operation: ApiModuleOperation,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiModuleOperationResult {
+    pub name: String,
+    pub success: bool,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiBulkOperationResponse {
+    pub results: Vec<ApiModuleOperationResult>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApiDeploymentResponse {
     pub success: bool,
     pub deployed: bool,
     pub monitor: Option<String>,
+    /// Non-fatal observations the daemon made while deploying the module.
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApiModuleStatusResponse {
     pub status: Vec<ApiModuleStatus>,
+    /// When the statuses were collected (seconds since the epoch).
+    pub snapshot_time: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
@@ -108,13 +241,21 @@ pub enum ApiModuleRunStatus {
     RUNNING,
     WAITING,
     STOPPED,
+    PAUSED,
     EXITED,
+    COMPLETED,
+    FAILED,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ApiModuleOperation {
     STOP,
     RESTART,
+    PAUSE,
+    RESUME,
+    /// Sends `SIGHUP` to the module, which conventionally makes it reload its
+    /// configuration.
+    RELOAD,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -125,6 +266,25 @@ pub struct ApiModuleStatus {
     pub liveness_status: Option<ApiProbeStatus>,
     pub exit_code: Option<i32>,
     pub time_since_status: u64,
+    pub command: Option<Vec<String>>,
+    #[serde(default)]
+    pub log_file_path: Option<String>,
+    #[serde(default)]
+    pub restarts: u32,
+    #[serde(default)]
+    pub kind: Option<ApiModuleKind>,
+    /// The absolute directory the module's process runs in.
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    /// The namespace the module was deployed in, if any.
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// The ports the module exposes, as given in its definition.
+    #[serde(default)]
+    pub ports: Vec<ApiPortMapping>,
+    /// The version of the deployed definition, if it has one.
+    #[serde(default)]
+    pub version: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -133,19 +293,117 @@ pub struct ApiLogFileRequest {
     pub module_kind: ApiModuleKind,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiClearLogRequest {
+    pub module_name: String,
+    pub module_kind: ApiModuleKind,
+    pub force: bool,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ApiLogResponse {
     pub log_file_path: OsString,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiAllLogsResponse {
+    /// The log file of each module, by module name.
+    pub logs: HashMap<String, ApiLogResponse>,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum ApiProbeStatus {
+    /// The probe has not been run yet.
+    Starting,
     Pending,
     Successful,
     RetriesExceeded,
     Failing,
     Error,
+    /// The readiness probe passed, so the module is ready to receive traffic.
+    Ready,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiFindModuleResponse {
+    pub module_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiRollbackResponse {
+    /// The definition the module was rolled back to.
+    pub definition: ApiModuleDefinition,
+    /// The key of the readiness monitor of the module, if it has a readiness
+    /// probe.
+    pub monitor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiHealthCheckResponse {
+    /// The status of the liveness probe of the module after the check.
+    pub probe_status: ApiProbeStatus,
+    /// When the module was checked, in seconds since the epoch.
+    pub checked_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiDependentsResponse {
+    /// The modules which depend on the module, sorted by name.
+    pub dependents: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiSnapshotResponse {
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiSnapshot {
+    /// The time the snapshot was taken at (seconds since the epoch).
+    pub taken_at: u64,
+    pub daemon: ApiDaemonMetrics,
+    pub modules: Vec<ApiModuleSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiDaemonMetrics {
+    pub pid: u32,
+    pub started_at: u64,
+    pub uptime_secs: u64,
+    pub modules_total: usize,
+    pub modules_running: usize,
+    pub modules_failing: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiModuleSnapshot {
+    pub kind: ApiModuleKind,
+    pub environment: HashMap<String, String>,
+    pub working_dir: Option<String>,
+    pub log_file_path: String,
+    pub status: ApiModuleStatus,
+    pub log_tail: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiReloadResponse {
+    pub applied: Vec<String>,
+    pub requires_restart: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiInfoResponse {
+    pub version: String,
+    pub pid: u32,
+    pub started_at: u64,
+    pub uptime_secs: u64,
+    pub os: String,
+    pub arch: String,
+    /// The optional capabilities supported by the daemon on this platform.
+    pub features: Vec<String>,
+    pub modules_total: usize,
+    pub modules_running: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -161,6 +419,48 @@ pub enum ApiPlannedAction {
     AlreadyDeployed,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiImportCommand {
+    pub modules: Vec<ApiModuleDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiImportResponse {
+    /// The modules which were registered.
+    pub imported: Vec<String>,
+    /// The modules which were already known to the daemon.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiLockCommand {
+    pub user: String,
+    pub pid: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiLockHolder {
+    pub user: String,
+    pub pid: u32,
+    /// When the lock was acquired (seconds since the epoch).
+    pub acquired_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiForceUnlockResponse {
+    /// The holder the lock was taken from, if it was held.
+    pub holder: Option<ApiLockHolder>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiLockResponse {
+    /// The token to release the lock with, only given if it was acquired.
+    pub token: Option<String>,
+    /// The holder of the lock, either the requesting client or the one
+    /// which already held it.
+    pub holder: ApiLockHolder,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ApiGetPlanRequest {
     pub modules: Vec<ApiModuleDefinition>,
@@ -179,14 +479,21 @@ pub(crate) fn deploy(
 ) -> ApiResult<ApiDeploymentResponse> {
     let planner = core_state.core.planner();
     let command = command.into_inner();
+    let definition = command.module_definition.clone();
 
     let (module_def, monitor) =
         from_service_with_monitor(command.module_definition)?;
     let module_name = module_def.name.clone();
 
+    let warnings = planner.deployment_warnings(&module_def);
     let deployed = planner.deploy(module_def, command.force)?;
+    if deployed {
+        core_state.history.record(definition);
+    }
 
-    let monitor_key = if deployed && monitor.is_some() {
+    let monitor_key = if (deployed || command.await_readiness)
+        && monitor.is_some()
+    {
         let monitor_key = planner.create_monitor(
             &module_name,
             monitor.unwrap(),
@@ -201,6 +508,7 @@ pub(crate) fn deploy(
         success: true,
         deployed,
         monitor: monitor_key,
+        warnings: if deployed { warnings } else { Vec::new() },
     }))
 }
 
@@ -211,9 +519,98 @@ pub(crate) fn deploy_task(
 ) -> ApiResult<ApiTaskDeploymentResponse> {
     let cmd = task.into_inner();
     let planner = core_state.core.planner();
-    let task = cmd.task_definition;
-    planner.deploy_task(&from_task(task))?;
-    Ok(Json(ApiTaskDeploymentResponse { success: true }))
+    let oneshots = &core_state.oneshots;
+    if cmd.task_definition.kind == ApiModuleKind::OneShot {
+        let defined = cmd.defined_oneshots.as_deref();
+        oneshots.record(&cmd.task_definition, defined)?;
+    }
+    let task = from_task(cmd.task_definition);
+    let result = if task.kind != ModuleKind::OneShot {
+        Some(planner.deploy_task(&task)?)
+    } else if oneshots.reserve(&task.name) {
+        Some(planner.deploy_oneshot(task)?)
+    } else {
+        None
+    };
+    Ok(Json(ApiTaskDeploymentResponse {
+        success: true,
+        skipped: result.is_none(),
+        output: result.and_then(|r| r.output),
+    }))
+}
+
+/// Runs many tasks, up to `max_concurrency` of them at the same time, and
+/// waits for all of them to complete.
+///
+/// The tasks are started in dependency order, whatever the order they were
+/// sent in, and each task only starts once the tasks of the batch it depends
+/// on have completed. The results are returned in the order the tasks were
+/// sent in.
+///
+/// The result of every task is reported, rather than failing on the first
+/// task which fails.
+#[post("/api/v1/tasks/batch", data = "<command>")]
+pub(crate) fn deploy_task_batch(
+    command: Json<ApiTaskBatchCommand>,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiTaskBatchResponse> {
+    let command = command.into_inner();
+    let planner = core_state.core.planner();
+    let tasks: Vec<_> =
+        command.tasks.into_iter().map(from_task).map(Arc::new).collect();
+    let order = dependency_order(&tasks)?;
+
+    let indices: HashMap<String, usize> = tasks
+        .iter()
+        .enumerate()
+        .map(|(idx, task)| (task.name.clone(), idx))
+        .collect();
+    let depends_on = |task: &Arc<ModuleDefinition>| -> Vec<usize> {
+        task.dependencies
+            .iter()
+            .filter_map(|dependency| indices.get(dependency).copied())
+            .collect()
+    };
+
+    let mut indexed: Vec<_> = tasks.into_iter().enumerate().collect();
+    indexed.sort_by_key(|(_, task)| {
+        order.iter().position(|name| *name == task.name)
+    });
+    let results =
+        run_batch(indexed, command.max_concurrency, depends_on, |task| {
+            run_batched_task(planner, task)
+        });
+    Ok(Json(ApiTaskBatchResponse { results }))
+}
+
+fn run_batched_task(
+    planner: &Planner,
+    task: &ModuleDefinition,
+) -> ApiTaskResult {
+    let started = Instant::now();
+    let result = if task.kind == ModuleKind::Task {
+        planner.run_task(task)
+    } else {
+        Err(anyhow!("Module {} is not a task", task.name))
+    };
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(result) => ApiTaskResult {
+            name: task.name.clone(),
+            exit_code: result.exit_status.code(),
+            output: result.output,
+            error: None,
+            duration_ms,
+        },
+        Err(e) => ApiTaskResult {
+            name: task.name.clone(),
+            exit_code: None,
+            output: None,
+            error: Some(e.to_string()),
+            duration_ms,
+        },
+    }
 }
 
 #[post("/api/v1/operation", data = "<module>")]
@@ -224,17 +621,227 @@ pub(crate) fn module_operation(
     let module = module.into_inner();
     let planner = core_state.core.planner();
 
-    match module.operation {
-        ApiModuleOperation::STOP => {
-            planner.stop_module(&module.module_name)?;
-        }
-        ApiModuleOperation::RESTART => {
-            planner.restart_module(&module.module_name)?;
-        }
-    };
+    apply_operation(planner, &module.module_name, &module.operation)?;
     Ok(Json(ApiOperationResponse { success: true }))
 }
 
+/// Suspends the processes of a running module with `SIGSTOP`.
+#[post("/api/v1/modules/<name>/pause")]
+pub(crate) fn pause_module(
+    name: String,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiOperationResponse> {
+    let planner = core_state.core.planner();
+    apply_operation(planner, &name, &ApiModuleOperation::PAUSE)?;
+    Ok(Json(ApiOperationResponse { success: true }))
+}
+
+/// Continues the processes of a paused module with `SIGCONT`.
+#[post("/api/v1/modules/<name>/resume")]
+pub(crate) fn resume_module(
+    name: String,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiOperationResponse> {
+    let planner = core_state.core.planner();
+    apply_operation(planner, &name, &ApiModuleOperation::RESUME)?;
+    Ok(Json(ApiOperationResponse { success: true }))
+}
+
+/// Runs the liveness probe of a running module once, outside of its regular
+/// polling (eg. after fixing what it was failing on), and returns the result.
+#[post("/api/v1/modules/<name>/healthcheck")]
+pub(crate) fn check_module_health(
+    name: String,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiHealthCheckResponse> {
+    let status = core_state.core.planner().check_health(&name)?;
+    Ok(Json(ApiHealthCheckResponse {
+        probe_status: (&status).into(),
+        checked_at: epoch_now(),
+    }))
+}
+
+/// Registers service definitions without deploying them.
+#[post("/api/v1/modules/import", data = "<command>")]
+pub(crate) fn import_modules(
+    command: Json<ApiImportCommand>,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiImportResponse> {
+    let module_defs: Vec<_> = command
+        .into_inner()
+        .modules
+        .into_iter()
+        .map(from_task_or_service)
+        .collect();
+    let names: Vec<_> = module_defs.iter().map(|m| m.name.clone()).collect();
+
+    let imported = core_state.core.planner().import_modules(module_defs)?;
+    let skipped = names
+        .into_iter()
+        .filter(|name| !imported.contains(name))
+        .collect();
+
+    Ok(Json(ApiImportResponse { imported, skipped }))
+}
+
+/// Acquires the deploy lock.
+///
+/// Responds with `409 Conflict` (and the current holder) if the lock is
+/// already held.
+#[post("/api/v1/lock", data = "<command>")]
+pub(crate) fn acquire_lock(
+    command: Json<ApiLockCommand>,
+    remote: Option<SocketAddr>,
+    core_state: State<CoreState>,
+) -> status::Custom<Json<ApiLockResponse>> {
+    let command = command.into_inner();
+    let local = remote.map_or(false, |addr| addr.ip().is_loopback());
+    let (status, token, holder) =
+        match core_state.lock.acquire(command.user, command.pid, local) {
+            Ok(holder) => (Status::Ok, Some(holder.token.clone()), holder),
+            Err(holder) => (Status::Conflict, None, holder),
+        };
+    let holder = ApiLockHolder::from(holder);
+
+    status::Custom(status, Json(ApiLockResponse { token, holder }))
+}
+
+/// Releases the deploy lock acquired with the given token.
+#[delete("/api/v1/lock?<token>")]
+pub(crate) fn release_lock(
+    token: String,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiOperationResponse> {
+    if !core_state.lock.release(&token) {
+        return Err(anyhow::Error::from(DaemonError::LockNotHeld).into());
+    }
+    Ok(Json(ApiOperationResponse { success: true }))
+}
+
+/// Releases the deploy lock regardless of which client holds it.
+#[delete("/api/v1/lock/force")]
+pub(crate) fn force_release_lock(
+    core_state: State<CoreState>,
+) -> Json<ApiForceUnlockResponse> {
+    let holder = core_state.lock.force_release().map(ApiLockHolder::from);
+    if let Some(holder) = &holder {
+        warn!(
+            "Deploy lock of {} (pid {}) was forcibly released",
+            holder.user, holder.pid
+        );
+    }
+    Json(ApiForceUnlockResponse { holder })
+}
+
+/// Redeploys a service with the definition it was deployed with before its
+/// current one, which is dropped from its history.
+///
+/// The current definition is taken from the history at once, so that
+/// concurrent rollbacks each roll back to a different definition.
+#[post("/api/v1/modules/<name>/rollback")]
+pub(crate) fn rollback_module(
+    name: String,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiRollbackResponse> {
+    let history = &core_state.history;
+    let definition = history.take_previous(&name).ok_or_else(|| {
+        anyhow::Error::from(DaemonError::NoPreviousDefinition(name.clone()))
+    })?;
+
+    let planner = core_state.core.planner();
+    let (module_def, monitor) = from_service_with_monitor(definition.clone())?;
+    let module_name = module_def.name.clone();
+    planner.deploy(module_def, true)?;
+
+    let monitor = monitor.map(|monitor| {
+        planner.create_monitor(&module_name, monitor, MonitorType::Readiness)
+    });
+    Ok(Json(ApiRollbackResponse {
+        definition,
+        monitor,
+    }))
+}
+
+/// Returns the modules which directly depend on a module, or all modules
+/// which depend on it with `transitive`.
+///
+/// Ranked after `find_module`, whose route it would otherwise collide with.
+#[get("/api/v1/modules/<name>/children?<transitive>", rank = 2)]
+pub(crate) fn module_children(
+    name: String,
+    transitive: Option<bool>,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiDependentsResponse> {
+    let modules: Vec<_> = core_state
+        .core
+        .planner()
+        .module_definitions()
+        .into_iter()
+        .map(|(definition, _)| definition)
+        .collect();
+    if !modules.iter().any(|m| m.name == name) {
+        return Err(anyhow::Error::from(DaemonError::NotFound(name)).into());
+    }
+
+    let transitive = transitive.unwrap_or(false);
+    Ok(Json(ApiDependentsResponse {
+        dependents: dependents(&modules, &name, transitive),
+    }))
+}
+
+fn apply_operation(
+    planner: &Planner,
+    name: &str,
+    operation: &ApiModuleOperation,
+) -> Result<()> {
+    match operation {
+        ApiModuleOperation::STOP => planner.stop_module(name),
+        ApiModuleOperation::RESTART => planner.restart_module(name),
+        ApiModuleOperation::PAUSE => planner.pause_module(name),
+        ApiModuleOperation::RESUME => planner.resume_module(name),
+        ApiModuleOperation::RELOAD => planner.reload_module(name),
+    }
+}
+
+/// Performs the same operation on many modules.
+///
+/// Each module is operated on from its own thread and the result of every
+/// operation is reported, rather than failing on the first error.
+#[post("/api/v1/operation/bulk", data = "<command>")]
+pub(crate) fn bulk_module_operation(
+    command: Json<ApiBulkOperationCommand>,
+    core_state: State<CoreState>,
+) -> Json<ApiBulkOperationResponse> {
+    let command = command.into_inner();
+    let planner = core_state.core.planner();
+    let operation = &command.operation;
+
+    let results = thread::scope(|s| {
+        let handles: Vec<_> = command
+            .names
+            .iter()
+            .map(|name| {
+                s.spawn(move |_| {
+                    let result = apply_operation(planner, name, operation);
+                    ApiModuleOperationResult {
+                        name: name.clone(),
+                        success: result.is_ok(),
+                        message: result.err().map(|e| e.to_string()),
+                    }
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("Module operation panicked"))
+            .collect()
+    })
+    .expect("Failed to join module operation threads");
+
+    Json(ApiBulkOperationResponse { results })
+}
+
 #[post("/api/v1/stop_all")]
 pub(crate) fn stop_all(
     core_state: State<CoreState>,
@@ -245,26 +852,33 @@ pub(crate) fn stop_all(
     Ok(Json(ApiOperationResponse { success: true }))
 }
 
-#[allow(clippy::unnecessary_wraps)]
-#[get("/api/v1/status")]
+/// Returns the status of all modules, optionally only of the given `kind`
+/// (eg. `Service`), `status` (eg. `running`) and/or `namespace`.
+#[get("/api/v1/status?<kind>&<status>&<namespace>")]
 pub(crate) fn status(
+    kind: Option<String>,
+    status: Option<String>,
+    namespace: Option<String>,
     core_state: State<CoreState>,
 ) -> ApiResult<ApiModuleStatusResponse> {
+    let kind = kind.map(|k| k.parse::<ApiModuleKind>()).transpose()?;
+    let run_status =
+        status.map(|s| s.parse::<ApiModuleRunStatus>()).transpose()?;
+
     let planner = core_state.core.planner();
     let status = planner
         .module_status()
         .into_iter()
-        .map(|m| ApiModuleStatus {
-            name: m.name,
-            pid: m.pid,
-            time_since_status: m.time_since_status,
-            exit_code: m.exit_code,
-            liveness_status: m.liveness_status.as_ref().map(|s| s.into()),
-            status: ApiModuleRunStatus::from(m.status),
-        })
+        .map(ApiModuleStatus::from)
+        .filter(|m| kind.map_or(true, |kind| m.kind == Some(kind)))
+        .filter(|m| run_status.map_or(true, |status| m.status == status))
+        .filter(|m| namespace.is_none() || m.namespace == namespace)
         .collect();
 
-    Ok(Json(ApiModuleStatusResponse { status }))
+    Ok(Json(ApiModuleStatusResponse {
+        status,
+        snapshot_time: epoch_now(),
+    }))
 }
 
 #[post("/api/v1/log_file", data = "<request>")]
@@ -281,6 +895,205 @@ pub(crate) fn log_file(
     Ok(Json(ApiLogResponse { log_file_path }))
 }
 
+/// Returns the log files of all the modules known to the daemon.
+#[get("/api/v1/logs/all")]
+pub(crate) fn all_log_files(
+    core_state: State<CoreState>,
+) -> ApiResult<ApiAllLogsResponse> {
+    let logs = core_state
+        .core
+        .planner()
+        .module_definitions()
+        .into_iter()
+        .map(|(definition, log_file_path)| {
+            (definition.name.clone(), ApiLogResponse { log_file_path })
+        })
+        .collect();
+
+    Ok(Json(ApiAllLogsResponse { logs }))
+}
+
+#[post("/api/v1/log_file/clear", data = "<request>")]
+pub(crate) fn clear_log_file(
+    request: Json<ApiClearLogRequest>,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiOperationResponse> {
+    let request = request.into_inner();
+    core_state.core.planner().clear_log(
+        &request.module_name,
+        &request.module_kind.into(),
+        request.force,
+    )?;
+
+    Ok(Json(ApiOperationResponse { success: true }))
+}
+
+#[get("/api/v1/modules/find/<prefix>")]
+pub(crate) fn find_module(
+    prefix: String,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiFindModuleResponse> {
+    let module_name = core_state.core.find_module_by_prefix(&prefix)?;
+    Ok(Json(ApiFindModuleResponse { module_name }))
+}
+
+#[post("/api/v1/snapshot?<path>")]
+pub(crate) fn snapshot(
+    path: Option<String>,
+    core_state: State<CoreState>,
+) -> ApiResult<ApiSnapshotResponse> {
+    let core = &core_state.core;
+    let planner = core.planner();
+    let path = path.unwrap_or_else(default_snapshot_path);
+
+    let mut definitions: HashMap<_, _> = planner
+        .module_definitions()
+        .into_iter()
+        .map(|(definition, log_path)| {
+            (definition.name.clone(), (definition, log_path))
+        })
+        .collect();
+
+    let modules: Vec<_> = planner
+        .module_status()
+        .into_iter()
+        .filter_map(|status| {
+            let (definition, log_path) = definitions.remove(&status.name)?;
+            let log_tail =
+                tail_lines(Path::new(&log_path), SNAPSHOT_LOG_LINES)
+                    .unwrap_or_default();
+            Some(ApiModuleSnapshot {
+                kind: (&definition.kind).into(),
+                environment: definition.environment.clone(),
+                working_dir: definition
+                    .working_dir
+                    .as_ref()
+                    .map(|dir| dir.to_string_lossy().to_string()),
+                log_file_path: log_path.to_string_lossy().to_string(),
+                status: status.into(),
+                log_tail,
+            })
+        })
+        .collect();
+
+    let now = epoch_now();
+    let daemon = ApiDaemonMetrics {
+        pid: std::process::id(),
+        started_at: core.started_at(),
+        uptime_secs: now.saturating_sub(core.started_at()),
+        modules_total: modules.len(),
+        modules_running: modules
+            .iter()
+            .filter(|m| m.status.status == ApiModuleRunStatus::RUNNING)
+            .count(),
+        modules_failing: modules
+            .iter()
+            .filter(|m| {
+                m.status.status == ApiModuleRunStatus::FAILED
+                    || m.status.liveness_status == Some(ApiProbeStatus::Failing)
+            })
+            .count(),
+    };
+
+    let snapshot = ApiSnapshot {
+        taken_at: now,
+        daemon,
+        modules,
+    };
+    let file = create_private_file(&path)
+        .with_context(|| format!("Failed to create snapshot file {}", path))?;
+    serde_json::to_writer_pretty(file, &snapshot)
+        .with_context(|| "Failed to write snapshot")?;
+
+    Ok(Json(ApiSnapshotResponse { path }))
+}
+
+fn default_snapshot_path() -> String {
+    dirs::home_dir()
+        .expect("Failed to locate users home dir")
+        .join(PROJECT_DIR)
+        .join(SNAPSHOT_FILE)
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Creates (or truncates) a file which only the user can read or write, since
+/// snapshots include the environment of modules which may contain secrets.
+fn create_private_file(path: &str) -> io::Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // The mode is only applied to files which did not exist yet
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[post("/api/v1/reload")]
+pub(crate) fn reload_config(
+    core_state: State<CoreState>,
+) -> ApiResult<ApiReloadResponse> {
+    let reload = core_state.core.reload_config()?;
+    Ok(Json(ApiReloadResponse {
+        applied: reload.applied,
+        requires_restart: reload.requires_restart,
+    }))
+}
+
+#[get("/api/v1/info")]
+pub(crate) fn info(core_state: State<CoreState>) -> Json<ApiInfoResponse> {
+    let core = &core_state.core;
+    let modules = core.planner().module_status();
+
+    let mut features = vec![];
+    if core_state.auth {
+        features.push(String::from("auth"));
+    }
+    if cfg!(target_os = "linux") {
+        features.push(String::from("cgroups"));
+        features.push(String::from("network_namespace"));
+    }
+
+    Json(ApiInfoResponse {
+        version: String::from(env!("CARGO_PKG_VERSION")),
+        pid: std::process::id(),
+        started_at: core.started_at(),
+        uptime_secs: epoch_now().saturating_sub(core.started_at()),
+        os: String::from(std::env::consts::OS),
+        arch: String::from(std::env::consts::ARCH),
+        features,
+        modules_total: modules.len(),
+        modules_running: modules
+            .iter()
+            .filter(|m| m.status == RunStatus::RUNNING)
+            .count(),
+    })
+}
+
+/// Answers as soon as the daemon is reachable, without doing any work, so
+/// that clients can check their connection to it.
+#[get("/api/v1/health")]
+pub(crate) fn daemon_health() -> Json<ApiOperationResponse> {
+    Json(ApiOperationResponse { success: true })
+}
+
+/// The response to requests without a valid API key, which the
+/// [ApiKeyCheck](crate::daemon::api::fairing::ApiKeyCheck) fairing reroutes
+/// here.
+#[get("/api/v1/unauthorized")]
+pub(crate) fn unauthorized() -> status::Custom<Json<ErrorResponse>> {
+    status::Custom(
+        Status::Unauthorized,
+        Json(ErrorResponse {
+            status: String::from("error"),
+            message: String::from("Missing or invalid daemon API key"),
+            code: 401,
+        }),
+    )
+}
+
 #[get("/api/v1/health/<monitor_key>")]
 pub(crate) fn health(
     monitor_key: String,
@@ -292,6 +1105,7 @@ pub(crate) fn health(
         .monitor_status(monitor_key.as_str());
 
     let probe_status = match status {
+        Some(MonitorStatus::Starting) => Some(ApiProbeStatus::Starting),
         Some(MonitorStatus::Pending) => Some(ApiProbeStatus::Pending),
         Some(MonitorStatus::Successful) => Some(ApiProbeStatus::Successful),
         Some(MonitorStatus::RetriesExceeded) => {
@@ -299,6 +1113,7 @@ pub(crate) fn health(
         }
         Some(MonitorStatus::Error) => Some(ApiProbeStatus::Error),
         Some(MonitorStatus::Failing) => Some(ApiProbeStatus::Failing),
+        Some(MonitorStatus::Ready) => Some(ApiProbeStatus::Ready),
         None => None,
     };
 
@@ -313,14 +1128,28 @@ pub(crate) fn get_plan(
     let planner = core_state.core.planner();
     let mut request = request.into_inner();
 
+    // The client looks up the plan by the names it sent
+    let sent_names: HashMap<_, _> = request
+        .modules
+        .iter()
+        .map(|m| (m.qualified_name(), m.name.clone()))
+        .collect();
     let modules: Vec<_> = request
         .modules
         .drain(..)
         .map(from_task_or_service)
         .collect();
 
-    let plan = planner.get_plan(&modules);
-    Json(plan.into())
+    let mut response: ApiGetPlanResponse = planner.get_plan(&modules).into();
+    response.plan = response
+        .plan
+        .into_iter()
+        .map(|(name, action)| match sent_names.get(&name) {
+            Some(sent_name) => (sent_name.clone(), action),
+            None => (name, action),
+        })
+        .collect();
+    Json(response)
 }
 
 #[get("/")]
@@ -0,0 +1,53 @@
+use crate::daemon::api::handlers::ApiModuleDefinition;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+
+/// Keeps the most recently deployed definitions of each service, so that a
+/// service can be rolled back to a previous definition.
+///
+/// The definitions of each service are kept in a ring buffer of
+/// `max_snapshots` entries, the last of which is the current definition.
+pub struct DefinitionHistory {
+    definitions: Mutex<HashMap<String, VecDeque<ApiModuleDefinition>>>,
+    max_snapshots: usize,
+}
+
+impl DefinitionHistory {
+    pub fn new(max_snapshots: usize) -> Self {
+        Self {
+            definitions: Mutex::new(HashMap::new()),
+            max_snapshots,
+        }
+    }
+
+    /// Records a definition which was just deployed.
+    pub fn record(&self, definition: ApiModuleDefinition) {
+        let mut definitions = self.definitions.lock();
+        let history = definitions
+            .entry(definition.qualified_name())
+            .or_insert_with(VecDeque::new);
+        if history.len() == self.max_snapshots {
+            history.pop_front();
+        }
+        history.push_back(definition);
+    }
+
+    /// Drops the current definition of a module to roll it back, returning
+    /// the definition deployed before it, which becomes the current one. The
+    /// name includes the namespace of the module, if it has one.
+    ///
+    /// The previous definition is kept, so a module can be rolled back once
+    /// for each snapshot but the oldest.
+    pub fn take_previous(
+        &self,
+        module_name: &str,
+    ) -> Option<ApiModuleDefinition> {
+        let mut definitions = self.definitions.lock();
+        let history = definitions.get_mut(module_name)?;
+        if history.len() < 2 {
+            return None;
+        }
+        history.pop_back();
+        history.back().cloned()
+    }
+}
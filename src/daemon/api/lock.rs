@@ -0,0 +1,187 @@
+use crate::daemon::time::epoch_now;
+use log::info;
+use nix::errno::Errno;
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+/// How long a deployment may hold the lock before it is considered stale.
+const DEFAULT_LOCK_TTL_SECS: u64 = 60 * 60;
+
+/// The client holding the deploy lock.
+#[derive(Clone, Debug)]
+pub struct LockHolder {
+    /// Identifies the holder when releasing the lock.
+    pub token: String,
+    pub user: String,
+    pub pid: u32,
+    /// When the lock was acquired (seconds since the epoch).
+    pub acquired_at: u64,
+    /// Whether the client connected over loopback, and so runs on the same
+    /// machine as the daemon.
+    pub local: bool,
+}
+
+impl LockHolder {
+    /// Whether the lock was held for longer than `ttl_secs`, or was left
+    /// behind by a client which exited without releasing it.
+    ///
+    /// The liveness of the process of the client is only checked for local
+    /// clients, since the pid of a remote client means nothing on the machine
+    /// of the daemon.
+    fn is_stale(&self, ttl_secs: u64) -> bool {
+        let expired = epoch_now().saturating_sub(self.acquired_at) >= ttl_secs;
+        let exited = self.local
+            && matches!(
+                kill(Pid::from_raw(self.pid as i32), None),
+                Err(nix::Error::Sys(Errno::ESRCH))
+            );
+        expired || exited
+    }
+}
+
+/// Prevents concurrent deployments (with `cartel deploy --lock`).
+pub struct DeployLock {
+    holder: Mutex<Option<LockHolder>>,
+    ttl_secs: u64,
+}
+
+impl Default for DeployLock {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOCK_TTL_SECS)
+    }
+}
+
+impl DeployLock {
+    pub fn new(ttl_secs: u64) -> DeployLock {
+        DeployLock {
+            holder: Mutex::new(None),
+            ttl_secs,
+        }
+    }
+
+    /// Acquires the lock for the given client, which is `local` if it
+    /// connected over loopback.
+    ///
+    /// A stale lock (see [LockHolder::is_stale]) is taken over. Returns the
+    /// current holder as an error if the lock is otherwise already held.
+    pub fn acquire(
+        &self,
+        user: String,
+        pid: u32,
+        local: bool,
+    ) -> Result<LockHolder, LockHolder> {
+        let mut holder = self.holder.lock();
+        if let Some(current) = holder.as_ref() {
+            if !current.is_stale(self.ttl_secs) {
+                return Err(current.clone());
+            }
+            info!(
+                "Taking over the stale deploy lock of {} (pid {})",
+                current.user, current.pid
+            );
+        }
+        let new_holder = LockHolder {
+            token: Uuid::new_v4().to_string(),
+            user,
+            pid,
+            acquired_at: epoch_now(),
+            local,
+        };
+        *holder = Some(new_holder.clone());
+        Ok(new_holder)
+    }
+
+    /// Releases the lock if it is held with the given token, returning
+    /// whether it was.
+    pub fn release(&self, token: &str) -> bool {
+        let mut holder = self.holder.lock();
+        match holder.as_ref() {
+            Some(current) if current.token == token => {
+                *holder = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Releases the lock regardless of who holds it (with `cartel unlock
+    /// --force`), returning the previous holder.
+    pub fn force_release(&self) -> Option<LockHolder> {
+        self.holder.lock().take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn own_pid() -> u32 {
+        std::process::id()
+    }
+
+    #[test]
+    fn test_acquire_and_release() {
+        let lock = DeployLock::default();
+        let holder = lock.acquire("alice".into(), own_pid(), true).unwrap();
+
+        assert!(!lock.release("another-token"));
+        assert!(lock.release(&holder.token));
+        assert!(!lock.release(&holder.token));
+        assert!(lock.acquire("bob".into(), own_pid(), true).is_ok());
+    }
+
+    #[test]
+    fn test_conflict_reports_holder() {
+        let lock = DeployLock::default();
+        lock.acquire("alice".into(), own_pid(), true).unwrap();
+
+        let current = lock.acquire("bob".into(), own_pid(), true).unwrap_err();
+        assert_eq!(current.user, "alice");
+        assert_eq!(current.pid, own_pid(), true);
+    }
+
+    #[test]
+    fn test_expired_lock_is_taken_over() {
+        let lock = DeployLock::new(0);
+        lock.acquire("alice".into(), own_pid(), true).unwrap();
+
+        let holder = lock.acquire("bob".into(), own_pid(), true).unwrap();
+        assert_eq!(holder.user, "bob");
+    }
+
+    #[test]
+    fn test_lock_of_exited_client_is_taken_over() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let exited_pid = child.id();
+        child.wait().unwrap();
+
+        let lock = DeployLock::default();
+        lock.acquire("alice".into(), exited_pid, true).unwrap();
+        assert!(lock.acquire("bob".into(), own_pid(), true).is_ok());
+    }
+
+    #[test]
+    fn test_lock_of_remote_client_is_kept() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let exited_pid = child.id();
+        child.wait().unwrap();
+
+        // The pid of a remote client is not of a process of this machine
+        let lock = DeployLock::default();
+        lock.acquire("alice".into(), exited_pid, false).unwrap();
+        let current = lock.acquire("bob".into(), own_pid(), true).unwrap_err();
+        assert_eq!(current.user, "alice");
+    }
+
+    #[test]
+    fn test_force_release() {
+        let lock = DeployLock::default();
+        assert!(lock.force_release().is_none());
+        lock.acquire("alice".into(), own_pid(), true).unwrap();
+
+        assert_eq!(lock.force_release().unwrap().user, "alice");
+        assert!(lock.acquire("bob".into(), own_pid(), true).is_ok());
+    }
+}
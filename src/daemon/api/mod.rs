@@ -1,7 +1,13 @@
+mod batch;
 mod convert;
+mod dependents;
 pub mod engine;
 pub mod error;
+mod fairing;
 mod handlers;
+mod history;
+mod lock;
+mod oneshots;
 
 pub use error::ErrorResponse;
 pub use handlers::*;
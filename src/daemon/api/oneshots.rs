@@ -0,0 +1,249 @@
+use crate::constants::PROJECT_DIR;
+use crate::daemon::api::convert::from_task;
+use crate::daemon::api::dependents::dependency_order;
+use crate::daemon::api::handlers::ApiModuleDefinition;
+use crate::daemon::Core;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+const ONESHOTS_FILE: &str = "oneshots.json";
+
+/// The one-shot modules deployed through the daemon, persisted to a file so
+/// that they are run again whenever the daemon starts.
+pub struct OneShotStore {
+    path: PathBuf,
+    /// Serializes the read-modify-write cycles of the file, and holds the
+    /// names of the one-shots which were started since the daemon started.
+    started: Mutex<HashSet<String>>,
+}
+
+impl OneShotStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            started: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Returns the default location of the file, inside the project
+    /// directory.
+    pub fn default_path() -> PathBuf {
+        dirs::home_dir()
+            .expect("Failed to locate users home dir")
+            .join(PROJECT_DIR)
+            .join(ONESHOTS_FILE)
+    }
+
+    /// Returns the recorded one-shot definitions, in the order they were
+    /// first recorded in.
+    pub fn load(&self) -> Result<Vec<ApiModuleDefinition>> {
+        let _guard = self.started.lock();
+        self.read()
+    }
+
+    /// Reserves a one-shot to be run, returning `false` if it was already
+    /// started since the daemon started, in which case it is skipped.
+    ///
+    /// The check and the reservation are done while holding the lock of the
+    /// store, so that a one-shot deployed by concurrent requests (or while
+    /// the one-shots run on startup) still only runs once.
+    pub fn reserve(&self, name: &str) -> bool {
+        self.started.lock().insert(name.to_string())
+    }
+
+    /// Records the definition of a one-shot, replacing the previously
+    /// recorded definition of the same module.
+    ///
+    /// When the names of all the one-shots defined along with it are given,
+    /// the recorded one-shots of the same namespace which are not amongst them
+    /// are forgotten, so that they no longer run on startup.
+    pub fn record(
+        &self,
+        definition: &ApiModuleDefinition,
+        defined: Option<&[String]>,
+    ) -> Result<()> {
+        let _guard = self.started.lock();
+        let mut definitions = self.read()?;
+        if let Some(defined) = defined {
+            definitions.retain(|d| {
+                d.namespace != definition.namespace
+                    || defined.contains(&d.name)
+            });
+        }
+        let name = definition.qualified_name();
+        match definitions.iter_mut().find(|d| d.qualified_name() == name) {
+            Some(existing) => *existing = definition.clone(),
+            None => definitions.push(definition.clone()),
+        }
+        let contents = serde_json::to_vec_pretty(&definitions)?;
+        write_atomically(&self.path, &contents).with_context(|| {
+            format!("Failed to write {}", self.path.display())
+        })
+    }
+
+    fn read(&self) -> Result<Vec<ApiModuleDefinition>> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to read {}", self.path.display())
+                })
+            }
+        };
+        serde_json::from_slice(&contents).with_context(|| {
+            format!("Failed to parse {}", self.path.display())
+        })
+    }
+}
+
+/// Writes the file through a temporary file which is then renamed over it,
+/// so that the file is never left partially written.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Runs the recorded one-shots in dependency order on a separate thread, as
+/// the daemon starts.
+///
+/// A one-shot which fails is logged, and the remaining ones still run. The
+/// one-shots which a client deployed in the meantime are skipped.
+pub fn run_at_startup(
+    core: &Arc<Core>,
+    store: &Arc<OneShotStore>,
+) -> Result<()> {
+    let definitions = store.load()?;
+    if definitions.is_empty() {
+        return Ok(());
+    }
+    let modules: Vec<_> =
+        definitions.into_iter().map(from_task).map(Arc::new).collect();
+    let order = dependency_order(&modules)?;
+    let mut by_name: HashMap<_, _> = modules
+        .into_iter()
+        .filter_map(|module| Arc::try_unwrap(module).ok())
+        .map(|module| (module.name.clone(), module))
+        .collect();
+
+    let core = Arc::clone(core);
+    let store = Arc::clone(store);
+    thread::spawn(move || {
+        for name in order {
+            let oneshot = match by_name.remove(&name) {
+                Some(oneshot) if store.reserve(&name) => oneshot,
+                _ => continue,
+            };
+            info!("Running one-shot {} on startup", name);
+            if let Err(e) = core.planner().deploy_oneshot(oneshot) {
+                warn!("One-shot {} failed on startup: {}", name, e);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn oneshot(name: &str, command: &str) -> ApiModuleDefinition {
+        serde_json::from_value(serde_json::json!({
+            "kind": "OneShot",
+            "name": name,
+            "command": [command],
+            "environment": {},
+            "log_file_path": null,
+            "dependencies": [],
+            "working_dir": null,
+            "termination_signal": "KILL",
+            "readiness_probe": null,
+            "liveness_probe": null,
+            "capture_output": false,
+            "max_memory_mb": null,
+            "cpu_quota_percent": null,
+            "network_namespace": null,
+            "shutdown_command": null,
+            "shutdown_timeout_secs": 10,
+            "inherit_env": true,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_records_oneshots() {
+        let dir = std::env::temp_dir()
+            .join(format!("cartel-oneshots-{}", std::process::id()));
+        let store = OneShotStore::new(dir.join(ONESHOTS_FILE));
+        assert!(store.load().unwrap().is_empty());
+
+        store.record(&oneshot("seed", "seed-v1"), None).unwrap();
+        store.record(&oneshot("migrate", "migrate"), None).unwrap();
+        store.record(&oneshot("seed", "seed-v2"), None).unwrap();
+
+        let recorded = store.load().unwrap();
+        let commands: Vec<_> =
+            recorded.iter().map(|d| d.command[0].as_str()).collect();
+        assert_eq!(commands, vec!["seed-v2", "migrate"]);
+        assert!(!dir.join("oneshots.tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_forgets_oneshots_no_longer_defined() {
+        let dir = std::env::temp_dir()
+            .join(format!("cartel-oneshots-prune-{}", std::process::id()));
+        let store = OneShotStore::new(dir.join(ONESHOTS_FILE));
+        let mut namespaced = oneshot("seed", "seed-test");
+        namespaced.namespace = Some("test".to_string());
+
+        store.record(&oneshot("seed", "seed"), None).unwrap();
+        store.record(&oneshot("migrate", "migrate"), None).unwrap();
+        store.record(&namespaced, None).unwrap();
+        let defined = vec!["migrate".to_string(), "cleanup".to_string()];
+        store
+            .record(&oneshot("cleanup", "cleanup"), Some(&defined))
+            .unwrap();
+
+        let recorded = store.load().unwrap();
+        let commands: Vec<_> =
+            recorded.iter().map(|d| d.command[0].as_str()).collect();
+        assert_eq!(commands, vec!["migrate", "seed-test", "cleanup"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_oneshots_are_reserved_once() {
+        let store = Arc::new(OneShotStore::new(PathBuf::from(ONESHOTS_FILE)));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let store = Arc::clone(&store);
+                thread::spawn(move || store.reserve("seed"))
+            })
+            .collect();
+
+        let reserved = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|reserved| *reserved)
+            .count();
+        assert_eq!(reserved, 1);
+        assert!(store.reserve("migrate"));
+        assert!(!store.reserve("seed"));
+    }
+}
@@ -1,24 +1,42 @@
 extern crate cartel;
 use std::error::Error;
+use std::path::PathBuf;
 
+use cartel::config::{create_config_if_not_exists, default_config_file_path};
 use cartel::daemon::cli::cli_app;
+use cartel::daemon::config::{init_logging, ConfigSource};
 use cartel::daemon::core::start_daemon;
-use cartel::detach::detach_tty;
-
-pub fn init() {
-    env_logger::init()
-}
+use cartel::daemon::logs::default_log_directory;
+use cartel::detach::{daemonize, detach_tty};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    init();
-    let config = cli_app()?;
+    let cli_config = cli_app()?;
+    let path = match cli_config.config_file {
+        Some(path) => PathBuf::from(path),
+        None => {
+            create_config_if_not_exists()?;
+            default_config_file_path()
+        }
+    };
+    let config_source = ConfigSource {
+        path,
+        overrides: cli_config.overrides,
+    };
+    let config = config_source.load()?;
+    init_logging(&config.daemon)?;
+
     #[cfg(unix)]
     {
-        if config.detach_tty {
+        if cli_config.detach_tty {
             let args = std::env::args();
             detach_tty(args, false);
         }
+        if cli_config.background {
+            let pidfile = config.daemon.pidfile_path();
+            let log_file = default_log_directory()?.join("daemon.log");
+            daemonize(&pidfile, &log_file)?;
+        }
     }
-    start_daemon()?;
+    start_daemon(config, config_source)?;
     Ok(())
 }
@@ -0,0 +1,236 @@
+//! Resource limits of modules, enforced through cgroups v2.
+//!
+//! Every module which sets a limit gets its own cgroup below
+//! `/sys/fs/cgroup/cartel`. The cgroup is kept around and reused when the
+//! module is redeployed.
+
+use crate::daemon::module::ModuleDefinition;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use nix::unistd::write;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup/cartel";
+/// The period of the CPU bandwidth limit, in microseconds.
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Creates the cgroups of modules below a common parent cgroup.
+pub struct CgroupManager {
+    root: PathBuf,
+}
+
+/// The cgroup of a single module.
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Default for CgroupManager {
+    fn default() -> Self {
+        Self::new(CGROUP_ROOT)
+    }
+}
+
+impl CgroupManager {
+    pub fn new<P: Into<PathBuf>>(root: P) -> CgroupManager {
+        CgroupManager { root: root.into() }
+    }
+
+    /// Creates the cgroup of a module and applies its resource limits.
+    ///
+    /// Returns `None` if the module has no resource limits, or if cgroups are
+    /// not supported on this platform (in which case a warning is logged).
+    pub fn create_for_module(
+        &self,
+        module: &ModuleDefinition,
+    ) -> Result<Option<Cgroup>> {
+        if module.max_memory_mb.is_none() && module.cpu_quota_percent.is_none()
+        {
+            return Ok(None);
+        }
+
+        if !cfg!(target_os = "linux") {
+            warn!(
+                "Ignoring the resource limits of module {}, as they are not \
+                supported on this platform",
+                module.name
+            );
+            return Ok(None);
+        }
+
+        info!("Creating cgroup for module: {}", module.name);
+        let cgroup = Cgroup {
+            path: self.root.join(&module.name),
+        };
+
+        // The cgroup is reused across deployments, so limits which are no
+        // longer set are reset.
+        match module.max_memory_mb {
+            Some(max_memory_mb) => {
+                self.create(&cgroup, "memory")?;
+                let max_memory_bytes = max_memory_mb * 1024 * 1024;
+                cgroup.write("memory.max", &max_memory_bytes.to_string())?;
+            }
+            None => cgroup.reset("memory.max")?,
+        }
+        match module.cpu_quota_percent {
+            Some(percent) => {
+                self.create(&cgroup, "cpu")?;
+                let quota = CPU_PERIOD_US * u64::from(percent) / 100;
+                let cpu_max = format!("{} {}", quota, CPU_PERIOD_US);
+                cgroup.write("cpu.max", &cpu_max)?;
+            }
+            None => cgroup.reset("cpu.max")?,
+        }
+        Ok(Some(cgroup))
+    }
+
+    /// Creates the cgroup (if it doesn't exist) with the given controller
+    /// enabled.
+    fn create(&self, cgroup: &Cgroup, controller: &str) -> Result<()> {
+        self.enable_controller(controller)?;
+        fs::create_dir_all(&cgroup.path).with_context(|| {
+            format!("Failed to create cgroup {}", cgroup.path.display())
+        })
+    }
+
+    /// Enables a controller for the cgroups of the modules.
+    fn enable_controller(&self, controller: &str) -> Result<()> {
+        fs::create_dir_all(&self.root).with_context(|| {
+            format!("Failed to create cgroup {}", self.root.display())
+        })?;
+        let subtree_control = self.root.join("cgroup.subtree_control");
+        fs::write(&subtree_control, format!("+{}", controller)).with_context(
+            || {
+                format!(
+                    "Failed to enable the {} controller in {}",
+                    controller,
+                    subtree_control.display()
+                )
+            },
+        )
+    }
+}
+
+impl Cgroup {
+    /// Makes the process spawned by the command join this cgroup before the
+    /// module's command is executed, so that every process it forks is
+    /// limited as well.
+    pub fn join_on_spawn(&self, command: &mut Command) -> Result<()> {
+        // The file is opened here so that a missing cgroup fails the
+        // deployment, rather than the process after it was forked.
+        let path = self.path.join("cgroup.procs");
+        let procs = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        // Writing 0 moves the writing process itself. write is
+        // async-signal-safe, so it may be called between fork and exec.
+        unsafe {
+            command.pre_exec(move || {
+                write(procs.as_raw_fd(), b"0")
+                    .map(|_| ())
+                    .map_err(io::Error::from)
+            });
+        }
+        Ok(())
+    }
+
+    /// Removes the limit set in the given file, if the file exists.
+    fn reset(&self, file: &str) -> Result<()> {
+        if self.path.join(file).exists() {
+            self.write(file, "max")?;
+        }
+        Ok(())
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<()> {
+        let path = self.path.join(file);
+        fs::write(&path, value)
+            .with_context(|| format!("Failed to write to {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::Path;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "cartel-cgroups-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_no_cgroup_without_limits() {
+        let root = temp_root("none");
+        let manager = CgroupManager::new(&root);
+        let module = ModuleDefinition::test_service("api");
+
+        assert!(manager.create_for_module(&module).unwrap().is_none());
+        assert!(!root.join("api").exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_applies_and_resets_limits() {
+        let root = temp_root("limits");
+        let manager = CgroupManager::new(&root);
+        let mut module = ModuleDefinition::test_service("api");
+        module.max_memory_mb = Some(64);
+        module.cpu_quota_percent = Some(50);
+
+        manager.create_for_module(&module).unwrap().unwrap();
+        let cgroup = root.join("api");
+        assert_eq!(read(&cgroup.join("memory.max")), "67108864");
+        assert_eq!(read(&cgroup.join("cpu.max")), "50000 100000");
+        assert_eq!(read(&root.join("cgroup.subtree_control")), "+cpu");
+
+        module.cpu_quota_percent = None;
+        manager.create_for_module(&module).unwrap().unwrap();
+        assert_eq!(read(&cgroup.join("memory.max")), "67108864");
+        assert_eq!(read(&cgroup.join("cpu.max")), "max");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_joins_cgroup_before_exec() {
+        let root = temp_root("join");
+        let cgroup = Cgroup {
+            path: root.clone(),
+        };
+        fs::write(root.join("cgroup.procs"), "").unwrap();
+
+        let mut command = Command::new("true");
+        cgroup.join_on_spawn(&mut command).unwrap();
+        assert!(command.status().unwrap().success());
+
+        assert_eq!(read(&root.join("cgroup.procs")), "0");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_join_fails_without_cgroup() {
+        let root = temp_root("missing");
+        let cgroup = Cgroup {
+            path: root.join("api"),
+        };
+        let mut command = Command::new("true");
+        assert!(cgroup.join_on_spawn(&mut command).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -1,8 +1,14 @@
+use crate::daemon::config::{ConfigOverrides, LOG_FORMATS};
 use anyhow::Result;
 use clap::{crate_version, App, Arg};
 
 pub struct DaemonCliConfig {
     pub detach_tty: bool,
+    pub background: bool,
+    /// A config file to use instead of the default one.
+    pub config_file: Option<String>,
+    /// Settings overriding the ones in the config file.
+    pub overrides: ConfigOverrides,
 }
 
 pub fn cli_app() -> Result<DaemonCliConfig> {
@@ -16,9 +22,73 @@ pub fn cli_app() -> Result<DaemonCliConfig> {
                 .help("Detatches the daemon from its controlling terminal")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("background")
+                .short("b")
+                .long("background")
+                .help(
+                    "Runs the daemon in the background, writing its pid to \
+                    the pidfile",
+                )
+                .takes_value(false)
+                .conflicts_with("detach_tty"),
+        )
+        .arg(
+            Arg::with_name("pidfile")
+                .long("pidfile")
+                .help("Path of the pidfile written in background mode")
+                .takes_value(true)
+                .requires("background"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .value_name("FILE")
+                .help("Sets a custom config file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("port")
+                .short("p")
+                .long("port")
+                .help("The port to listen on")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("bind_addr")
+                .long("bind")
+                .value_name("ADDR")
+                .help("The address to listen on")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log_level")
+                .long("log-level")
+                .help("The maximum level of the logs (eg. info, debug)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("log_format")
+                .long("log-format")
+                .help("The format of the logs")
+                .takes_value(true)
+                .possible_values(&LOG_FORMATS),
+        )
         .get_matches();
 
+    let overrides = ConfigOverrides {
+        port: matches.value_of("port").map(String::from),
+        bind_addr: matches.value_of("bind_addr").map(String::from),
+        log_level: matches.value_of("log_level").map(String::from),
+        log_format: matches.value_of("log_format").map(String::from),
+        pidfile: matches.value_of("pidfile").map(String::from),
+    };
+
     Ok(DaemonCliConfig {
         detach_tty: matches.is_present("detach_tty"),
+        background: matches.is_present("background"),
+        config_file: matches.value_of("config").map(String::from),
+        overrides,
     })
 }
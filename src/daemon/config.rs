@@ -0,0 +1,168 @@
+use crate::config::{self, DaemonConfig, PersistedConfig};
+use anyhow::{anyhow, bail, Result};
+use log::LevelFilter;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tracing::Span;
+use tracing_subscriber::EnvFilter;
+
+pub const DEFAULT_PORT: u16 = 13754;
+pub const DEFAULT_BIND_ADDR: &str = "127.0.0.1";
+pub const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+pub const DEFAULT_MAX_SNAPSHOTS: usize = 3;
+pub const LOG_FORMATS: [&str; 2] = ["pretty", "json"];
+
+/// The format the daemon logs are written in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LogFormat {
+    /// Human readable lines.
+    Pretty,
+    /// One JSON object per line, to be consumed by log aggregators.
+    Json,
+}
+
+/// Daemon settings given on the command line. These take precedence over the
+/// ones in the config file.
+#[derive(Default)]
+pub struct ConfigOverrides {
+    pub port: Option<String>,
+    pub bind_addr: Option<String>,
+    pub log_level: Option<String>,
+    pub log_format: Option<String>,
+    pub pidfile: Option<String>,
+}
+
+/// Where the daemon reads its config from.
+pub struct ConfigSource {
+    /// The TOML config file.
+    pub path: PathBuf,
+    /// Settings overriding the ones read from the file.
+    pub overrides: ConfigOverrides,
+}
+
+impl ConfigSource {
+    /// Reads the config file and applies the overrides on top of it.
+    pub fn load(&self) -> Result<PersistedConfig> {
+        let mut cfg = config::read_persisted_config_from_path(&self.path)?;
+        let daemon = &mut cfg.daemon;
+        let overrides = &self.overrides;
+
+        if overrides.port.is_some() {
+            daemon.port = overrides.port.clone();
+        }
+        if overrides.bind_addr.is_some() {
+            daemon.bind_addr = overrides.bind_addr.clone();
+        }
+        if overrides.log_level.is_some() {
+            daemon.log_level = overrides.log_level.clone();
+        }
+        if overrides.log_format.is_some() {
+            daemon.log_format = overrides.log_format.clone();
+        }
+        if overrides.pidfile.is_some() {
+            daemon.pidfile = overrides.pidfile.clone();
+        }
+        Ok(cfg)
+    }
+}
+
+/// Returns the port the daemon API should listen on.
+pub fn port(cfg: &DaemonConfig) -> Result<u16> {
+    match &cfg.port {
+        Some(port) => port
+            .parse::<u16>()
+            .map_err(|_| anyhow!("Invalid daemon port '{}'", port)),
+        None => Ok(DEFAULT_PORT),
+    }
+}
+
+/// Returns the address the daemon API should listen on.
+pub fn bind_addr(cfg: &DaemonConfig) -> &str {
+    cfg.bind_addr.as_deref().unwrap_or(DEFAULT_BIND_ADDR)
+}
+
+/// Returns the number of definitions kept per service for rollbacks. At
+/// least two are needed to roll back at all.
+pub fn max_snapshots(cfg: &DaemonConfig) -> Result<usize> {
+    match &cfg.max_snapshots {
+        Some(max) => match max.parse::<usize>() {
+            Ok(max) if max >= 2 => Ok(max),
+            _ => bail!("Invalid daemon max_snapshots '{}'", max),
+        },
+        None => Ok(DEFAULT_MAX_SNAPSHOTS),
+    }
+}
+
+/// Returns the maximum level of the daemon logs.
+pub fn log_level(cfg: &DaemonConfig) -> Result<LevelFilter> {
+    match &cfg.log_level {
+        Some(level) => LevelFilter::from_str(level)
+            .map_err(|_| anyhow!("Invalid daemon log level '{}'", level)),
+        None => Ok(DEFAULT_LOG_LEVEL),
+    }
+}
+
+/// Returns the format of the daemon logs.
+pub fn log_format(cfg: &DaemonConfig) -> Result<LogFormat> {
+    match cfg.log_format.as_deref() {
+        None | Some("pretty") => Ok(LogFormat::Pretty),
+        Some("json") => Ok(LogFormat::Json),
+        Some(format) => bail!("Invalid daemon log format '{}'", format),
+    }
+}
+
+/// Initializes the daemon logger.
+///
+/// The `RUST_LOG` environment variable takes precedence when set. Otherwise
+/// the configured log level is used, which can later be changed at runtime
+/// through [set_log_level].
+///
+/// With the JSON format each record is written as a single line by
+/// `tracing-subscriber`, along with the fields of the spans it was logged in
+/// (eg. the `module_name` of the `module` span).
+pub fn init_logging(cfg: &DaemonConfig) -> Result<()> {
+    let level = log_level(cfg)?;
+    match log_format(cfg)? {
+        LogFormat::Pretty => {
+            let mut builder = env_logger::Builder::from_default_env();
+            if !rust_log_is_set() {
+                builder.filter_level(LevelFilter::Trace);
+            }
+            builder.init();
+        }
+        LogFormat::Json => {
+            // Records of the log crate are forwarded to the subscriber
+            let builder = tracing_subscriber::fmt().json();
+            if rust_log_is_set() {
+                builder.with_env_filter(EnvFilter::from_default_env()).init();
+            } else {
+                builder.with_max_level(tracing::Level::TRACE).init();
+            }
+        }
+    }
+    set_log_level(level);
+    Ok(())
+}
+
+/// Returns the span of the work done on a module, which adds its name as the
+/// `module_name` of the records logged within it.
+pub fn module_span(module_name: &str) -> Span {
+    tracing::info_span!("module", module_name = %module_name)
+}
+
+/// Changes the maximum level of the daemon logs.
+///
+/// Returns false (and does nothing) if the log level is controlled by the
+/// `RUST_LOG` environment variable.
+pub fn set_log_level(level: LevelFilter) -> bool {
+    if rust_log_is_set() {
+        return false;
+    }
+    log::set_max_level(level);
+    true
+}
+
+fn rust_log_is_set() -> bool {
+    env::var_os("RUST_LOG").is_some()
+}
@@ -2,15 +2,32 @@ use crate::daemon::monitor::{self, MonitorHandle};
 use crate::daemon::planner::Planner;
 use crate::daemon::{api, env_grabber, signal};
 
-use crate::config::{self, PersistedConfig};
+use crate::config::PersistedConfig;
+use crate::daemon::config::{self, ConfigSource};
 use crate::daemon::env_grabber::{env_grabber_thread, CurrentEnvHolder};
+use crate::daemon::error::DaemonError;
+use crate::daemon::time::epoch_now;
 use std::error::Error;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+
+/// The current daemon config, shared between the core and the planner so that
+/// both see the config once it is reloaded.
+pub type SharedConfig = Arc<RwLock<Arc<PersistedConfig>>>;
 
 /// Holds the core daemon state.
 pub struct Core {
     pub planner: Planner,
-    pub config: Arc<PersistedConfig>,
+    config: SharedConfig,
+    config_source: ConfigSource,
+    started_at: u64,
+}
+
+/// The outcome of re-reading the daemon configuration.
+pub struct ConfigReload {
+    /// Settings that changed and took effect immediately.
+    pub applied: Vec<String>,
+    /// Settings that changed but only take effect after a restart.
+    pub requires_restart: Vec<String>,
 }
 
 impl Core {
@@ -26,29 +43,134 @@ impl Core {
         monitor_handle: MonitorHandle,
         env_holder: Arc<CurrentEnvHolder>,
         cfg: Arc<PersistedConfig>,
+        config_source: ConfigSource,
     ) -> Core {
+        let config = Arc::new(RwLock::new(cfg));
         Core {
-            planner: Planner::new(monitor_handle, env_holder, Arc::clone(&cfg)),
-            config: cfg,
+            planner: Planner::new(
+                monitor_handle,
+                env_holder,
+                Arc::clone(&config),
+            ),
+            config,
+            config_source,
+            started_at: epoch_now(),
         }
     }
 
+    /// Returns the time the daemon was started at (seconds since the epoch).
+    pub fn started_at(&self) -> u64 {
+        self.started_at
+    }
+
     /// Return a reference to the planner.
     pub fn planner(&self) -> &Planner {
         &self.planner
     }
 
-    /// Return a reference to the shared config.
-    pub fn config(&self) -> &PersistedConfig {
-        &self.config
+    /// Return the current shared config.
+    pub fn config(&self) -> Arc<PersistedConfig> {
+        Arc::clone(&self.config.read().unwrap())
+    }
+
+    /// Returns a handle to the config, through which the config is seen once
+    /// it is reloaded.
+    pub fn shared_config(&self) -> SharedConfig {
+        Arc::clone(&self.config)
+    }
+
+    /// Re-reads the config file the daemon was started with.
+    ///
+    /// The new config replaces the current one, which the planner reads its
+    /// settings from. Settings which are only read on startup (such as the
+    /// port) are reported as requiring a restart rather than applied.
+    pub fn reload_config(&self) -> anyhow::Result<ConfigReload> {
+        let new_cfg = Arc::new(self.config_source.load()?);
+        let new_log_level = config::log_level(&new_cfg.daemon)?;
+        let mut current = self.config.write().unwrap();
+
+        let mut applied = vec![];
+        if current.daemon.log_level != new_cfg.daemon.log_level
+            && config::set_log_level(new_log_level)
+        {
+            applied.push("daemon.log_level".to_string());
+        }
+        if current.daemon.use_env_grabber != new_cfg.daemon.use_env_grabber {
+            applied.push("daemon.use_env_grabber".to_string());
+        }
+
+        let mut requires_restart = vec![];
+        if current.daemon.port != new_cfg.daemon.port {
+            requires_restart.push("daemon.port".to_string());
+        }
+        if current.daemon.bind_addr != new_cfg.daemon.bind_addr {
+            requires_restart.push("daemon.bind_addr".to_string());
+        }
+        if current.daemon.pidfile != new_cfg.daemon.pidfile {
+            requires_restart.push("daemon.pidfile".to_string());
+        }
+        if current.daemon.log_format != new_cfg.daemon.log_format {
+            requires_restart.push("daemon.log_format".to_string());
+        }
+        if current.daemon.api_key != new_cfg.daemon.api_key {
+            requires_restart.push("daemon.api_key".to_string());
+        }
+        if current.daemon.max_snapshots != new_cfg.daemon.max_snapshots {
+            requires_restart.push("daemon.max_snapshots".to_string());
+        }
+
+        *current = new_cfg;
+        Ok(ConfigReload {
+            applied,
+            requires_restart,
+        })
+    }
+
+    /// Finds the name of the module starting with the given prefix.
+    ///
+    /// An exact match is always preferred. Otherwise the module name is only
+    /// returned if exactly one module starts with the prefix, and an error
+    /// listing the candidates is returned if more than one does.
+    pub fn find_module_by_prefix(
+        &self,
+        prefix: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let module_names = self.planner.module_names();
+        if module_names.iter().any(|name| name == prefix) {
+            return Ok(Some(prefix.to_string()));
+        }
+
+        let mut candidates: Vec<_> = module_names
+            .into_iter()
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+
+        match candidates.len() {
+            0 => Ok(None),
+            1 => Ok(candidates.pop()),
+            _ => {
+                candidates.sort();
+                Err(DaemonError::AmbiguousPrefix {
+                    prefix: prefix.to_string(),
+                    candidates,
+                }
+                .into())
+            }
+        }
     }
 }
 
 /// Start the daemon
-pub fn start_daemon() -> Result<(), Box<dyn Error>> {
+///
+/// # Arguments
+/// * `cfg` - The config the daemon starts with
+/// * `config_source` - Where the config was read from, used when reloading
+pub fn start_daemon(
+    cfg: PersistedConfig,
+    config_source: ConfigSource,
+) -> Result<(), Box<dyn Error>> {
     let monitor = monitor::MonitorState::new();
-    config::create_config_if_not_exists()?;
-    let cfg = Arc::new(config::read_persisted_config()?);
+    let cfg = Arc::new(cfg);
 
     // Create the Tokio async runtime and pass a handle to it so that it can be
     // invoked from a sync context from within the API handlers.
@@ -57,7 +179,8 @@ pub fn start_daemon() -> Result<(), Box<dyn Error>> {
     let core = Arc::new(Core::new(
         monitor_handle,
         Arc::clone(&env_holder),
-        Arc::clone(&cfg),
+        cfg,
+        config_source,
     ));
 
     // Setup signal handlers to collect dead child processes.
@@ -68,12 +191,10 @@ pub fn start_daemon() -> Result<(), Box<dyn Error>> {
     // mode and collects the environment variables from this process. The
     // environment variables are persisted and then used when starting new
     // services or tasks. See [`env_grabber_thread`] for more.
-    if cfg.daemon.use_env_grabber.unwrap_or(false) {
-        env_grabber_thread(Arc::clone(&env_holder));
-    }
+    env_grabber_thread(Arc::clone(&env_holder), core.shared_config());
 
     // Start the API.
-    api::engine::start(&core);
+    api::engine::start(&core)?;
 
     Ok(())
 }
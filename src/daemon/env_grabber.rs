@@ -1,3 +1,4 @@
+use crate::daemon::core::SharedConfig;
 use crate::shell::interactive_shell_cmd_line;
 use anyhow::Result;
 use log::{debug, info};
@@ -68,12 +69,25 @@ fn parse_printenv_output(output: &[u8]) -> HashMap<String, String> {
 /// daemon, and prevent issues arising from a stale set of environment
 /// variables.
 ///
+/// The environment is only grabbed while `use_env_grabber` is enabled in the
+/// current config, so that the setting can be toggled by reloading the config.
+///
 /// This feature is still experimental, and may be removed if it does not
 /// provide substantial benefit to offset the complexity it introduces.
-pub fn env_grabber_thread(current_env_holder: Arc<CurrentEnvHolder>) {
+pub fn env_grabber_thread(
+    current_env_holder: Arc<CurrentEnvHolder>,
+    config: SharedConfig,
+) {
     info!("Starting env-grabber thread");
     std::thread::spawn(move || loop {
         std::thread::sleep(Duration::from_millis(5000));
+        let enabled = {
+            let config = config.read().unwrap();
+            config.daemon.use_env_grabber.unwrap_or(false)
+        };
+        if !enabled {
+            continue;
+        }
         debug!("env-grab started");
         let new_env = grab_env().expect("failed");
         current_env_holder.replace(new_env);
@@ -14,6 +14,28 @@ pub enum DaemonError {
     #[error("Module with name '{0}' is not running or doesn't exist.")]
     NotRunning(String),
 
+    /// Represents the case when attempting to resume a module that is not
+    /// paused.
+    #[error("Module with name '{0}' is not paused")]
+    NotPaused(String),
+
+    /// Represents the case when attempting an operation that requires a
+    /// module to not be running. For example, clearing the logs of a running
+    /// service.
+    #[error(
+        "Module with name '{0}' is still running. Stop it first or use \
+        --force."
+    )]
+    StillRunning(String),
+
+    /// Represents the case when a module name prefix matches more than one
+    /// module.
+    #[error("Module name '{prefix}' is ambiguous, did you mean one of {candidates:?}?")]
+    AmbiguousPrefix {
+        prefix: String,
+        candidates: Vec<String>,
+    },
+
     /// Represents the case some of the module in the given subset of
     /// modules do not exist. For example, trying to deploy a set of modules
     /// where one doesn't exist.
@@ -33,6 +55,35 @@ pub enum DaemonError {
         log_file: OsString,
     },
 
+    /// Represents an attempt to restart a one-shot module, which only ever
+    /// runs once.
+    #[error("Module '{0}' is a one-shot and cannot be restarted")]
+    OneShotRestart(String),
+
+    /// Represents an attempt to roll back a module which has not been
+    /// deployed with a different definition before.
+    #[error("Module '{0}' has no previous definition to roll back to")]
+    NoPreviousDefinition(String),
+
+    /// Represents an attempt to import a module which is not a service, since
+    /// only services are kept track of before they are deployed.
+    #[error("Module '{0}' is not a service and cannot be imported")]
+    NotImportable(String),
+
+    /// Represents an attempt to check the health of a module which has no
+    /// liveness probe.
+    #[error("Module '{0}' has no liveness probe")]
+    NoLivenessProbe(String),
+
+    /// Represents an attempt to start or stop a module while its process is
+    /// still being stopped, eg. by a concurrent restart.
+    #[error("Module '{0}' is being stopped, try again once it has stopped")]
+    Stopping(String),
+
+    /// Represents an attempt to release the deploy lock without holding it.
+    #[error("The deploy lock is not held by this client")]
+    LockNotHeld,
+
     /// Represents a failure to read from input.
     #[error("Read error")]
     ReadError { source: std::io::Error },
@@ -1,22 +1,31 @@
+use crate::daemon::cgroups::CgroupManager;
+use crate::daemon::core::SharedConfig;
 use crate::daemon::env_grabber::CurrentEnvHolder;
 use crate::daemon::error::DaemonError;
 use crate::daemon::logs::log_file_module;
-use crate::daemon::module::{ModuleDefinition, TermSignal};
+use crate::daemon::module::{ModuleDefinition, ModuleKind, TermSignal};
 use crate::daemon::monitor::{monitor_key, MonitorType};
 use crate::daemon::planner::{Monitor, MonitorHandle};
+use crate::daemon::redact::redacting_pipe;
+use crate::daemon::socket_activation::{
+    activation_command, remove_socket, ActivationSocket,
+};
+use crate::daemon::spawn;
 use crate::daemon::time::epoch_now;
 use crate::process::{CommandExt, Process};
 
 use crate::command_builder::CommandBuilder;
 use anyhow::{Context, Result};
-use log::info;
+use log::{info, warn};
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::env;
 use std::ffi::OsString;
-use std::fs::File;
-use std::path::Path;
-use std::process::ExitStatus;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ExitStatus};
 use std::sync::Arc;
+use std::time::Duration;
 
 pub struct Executor {
     module_map: HashMap<String, ModuleStatus>,
@@ -26,7 +35,19 @@ pub struct Executor {
 }
 
 pub struct ExecutorConfig {
-    pub use_env_grabber_env: bool,
+    /// The daemon config, which is replaced when it is reloaded.
+    pub daemon_config: SharedConfig,
+    /// Enforces the resource limits of modules.
+    pub cgroups: CgroupManager,
+}
+
+impl ExecutorConfig {
+    /// Whether modules inherit the environment grabbed by the env-grabber, as
+    /// currently configured.
+    pub fn use_env_grabber_env(&self) -> bool {
+        let config = self.daemon_config.read().unwrap();
+        config.daemon.use_env_grabber.unwrap_or(false)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -34,7 +55,20 @@ pub enum RunStatus {
     RUNNING,
     WAITING,
     STOPPED,
+    /// A service whose processes were suspended with `SIGSTOP`.
+    PAUSED,
     EXITED,
+    /// A one-shot which exited successfully.
+    COMPLETED,
+    /// A one-shot which exited with a non-zero exit code.
+    FAILED,
+}
+
+impl RunStatus {
+    /// Whether the module has processes which haven't exited yet.
+    pub fn is_alive(&self) -> bool {
+        matches!(self, RunStatus::RUNNING | RunStatus::PAUSED)
+    }
 }
 
 #[derive(Debug)]
@@ -47,8 +81,17 @@ pub struct ModuleStatus {
     pub exit_status: Option<ExitStatus>,
     pub log_file_path: OsString,
     pub monitor_key: Option<String>,
+    /// The number of times the module was restarted.
+    pub restarts: u32,
+    /// The absolute working directory of the module's process, resolved when
+    /// it was spawned.
+    pub working_dir: Option<PathBuf>,
 
     child: Option<Process>,
+    /// Set while the process of the module is waited on after it was told to
+    /// stop (see [PendingStop]), during which the module cannot be started
+    /// or stopped by anyone else.
+    stopping: bool,
 }
 
 impl ModuleStatus {
@@ -65,8 +108,82 @@ impl ModuleStatus {
             exit_time: 0,
             exit_status: None,
             monitor_key: None,
+            restarts: 0,
+            working_dir: None,
             log_file_path: log_file_path.as_os_str().to_os_string(),
+            stopping: false,
+        }
+    }
+}
+
+/// A module which was told to stop, and whose process has yet to be waited
+/// on (see [Executor::stop_module]).
+#[must_use = "the process of the module must be waited on"]
+pub struct PendingStop {
+    process: Process,
+    definition: Arc<ModuleDefinition>,
+    /// The shutdown command of the module, if it is shut down gracefully.
+    shutdown: Option<Child>,
+}
+
+impl PendingStop {
+    /// The name of the module being stopped.
+    pub fn module_name(&self) -> &str {
+        &self.definition.name
+    }
+
+    /// Waits for the process of the module to exit.
+    ///
+    /// A module shut down by its shutdown command which does not exit within
+    /// the shutdown timeout is sent `SIGTERM`, and if it still does not exit
+    /// within the timeout `SIGKILL`. The Unix socket of a socket activated
+    /// module is removed once it has exited.
+    pub fn wait(self) -> Result<()> {
+        let definition = self.definition.clone();
+        self.wait_for_exit()?;
+        match &definition.socket_activation {
+            Some(config) => remove_socket(config),
+            None => Ok(()),
+        }
+    }
+
+    fn wait_for_exit(self) -> Result<()> {
+        let PendingStop {
+            mut process,
+            definition: module,
+            shutdown,
+        } = self;
+        let mut shutdown = match shutdown {
+            Some(shutdown) => shutdown,
+            None => {
+                process.wait()?;
+                return Ok(());
+            }
+        };
+
+        let timeout = Duration::from_secs(module.shutdown_timeout_secs);
+        if process.wait_timeout(timeout)?.is_none() {
+            warn!(
+                "Module {} did not shut down within {}s, terminating it",
+                module.name, module.shutdown_timeout_secs
+            );
+            process.terminate()?;
+            if process.wait_timeout(timeout)?.is_none() {
+                warn!(
+                    "Module {} did not terminate within {}s, killing it",
+                    module.name, module.shutdown_timeout_secs
+                );
+                process.kill()?;
+                process.wait()?;
+            }
+        }
+
+        // Do not leave the shutdown command behind if it is still running
+        if shutdown.try_wait()?.is_none() {
+            shutdown.kill()?;
         }
+        shutdown.wait()?;
+        Ok(())
     }
 }
 
@@ -108,9 +225,9 @@ impl Executor {
     /// Attempt to collect any dead processes.
     ///
     /// Looks for any processes that may have exited and updates their status as
-    /// well as their exit time. If the dead process moved was `RUNNING` then
-    /// that indicates a process exited (or got killed). Any other status is
-    /// mapped to `STOPPED` (i.e. stopped by the user).
+    /// well as their exit time. If the dead process moved was `RUNNING` or
+    /// `PAUSED` then that indicates a process exited (or got killed). Any other
+    /// status is mapped to `STOPPED` (i.e. stopped by the user).
     pub fn collect(&mut self) {
         let mut expired_probes = vec![];
 
@@ -120,7 +237,9 @@ impl Executor {
                     module.exit_time = epoch_now();
                     module.exit_status = Option::from(status);
                     module.status = match module.status {
-                        RunStatus::RUNNING => RunStatus::EXITED,
+                        RunStatus::RUNNING | RunStatus::PAUSED => {
+                            RunStatus::EXITED
+                        }
                         _ => RunStatus::STOPPED,
                     };
                     if let Some(handle) = module.monitor_key.take() {
@@ -142,80 +261,204 @@ impl Executor {
         }
     }
 
-    /// Redeploys a module with a newer module definition.
-    pub fn redeploy_module(
+    /// Returns the definition a module is restarted with, which is the one
+    /// of its last attempted deploy.
+    pub fn restart_definition(
+        &self,
+        module_name: &str,
+    ) -> Result<Arc<ModuleDefinition>> {
+        let existing = Arc::clone(
+            &self
+                .module_status_by_name(module_name)
+                .ok_or_else(|| DaemonError::NotFound(module_name.to_string()))?
+                .module_definition,
+        );
+        if existing.kind == ModuleKind::OneShot {
+            return Err(
+                DaemonError::OneShotRestart(module_name.to_string()).into()
+            );
+        }
+        Ok(existing)
+    }
+
+    /// Runs a module again after it was stopped by a restart, counting the
+    /// restart.
+    pub fn run_restarted(
         &mut self,
         module: Arc<ModuleDefinition>,
     ) -> Result<()> {
-        info!("Redeploying module: {}", module.name);
-        self.stop_module(&module.name)?;
-        self.run_module(module)
-    }
-
-    /// Restarts a module (re-using the same module definition).
-    pub fn restart_module(&mut self, module_name: &str) -> Result<()> {
-        info!("Restarting module: {}", module_name);
-        let existing = {
-            let module = self.module_status_by_name(module_name);
-            Arc::clone(
-                &module
-                    .ok_or_else(|| {
-                        DaemonError::NotFound(module_name.to_string())
-                    })?
-                    .module_definition,
-            )
-        };
-        self.stop_module(module_name)?;
-        self.run_module(existing)
+        let module_name = module.name.clone();
+        self.run_module(module)?;
+        if let Some(module) = self.module_map.get_mut(&module_name) {
+            module.restarts += 1;
+        }
+        Ok(())
     }
 
     /// Stops a module by name.
     ///
+    /// The module is signalled (or its shutdown command started) right away,
+    /// while waiting for its process to exit is left to the returned
+    /// [PendingStop], so that the executor need not be locked meanwhile.
+    /// Returns `None` if the module was not running.
+    ///
+    /// The module is reserved until [Executor::finish_stop] is called once
+    /// the process has exited, failing any attempt to start or stop it again
+    /// meanwhile.
+    ///
     /// Note: This will not stop dependent modules.
-    pub fn stop_module(&mut self, name: &str) -> Result<()> {
+    pub fn stop_module(&mut self, name: &str) -> Result<Option<PendingStop>> {
         info!("Stopping module: {}", name);
-        match self.module_map.get_mut(name) {
-            Some(module) => {
-                if let Some(process) = &mut module.child {
-                    // Bail if already stopped
-                    if module.status != RunStatus::RUNNING {
-                        return Ok(());
-                    }
+        let module = self
+            .module_map
+            .get_mut(name)
+            .ok_or_else(|| DaemonError::NotRunning(name.to_string()))?;
+        if module.stopping {
+            return Err(DaemonError::Stopping(name.to_string()).into());
+        }
+        // Bail if already stopped
+        let was_paused = match module.status {
+            RunStatus::RUNNING => false,
+            RunStatus::PAUSED => true,
+            _ => return Ok(None),
+        };
+        let mut process = match module.child.take() {
+            Some(process) => process,
+            None => return Ok(None),
+        };
 
-                    module.status = RunStatus::STOPPED;
-                    module.exit_time = epoch_now();
+        module.status = RunStatus::STOPPED;
+        module.exit_time = epoch_now();
+        module.stopping = true;
 
-                    // Remove monitor tracking its liveness
-                    if let Some(monitor_key) = &module.monitor_key {
-                        self.monitor_handle.remove_monitor(
-                            monitor_key.clone(),
-                            MonitorType::Liveness,
-                        );
-                    }
+        // Remove monitor tracking its liveness
+        if let Some(monitor_key) = &module.monitor_key {
+            self.monitor_handle
+                .remove_monitor(monitor_key.clone(), MonitorType::Liveness);
+        }
 
-                    let module_name = module.module_definition.name.clone();
+        let definition = Arc::clone(&module.module_definition);
+        let module_name = &definition.name;
 
-                    // Signal child process to die
-                    match module.module_definition.termination_signal {
-                        TermSignal::KILL => process.kill(),
-                        TermSignal::TERM => process.terminate(),
-                        TermSignal::INT => process.interrupt(),
-                    }
-                    .with_context(|| {
-                        format!(
-                            "Failed to signal process {} to stop",
-                            module_name
-                        )
-                    })?;
-
-                    process.wait()?;
-                }
-                Ok(())
+        if let Some(shutdown_command) = &definition.shutdown_command {
+            // The process has to be running to shut down
+            if was_paused {
+                process.resume().with_context(|| {
+                    format!("Failed to resume process {}", module_name)
+                })?;
             }
-            None => Err(DaemonError::NotRunning(name.to_string()).into()),
+            let shutdown =
+                Self::start_shutdown_command(&definition, shutdown_command)?;
+            return Ok(Some(PendingStop {
+                process,
+                definition,
+                shutdown: Some(shutdown),
+            }));
+        }
+
+        // Signal child process to die
+        match definition.termination_signal {
+            TermSignal::KILL => process.kill(),
+            TermSignal::TERM => process.terminate(),
+            TermSignal::INT => process.interrupt(),
+        }
+        .with_context(|| {
+            format!("Failed to signal process {} to stop", module_name)
+        })?;
+
+        // A paused process only handles the signal once resumed
+        if was_paused {
+            process.resume().with_context(|| {
+                format!("Failed to resume process {}", module_name)
+            })?;
+        }
+
+        Ok(Some(PendingStop {
+            process,
+            definition,
+            shutdown: None,
+        }))
+    }
+
+    /// Releases a module reserved by [Executor::stop_module], once its
+    /// process has been waited on.
+    pub fn finish_stop(&mut self, name: &str) {
+        if let Some(module) = self.module_map.get_mut(name) {
+            module.stopping = false;
         }
     }
 
+    fn start_shutdown_command(
+        module: &ModuleDefinition,
+        shutdown_command: &[String],
+    ) -> Result<Child> {
+        let mut cmd = CommandBuilder::new(shutdown_command);
+        cmd.inherit_env(module.inherit_env)
+            .env(&module.environment)
+            .stdout_null()
+            .stderr_null()
+            .work_dir(module.working_dir.as_deref());
+        cmd.build().spawn().with_context(|| {
+            format!("Failed to run the shutdown command of {}", module.name)
+        })
+    }
+
+    /// Pauses a running module by sending `SIGSTOP` to its processes.
+    pub fn pause_module(&mut self, name: &str) -> Result<()> {
+        info!("Pausing module: {}", name);
+        let module = self
+            .module_map
+            .get_mut(name)
+            .filter(|m| m.status == RunStatus::RUNNING)
+            .ok_or_else(|| DaemonError::NotRunning(name.to_string()))?;
+
+        if let Some(process) = &mut module.child {
+            process.pause().with_context(|| {
+                format!("Failed to signal process {} to pause", name)
+            })?;
+            module.status = RunStatus::PAUSED;
+        }
+        Ok(())
+    }
+
+    /// Resumes a paused module by sending `SIGCONT` to its processes.
+    pub fn resume_module(&mut self, name: &str) -> Result<()> {
+        info!("Resuming module: {}", name);
+        let module = self
+            .module_map
+            .get_mut(name)
+            .filter(|m| m.status == RunStatus::PAUSED)
+            .ok_or_else(|| DaemonError::NotPaused(name.to_string()))?;
+
+        if let Some(process) = &mut module.child {
+            process.resume().with_context(|| {
+                format!("Failed to signal process {} to resume", name)
+            })?;
+            module.status = RunStatus::RUNNING;
+        }
+        Ok(())
+    }
+
+    /// Signals a running module to reload its configuration by sending
+    /// `SIGHUP` to its processes.
+    ///
+    /// The module keeps running, so its status is left unchanged.
+    pub fn reload_module(&mut self, name: &str) -> Result<()> {
+        info!("Reloading module: {}", name);
+        let module = self
+            .module_map
+            .get_mut(name)
+            .filter(|m| m.status == RunStatus::RUNNING)
+            .ok_or_else(|| DaemonError::NotRunning(name.to_string()))?;
+
+        if let Some(process) = &mut module.child {
+            process.hangup().with_context(|| {
+                format!("Failed to signal process {} to reload", name)
+            })?;
+        }
+        Ok(())
+    }
+
     /// Executes a service module, and registers its state.
     ///
     /// The service is expected to be a long-running process and is run as a
@@ -225,6 +468,10 @@ impl Executor {
     /// * `module` - The module definition of the service
     pub fn run_module(&mut self, module: Arc<ModuleDefinition>) -> Result<()> {
         info!("Executing module: {}", module.name);
+        // The process of the module may not have exited yet
+        if self.module_map.get(&module.name).map_or(false, |m| m.stopping) {
+            return Err(DaemonError::Stopping(module.name.clone()).into());
+        }
 
         let log_file_pathbuf = log_file_module(&module)?;
         let log_file_path = log_file_pathbuf.as_path();
@@ -232,7 +479,7 @@ impl Executor {
         let environment_variables = Self::environment_variables(
             &module,
             &self.env_holder,
-            self.cfg.use_env_grabber_env,
+            self.cfg.use_env_grabber_env(),
         );
 
         let module_entry = self
@@ -243,15 +490,36 @@ impl Executor {
             });
 
         let (stdout_file, stderr_file) =
-            Self::prepare_log_files(log_file_path)?;
+            Self::prepare_log_files(log_file_path, &module.stdout_filter)?;
+
+        let cgroup = self.cfg.cgroups.create_for_module(&module)?;
+
+        let socket = module
+            .socket_activation
+            .as_ref()
+            .map(ActivationSocket::listen)
+            .transpose()?;
+        let command_line = match &socket {
+            Some(_) => Cow::Owned(activation_command(&module.command)),
+            None => Cow::Borrowed(&module.command),
+        };
 
-        let mut cmd = CommandBuilder::new(&module.command);
-        cmd.env(&environment_variables)
+        let mut cmd = CommandBuilder::new(&command_line);
+        cmd.inherit_env(module.inherit_env)
+            .env(&environment_variables)
             .stdout_file(stdout_file)
             .stderr_file(stderr_file)
             .work_dir(module.working_dir.as_deref());
 
-        let child = cmd.build().group_spawn().with_context(|| {
+        let mut command = cmd.build();
+        spawn::configure(&mut command, &module)?;
+        if let Some(cgroup) = cgroup {
+            cgroup.join_on_spawn(&mut command)?;
+        }
+        if let Some(socket) = socket {
+            socket.pass_to(&mut command);
+        }
+        let child = command.group_spawn().with_context(|| {
             format!("Failed to run service '{}'", module.name)
         })?;
 
@@ -261,6 +529,7 @@ impl Executor {
         module_entry.uptime = epoch_now();
         module_entry.module_definition = Arc::clone(&module);
         module_entry.monitor_key = liveness_probe;
+        module_entry.working_dir = Self::resolve_working_dir(&module);
 
         info!(
             "Process ({}) started, for module {}",
@@ -270,25 +539,90 @@ impl Executor {
         Ok(())
     }
 
-    /// Perform cleanup by attempting to kill all running child processes.
-    pub fn cleanup(&mut self) -> Result<()> {
+    /// Registers a module without running it, so that it is `WAITING` until it
+    /// is deployed.
+    ///
+    /// Returns false if the module is already known, in which case it is left
+    /// untouched.
+    pub fn register_module(
+        &mut self,
+        module: Arc<ModuleDefinition>,
+    ) -> Result<bool> {
+        if self.module_map.contains_key(&module.name) {
+            return Ok(false);
+        }
+        let log_file_pathbuf = log_file_module(&module)?;
+        let module_entry =
+            ModuleStatus::empty_from(&module, log_file_pathbuf.as_path());
+        self.module_map.insert(module.name.clone(), module_entry);
+        Ok(true)
+    }
+
+    /// Registers the outcome of a one-shot module which has already run.
+    ///
+    /// The one-shot is recorded as either `COMPLETED` or `FAILED` depending on
+    /// its exit status.
+    pub fn record_oneshot(
+        &mut self,
+        module: Arc<ModuleDefinition>,
+        result: &task_executor::TaskResult,
+    ) -> Result<()> {
+        let log_file_pathbuf = log_file_module(&module)?;
+        let mut module_entry =
+            ModuleStatus::empty_from(&module, log_file_pathbuf.as_path());
+
+        module_entry.status = if result.exit_status.success() {
+            RunStatus::COMPLETED
+        } else {
+            RunStatus::FAILED
+        };
+        module_entry.pid = result.pid;
+        module_entry.uptime = result.start_time;
+        module_entry.exit_time = epoch_now();
+        module_entry.exit_status = Some(result.exit_status);
+        module_entry.working_dir = Self::resolve_working_dir(&module);
+
+        info!(
+            "One-shot {} finished with exit-code {}",
+            module.name,
+            result.exit_status.code().unwrap_or(-1)
+        );
+        self.module_map.insert(module.name.clone(), module_entry);
+        Ok(())
+    }
+
+    /// Perform cleanup by attempting to stop all running child processes.
+    ///
+    /// Returns the modules which were told to stop, to be waited on.
+    pub fn cleanup(&mut self) -> Result<Vec<PendingStop>> {
         let module_names: Vec<String> = self
             .running_modules()
             .map(|m| m.module_definition.name.clone())
             .collect();
+        let mut pending = Vec::new();
         for module in module_names {
-            self.stop_module(module.as_str())?;
+            pending.extend(self.stop_module(module.as_str())?);
         }
-        Ok(())
+        Ok(pending)
     }
 }
 
 impl Executor {
+    /// Resolves the directory the processes of a module run in, which is the
+    /// working directory of the daemon unless the module sets one.
+    fn resolve_working_dir(module: &ModuleDefinition) -> Option<PathBuf> {
+        let dir = match &module.working_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => env::current_dir(),
+        };
+        dir.and_then(fs::canonicalize).ok()
+    }
+
     fn running_modules(&self) -> impl Iterator<Item = &ModuleStatus> {
         self.module_map
             .values()
             .into_iter()
-            .filter(|m| m.status == RunStatus::RUNNING)
+            .filter(|m| m.status.is_alive())
     }
 
     fn running_modules_mut(
@@ -296,7 +630,7 @@ impl Executor {
     ) -> impl Iterator<Item = &mut ModuleStatus> {
         self.module_map
             .values_mut()
-            .filter(|m| m.status == RunStatus::RUNNING)
+            .filter(|m| m.status.is_alive())
     }
 
     fn merge_envs(
@@ -314,7 +648,8 @@ impl Executor {
         env_holder: &CurrentEnvHolder,
         use_env_grabber_env: bool,
     ) -> Cow<'a, HashMap<String, String>> {
-        if use_env_grabber_env {
+        // The grabbed environment is part of the inherited one
+        if use_env_grabber_env && module.inherit_env {
             let merged_environment =
                 Self::merge_envs(env_holder.read(), &module.environment);
             Cow::Owned(merged_environment)
@@ -346,11 +681,23 @@ impl Executor {
         monitor_key
     }
 
+    /// Creates the log file of a module, returning the files its stdout and
+    /// stderr are written to.
+    ///
+    /// With a `stdout_filter`, both are the write end of a single pipe, whose
+    /// output is written to the log file with the values of the filter
+    /// redacted.
     pub(super) fn prepare_log_files(
         log_file_path: &Path,
+        stdout_filter: &[String],
     ) -> Result<(File, File)> {
-        let stdout_file = File::create(log_file_path)
+        let log_file = File::create(log_file_path)
             .with_context(|| "Failed to create log file")?;
+        let stdout_file = if stdout_filter.is_empty() {
+            log_file
+        } else {
+            redacting_pipe(log_file, stdout_filter)?
+        };
         let stderr_file = stdout_file
             .try_clone()
             .with_context(|| "Failed to create log file")?;
@@ -366,60 +713,234 @@ pub mod task_executor {
     use crate::daemon::executor::ExecutorConfig;
     use crate::daemon::logs::log_file_module;
     use crate::daemon::module::{ModuleDefinition, ModuleKind};
+    use crate::daemon::redact::redact_output;
+    use crate::daemon::spawn;
+    use crate::daemon::time::epoch_now;
     use anyhow::{Context, Result};
-    use std::process::ExitStatus;
+    use std::io::{Read, Write};
+    use std::process::{ChildStdout, ExitStatus, Stdio};
     use std::sync::Arc;
 
+    /// The maximum amount of stdout bytes captured from a task.
+    const MAX_CAPTURED_OUTPUT: usize = 1024 * 1024;
+
+    pub struct TaskResult {
+        pub pid: u32,
+        /// The time the task was started at (seconds since the epoch).
+        pub start_time: u64,
+        pub exit_status: ExitStatus,
+        /// The captured stdout of the task, if output capture was enabled.
+        pub output: Option<String>,
+    }
+
     /// Executes a task and waits for it until it is finished.
     ///
     /// The task will block the current thread, and report its exit status on
     /// completion. If the task exits with any code other than zero then an
     /// Error is thrown.
+    ///
+    /// When output capture is enabled for the task its stdout is still
+    /// written to the log file, but up to [MAX_CAPTURED_OUTPUT] bytes are also
+    /// collected and returned in the [TaskResult].
     pub fn execute_task(
         task_definition: &ModuleDefinition,
         cfg: &ExecutorConfig,
         env_holder: Arc<CurrentEnvHolder>,
-    ) -> Result<ExitStatus> {
+    ) -> Result<TaskResult> {
         assert!(task_definition.kind == ModuleKind::Task);
+        let result = run_to_completion(task_definition, cfg, env_holder)?;
+        check_success(task_definition, result)
+    }
+
+    /// Executes a task and waits for it until it is finished.
+    ///
+    /// Unlike [execute_task] a non-zero exit code is not treated as an error,
+    /// so that the exit codes of tasks run in a batch can all be reported.
+    pub fn run_task(
+        task_definition: &ModuleDefinition,
+        cfg: &ExecutorConfig,
+        env_holder: Arc<CurrentEnvHolder>,
+    ) -> Result<TaskResult> {
+        assert!(task_definition.kind == ModuleKind::Task);
+        run_to_completion(task_definition, cfg, env_holder)
+    }
+
+    /// Executes a one-shot and waits for it until it is finished.
+    ///
+    /// Unlike [execute_task] a non-zero exit code is not treated as an error,
+    /// so that the outcome of the one-shot can be recorded either way.
+    pub fn execute_oneshot(
+        oneshot_definition: &ModuleDefinition,
+        cfg: &ExecutorConfig,
+        env_holder: Arc<CurrentEnvHolder>,
+    ) -> Result<TaskResult> {
+        assert!(oneshot_definition.kind == ModuleKind::OneShot);
+        run_to_completion(oneshot_definition, cfg, env_holder)
+    }
+
+    /// Turns a non-zero exit code of a finished task into an error.
+    pub fn check_success(
+        task_definition: &ModuleDefinition,
+        result: TaskResult,
+    ) -> Result<TaskResult> {
+        if !result.exit_status.success() {
+            return Err(DaemonError::TaskFailed {
+                task_name: task_definition.name.clone(),
+                code: result.exit_status.code().unwrap_or(-1),
+                log_file: log_file_module(task_definition)?.into_os_string(),
+            }
+            .into());
+        }
+        Ok(result)
+    }
+
+    fn run_to_completion(
+        task_definition: &ModuleDefinition,
+        cfg: &ExecutorConfig,
+        env_holder: Arc<CurrentEnvHolder>,
+    ) -> Result<TaskResult> {
         let log_file_pathbuf = log_file_module(task_definition)?;
         let log_file_path = log_file_pathbuf.as_path();
         let environment_vars = Executor::environment_variables(
             task_definition,
             &env_holder,
-            cfg.use_env_grabber_env,
+            cfg.use_env_grabber_env(),
         );
 
+        let stdout_filter = &task_definition.stdout_filter;
         let (stdout_file, stderr_file) =
-            Executor::prepare_log_files(log_file_path)?;
+            Executor::prepare_log_files(log_file_path, stdout_filter)?;
 
         let mut cmd = CommandBuilder::new(&task_definition.command);
-        cmd.env(&environment_vars)
-            .stdout_file(stdout_file)
+        cmd.inherit_env(task_definition.inherit_env)
+            .env(&environment_vars)
             .stderr_file(stderr_file)
             .work_dir(task_definition.working_dir.as_deref());
 
-        let exit_status = cmd
-            .build()
-            .spawn()
-            .with_context(|| {
-                format!(
-                    "Failed to start task {}",
-                    &task_definition.command.join(" ")
-                )
-            })?
-            .wait()
-            .with_context(|| {
-                format!("Task {} failed to execute", task_definition.name)
-            })?;
+        if task_definition.capture_output {
+            cmd.stdout(Stdio::piped());
+        } else {
+            cmd.stdout_file(stdout_file.try_clone()?);
+        }
 
-        if !exit_status.success() {
-            return Err(DaemonError::TaskFailed {
-                task_name: task_definition.name.clone(),
-                code: exit_status.code().unwrap_or(-1),
-                log_file: log_file_path.as_os_str().to_os_string(),
+        let cgroup = cfg.cgroups.create_for_module(task_definition)?;
+
+        let mut command = cmd.build();
+        spawn::configure(&mut command, task_definition)?;
+        if let Some(cgroup) = cgroup {
+            cgroup.join_on_spawn(&mut command)?;
+        }
+        let mut child = command.spawn().with_context(|| {
+            format!(
+                "Failed to start task {}",
+                &task_definition.command.join(" ")
+            )
+        })?;
+        let pid = child.id();
+        let start_time = epoch_now();
+
+        let output = match child.stdout.take() {
+            Some(stdout) => {
+                Some(tee_output(stdout, stdout_file, stdout_filter)?)
             }
-            .into());
+            None => None,
+        };
+
+        let exit_status = child.wait().with_context(|| {
+            format!("Task {} failed to execute", task_definition.name)
+        })?;
+
+        Ok(TaskResult {
+            pid,
+            start_time,
+            exit_status,
+            output,
+        })
+    }
+
+    /// Copies all of the child's stdout to the log file while keeping the
+    /// first [MAX_CAPTURED_OUTPUT] bytes in memory.
+    ///
+    /// The values of `stdout_filter` are redacted from the captured output
+    /// as well.
+    fn tee_output(
+        mut stdout: ChildStdout,
+        mut log_file: impl Write,
+        stdout_filter: &[String],
+    ) -> Result<String> {
+        let mut captured = Vec::new();
+        let mut buf = [0; 8192];
+        loop {
+            let read = stdout
+                .read(&mut buf)
+                .with_context(|| "Failed to read task output")?;
+            if read == 0 {
+                break;
+            }
+            log_file
+                .write_all(&buf[..read])
+                .with_context(|| "Failed to write task output to log file")?;
+            let remaining =
+                MAX_CAPTURED_OUTPUT.saturating_sub(captured.len());
+            captured.extend_from_slice(&buf[..read.min(remaining)]);
+        }
+        let captured = redact_output(&captured, stdout_filter);
+        Ok(String::from_utf8_lossy(&captured).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::process::Command;
+    use std::time::Instant;
+
+    #[test]
+    fn test_pending_stop_escalates_to_terminate() {
+        let mut module = ModuleDefinition::test_service("api");
+        module.shutdown_timeout_secs = 1;
+        let shutdown_command = vec![String::from("true")];
+        let process = Process::ungroupped(
+            Command::new("sleep").arg("60").spawn().unwrap(),
+        );
+        let shutdown =
+            Executor::start_shutdown_command(&module, &shutdown_command)
+                .unwrap();
+        let stop = PendingStop {
+            process,
+            definition: Arc::new(module),
+            shutdown: Some(shutdown),
+        };
+
+        let started = Instant::now();
+        stop.wait().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_stderr_is_redacted() {
+        let log_file_path = std::env::temp_dir()
+            .join(format!("cartel-redacted-{}.log", std::process::id()));
+        let filter = vec![String::from("hunter2")];
+        let (stdout_file, stderr_file) =
+            Executor::prepare_log_files(&log_file_path, &filter).unwrap();
+
+        Command::new("sh")
+            .args(&["-c", "echo out=hunter2; echo err=hunter2 >&2"])
+            .stdout(stdout_file)
+            .stderr(stderr_file)
+            .status()
+            .unwrap();
+
+        // The output is written to the log file once the pipe is closed
+        let expected = "out=[REDACTED]\nerr=[REDACTED]\n";
+        let started = Instant::now();
+        let mut log = String::new();
+        while log != expected && started.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(10));
+            log = fs::read_to_string(&log_file_path).unwrap();
         }
-        Ok(exit_status)
+        fs::remove_file(&log_file_path).unwrap();
+        assert_eq!(log, expected);
     }
 }
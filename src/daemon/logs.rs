@@ -1,8 +1,10 @@
 use crate::daemon::module::{ModuleDefinition, ModuleKind};
 use anyhow::{Context, Result};
 use dirs::home_dir;
-use std::fs;
-use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 
 const PROJECT_DIR: &str = ".cartel";
 const LOG_DIR: &str = "logs";
@@ -33,6 +35,9 @@ pub fn log_file_path(
         ModuleKind::Service => {
             base.join(format!("{}.service.log", module_name))
         }
+        ModuleKind::OneShot => {
+            base.join(format!("{}.oneshot.log", module_name))
+        }
     };
     Ok(path)
 }
@@ -47,3 +52,23 @@ pub fn log_file_module(module: &ModuleDefinition) -> Result<PathBuf> {
         _ => log_file_path(&module.name, &module.kind),
     }
 }
+
+/// Returns (up to) the last `n` lines of the given log file.
+///
+/// Lines which are not valid UTF-8 are converted lossily.
+pub fn tail_lines(path: &Path, n: usize) -> Result<Vec<String>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open log file {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut lines = VecDeque::with_capacity(n);
+    let mut buf = vec![];
+
+    while reader.read_until(b'\n', &mut buf)? > 0 {
+        if lines.len() == n {
+            lines.pop_front();
+        }
+        lines.push_back(String::from_utf8_lossy(&buf).trim_end().to_string());
+        buf.clear();
+    }
+    Ok(lines.into_iter().collect())
+}
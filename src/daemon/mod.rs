@@ -1,5 +1,7 @@
 pub mod api;
+pub mod cgroups;
 pub mod cli;
+pub mod config;
 pub mod core;
 pub mod env_grabber;
 pub mod error;
@@ -8,7 +10,10 @@ pub mod logs;
 pub mod module;
 pub mod monitor;
 pub mod planner;
+pub mod redact;
 pub mod signal;
+pub mod socket_activation;
+pub mod spawn;
 pub mod time;
 
 pub use self::core::Core;
@@ -5,7 +5,7 @@ use std::path::PathBuf;
 use crate::daemon::planner::Monitor;
 
 /// The type of the module.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum ModuleKind {
     /// A task is a module with a limited lifetime, used to perform some
     /// temporary operation or some setup.
@@ -13,6 +13,9 @@ pub enum ModuleKind {
     /// A service is a longer running module. It's lifetime will be managed and
     /// can be started, stopped independently.
     Service,
+    /// A one-shot is a task which runs at most once for the lifetime of the
+    /// daemon and is never restarted.
+    OneShot,
 }
 
 /// The choice of terminating signal to use when terminating the process.
@@ -28,6 +31,25 @@ pub enum TermSignal {
     INT,
 }
 
+/// A port exposed by a module. Only kept to be reported back, the daemon
+/// does not enforce it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PortMapping {
+    pub host_port: u16,
+    pub container_port: u16,
+    pub protocol: String,
+}
+
+/// A socket the daemon listens on and passes to the module's process, see
+/// [crate::daemon::socket_activation].
+#[derive(Debug, PartialEq, Clone)]
+pub struct SocketActivationConfig {
+    /// The path of a Unix socket.
+    pub path: Option<String>,
+    /// The port of a TCP socket on the loopback interface.
+    pub port: Option<u16>,
+}
+
 #[derive(Debug)]
 pub struct ModuleDefinition {
     pub kind: ModuleKind,
@@ -39,6 +61,38 @@ pub struct ModuleDefinition {
     pub working_dir: Option<PathBuf>,
     pub termination_signal: TermSignal,
     pub liveness_probe: Option<Monitor>,
+    pub capture_output: bool,
+    /// The maximum amount of memory the module may use, enforced through
+    /// cgroups on Linux.
+    pub max_memory_mb: Option<u64>,
+    /// The maximum share of a single CPU the module may use, in percent,
+    /// enforced through cgroups on Linux.
+    pub cpu_quota_percent: Option<u32>,
+    /// The path of a network namespace the module's process joins (Linux
+    /// only).
+    pub network_namespace: Option<String>,
+    /// A command which stops the module gracefully, run instead of sending
+    /// the termination signal.
+    pub shutdown_command: Option<Vec<String>>,
+    /// How long to wait for the module to exit after each step of a graceful
+    /// shutdown, before escalating to the next one.
+    pub shutdown_timeout_secs: u64,
+    /// Whether the module's process inherits the environment of the daemon.
+    pub inherit_env: bool,
+    /// The ports the module exposes, as given in its definition.
+    pub ports: Vec<PortMapping>,
+    /// The file creation mask of the module's process.
+    pub umask: Option<u32>,
+    /// Strings redacted from the stdout and stderr of the module's process
+    /// before they are written to the log file.
+    pub stdout_filter: Vec<String>,
+    /// The version of the definition, as given by the user.
+    pub version: Option<String>,
+    /// The socket passed to the module's process, if it is socket activated.
+    pub socket_activation: Option<SocketActivationConfig>,
+    /// The namespace the module was deployed in, if any. The `name` of the
+    /// module is qualified with it, eg. `auth@test`.
+    pub namespace: Option<String>,
 }
 
 impl Hash for ModuleDefinition {
@@ -65,6 +119,19 @@ impl ModuleDefinition {
         working_dir: Option<PathBuf>,
         termination_signal: TermSignal,
         liveness_probe: Option<Monitor>,
+        capture_output: bool,
+        max_memory_mb: Option<u64>,
+        cpu_quota_percent: Option<u32>,
+        network_namespace: Option<String>,
+        shutdown_command: Option<Vec<String>>,
+        shutdown_timeout_secs: u64,
+        inherit_env: bool,
+        ports: Vec<PortMapping>,
+        umask: Option<u32>,
+        stdout_filter: Vec<String>,
+        version: Option<String>,
+        socket_activation: Option<SocketActivationConfig>,
+        namespace: Option<String>,
     ) -> ModuleDefinition {
         ModuleDefinition {
             kind,
@@ -76,6 +143,50 @@ impl ModuleDefinition {
             working_dir,
             termination_signal,
             liveness_probe,
+            capture_output,
+            max_memory_mb,
+            cpu_quota_percent,
+            network_namespace,
+            shutdown_command,
+            shutdown_timeout_secs,
+            inherit_env,
+            ports,
+            umask,
+            stdout_filter,
+            version,
+            socket_activation,
+            namespace,
         }
     }
 }
+
+#[cfg(test)]
+impl ModuleDefinition {
+    /// Returns a service without any of the optional settings.
+    pub fn test_service(name: &str) -> ModuleDefinition {
+        ModuleDefinition::new(
+            ModuleKind::Service,
+            name.to_string(),
+            vec![String::from("sleep"), String::from("60")],
+            HashMap::new(),
+            None,
+            vec![],
+            None,
+            TermSignal::KILL,
+            None,
+            false,
+            None,
+            None,
+            None,
+            None,
+            10,
+            true,
+            vec![],
+            None,
+            vec![],
+            None,
+            None,
+            None,
+        )
+    }
+}
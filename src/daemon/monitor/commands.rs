@@ -5,6 +5,7 @@ pub enum MonitorTask {
     Executable(ExecMonitor),
     LogLine(LogLineMonitor),
     Net(NetMonitor),
+    Http(HttpMonitor),
 }
 
 #[derive(Debug, Clone)]
@@ -34,6 +35,17 @@ impl NetMonitor {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct HttpMonitor {
+    pub url: String,
+}
+
+impl HttpMonitor {
+    pub fn from(url: String) -> Self {
+        Self { url }
+    }
+}
+
 impl ExecMonitor {
     pub fn from(command: Vec<String>, working_dir: Option<String>) -> Self {
         Self {
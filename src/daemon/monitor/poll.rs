@@ -1,5 +1,7 @@
+use crate::daemon::config::module_span;
 use crate::daemon::error::DaemonError;
 use crate::daemon::monitor::commands::*;
+use crate::daemon::monitor::runtime::monitor_module_name;
 use crate::daemon::monitor::state::{MonitorState, MonitorStatus};
 use crate::daemon::time::epoch_now;
 use anyhow::{anyhow, Result};
@@ -8,6 +10,7 @@ use grep_regex::RegexMatcher;
 use grep_searcher::sinks::UTF8;
 use grep_searcher::Searcher;
 use log::{debug, info};
+use reqwest::redirect::Policy;
 use std::collections::HashMap;
 use std::process::Stdio;
 use std::sync::Arc;
@@ -15,7 +18,9 @@ use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::process;
 use tokio::sync::mpsc;
+use tokio::task;
 use tokio::time::timeout;
+use tracing::Instrument;
 
 pub(super) async fn readiness_poll_tickr(tx: mpsc::Sender<MonitorCommand>) {
     let mut interval =
@@ -71,6 +76,9 @@ pub(super) async fn channel_rx(
                 monitor_type,
             } => {
                 info!("Registering monitor: {}", key);
+                // Until the first poll (within a few seconds) the monitor is
+                // starting rather than pending.
+                monitor_state.register(key.clone());
                 match monitor_type {
                     MonitorType::Liveness => {
                         liveness_monitor_list.push((key, monitor))
@@ -173,10 +181,10 @@ async fn poll_readiness_check(
             attempt_count.remove_entry(&key);
             status.push((key, MonitorStatus::Error));
         } else if poll_successful {
-            // If the poll succeeded remove and set status to error
+            // If the poll succeeded remove and set status to ready
             monitor_list.swap_remove(idx);
             attempt_count.remove_entry(&key);
-            status.push((key, MonitorStatus::Successful));
+            status.push((key, MonitorStatus::Ready));
         } else if attempts >= retries {
             // If it failed too many times remove and update status
             monitor_list.swap_remove(idx);
@@ -221,30 +229,47 @@ async fn poll_monitors(
 ) -> Vec<(String, Result<bool>)> {
     let mut results = vec![];
     for (key, monitor) in monitors {
-        match &monitor.task {
-            MonitorTask::Executable(exe_monitor) => {
-                debug!("Polling exe monitor: {}", key);
-                let result = poll_exe_monitor(exe_monitor).await;
-                debug!("Exe monitor result: {:?}", result);
-                results.push((key.to_string(), result));
-            }
-            MonitorTask::LogLine(log_line_monitor) => {
-                debug!("Polling log line monitor: {}", key);
-                let result = poll_log_line_monitor(log_line_monitor).await;
-                debug!("Log line monitor result: {:?}", result);
-                results.push((key.to_string(), result));
-            }
-            MonitorTask::Net(net_monitor) => {
-                debug!("Polling net monitor: {}", key);
-                let result = poll_net_monitor(net_monitor).await;
-                debug!("Net monitor result: {:?}", result);
-                results.push((key.to_string(), result));
-            }
-        }
+        let result = poll_monitor(key, monitor).await;
+        results.push((key.to_string(), result));
     }
     results
 }
 
+/// Polls a single monitor once, returning whether its check passed.
+pub(super) async fn poll_monitor(key: &str, monitor: &Monitor) -> Result<bool> {
+    let span = module_span(monitor_module_name(key));
+    poll_monitor_task(key, monitor).instrument(span).await
+}
+
+async fn poll_monitor_task(key: &str, monitor: &Monitor) -> Result<bool> {
+    match &monitor.task {
+        MonitorTask::Executable(exe_monitor) => {
+            debug!("Polling exe monitor: {}", key);
+            let result = poll_exe_monitor(exe_monitor).await;
+            debug!("Exe monitor result: {:?}", result);
+            result
+        }
+        MonitorTask::LogLine(log_line_monitor) => {
+            debug!("Polling log line monitor: {}", key);
+            let result = poll_log_line_monitor(log_line_monitor).await;
+            debug!("Log line monitor result: {:?}", result);
+            result
+        }
+        MonitorTask::Net(net_monitor) => {
+            debug!("Polling net monitor: {}", key);
+            let result = poll_net_monitor(net_monitor).await;
+            debug!("Net monitor result: {:?}", result);
+            result
+        }
+        MonitorTask::Http(http_monitor) => {
+            debug!("Polling http monitor: {}", key);
+            let result = poll_http_monitor(http_monitor).await;
+            debug!("Http monitor result: {:?}", result);
+            result
+        }
+    }
+}
+
 async fn poll_exe_monitor(exe_monitor: &ExecMonitor) -> Result<bool> {
     let (head, tail) = exe_monitor
         .command
@@ -303,3 +328,29 @@ async fn poll_net_monitor(net_monitor: &NetMonitor) -> Result<bool> {
 
     Ok(result)
 }
+
+/// The time allowed for an HTTP probe to receive a response.
+const HTTP_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Sends a GET request to the URL of the monitor. The service is considered
+/// healthy if it responds with a 2xx status, redirects are not followed.
+///
+/// reqwest runs on a different version of tokio than the monitors, so its
+/// blocking client is used from a blocking thread instead.
+async fn poll_http_monitor(http_monitor: &HttpMonitor) -> Result<bool> {
+    let url = http_monitor.url.clone();
+    let request = task::spawn_blocking(move || {
+        reqwest::blocking::Client::builder()
+            .timeout(HTTP_PROBE_TIMEOUT)
+            .redirect(Policy::none())
+            .build()?
+            .get(&url)
+            .send()
+    });
+    match request.await? {
+        Ok(response) => Ok(response.status().is_success()),
+        // An invalid URL is a mistake in the probe configuration
+        Err(e) if e.is_builder() => Err(e.into()),
+        Err(_) => Ok(false),
+    }
+}
@@ -1,6 +1,7 @@
 use crate::daemon::monitor::commands::*;
 use crate::daemon::monitor::poll::{
-    channel_rx, cleanup_tickr, liveness_poll_tickr, readiness_poll_tickr,
+    channel_rx, cleanup_tickr, liveness_poll_tickr, poll_monitor,
+    readiness_poll_tickr,
 };
 use crate::daemon::monitor::state::{MonitorState, MonitorStatus};
 use anyhow::Result;
@@ -8,9 +9,14 @@ use log::info;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 use tokio::runtime::{self, Handle, Runtime};
 use tokio::sync::mpsc;
 
+/// How long a single poll of a monitor may take (see
+/// [MonitorHandle::poll_once]), which is longer than any of the probes wait.
+const POLL_ONCE_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct MonitorHandle {
     /// Handle to the tokio runtime of the monitor worker.
     runtime_handle: Handle,
@@ -66,6 +72,29 @@ impl MonitorHandle {
         self.monitor_state.monitor_status(monitor_name)
     }
 
+    /// Polls a monitor once, outside of its regular polling, and records the
+    /// result as the status of the monitor with the given key.
+    ///
+    /// Blocks until the poll completes, or fails with [MonitorStatus::Error]
+    /// if it takes longer than [POLL_ONCE_TIMEOUT].
+    pub fn poll_once(&self, key: String, monitor: Monitor) -> MonitorStatus {
+        let (result_tx, result_rx) = std::sync::mpsc::channel();
+        let poll_key = key.clone();
+        self.runtime_handle.spawn(async move {
+            let result = poll_monitor(&poll_key, &monitor).await;
+            // The receiver is gone if the caller stopped waiting for it
+            let _ = result_tx.send(result);
+        });
+
+        let status = match result_rx.recv_timeout(POLL_ONCE_TIMEOUT) {
+            Ok(Ok(true)) => MonitorStatus::Successful,
+            Ok(Ok(false)) => MonitorStatus::Failing,
+            Ok(Err(_)) | Err(_) => MonitorStatus::Error,
+        };
+        self.monitor_state.update_states(vec![(key, status)]);
+        status
+    }
+
     pub fn monitor_statuses(&self) -> HashMap<String, MonitorStatus> {
         self.monitor_state.monitor_statuses()
     }
@@ -100,6 +129,12 @@ pub fn monitor_key(name: &str, monitor_type: &MonitorType) -> String {
     }
 }
 
+/// Returns the name of the module of a key created by [monitor_key].
+pub(super) fn monitor_module_name(key: &str) -> &str {
+    // The name is followed by the five groups of the UUID and the type
+    key.rsplitn(7, '-').last().unwrap_or(key)
+}
+
 pub fn spawn_runtime(monitor_state: Arc<MonitorState>) -> MonitorHandle {
     let (tx, rx) = mpsc::channel::<MonitorCommand>(32);
     let tx_readiness = tx.clone();
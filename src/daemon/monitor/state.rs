@@ -8,6 +8,10 @@ pub enum MonitorStatus {
     RetriesExceeded = 0x3,
     Error = 0x4,
     Failing = 0x5,
+    /// The monitor was registered but has not been polled yet.
+    Starting = 0x6,
+    /// The readiness probe passed, so the module is ready to receive traffic.
+    Ready = 0x7,
 }
 
 pub struct MonitorState {
@@ -28,13 +32,25 @@ impl MonitorState {
         map.clone()
     }
 
+    /// Tracks the status of a new monitor, which is starting until it is
+    /// first polled.
+    pub(super) fn register(&self, monitor_name: String) {
+        let mut map = self.monitor_map.lock();
+        map.insert(monitor_name, MonitorStatus::Starting);
+    }
+
+    /// Updates the status of monitors. Monitors which were removed meanwhile
+    /// (eg. by a poll which finished after their module was stopped) are not
+    /// tracked again.
     pub(super) fn update_states(
         &self,
         new_states: Vec<(String, MonitorStatus)>,
     ) {
         let mut map = self.monitor_map.lock();
         new_states.into_iter().for_each(|(monitor, is_done)| {
-            map.insert(monitor, is_done);
+            if let Some(status) = map.get_mut(&monitor) {
+                *status = is_done;
+            }
         });
     }
 
@@ -57,3 +73,26 @@ impl Default for MonitorState {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_update_states_of_removed_monitor() {
+        let state = MonitorState::new();
+        state.register(String::from("a"));
+        state.register(String::from("b"));
+        state.remove_keys(&["b"]);
+
+        state.update_states(vec![
+            (String::from("a"), MonitorStatus::Successful),
+            (String::from("b"), MonitorStatus::Successful),
+        ]);
+        assert!(matches!(
+            state.monitor_status("a"),
+            Some(MonitorStatus::Successful)
+        ));
+        assert!(state.monitor_status("b").is_none());
+    }
+}
@@ -1,18 +1,25 @@
-use crate::config::PersistedConfig;
+use crate::daemon::cgroups::CgroupManager;
+use crate::daemon::config::module_span;
+use crate::daemon::core::SharedConfig;
 use crate::daemon::env_grabber::CurrentEnvHolder;
 use crate::daemon::error::DaemonError;
+use crate::daemon::executor::task_executor::TaskResult;
 use crate::daemon::executor::{
-    task_executor, Executor, ExecutorConfig, ModuleStatus, RunStatus,
+    task_executor, Executor, ExecutorConfig, ModuleStatus, PendingStop,
+    RunStatus,
 };
 use crate::daemon::logs::log_file_path;
-use crate::daemon::module::{ModuleDefinition, ModuleKind};
+use crate::daemon::module::{ModuleDefinition, ModuleKind, PortMapping};
 use crate::daemon::monitor::{monitor_key, MonitorType};
 pub use crate::daemon::monitor::{Monitor, MonitorHandle, MonitorStatus};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use log::info;
 use parking_lot::{Mutex, MutexGuard};
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
+use std::fs::File;
 use std::iter::FromIterator;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 pub struct Planner {
@@ -33,11 +40,19 @@ pub struct Planner {
 
 pub struct PsStatus {
     pub name: String,
+    pub kind: ModuleKind,
     pub pid: u32,
     pub status: RunStatus,
     pub liveness_status: Option<MonitorStatus>,
     pub exit_code: Option<i32>,
     pub time_since_status: u64,
+    pub command: Vec<String>,
+    pub log_file_path: OsString,
+    pub restarts: u32,
+    pub working_dir: Option<PathBuf>,
+    pub ports: Vec<PortMapping>,
+    pub version: Option<String>,
+    pub namespace: Option<String>,
 }
 
 pub enum PlannedAction {
@@ -54,10 +69,11 @@ impl Planner {
     pub fn new(
         monitor_handle: MonitorHandle,
         env_holder: Arc<CurrentEnvHolder>,
-        cfg: Arc<PersistedConfig>,
+        daemon_config: SharedConfig,
     ) -> Planner {
         let executor_config = Arc::new(ExecutorConfig {
-            use_env_grabber_env: cfg.daemon.use_env_grabber.unwrap_or(false),
+            daemon_config,
+            cgroups: CgroupManager::default(),
         });
         Planner {
             executor: Mutex::new(Executor::new(
@@ -85,6 +101,8 @@ impl Planner {
         module_def: ModuleDefinition,
         force: bool,
     ) -> Result<bool> {
+        let span = module_span(&module_def.name);
+        let _enter = span.enter();
         let mut executor = self.executor();
         let existing = executor.module_status_by_name(&module_def.name);
 
@@ -102,7 +120,10 @@ impl Planner {
                     liveness_status,
                 ) || force
                 {
-                    executor.redeploy_module(Arc::new(module_def))?;
+                    info!("Redeploying module: {}", module_def.name);
+                    let stopped = executor.stop_module(&module_def.name)?;
+                    Self::wait_unlocked(&mut executor, stopped)?;
+                    executor.run_module(Arc::new(module_def))?;
                     Ok(true)
                 } else {
                     Ok(false)
@@ -112,6 +133,25 @@ impl Planner {
         }
     }
 
+    /// Returns any non-fatal observations about a module that was just
+    /// deployed, which should be surfaced to the user.
+    pub fn deployment_warnings(
+        &self,
+        module_def: &ModuleDefinition,
+    ) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.executor_config.use_env_grabber_env()
+            && self.env_holder.read().is_empty()
+        {
+            warnings.push(format!(
+                "The shell environment has not been captured yet, {} was \
+                started with only its own environment variables",
+                module_def.name
+            ));
+        }
+        warnings
+    }
+
     /// Deploys one or more modules (modules already in the correct state do not
     /// get affected).
     ///
@@ -137,13 +177,79 @@ impl Planner {
     pub fn deploy_task(
         &self,
         task_definition: &ModuleDefinition,
-    ) -> Result<i32> {
+    ) -> Result<TaskResult> {
+        let span = module_span(&task_definition.name);
+        let _enter = span.enter();
         task_executor::execute_task(
             task_definition,
             &self.executor_config,
             Arc::clone(&self.env_holder),
         )
-        .map(|exit_status| exit_status.code().unwrap_or(-1))
+    }
+
+    /// Runs a task, without treating a non-zero exit code as an error.
+    pub fn run_task(
+        &self,
+        task_definition: &ModuleDefinition,
+    ) -> Result<TaskResult> {
+        let span = module_span(&task_definition.name);
+        let _enter = span.enter();
+        task_executor::run_task(
+            task_definition,
+            &self.executor_config,
+            Arc::clone(&self.env_holder),
+        )
+    }
+
+    /// Runs a one-shot module.
+    ///
+    /// One-shots only run once for the lifetime of the daemon, which callers
+    /// ensure by reserving them in the `OneShotStore` first. The outcome of
+    /// the one-shot is recorded so that it shows up in the module status, and
+    /// an error is returned if it failed.
+    pub fn deploy_oneshot(
+        &self,
+        oneshot_definition: ModuleDefinition,
+    ) -> Result<TaskResult> {
+        let span = module_span(&oneshot_definition.name);
+        let _enter = span.enter();
+        let oneshot_definition = Arc::new(oneshot_definition);
+        let result = task_executor::execute_oneshot(
+            &oneshot_definition,
+            &self.executor_config,
+            Arc::clone(&self.env_holder),
+        )?;
+        self.executor()
+            .record_oneshot(Arc::clone(&oneshot_definition), &result)?;
+        task_executor::check_success(&oneshot_definition, result)
+    }
+
+    /// Registers services without deploying them, so that they can be
+    /// deployed later on.
+    ///
+    /// Returns the names of the services which were registered. Services which
+    /// are already known to the daemon are skipped.
+    pub fn import_modules(
+        &self,
+        module_defs: Vec<ModuleDefinition>,
+    ) -> Result<Vec<String>> {
+        if let Some(module_def) =
+            module_defs.iter().find(|m| m.kind != ModuleKind::Service)
+        {
+            return Err(
+                DaemonError::NotImportable(module_def.name.clone()).into()
+            );
+        }
+
+        let mut executor = self.executor();
+        let mut imported = vec![];
+        for module_def in module_defs {
+            let name = module_def.name.clone();
+            if executor.register_module(Arc::new(module_def))? {
+                imported.push(name);
+            }
+        }
+        Ok(imported)
     }
 
     /// Restarts an existing module.
@@ -151,12 +257,71 @@ impl Planner {
     /// The module could either be running, stopped or exited and the module
     /// definition of the last attempted deploy will be used.
     pub fn restart_module(&self, mod_name: &str) -> Result<()> {
-        self.executor().restart_module(mod_name)
+        let span = module_span(mod_name);
+        let _enter = span.enter();
+        info!("Restarting module: {}", mod_name);
+        let mut executor = self.executor();
+        let existing = executor.restart_definition(mod_name)?;
+        let stopped = executor.stop_module(mod_name)?;
+        Self::wait_unlocked(&mut executor, stopped)?;
+        executor.run_restarted(existing)
     }
 
     /// Stops a running module.
     pub fn stop_module(&self, mod_name: &str) -> Result<()> {
-        self.executor().stop_module(mod_name)
+        let span = module_span(mod_name);
+        let _enter = span.enter();
+        let mut executor = self.executor();
+        let stopped = executor.stop_module(mod_name)?;
+        Self::wait_unlocked(&mut executor, stopped)
+    }
+
+    /// Pauses a running module.
+    pub fn pause_module(&self, mod_name: &str) -> Result<()> {
+        let span = module_span(mod_name);
+        let _enter = span.enter();
+        self.executor().pause_module(mod_name)
+    }
+
+    /// Resumes a paused module.
+    pub fn resume_module(&self, mod_name: &str) -> Result<()> {
+        let span = module_span(mod_name);
+        let _enter = span.enter();
+        self.executor().resume_module(mod_name)
+    }
+
+    /// Signals a running module to reload its configuration.
+    pub fn reload_module(&self, mod_name: &str) -> Result<()> {
+        let span = module_span(mod_name);
+        let _enter = span.enter();
+        self.executor().reload_module(mod_name)
+    }
+
+    /// Runs the liveness probe of a running module once, outside of its
+    /// regular polling, and returns its new status.
+    pub fn check_health(&self, mod_name: &str) -> Result<MonitorStatus> {
+        let span = module_span(mod_name);
+        let _enter = span.enter();
+        let (key, monitor) = {
+            let executor = self.executor();
+            let module = executor
+                .module_status_by_name(mod_name)
+                .ok_or_else(|| DaemonError::NotFound(mod_name.to_string()))?;
+            let monitor = module
+                .module_definition
+                .liveness_probe
+                .clone()
+                .ok_or_else(|| {
+                    DaemonError::NoLivenessProbe(mod_name.to_string())
+                })?;
+            let key = module
+                .monitor_key
+                .clone()
+                .ok_or_else(|| DaemonError::NotRunning(mod_name.to_string()))?;
+            (key, monitor)
+        };
+        // The executor is not locked while the probe runs
+        Ok(self.monitor_handle.poll_once(key, monitor))
     }
 
     /// Returns the log path of a module.
@@ -167,7 +332,7 @@ impl Planner {
     ) -> Result<OsString> {
         match module_kind {
             ModuleKind::Service => self.log_path_running(module_name),
-            ModuleKind::Task => {
+            ModuleKind::Task | ModuleKind::OneShot => {
                 Ok(log_file_path(module_name, module_kind)?.into_os_string())
             }
         }
@@ -184,6 +349,62 @@ impl Planner {
             .map(|m| m.log_file_path.clone())
     }
 
+    /// Truncates the log file of a module.
+    ///
+    /// Services that are currently running will only have their log file
+    /// truncated if `force` is given.
+    pub fn clear_log(
+        &self,
+        module_name: &str,
+        module_kind: &ModuleKind,
+        force: bool,
+    ) -> Result<()> {
+        let path = match module_kind {
+            ModuleKind::Service => {
+                let executor = self.executor();
+                let module = executor
+                    .module_status_by_name(module_name)
+                    .ok_or_else(|| {
+                        DaemonError::NotFound(module_name.to_string())
+                    })?;
+                if module.status.is_alive() && !force {
+                    return Err(DaemonError::StillRunning(
+                        module_name.to_string(),
+                    )
+                    .into());
+                }
+                module.log_file_path.clone()
+            }
+            ModuleKind::Task | ModuleKind::OneShot => {
+                log_file_path(module_name, module_kind)?.into_os_string()
+            }
+        };
+
+        File::create(&path).with_context(|| {
+            format!("Failed to clear log file {:?}", path)
+        })?;
+        Ok(())
+    }
+
+    /// Returns the names of all modules known to the daemon.
+    pub fn module_names(&self) -> Vec<String> {
+        self.executor()
+            .modules()
+            .map(|m| m.module_definition.name.clone())
+            .collect()
+    }
+
+    /// Returns the definition and log file path of each module known to the
+    /// daemon.
+    pub fn module_definitions(&self) -> Vec<(Arc<ModuleDefinition>, OsString)> {
+        self.executor()
+            .modules()
+            .map(|m| {
+                (Arc::clone(&m.module_definition), m.log_file_path.clone())
+            })
+            .collect()
+    }
+
     /// Returns a summarized version of each modules status.
     pub fn module_status(&self) -> Vec<PsStatus> {
         let mut statuses = self.monitor_handle.monitor_statuses();
@@ -191,6 +412,7 @@ impl Planner {
             .modules()
             .map(|m| PsStatus {
                 name: m.module_definition.name.clone(),
+                kind: m.module_definition.kind.clone(),
                 pid: m.pid,
                 status: m.status.clone(),
                 liveness_status: match &m.monitor_key {
@@ -200,10 +422,20 @@ impl Planner {
                 exit_code: m.exit_status.and_then(|e| e.code()),
                 time_since_status: match m.status {
                     RunStatus::RUNNING => m.uptime,
+                    RunStatus::PAUSED => m.uptime,
                     RunStatus::STOPPED => m.exit_time,
                     RunStatus::EXITED => m.exit_time,
+                    RunStatus::COMPLETED => m.exit_time,
+                    RunStatus::FAILED => m.exit_time,
                     RunStatus::WAITING => 0,
                 },
+                command: m.module_definition.command.clone(),
+                log_file_path: m.log_file_path.clone(),
+                restarts: m.restarts,
+                working_dir: m.working_dir.clone(),
+                ports: m.module_definition.ports.clone(),
+                version: m.module_definition.version.clone(),
+                namespace: m.module_definition.namespace.clone(),
             })
             .collect()
     }
@@ -264,14 +496,16 @@ impl Planner {
 
     /// Performs cleanup (by killing all running children).
     pub fn cleanup(&self) -> Result<()> {
-        self.executor().cleanup()
+        let mut executor = self.executor();
+        let stopped = executor.cleanup()?;
+        Self::wait_unlocked(&mut executor, stopped)
     }
 
     /// Stops all running services.
     pub fn stop_all(&self) -> Result<()> {
         // Currently uses cleanup, but having this as a separate function since
         // it may change in the future.
-        self.executor().cleanup()
+        self.cleanup()
     }
 
     /// Creates a monitor and returns it.
@@ -327,6 +561,28 @@ impl Planner {
         self.executor.lock()
     }
 
+    /// Waits for stopped modules to exit without holding the executor lock,
+    /// so that a slow graceful shutdown does not block other requests (such
+    /// as `cartel ps`) meanwhile.
+    ///
+    /// The modules stay reserved by [Executor::stop_module] until then, so
+    /// that no one else starts them in the meantime.
+    fn wait_unlocked(
+        executor: &mut MutexGuard<Executor>,
+        stopped: impl IntoIterator<Item = PendingStop>,
+    ) -> Result<()> {
+        let stopped: Vec<_> = stopped.into_iter().collect();
+        let names: Vec<_> =
+            stopped.iter().map(|s| s.module_name().to_string()).collect();
+        let result = MutexGuard::unlocked(executor, || {
+            stopped.into_iter().try_for_each(PendingStop::wait)
+        });
+        for name in &names {
+            executor.finish_stop(name);
+        }
+        result
+    }
+
     fn should_redeploy(
         module_def: &ModuleDefinition,
         module_status: &ModuleStatus,
@@ -340,11 +596,20 @@ impl Planner {
             return true;
         }
 
+        // Every setting which affects how the process is spawned
         let current = module_status.module_definition.as_ref();
         current.command != module_def.command
             || current.environment != module_def.environment
             || current.log_file_path != module_def.log_file_path
             || current.working_dir != module_def.working_dir
+            || current.inherit_env != module_def.inherit_env
+            || current.umask != module_def.umask
+            || current.stdout_filter != module_def.stdout_filter
+            || current.version != module_def.version
+            || current.socket_activation != module_def.socket_activation
+            || current.max_memory_mb != module_def.max_memory_mb
+            || current.cpu_quota_percent != module_def.cpu_quota_percent
+            || current.network_namespace != module_def.network_namespace
     }
 
     fn deployment_set(
@@ -369,3 +634,123 @@ impl Planner {
             .filter(move |m| selection_set.contains(&m.name)))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::PersistedConfig;
+    use crate::daemon::module::{SocketActivationConfig, TermSignal};
+    use crate::daemon::monitor::{spawn_runtime, MonitorState};
+    use std::fs;
+    use std::path::Path;
+    use std::sync::RwLock;
+
+    fn running(module_def: ModuleDefinition) -> ModuleStatus {
+        let mut status =
+            ModuleStatus::empty_from(&Arc::new(module_def), Path::new("log"));
+        status.status = RunStatus::RUNNING;
+        status
+    }
+
+    /// Whether a running module is redeployed after applying `change` to its
+    /// definition.
+    fn redeploys_after<F: FnOnce(&mut ModuleDefinition)>(change: F) -> bool {
+        let status = running(ModuleDefinition::test_service("api"));
+        let mut module_def = ModuleDefinition::test_service("api");
+        change(&mut module_def);
+        Planner::should_redeploy(&module_def, &status, None)
+    }
+
+    #[test]
+    fn test_unchanged_module_is_not_redeployed() {
+        assert!(!redeploys_after(|_| {}));
+        assert!(!redeploys_after(|m| m.shutdown_timeout_secs = 30));
+    }
+
+    #[test]
+    fn test_stopped_or_failing_module_is_redeployed() {
+        let module_def = ModuleDefinition::test_service("api");
+        let mut status = running(ModuleDefinition::test_service("api"));
+        let failing = Some(MonitorStatus::Failing);
+        assert!(Planner::should_redeploy(&module_def, &status, failing));
+
+        status.status = RunStatus::STOPPED;
+        assert!(Planner::should_redeploy(&module_def, &status, None));
+    }
+
+    #[test]
+    fn test_process_settings_cause_redeploy() {
+        assert!(redeploys_after(|m| m.command.push(String::from("-v"))));
+        assert!(redeploys_after(|m| {
+            m.environment.insert(String::from("A"), String::from("1"));
+        }));
+        assert!(redeploys_after(|m| m.log_file_path = Some("a.log".into())));
+        assert!(redeploys_after(|m| m.working_dir = Some("/tmp".into())));
+        assert!(redeploys_after(|m| m.inherit_env = false));
+        assert!(redeploys_after(|m| m.umask = Some(0o077)));
+        assert!(redeploys_after(|m| m.stdout_filter = vec!["s".into()]));
+        assert!(redeploys_after(|m| m.version = Some(String::from("2"))));
+        assert!(redeploys_after(|m| {
+            m.socket_activation = Some(SocketActivationConfig {
+                path: None,
+                port: Some(8080),
+            });
+        }));
+        assert!(redeploys_after(|m| m.network_namespace = Some("ns".into())));
+    }
+
+    #[test]
+    fn test_resource_limits_cause_redeploy() {
+        assert!(redeploys_after(|m| m.max_memory_mb = Some(512)));
+        assert!(redeploys_after(|m| m.cpu_quota_percent = Some(50)));
+    }
+
+    fn test_planner() -> Planner {
+        let config: PersistedConfig =
+            toml::from_str("[daemon]\n\n[client]").unwrap();
+        Planner::new(
+            spawn_runtime(Arc::new(MonitorState::new())),
+            Arc::new(CurrentEnvHolder::new()),
+            Arc::new(RwLock::new(Arc::new(config))),
+        )
+    }
+
+    #[test]
+    fn test_concurrent_restarts_do_not_orphan_processes() {
+        let dir = std::env::temp_dir()
+            .join(format!("cartel-restarts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut module_def = ModuleDefinition::test_service("api");
+        module_def.log_file_path =
+            Some(dir.join("api.log").to_string_lossy().into_owned());
+        // Takes a second to exit once told to stop, so the restarts overlap
+        let script = "trap 'sleep 1; exit' TERM; while :; do sleep 0.1; done";
+        module_def.command = vec!["sh".into(), "-c".into(), script.into()];
+        module_def.termination_signal = TermSignal::TERM;
+
+        let planner = test_planner();
+        planner.deploy(module_def, false).unwrap();
+        let results: Vec<_> = crossbeam_utils::thread::scope(|s| {
+            let restarts: Vec<_> = (0..2)
+                .map(|_| s.spawn(|_| planner.restart_module("api")))
+                .collect();
+            restarts.into_iter().map(|r| r.join().unwrap()).collect()
+        })
+        .unwrap();
+
+        // The second restart finds the module reserved by the first one
+        let failed: Vec<_> =
+            results.iter().filter_map(|r| r.as_ref().err()).collect();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0].is::<DaemonError>());
+        {
+            let executor = planner.executor();
+            let module = executor.module_status_by_name("api").unwrap();
+            assert_eq!(module.status, RunStatus::RUNNING);
+            assert_eq!(module.restarts, 1);
+        }
+
+        planner.stop_module("api").unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
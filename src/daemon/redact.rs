@@ -0,0 +1,182 @@
+//! Redaction of sensitive values (eg. secrets echoed by a command) from the
+//! output of modules, before it is written to their log file.
+
+use anyhow::{Context, Result};
+use log::warn;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::unistd::pipe;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::io::FromRawFd;
+use std::thread;
+
+/// What occurrences of the redacted values are replaced with.
+const REDACTED: &[u8] = b"[REDACTED]";
+
+/// A writer which replaces every occurrence of some values with
+/// `[REDACTED]` before passing the output to the underlying writer.
+///
+/// An occurrence may be split across several writes, so output which may be
+/// the start of an occurrence is held back until enough of it was written to
+/// tell. Any output still held back is written when the writer is dropped.
+pub struct RedactingWriter<W: Write> {
+    inner: W,
+    /// The values to redact, longest first so that the longest match wins.
+    values: Vec<Vec<u8>>,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> RedactingWriter<W> {
+    pub fn new(inner: W, values: &[String]) -> Self {
+        let mut values: Vec<Vec<u8>> = values
+            .iter()
+            .filter(|value| !value.is_empty())
+            .map(|value| value.as_bytes().to_vec())
+            .collect();
+        values.sort_by(|a, b| b.len().cmp(&a.len()));
+        RedactingWriter {
+            inner,
+            values,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Writes out the pending output, redacting it along the way.
+    ///
+    /// Unless `complete`, the end of the output is held back if it is the
+    /// start of a value.
+    fn write_pending(&mut self, complete: bool) -> io::Result<()> {
+        let mut output = Vec::with_capacity(self.pending.len());
+        let mut idx = 0;
+        while idx < self.pending.len() {
+            let rest = &self.pending[idx..];
+            // A longer value may still match once more output is written
+            let partial = |v: &Vec<u8>| {
+                v.len() > rest.len() && v.starts_with(rest)
+            };
+            if !complete && self.values.iter().any(partial) {
+                break;
+            }
+            let redacted = self.values.iter().find(|v| rest.starts_with(v));
+            if let Some(value) = redacted {
+                output.extend_from_slice(REDACTED);
+                idx += value.len();
+            } else {
+                output.push(rest[0]);
+                idx += 1;
+            }
+        }
+        self.pending.drain(..idx);
+        self.inner.write_all(&output)
+    }
+}
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.write_pending(false)?;
+        Ok(buf.len())
+    }
+
+    /// Flushes the underlying writer. Output which may be the start of a value
+    /// is still held back.
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for RedactingWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.write_pending(true);
+    }
+}
+
+/// Returns the output with the given values redacted.
+pub fn redact_output(output: &[u8], values: &[String]) -> Vec<u8> {
+    let mut redacted = Vec::with_capacity(output.len());
+    {
+        let mut writer = RedactingWriter::new(&mut redacted, values);
+        // Writing to a vector cannot fail
+        let _ = writer.write_all(output);
+    }
+    redacted
+}
+
+/// Returns the write end of a pipe whose output is written to the log file
+/// with the given values redacted, to be used as the stdout and stderr of a
+/// module.
+///
+/// The output is copied on a separate thread, until every process holding
+/// the write end has closed it.
+pub fn redacting_pipe(log_file: File, values: &[String]) -> Result<File> {
+    let (read_fd, write_fd) = pipe().context("Failed to create stdout pipe")?;
+    for fd in &[read_fd, write_fd] {
+        // The read end must not leak into spawned processes, otherwise they
+        // would keep the pipe open. The write end is duplicated as the stdout
+        // of the module, which clears the flag on the duplicate.
+        fcntl(*fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))
+            .context("Failed to configure stdout pipe")?;
+    }
+    // Safety: the pipe was just created, so nothing else owns its ends.
+    let (mut reader, writer) = unsafe {
+        (File::from_raw_fd(read_fd), File::from_raw_fd(write_fd))
+    };
+
+    let mut log_file = RedactingWriter::new(log_file, values);
+    thread::spawn(move || {
+        if let Err(e) = io::copy(&mut reader, &mut log_file) {
+            warn!("Failed to write redacted output to log file: {}", e);
+        }
+    });
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn redact(writes: &[&str], values: &[&str]) -> String {
+        let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+        let mut output = Vec::new();
+        {
+            let mut writer = RedactingWriter::new(&mut output, &values);
+            for write in writes {
+                writer.write_all(write.as_bytes()).unwrap();
+            }
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_redacts_values() {
+        let output = redact(&["token=abc123 user=bob\n"], &["abc123", "bob"]);
+        assert_eq!(output, "token=[REDACTED] user=[REDACTED]\n");
+    }
+
+    #[test]
+    fn test_redacts_values_split_across_writes() {
+        let output = redact(&["token=ab", "c1", "23\n"], &["abc123"]);
+        assert_eq!(output, "token=[REDACTED]\n");
+    }
+
+    #[test]
+    fn test_writes_partial_values_when_dropped() {
+        let output = redact(&["token=abc"], &["abc123"]);
+        assert_eq!(output, "token=abc");
+    }
+
+    #[test]
+    fn test_redact_output() {
+        let values = vec![String::from("abc123")];
+        let output = redact_output(b"token=abc123\ntoken=abc", &values);
+        assert_eq!(output, b"token=[REDACTED]\ntoken=abc");
+    }
+
+    #[test]
+    fn test_prefers_longest_value() {
+        let values = ["secret", "secret-key"];
+        assert_eq!(redact(&["secret-key\n"], &values), "[REDACTED]\n");
+        assert_eq!(redact(&["secret", "-key\n"], &values), "[REDACTED]\n");
+        assert_eq!(redact(&["secret", "-\n"], &values), "[REDACTED]-\n");
+    }
+}
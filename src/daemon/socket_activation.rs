@@ -0,0 +1,217 @@
+//! Socket activation of services, following the convention of systemd (see
+//! `sd_listen_fds(3)`).
+//!
+//! The daemon creates the listening socket of the service and passes it to
+//! the service's process as file descriptor 3, with `LISTEN_FDS` set to the
+//! number of passed sockets and `LISTEN_PID` set to the pid of the process.
+
+use crate::daemon::module::SocketActivationConfig;
+use anyhow::{bail, Context, Result};
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::unistd::dup2;
+use std::fs;
+use std::io;
+use std::net::{Ipv4Addr, TcpListener};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+
+/// The first file descriptor of the passed sockets (`SD_LISTEN_FDS_START`).
+const LISTEN_FDS_START: RawFd = 3;
+
+/// `LISTEN_PID` is only known once the process is forked, while the
+/// environment of a [Command] is fixed before it is spawned. The command is
+/// thus run through a shell which sets it to its own pid, and then replaces
+/// itself with the command.
+const SET_LISTEN_PID: &str = "export LISTEN_PID=$$; exec \"$0\" \"$@\"";
+
+/// A listening socket passed to a socket activated service.
+pub enum ActivationSocket {
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+impl ActivationSocket {
+    /// Creates the socket described by the configuration. TCP sockets listen
+    /// on the loopback interface.
+    ///
+    /// A Unix socket left behind by a previous run of the service is
+    /// replaced. A socket which still accepts connections or any other file
+    /// at its path is an error.
+    pub fn listen(config: &SocketActivationConfig) -> Result<ActivationSocket> {
+        match (&config.path, config.port) {
+            (Some(path), None) => {
+                remove_stale_socket(Path::new(path))?;
+                let listener = UnixListener::bind(path).with_context(|| {
+                    format!("Failed to listen on socket {}", path)
+                })?;
+                Ok(ActivationSocket::Unix(listener))
+            }
+            (None, Some(port)) => {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+                    .with_context(|| {
+                        format!("Failed to listen on port {}", port)
+                    })?;
+                Ok(ActivationSocket::Tcp(listener))
+            }
+            _ => bail!(
+                "Socket activation requires exactly one of a path or a port"
+            ),
+        }
+    }
+
+    /// Passes the socket to the process spawned by the command, which must
+    /// have been created from [activation_command].
+    ///
+    /// The socket is closed in the daemon once the command is dropped.
+    pub fn pass_to(self, command: &mut Command) {
+        command.env("LISTEN_FDS", "1");
+        let fd = self.as_raw_fd();
+        // dup2 and fcntl are async-signal-safe, so they may be called between
+        // fork and exec.
+        unsafe {
+            command.pre_exec(move || {
+                let _socket = &self;
+                move_to_listen_fd(fd).map_err(io::Error::from)
+            });
+        }
+    }
+}
+
+impl AsRawFd for ActivationSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        match self {
+            ActivationSocket::Unix(listener) => listener.as_raw_fd(),
+            ActivationSocket::Tcp(listener) => listener.as_raw_fd(),
+        }
+    }
+}
+
+/// Returns a command line which runs the given command with `LISTEN_PID` set
+/// to the pid of its process.
+pub fn activation_command(command: &[String]) -> Vec<String> {
+    let mut wrapped = vec![
+        String::from("/bin/sh"),
+        String::from("-c"),
+        String::from(SET_LISTEN_PID),
+    ];
+    wrapped.extend_from_slice(command);
+    wrapped
+}
+
+/// Makes the socket available as the first passed file descriptor, without
+/// the close-on-exec flag.
+fn move_to_listen_fd(fd: RawFd) -> nix::Result<()> {
+    if fd == LISTEN_FDS_START {
+        // Duplicating the descriptor onto itself would keep the flag set
+        fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()))?;
+    } else {
+        dup2(fd, LISTEN_FDS_START)?;
+    }
+    Ok(())
+}
+
+/// Removes the Unix socket of the service, once its process has exited.
+pub fn remove_socket(config: &SocketActivationConfig) -> Result<()> {
+    let path = match &config.path {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e)
+            .with_context(|| format!("Failed to remove socket {}", path)),
+        _ => Ok(()),
+    }
+}
+
+/// Unlinks a socket at the path nobody listens on anymore. Connecting to it
+/// first makes sure that the socket of a running process is never replaced.
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    match UnixStream::connect(path) {
+        Ok(_) => bail!(
+            "Cannot listen on {}, since the socket is in use",
+            path.display()
+        ),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+            fs::remove_file(path).with_context(|| {
+                format!("Failed to remove stale socket {}", path.display())
+            })
+        }
+        Err(e) => match fs::symlink_metadata(path) {
+            Ok(metadata) if !metadata.file_type().is_socket() => bail!(
+                "Cannot listen on {}, since it exists and is not a socket",
+                path.display()
+            ),
+            _ => Err(e).with_context(|| {
+                format!("Failed to inspect socket {}", path.display())
+            }),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_passes_socket_to_process() {
+        let config = SocketActivationConfig {
+            path: None,
+            port: Some(0),
+        };
+        let socket = ActivationSocket::listen(&config).unwrap();
+        let script = "echo $$ $LISTEN_PID $LISTEN_FDS; test -e /dev/fd/3";
+        let command_line = activation_command(&[
+            String::from("/bin/sh"),
+            String::from("-c"),
+            String::from(script),
+        ]);
+
+        let (program, args) = command_line.split_first().unwrap();
+        let mut command = Command::new(program);
+        command.args(args);
+        socket.pass_to(&mut command);
+        let output = command.output().unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let values: Vec<_> = stdout.split_whitespace().collect();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[0], values[1]);
+        assert_eq!(values[2], "1");
+    }
+
+    fn socket_config(name: &str) -> SocketActivationConfig {
+        let path = std::env::temp_dir()
+            .join(format!("cartel-{}-{}.sock", name, std::process::id()));
+        SocketActivationConfig {
+            path: Some(path.to_str().unwrap().to_string()),
+            port: None,
+        }
+    }
+
+    #[test]
+    fn test_replaces_stale_socket() {
+        let config = socket_config("stale");
+        // A dropped listener leaves its socket file behind
+        drop(ActivationSocket::listen(&config).unwrap());
+        assert!(Path::new(config.path.as_ref().unwrap()).exists());
+
+        drop(ActivationSocket::listen(&config).unwrap());
+        remove_socket(&config).unwrap();
+        assert!(!Path::new(config.path.as_ref().unwrap()).exists());
+    }
+
+    #[test]
+    fn test_refuses_socket_in_use() {
+        let config = socket_config("in-use");
+        let _socket = ActivationSocket::listen(&config).unwrap();
+
+        assert!(ActivationSocket::listen(&config).is_err());
+        remove_socket(&config).unwrap();
+    }
+}
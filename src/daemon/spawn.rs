@@ -0,0 +1,87 @@
+//! Process settings of modules which are applied in the child process, after
+//! it is forked and before the module's command is executed.
+
+use crate::daemon::module::ModuleDefinition;
+use anyhow::Result;
+use nix::libc::mode_t;
+use nix::sys::stat::{umask, Mode};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+/// Applies the process settings of the module to the command which spawns it.
+///
+/// # Errors
+///
+/// Fails if a setting cannot be applied, or is not supported on this platform.
+pub fn configure(
+    command: &mut Command,
+    module: &ModuleDefinition,
+) -> Result<()> {
+    if let Some(path) = &module.network_namespace {
+        imp::join_network_namespace(command, path)?;
+    }
+    if let Some(mask) = module.umask {
+        set_umask(command, mask);
+    }
+    Ok(())
+}
+
+/// Sets the file creation mask of the spawned process.
+fn set_umask(command: &mut Command, mask: u32) {
+    let mask = Mode::from_bits_truncate(mask as mode_t);
+    // umask is async-signal-safe, so it may be called between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            umask(mask);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use anyhow::{Context, Result};
+    use nix::sched::{setns, CloneFlags};
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    /// Makes the spawned process join the network namespace at the given
+    /// path (eg. `/var/run/netns/myns`).
+    pub fn join_network_namespace(
+        command: &mut Command,
+        path: &str,
+    ) -> Result<()> {
+        // The namespace is opened here so that a missing namespace fails the
+        // deployment, rather than the process after it was forked.
+        let namespace = File::open(path).with_context(|| {
+            format!("Failed to open network namespace {}", path)
+        })?;
+        unsafe {
+            command.pre_exec(move || {
+                setns(namespace.as_raw_fd(), CloneFlags::CLONE_NEWNET)
+                    .map_err(io::Error::from)
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use anyhow::{bail, Result};
+    use std::process::Command;
+
+    pub fn join_network_namespace(
+        _command: &mut Command,
+        path: &str,
+    ) -> Result<()> {
+        bail!(
+            "Cannot join network namespace {}, as network namespaces are \
+            not supported on this platform",
+            path
+        )
+    }
+}
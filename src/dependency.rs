@@ -1,6 +1,10 @@
 use crate::collections::{FromIndexContainer, FromOwnedIndexContainer, VecExt};
 use anyhow::{bail, Result};
-use std::collections::{HashMap, HashSet};
+use serde_json::{json, Value};
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
 use std::hash::{Hash, Hasher};
 
 pub struct DependencyGraph<'a, T, M>
@@ -10,6 +14,9 @@ where
 {
     edge_map: HashMap<String, Vec<DependencyNode<&'a T, M>>>,
     node_list: Vec<DependencyNode<&'a T, M>>,
+    /// The edges (from, to) which were passed on from a removed node through
+    /// a weak edge, and so only affect ordering.
+    weak_edges: HashSet<(String, String)>,
 }
 
 #[derive(Debug)]
@@ -26,8 +33,12 @@ pub struct DependencyNode<T, M> {
     /// Whether this node is "weak". A is_weak node should only be included in the
     /// final graph if a dependency of at least one strong node.
     pub is_weak: bool,
+    /// The length of the longest dependency chain below this node. Populated
+    /// when the graph is sorted.
+    depth: Cell<usize>,
 }
 
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum EdgeDirection {
     /// The edge points to the given node.
     To,
@@ -59,6 +70,25 @@ pub struct DependencyEdge<M: PartialOrd> {
 pub trait WithDependencies<M: PartialOrd>: WithKey {
     fn dependencies(&self) -> Vec<DependencyEdge<M>>;
     fn is_group(&self) -> bool;
+
+    /// Returns a hash of the key and the dependency edges of this node.
+    ///
+    /// Two nodes with the same hash have the same dependencies, which can be
+    /// used to detect changes in the structure of a graph. Edge markers are
+    /// not taken into account, nor is the order the dependencies are listed
+    /// in.
+    fn dependency_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.key_ref().hash(&mut hasher);
+        let mut edges: Vec<_> = self
+            .dependencies()
+            .into_iter()
+            .map(|e| (e.edge_src, e.edge_dst, e.direction, e.is_weak))
+            .collect();
+        edges.sort();
+        edges.hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
 pub trait WithKey {
@@ -66,6 +96,11 @@ pub trait WithKey {
     fn key_ref(&self) -> &str;
 }
 
+pub trait WithKind {
+    /// The kind of the node, as shown when the graph is serialized.
+    fn kind_name(&self) -> String;
+}
+
 impl<T: Copy, M: Copy> Clone for DependencyNode<T, M> {
     fn clone(&self) -> Self {
         DependencyNode {
@@ -74,10 +109,22 @@ impl<T: Copy, M: Copy> Clone for DependencyNode<T, M> {
             is_weak: self.is_weak,
             marker: self.marker,
             origin_nodes: self.origin_nodes.clone(),
+            depth: self.depth.clone(),
         }
     }
 }
 
+impl<T, M> DependencyNode<T, M> {
+    /// Returns the depth of this node in the graph.
+    ///
+    /// Nodes without any dependencies have a depth of zero, and every other
+    /// node is one deeper than its deepest dependency. The depth is only
+    /// available after the graph has been sorted.
+    pub fn depth(&self) -> usize {
+        self.depth.get()
+    }
+}
+
 impl<T, M> Hash for DependencyNode<T, M> {
     fn hash<S: Hasher>(&self, state: &mut S) {
         self.key.hash(state);
@@ -166,6 +213,13 @@ where
                 let edge_dst = &edge.edge_dst;
                 let edge_src = &edge.edge_src;
                 let marker = edge.marker;
+
+                // Weak edges only affect ordering, so a weak edge to a module
+                // which isn't defined at all can be ignored.
+                if edge.is_weak && !arena.has_original_node(edge_dst) {
+                    return;
+                }
+
                 let original_node =
                     arena.get_original_node(edge_dst.as_str(), src);
 
@@ -210,7 +264,130 @@ where
         DependencyGraph {
             edge_map,
             node_list,
+            weak_edges: HashSet::new(),
+        }
+    }
+
+    /// Returns a copy of the graph without the given nodes and their edges.
+    ///
+    /// Nodes which were ordered after a removed node through a weak edge (eg.
+    /// `after`) are ordered after its dependencies instead, through edges
+    /// which are weak as well. Removing a node which a remaining node strongly
+    /// depends on is an error, since the remaining node could not be deployed
+    /// without it. Nodes which are only reachable through the removed nodes
+    /// are dropped along with them.
+    pub fn without(&self, names: &[&str]) -> Result<DependencyGraph<'a, T, M>> {
+        let removed: HashSet<&str> = names.iter().copied().collect();
+        let nodes: HashMap<&str, &DependencyNode<&T, M>> = self
+            .node_list
+            .iter()
+            .chain(self.edge_map.values().flatten())
+            .map(|node| (node.key.as_str(), node))
+            .collect();
+        let mut edge_map = HashMap::new();
+        let mut weak_edges: HashSet<(String, String)> = self
+            .weak_edges
+            .iter()
+            .filter(|(from, to)| {
+                !removed.contains(from.as_str())
+                    && !removed.contains(to.as_str())
+            })
+            .cloned()
+            .collect();
+        // Remaining nodes which strongly depend on a removed node
+        let mut broken = Vec::new();
+
+        for (key, edges) in &self.edge_map {
+            if removed.contains(key.as_str()) {
+                continue;
+            }
+            let mut kept: Vec<DependencyNode<&'a T, M>> = Vec::new();
+            let mut skipped = HashSet::new();
+            // The kept edges which were only passed on through weak edges
+            let mut passed_on = HashSet::new();
+            let mut stack: Vec<_> =
+                edges.iter().rev().map(|edge| (edge, false)).collect();
+            while let Some((edge, is_passed_on)) = stack.pop() {
+                if !removed.contains(edge.key.as_str()) {
+                    if is_passed_on && !kept.contains(edge) {
+                        passed_on.insert(edge.key.as_str());
+                    } else if !is_passed_on {
+                        passed_on.remove(edge.key.as_str());
+                    }
+                    if !kept.contains(edge) {
+                        kept.push(edge.clone());
+                    }
+                } else if !is_passed_on
+                    && !self.is_weak_edge(nodes.get(key.as_str()), edge)
+                {
+                    broken.push((key.as_str(), edge.key.as_str()));
+                } else if skipped.insert(edge.key.as_str()) {
+                    let promoted = self.edge_map.get(&edge.key);
+                    stack.extend(
+                        promoted.into_iter().flatten().rev().map(|e| (e, true)),
+                    );
+                }
+            }
+            for to in passed_on {
+                weak_edges.insert((key.clone(), to.to_string()));
+            }
+            edge_map.insert(key.clone(), kept);
+        }
+
+        let node_list = self
+            .node_list
+            .iter()
+            .filter(|node| !removed.contains(node.key.as_str()))
+            .cloned()
+            .collect();
+        let graph = DependencyGraph {
+            edge_map,
+            node_list,
+            weak_edges,
+        };
+
+        let reachable = graph.reachable_nodes();
+        for (dependent, dependency) in broken {
+            if reachable.contains_key(dependent) {
+                bail!(
+                    "Cannot remove '{}' since '{}' depends on it",
+                    dependency,
+                    dependent
+                );
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Whether the edge between two nodes only affects their ordering.
+    ///
+    /// This is the case if every edge declared between them (by either node)
+    /// is weak, or if the edge was passed on through a weak edge. Edges
+    /// declared by some other node (eg. the ordering of
+    /// `ordered_dependencies`) are considered strong.
+    fn is_weak_edge(
+        &self,
+        from: Option<&&DependencyNode<&T, M>>,
+        to: &DependencyNode<&T, M>,
+    ) -> bool {
+        let from = match from {
+            Some(from) => from,
+            None => return false,
+        };
+        if self.weak_edges.contains(&(from.key.clone(), to.key.clone())) {
+            return true;
         }
+        let declared: Vec<_> = from
+            .value
+            .dependencies()
+            .into_iter()
+            .chain(to.value.dependencies())
+            .filter(|edge| {
+                (edge.edge_src == from.key && edge.edge_dst == to.key)
+                    || (edge.edge_src == to.key && edge.edge_dst == from.key)
+            })
+            .collect();
+        !declared.is_empty() && declared.iter().all(|edge| edge.is_weak)
     }
 
     /// Upgrade the current marker on the dependency node.
@@ -234,6 +411,26 @@ where
         }
     }
 
+    /// Record the depth of a node that is about to be added to the sorted
+    /// output.
+    ///
+    /// Since nodes are sorted in DFS post-order, the depths of all of its
+    /// (non-weak) dependencies have already been recorded at this point.
+    fn record_depth<'n>(
+        node: &'n DependencyNode<&T, M>,
+        edges: &[DependencyNode<&T, M>],
+        depths: &mut HashMap<&'n str, usize>,
+    ) {
+        let depth = edges
+            .iter()
+            .filter_map(|edge| depths.get(edge.key.as_str()))
+            .map(|depth| depth + 1)
+            .max()
+            .unwrap_or(0);
+        node.depth.set(depth);
+        depths.insert(node.key.as_str(), depth);
+    }
+
     /// Split nodes into groups by their level.
     fn split_by_level<'l, 's, R: Eq + Hash>(
         level_info: HashMap<&'l R, NodeMeta>,
@@ -265,6 +462,7 @@ where
         let mut sorted = Vec::new();
         let mut stack: Vec<(bool, &DependencyNode<&T, M>)> = Vec::new();
         let mut marked: HashMap<_, MarkType> = HashMap::new();
+        let mut depths: HashMap<&str, usize> = HashMap::new();
         let mut unmarked: Vec<_> = self.node_list.iter().collect();
 
         // While we have still nodes unmarked
@@ -280,7 +478,10 @@ where
                     continue;
                 }
 
+                let edges = self.edge_map.get(&node.key).unwrap();
+
                 if is_parent {
+                    Self::record_depth(node, edges, &mut depths);
                     sorted.push(node);
                     marked.entry(node).and_modify(|e| *e = MarkType::Permanent);
                     continue;
@@ -298,7 +499,7 @@ where
                 marked.insert(node, MarkType::Temporary);
                 stack.push((true, node));
 
-                for edge in self.edge_map.get(&node.key).unwrap() {
+                for edge in edges {
                     stack.push((false, edge));
                 }
             }
@@ -316,6 +517,7 @@ where
         let mut sorted = Vec::new();
         let mut stack: Vec<(bool, &DependencyNode<&T, M>, u8)> = Vec::new();
         let mut marked: HashMap<_, NodeMeta> = HashMap::new();
+        let mut depths: HashMap<&str, usize> = HashMap::new();
         let mut unmarked: Vec<_> = self.node_list.iter().collect();
 
         // While we have still nodes unmarked
@@ -334,6 +536,7 @@ where
                 let edges = self.edge_map.get(&node.key).unwrap();
 
                 if is_parent {
+                    Self::record_depth(node, edges, &mut depths);
                     sorted.push(node);
                     marked.entry(node).and_modify(|e| {
                         e.mark = MarkType::Permanent;
@@ -375,6 +578,248 @@ where
     }
 }
 
+impl<'a, T, M> DependencyGraph<'a, T, M>
+where
+    T: WithDependencies<M> + Eq + Hash,
+    M: PartialOrd + Default,
+{
+    /// Returns the keys of the nodes that the given node depends on, sorted by
+    /// key.
+    ///
+    /// Weak nodes are not included, since they are not part of the final
+    /// graph.
+    pub fn dependencies_of(&self, key: &str) -> Vec<&str> {
+        let mut keys: Vec<_> = self
+            .edge_map
+            .get(key)
+            .into_iter()
+            .flatten()
+            .filter(|node| !node.is_weak)
+            .map(|node| node.key.as_str())
+            .collect();
+        keys.sort_unstable();
+        keys.dedup();
+        keys
+    }
+
+    /// Returns the keys of the nodes which depend on the given node, sorted by
+    /// key.
+    ///
+    /// With `transitive`, the nodes which depend on it through other nodes
+    /// are included as well.
+    pub fn dependents_of(&self, key: &str, transitive: bool) -> Vec<&str> {
+        let nodes = self.reachable_nodes();
+        let mut dependents = BTreeSet::new();
+        let mut stack = vec![key];
+
+        while let Some(dependency) = stack.pop() {
+            for node_key in nodes.keys() {
+                let depends_on = self.dependencies_of(node_key);
+                if *node_key != key
+                    && depends_on.contains(&dependency)
+                    && dependents.insert(*node_key)
+                    && transitive
+                {
+                    stack.push(*node_key);
+                }
+            }
+        }
+        dependents.into_iter().collect()
+    }
+
+    /// Returns a hash of the structure of the graph.
+    ///
+    /// Combines the [WithDependencies::dependency_hash] of every node in the
+    /// graph (in order of their keys), so the hash changes whenever a node is
+    /// added or removed, or its dependencies change.
+    pub fn dependency_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for (key, node) in self.reachable_nodes() {
+            key.hash(&mut hasher);
+            node.value.dependency_hash().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Returns the number of layers of the graph, ie. the number of distinct
+    /// depths of its nodes.
+    ///
+    /// Nodes within the same layer do not depend on each other, so this is the
+    /// least number of steps needed to deploy the graph.
+    pub fn layer_count(&self) -> usize {
+        self.layers().values().max().map_or(0, |depth| depth + 1)
+    }
+
+    /// Returns the keys of the nodes at depth `n`, sorted by key.
+    ///
+    /// See [DependencyNode::depth] for how the depth is determined, unlike it
+    /// however the graph does not need to be sorted first.
+    pub fn nodes_at_layer(&self, n: usize) -> Vec<String> {
+        self.layers()
+            .into_iter()
+            .filter(|(_, depth)| *depth == n)
+            .map(|(key, _)| key.to_string())
+            .collect()
+    }
+
+    /// Returns the depth of every (non-weak) node of the graph.
+    fn layers(&self) -> BTreeMap<&str, usize> {
+        let mut depths = BTreeMap::new();
+        for key in self.reachable_nodes().keys() {
+            self.layer_of(key, &mut depths, &mut HashSet::new());
+        }
+        depths
+    }
+
+    fn layer_of<'s>(
+        &'s self,
+        key: &'s str,
+        depths: &mut BTreeMap<&'s str, usize>,
+        visiting: &mut HashSet<&'s str>,
+    ) -> usize {
+        if let Some(depth) = depths.get(key) {
+            return *depth;
+        }
+        // Cycles are reported when the graph is sorted
+        if !visiting.insert(key) {
+            return 0;
+        }
+        let depth = self
+            .dependencies_of(key)
+            .into_iter()
+            .map(|dependency| self.layer_of(dependency, depths, visiting) + 1)
+            .max()
+            .unwrap_or(0);
+        visiting.remove(key);
+        depths.insert(key, depth);
+        depth
+    }
+
+    /// Returns all (non-weak) nodes of the graph, sorted by their key.
+    fn reachable_nodes(&self) -> BTreeMap<&str, &DependencyNode<&T, M>> {
+        let mut nodes = BTreeMap::new();
+        let mut stack: Vec<_> =
+            self.node_list.iter().filter(|n| !n.is_weak).collect();
+
+        while let Some(node) = stack.pop() {
+            if nodes.contains_key(node.key.as_str()) {
+                continue;
+            }
+            nodes.insert(node.key.as_str(), node);
+            if let Some(edges) = self.edge_map.get(&node.key) {
+                stack.extend(edges.iter().filter(|n| !n.is_weak));
+            }
+        }
+        nodes
+    }
+}
+
+impl<'a, T, M> DependencyGraph<'a, T, M>
+where
+    T: WithDependencies<M> + WithKind + Eq + Hash,
+    M: PartialOrd + Copy + Default,
+{
+    /// Returns a copy of the graph with only the nodes of the given kind (as
+    /// named by [WithKind::kind_name]).
+    ///
+    /// Dependencies through nodes of other kinds are kept, so a node which
+    /// depends on another of the same kind through a node of some other kind
+    /// depends on it directly in the filtered graph.
+    pub fn filter_kind<K: fmt::Display>(
+        &self,
+        kind: &K,
+    ) -> DependencyGraph<'a, T, M> {
+        let kind = kind.to_string();
+        let nodes: HashMap<&str, &DependencyNode<&T, M>> = self
+            .node_list
+            .iter()
+            .chain(self.edge_map.values().flatten())
+            .map(|node| (node.key.as_str(), node))
+            .collect();
+
+        let edge_map = self
+            .edge_map
+            .iter()
+            .filter(|(key, _)| {
+                nodes
+                    .get(key.as_str())
+                    .map_or(false, |node| node.value.kind_name() == kind)
+            })
+            .map(|(key, edges)| (key.clone(), self.of_kind(edges, &kind)))
+            .collect();
+        let node_list = self.of_kind(&self.node_list, &kind);
+
+        DependencyGraph {
+            edge_map,
+            node_list,
+            weak_edges: self.weak_edges.clone(),
+        }
+    }
+
+    /// Returns the nodes of the given kind among `nodes`, replacing the nodes
+    /// of any other kind with the nodes of the given kind they depend on.
+    fn of_kind(
+        &self,
+        nodes: &[DependencyNode<&'a T, M>],
+        kind: &str,
+    ) -> Vec<DependencyNode<&'a T, M>> {
+        let mut found: Vec<DependencyNode<&'a T, M>> = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack: Vec<_> = nodes.iter().rev().collect();
+        while let Some(node) = stack.pop() {
+            if node.value.kind_name() == kind {
+                if !found.contains(node) {
+                    found.push(node.clone());
+                }
+            } else if visited.insert(node.key.as_str()) {
+                let dependencies = self.edge_map.get(&node.key);
+                stack.extend(dependencies.into_iter().flatten().rev());
+            }
+        }
+        found
+    }
+}
+
+impl<'a, T, M> DependencyGraph<'a, T, M>
+where
+    T: WithDependencies<M> + WithKind + Eq + Hash,
+    M: PartialOrd + Default,
+{
+    /// Serializes the graph into JSON, for use by external tooling.
+    ///
+    /// The format is part of the public interface and is as follows:
+    ///
+    /// ```json
+    /// {
+    ///   "nodes": [{ "name": "db", "kind": "Service" }],
+    ///   "edges": [{ "from": "api", "to": "db" }]
+    /// }
+    /// ```
+    ///
+    /// Each edge points from a node to one of its dependencies. Nodes are
+    /// sorted by name and edges by their source and then their target, so
+    /// that the output is stable.
+    pub fn to_json(&self) -> Value {
+        let nodes = self.reachable_nodes();
+        let edges: Vec<_> = nodes
+            .keys()
+            .flat_map(|from| {
+                self.dependencies_of(from)
+                    .into_iter()
+                    .map(move |to| json!({ "from": from, "to": to }))
+            })
+            .collect();
+        let nodes: Vec<_> = nodes
+            .iter()
+            .map(|(name, node)| {
+                json!({ "name": name, "kind": node.value.kind_name() })
+            })
+            .collect();
+
+        json!({ "nodes": nodes, "edges": edges })
+    }
+}
+
 pub struct SortedDeps<'a, R> {
     pub groups: Vec<Vec<&'a R>>,
     pub flat: Vec<&'a R>,
@@ -420,6 +865,11 @@ where
         self.node_list.push(idx);
     }
 
+    /// Whether a node with the given key exists in the source array.
+    pub fn has_original_node(&self, key: &str) -> bool {
+        self.source_array_index.contains_key(key)
+    }
+
     /// Gets the original node out of the souce array.
     pub fn get_original_node(&self, key: &str, source: &'a [S]) -> &'a S {
         &source[self.source_array_index[key]]
@@ -554,6 +1004,7 @@ where
             marker,
             origin_nodes,
             is_weak,
+            depth: Cell::new(0),
         }
     }
 }
@@ -586,6 +1037,8 @@ mod test {
         ModuleDefinition {
             name: name.to_string(),
             kind: ModuleKind::Service,
+            labels: HashMap::new(),
+            documentation: None,
             inner: InnerDefinition::Service(ServiceOrTaskDefinition::new(
                 name.to_string(),
                 vec!["dummy".to_string()],
@@ -605,7 +1058,24 @@ mod test {
                 None,
                 None,
                 None,
+                None,
+                None,
+                false,
                 false,
+                0,
+                0,
+                None,
+                None,
+                None,
+                None,
+                10,
+                true,
+                None,
+                vec![],
+                None,
+                vec![],
+                None,
+                None,
             )),
         }
     }
@@ -737,4 +1207,239 @@ mod test {
         assert!(is_before("m10", "m8", &result));
         assert!(is_before("m11", "m10", &result));
     }
+
+    #[test]
+    fn test_weak_dependency_on_undefined_module() {
+        let m1 = make_module("m1", vec!["m2"], vec![], vec![], vec!["m3"]);
+        let m2 = make_module("m2", vec![], vec![], vec![], vec!["missing"]);
+        let m3 = make_module("m3", vec![], vec![], vec![], vec![]);
+        let modules = vec![m1, m2, m3];
+        let selected = vec!["m1"];
+
+        let graph = DependencyGraph::from(&modules, &selected);
+        let result: Vec<&str> = graph
+            .dependency_sort()
+            .unwrap()
+            .iter()
+            .map(|v| &v.value.name[..])
+            .collect();
+
+        assert!(eq_lists(&result, &vec!["m1", "m2"]));
+        assert!(is_before("m2", "m1", &result));
+    }
+
+    #[test]
+    fn test_dependency_depth() {
+        let m1 = make_module("m1", vec!["m2", "m4"], vec![], vec![], vec![]);
+        let m2 = make_module("m2", vec!["m3"], vec![], vec![], vec![]);
+        let m3 = make_module("m3", vec![], vec![], vec![], vec![]);
+        let m4 = make_module("m4", vec![], vec![], vec![], vec![]);
+        let modules = vec![m1, m2, m3, m4];
+        let selected = vec!["m1"];
+
+        let graph = DependencyGraph::from(&modules, &selected);
+        let depths: HashMap<&str, usize> = graph
+            .dependency_sort()
+            .unwrap()
+            .iter()
+            .map(|v| (&v.value.name[..], v.depth()))
+            .collect();
+
+        assert_eq!(depths["m1"], 2);
+        assert_eq!(depths["m2"], 1);
+        assert_eq!(depths["m3"], 0);
+        assert_eq!(depths["m4"], 0);
+
+        let graph = DependencyGraph::from(&modules, &selected);
+        let sorted = graph.group_sort().unwrap();
+        let depths: HashMap<&str, usize> = sorted
+            .flat
+            .iter()
+            .map(|v| (&v.value.name[..], v.depth()))
+            .collect();
+
+        assert_eq!(depths["m1"], 2);
+        assert_eq!(depths["m2"], 1);
+        assert_eq!(depths["m3"], 0);
+        assert_eq!(depths["m4"], 0);
+    }
+
+    #[test]
+    fn test_dependency_layers() {
+        let m1 = make_module("m1", vec!["m2", "m4"], vec![], vec![], vec![]);
+        let m2 = make_module("m2", vec!["m3"], vec![], vec![], vec![]);
+        let m3 = make_module("m3", vec![], vec![], vec![], vec![]);
+        let m4 = make_module("m4", vec![], vec![], vec![], vec![]);
+        let m5 = make_module("m5", vec![], vec![], vec![], vec![]);
+        let modules = vec![m1, m2, m3, m4, m5];
+        let selected = vec!["m1"];
+
+        let graph = DependencyGraph::from(&modules, &selected);
+
+        assert_eq!(graph.layer_count(), 3);
+        assert_eq!(graph.nodes_at_layer(0), vec!["m3", "m4"]);
+        assert_eq!(graph.nodes_at_layer(1), vec!["m2"]);
+        assert_eq!(graph.nodes_at_layer(2), vec!["m1"]);
+        assert!(graph.nodes_at_layer(3).is_empty());
+    }
+
+    #[test]
+    fn test_dependency_graph_without() {
+        let m1 = make_module("m1", vec!["m2"], vec![], vec![], vec!["m3"]);
+        let m2 = make_module("m2", vec![], vec![], vec![], vec![]);
+        let m3 = make_module("m3", vec!["m4"], vec![], vec![], vec![]);
+        let m4 = make_module("m4", vec![], vec![], vec![], vec![]);
+        let m5 = make_module("m5", vec!["m3"], vec![], vec![], vec![]);
+        let modules = vec![m1, m2, m3, m4, m5];
+        let selected = vec!["m1", "m5"];
+        let graph = DependencyGraph::from(&modules, &selected);
+
+        // m1 and m5 strongly depend on m2 and m3 respectively
+        assert!(graph.without(&["m2"]).is_err());
+        assert!(graph.without(&["m3"]).is_err());
+
+        // m1 is only ordered after m3, so it is ordered after m4 instead
+        let pruned = graph.without(&["m5", "m3"]).unwrap();
+        let result: Vec<&str> = pruned
+            .dependency_sort()
+            .unwrap()
+            .iter()
+            .map(|v| &v.value.name[..])
+            .collect();
+        assert!(eq_lists(&result, &["m1", "m2", "m4"]));
+        assert_eq!(pruned.dependencies_of("m1"), vec!["m2", "m4"]);
+    }
+
+    #[test]
+    fn test_dependency_graph_without_weak_edge() {
+        let m1 = make_module("m1", vec!["m2"], vec![], vec![], vec!["m3"]);
+        let m2 = make_module("m2", vec![], vec![], vec![], vec![]);
+        let m3 = make_module("m3", vec!["m4"], vec![], vec![], vec![]);
+        let m4 = make_module("m4", vec![], vec![], vec![], vec![]);
+        let m5 = make_module("m5", vec!["m3"], vec![], vec![], vec![]);
+        let modules = vec![m1, m2, m3, m4, m5];
+        let selected = vec!["m1", "m5"];
+        let graph = DependencyGraph::from(&modules, &selected);
+
+        // The edge from m1 to m4 which is passed on through the weak edge
+        // to m3 is weak as well, so m4 can be removed afterwards
+        let pruned = graph.without(&["m5", "m3"]).unwrap();
+        let pruned = pruned.without(&["m4"]).unwrap();
+        assert_eq!(pruned.dependencies_of("m1"), vec!["m2"]);
+        assert!(pruned.without(&["m2"]).is_err());
+
+        // Or along with m3
+        let pruned = graph.without(&["m5", "m3", "m4"]).unwrap();
+        let result: Vec<&str> = pruned
+            .dependency_sort()
+            .unwrap()
+            .iter()
+            .map(|v| &v.value.name[..])
+            .collect();
+        assert!(eq_lists(&result, &["m1", "m2"]));
+    }
+
+    #[test]
+    fn test_dependents_of() {
+        let m1 = make_module("m1", vec!["m2", "m3"], vec![], vec![], vec![]);
+        let m2 = make_module("m2", vec!["m3"], vec![], vec![], vec![]);
+        let m3 = make_module("m3", vec!["m4"], vec![], vec![], vec![]);
+        let m4 = make_module("m4", vec![], vec![], vec![], vec![]);
+        let m5 = make_module("m5", vec!["m4"], vec![], vec![], vec![]);
+        let modules = vec![m1, m2, m3, m4, m5];
+        let selected = vec!["m1", "m2", "m3", "m4", "m5"];
+        let graph = DependencyGraph::from(&modules, &selected);
+
+        assert_eq!(graph.dependents_of("m4", false), vec!["m3", "m5"]);
+        assert_eq!(
+            graph.dependents_of("m4", true),
+            vec!["m1", "m2", "m3", "m5"]
+        );
+        assert!(graph.dependents_of("m1", true).is_empty());
+    }
+
+    #[test]
+    fn test_dependency_graph_to_json() {
+        let m1 = make_module("m1", vec!["m2", "m3"], vec![], vec![], vec![]);
+        let m2 = make_module("m2", vec!["m3"], vec![], vec![], vec![]);
+        let m3 = make_module("m3", vec![], vec![], vec![], vec!["m4"]);
+        let m4 = make_module("m4", vec![], vec![], vec![], vec![]);
+        let m5 = make_module("m5", vec![], vec![], vec![], vec![]);
+
+        let mods = vec![m1, m2, m3, m4, m5];
+        let selected = vec!["m1"];
+        let graph = DependencyGraph::from(&mods, &selected);
+
+        let expected = json!({
+            "nodes": [
+                { "name": "m1", "kind": "Service" },
+                { "name": "m2", "kind": "Service" },
+                { "name": "m3", "kind": "Service" },
+            ],
+            "edges": [
+                { "from": "m1", "to": "m2" },
+                { "from": "m1", "to": "m3" },
+                { "from": "m2", "to": "m3" },
+            ],
+        });
+        assert_eq!(graph.to_json(), expected);
+    }
+
+    #[test]
+    fn test_dependency_graph_filter_kind() {
+        let m1 = make_module("m1", vec!["t1", "m3"], vec![], vec![], vec![]);
+        let mut t1 = make_module("t1", vec!["m2"], vec![], vec![], vec![]);
+        t1.kind = ModuleKind::Task;
+        let m2 = make_module("m2", vec![], vec![], vec![], vec![]);
+        let m3 = make_module("m3", vec![], vec![], vec![], vec![]);
+
+        let mods = vec![m1, t1, m2, m3];
+        let selected = vec!["m1"];
+        let graph = DependencyGraph::from(&mods, &selected);
+
+        let services = graph.filter_kind(&ModuleKind::Service);
+        let expected = json!({
+            "nodes": [
+                { "name": "m1", "kind": "Service" },
+                { "name": "m2", "kind": "Service" },
+                { "name": "m3", "kind": "Service" },
+            ],
+            "edges": [
+                { "from": "m1", "to": "m2" },
+                { "from": "m1", "to": "m3" },
+            ],
+        });
+        assert_eq!(services.to_json(), expected);
+
+        let tasks = graph.filter_kind(&ModuleKind::Task);
+        let expected = json!({
+            "nodes": [{ "name": "t1", "kind": "Task" }],
+            "edges": [],
+        });
+        assert_eq!(tasks.to_json(), expected);
+    }
+
+    #[test]
+    fn test_dependency_hash() {
+        let m1 = make_module("m1", vec!["m2"], vec![], vec![], vec![]);
+        let m2 = make_module("m2", vec![], vec![], vec![], vec![]);
+        let m1_changed = make_module("m1", vec![], vec![], vec![], vec![]);
+
+        assert_eq!(m1.dependency_hash(), m1.dependency_hash());
+        assert_ne!(m1.dependency_hash(), m1_changed.dependency_hash());
+        assert_ne!(m1.dependency_hash(), m2.dependency_hash());
+
+        let m3 = make_module("m3", vec!["m1", "m2"], vec![], vec![], vec![]);
+        let m3_reordered =
+            make_module("m3", vec!["m2", "m1"], vec![], vec![], vec![]);
+        assert_eq!(m3.dependency_hash(), m3_reordered.dependency_hash());
+
+        let selected = vec!["m1"];
+        let mods = vec![m1, m2];
+        let graph = DependencyGraph::from(&mods, &selected);
+        let mods_changed = vec![m1_changed];
+        let graph_changed = DependencyGraph::from(&mods_changed, &selected);
+
+        assert_ne!(graph.dependency_hash(), graph_changed.dependency_hash());
+    }
 }
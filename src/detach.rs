@@ -1,11 +1,16 @@
+use anyhow::{Context, Result};
 use nix::libc::{
-    c_int, exit, fork, getpgrp, getpid, setsid, wait, WEXITSTATUS, WIFEXITED,
+    c_int, exit, fork, getpgrp, getpid, setsid, wait, STDERR_FILENO,
+    STDIN_FILENO, STDOUT_FILENO, WEXITSTATUS, WIFEXITED,
 };
-use nix::unistd::execvp;
+use nix::unistd::{self, execvp, ForkResult};
 use std::env::Args;
 use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
 use std::panic;
+use std::path::Path;
 
 /// Detaches the daemon from its controlling terminal and also daemonizes it by
 /// forking and exiting the parent leaving a child to be inherited by pid 1.
@@ -66,3 +71,39 @@ pub fn detach_tty(args: Args, wait_child: bool) {
 
     execvp(&current_exe_path, &argv).unwrap();
 }
+
+/// Daemonizes the current process so that it keeps running in the background.
+///
+/// Forks and exits the parent, then starts a new session for the child to
+/// detach it from the controlling terminal. The pid of the child is written
+/// to `pidfile` and its standard streams are redirected, stdin to /dev/null
+/// and stdout/stderr to `log_file`.
+///
+/// Must be called before any threads are spawned.
+pub fn daemonize(pidfile: &Path, log_file: &Path) -> Result<()> {
+    // Safe as long as the process is still single threaded
+    match unsafe { unistd::fork() }.context("Failed to fork daemon")? {
+        ForkResult::Parent { .. } => std::process::exit(0),
+        ForkResult::Child => {}
+    }
+
+    unistd::setsid().context("Failed to start a new session")?;
+
+    fs::write(pidfile, unistd::getpid().to_string()).with_context(|| {
+        format!("Failed to write pidfile {}", pidfile.display())
+    })?;
+
+    let dev_null = File::open("/dev/null")?;
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .with_context(|| {
+            format!("Failed to open daemon log {}", log_file.display())
+        })?;
+
+    unistd::dup2(dev_null.as_raw_fd(), STDIN_FILENO)?;
+    unistd::dup2(log.as_raw_fd(), STDOUT_FILENO)?;
+    unistd::dup2(log.as_raw_fd(), STDERR_FILENO)?;
+    Ok(())
+}
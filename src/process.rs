@@ -1,5 +1,10 @@
 use std::io::Result;
 use std::process::{Child, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often [Process::wait_timeout] checks whether the process has exited.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 #[derive(Debug)]
 pub struct GroupChild {
@@ -66,6 +71,39 @@ impl Process {
         }
     }
 
+    /// Pause the process.
+    ///
+    /// On Unix this sends `SIGSTOP` to the process (if ungroupped) or process
+    /// group (if groupped). This is not supported on Windows.
+    pub fn pause(&mut self) -> Result<()> {
+        match self {
+            Self::Groupped(grp) => grp.pause(),
+            Self::Ungroupped(ungrp) => ungrp.pause(),
+        }
+    }
+
+    /// Resume a paused process.
+    ///
+    /// On Unix this sends `SIGCONT` to the process (if ungroupped) or process
+    /// group (if groupped). This is not supported on Windows.
+    pub fn resume(&mut self) -> Result<()> {
+        match self {
+            Self::Groupped(grp) => grp.resume(),
+            Self::Ungroupped(ungrp) => ungrp.resume(),
+        }
+    }
+
+    /// Signal the process to reload its configuration.
+    ///
+    /// On Unix this sends `SIGHUP` to the process (if ungroupped) or process
+    /// group (if groupped). This is not supported on Windows.
+    pub fn hangup(&mut self) -> Result<()> {
+        match self {
+            Self::Groupped(grp) => grp.hangup(),
+            Self::Ungroupped(ungrp) => ungrp.hangup(),
+        }
+    }
+
     /// Return the process id.
     ///
     /// On Unix this will be the `pid` of the process (if ungroupped) or the
@@ -87,6 +125,24 @@ impl Process {
         }
     }
 
+    /// Waits for the process (or group) to exit for at most `timeout`,
+    /// returning `None` if it is still running by then.
+    pub fn wait_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<ExitStatus>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.try_wait()? {
+                return Ok(Some(status));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(WAIT_POLL_INTERVAL);
+        }
+    }
+
     /// Attempts to collect the exit status of the process (or group) if it has
     /// already exited.
     ///
@@ -137,6 +193,24 @@ pub trait ChildExt {
     /// On Unix this sends `SIGKILL` to the pgid of this process. On Windows this
     /// will perform a [`std::process::Child#kill`].
     fn kill(&mut self) -> Result<()>;
+
+    /// Pause the child process.
+    ///
+    /// On Unix this sends `SIGSTOP` to the pid of this process. This is not
+    /// supported on Windows.
+    fn pause(&mut self) -> Result<()>;
+
+    /// Resume the paused child process.
+    ///
+    /// On Unix this sends `SIGCONT` to the pid of this process. This is not
+    /// supported on Windows.
+    fn resume(&mut self) -> Result<()>;
+
+    /// Signal the child process to reload its configuration.
+    ///
+    /// On Unix this sends `SIGHUP` to the pid of this process. This is not
+    /// supported on Windows.
+    fn hangup(&mut self) -> Result<()>;
 }
 
 impl GroupChild {
@@ -164,6 +238,30 @@ impl GroupChild {
         self.imp.kill()
     }
 
+    /// Pause the child process group.
+    ///
+    /// On Unix this sends `SIGSTOP` to the pgid of this process. This is not
+    /// supported on Windows.
+    pub fn pause(&mut self) -> Result<()> {
+        self.imp.pause()
+    }
+
+    /// Resume the paused child process group.
+    ///
+    /// On Unix this sends `SIGCONT` to the pgid of this process. This is not
+    /// supported on Windows.
+    pub fn resume(&mut self) -> Result<()> {
+        self.imp.resume()
+    }
+
+    /// Signal the child process group to reload its configuration.
+    ///
+    /// On Unix this sends `SIGHUP` to the pgid of this process. This is not
+    /// supported on Windows.
+    pub fn hangup(&mut self) -> Result<()> {
+        self.imp.hangup()
+    }
+
     /// Return group process identifier.
     ///
     /// On Unix this will be the `pgid` of the process group. On Windows the
@@ -223,6 +321,21 @@ mod imp {
             signal_process_group(self.pgid, Signal::SIGKILL)
         }
 
+        /// Sends SIGSTOP to the pgid of this process.
+        pub(crate) fn pause(&mut self) -> Result<()> {
+            signal_process_group(self.pgid, Signal::SIGSTOP)
+        }
+
+        /// Sends SIGCONT to the pgid of this process.
+        pub(crate) fn resume(&mut self) -> Result<()> {
+            signal_process_group(self.pgid, Signal::SIGCONT)
+        }
+
+        /// Sends SIGHUP to the pgid of this process.
+        pub(crate) fn hangup(&mut self) -> Result<()> {
+            signal_process_group(self.pgid, Signal::SIGHUP)
+        }
+
         /// Return the pid of the child process.
         #[inline]
         pub fn id(&self) -> u32 {
@@ -324,6 +437,21 @@ mod imp {
         fn kill(&mut self) -> Result<()> {
             signal_process(self.id(), Signal::SIGKILL)
         }
+
+        /// Sends SIGSTOP to the pid of this process.
+        fn pause(&mut self) -> Result<()> {
+            signal_process(self.id(), Signal::SIGSTOP)
+        }
+
+        /// Sends SIGCONT to the pid of this process.
+        fn resume(&mut self) -> Result<()> {
+            signal_process(self.id(), Signal::SIGCONT)
+        }
+
+        /// Sends SIGHUP to the pid of this process.
+        fn hangup(&mut self) -> Result<()> {
+            signal_process(self.id(), Signal::SIGHUP)
+        }
     }
 
     impl CommandExt for Command {
@@ -351,7 +479,7 @@ mod imp {
 #[cfg(target_family = "windows")]
 mod imp {
     use super::{CommandExt, GroupChild};
-    use std::io::Result;
+    use std::io::{Error, ErrorKind, Result};
     use std::process::{Child, Command, ExitStatus};
 
     #[derive(Debug)]
@@ -376,6 +504,18 @@ mod imp {
             self.inner.kill()
         }
 
+        pub(crate) fn pause(&mut self) -> Result<()> {
+            Err(unsupported())
+        }
+
+        pub(crate) fn resume(&mut self) -> Result<()> {
+            Err(unsupported())
+        }
+
+        pub(crate) fn hangup(&mut self) -> Result<()> {
+            Err(unsupported())
+        }
+
         #[inline]
         pub fn id(&self) -> u32 {
             self.inner.id()
@@ -406,6 +546,25 @@ mod imp {
         fn kill(&mut self) -> Result<()> {
             self.kill()
         }
+
+        fn pause(&mut self) -> Result<()> {
+            Err(unsupported())
+        }
+
+        fn resume(&mut self) -> Result<()> {
+            Err(unsupported())
+        }
+
+        fn hangup(&mut self) -> Result<()> {
+            Err(unsupported())
+        }
+    }
+
+    fn unsupported() -> Error {
+        Error::new(
+            ErrorKind::Other,
+            "Signalling processes is not supported on Windows",
+        )
     }
 
     impl CommandExt for Command {
@@ -1,12 +1,21 @@
+use std::io;
+use std::os::raw::c_int;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Weak};
 use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often [Control::sleep] checks whether the flag was stopped or
+/// interrupted.
+const SLEEP_POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 /// Struct to check execution status of spawned thread.
 #[derive(Debug)]
 pub struct Flag {
     alive: Arc<AtomicBool>,
     interrupt: Arc<AtomicBool>,
+    /// Identifies the flag (and its controls) when debugging.
+    name: Option<String>,
 }
 
 impl Drop for Flag {
@@ -24,14 +33,24 @@ impl Flag {
         Flag {
             alive: Arc::new(AtomicBool::new(true)),
             interrupt: Arc::new(AtomicBool::new(false)),
+            name: None,
         }
     }
 
+    /// Creates new flag with a name, which is included in its debug output
+    /// (and that of its controls) and in the panic message of [Flag::alive].
+    pub fn with_name(name: &str) -> Self {
+        let mut flag = Flag::new();
+        flag.name = Some(name.to_string());
+        flag
+    }
+
     /// Creates new `Control` to control this flag.
     pub fn take_control(&self) -> Control {
         Control {
             alive: Arc::downgrade(&self.alive),
             interrupt: self.interrupt.clone(),
+            name: self.name.clone(),
         }
     }
 
@@ -42,7 +61,12 @@ impl Flag {
     /// This method panics, if interrupt flag was set.
     pub fn alive(&self) -> bool {
         if (*self.interrupt).load(Ordering::Relaxed) {
-            panic!("thread interrupted by thread-contol");
+            match &self.name {
+                Some(name) => {
+                    panic!("thread '{}' interrupted by thread-contol", name)
+                }
+                None => panic!("thread interrupted by thread-contol"),
+            }
         }
         (*self.alive).load(Ordering::Relaxed)
     }
@@ -54,6 +78,17 @@ impl Flag {
             && !(*self.interrupt).load(Ordering::Relaxed)
     }
 
+    /// Clears the stop and interrupt flags, so that the flag can be passed to
+    /// a new thread. Existing controls keep controlling the flag.
+    ///
+    /// The caller must ensure that the thread which was checking the flag has
+    /// actually exited before resetting it, otherwise that thread would keep
+    /// running along with the new one.
+    pub fn reset(&mut self) {
+        (*self.alive).store(true, Ordering::Relaxed);
+        (*self.interrupt).store(false, Ordering::Relaxed);
+    }
+
     /// Set interrupt flag and drop the instance
     pub fn interrupt(self) {
         (self.interrupt).store(true, Ordering::Relaxed)
@@ -65,6 +100,8 @@ impl Flag {
 pub struct Control {
     alive: Weak<AtomicBool>,
     interrupt: Arc<AtomicBool>,
+    /// The name of the flag this controls, if it has one.
+    name: Option<String>,
 }
 
 impl Control {
@@ -90,6 +127,36 @@ impl Control {
     pub fn is_interrupted(&self) -> bool {
         (*self.interrupt).load(Ordering::Relaxed)
     }
+
+    /// Sleeps for the given duration, waking up early if the flag is stopped
+    /// or interrupted in the meantime.
+    ///
+    /// Returns `true` if the whole duration was slept.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_done() || self.is_interrupted() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep((deadline - now).min(SLEEP_POLL_INTERVAL));
+        }
+    }
+
+    /// Interrupts execution of thread the first time the process receives
+    /// `signal`, instead of the default action of the signal.
+    ///
+    /// The default action is restored once the signal is received, so that
+    /// receiving it again (eg. pressing Ctrl-C twice) still terminates the
+    /// process.
+    pub fn interrupt_on(&self, signal: c_int) -> io::Result<()> {
+        signal_hook::flag::register(signal, Arc::clone(&self.interrupt))?;
+        signal_hook::cleanup::register(signal, vec![signal])?;
+        Ok(())
+    }
 }
 
 /// Makes pair with connected flag and control.
@@ -98,3 +165,29 @@ pub fn make_pair() -> (Flag, Control) {
     let control = flag.take_control();
     (flag, control)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sleep_completes() {
+        let (_flag, control) = make_pair();
+        assert!(control.sleep(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_sleep_wakes_up_when_interrupted() {
+        let (_flag, control) = make_pair();
+        let interrupter = control.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            interrupter.interrupt();
+        });
+
+        let started = Instant::now();
+        assert!(!control.sleep(Duration::from_secs(10)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        handle.join().unwrap();
+    }
+}
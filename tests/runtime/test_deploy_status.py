@@ -0,0 +1,64 @@
+import re
+
+
+def module_status(cartel, name):
+    out = cartel.client_cmd(["ps"])
+    match = re.search(rf"^\d+\s+{name}\s+\S+\s+(\w+)\s+.*", out, re.M)
+    return match.group(1) if match else None
+
+
+def test_deploy_then_status_reports_running(cartel):
+    # GIVEN
+    cartel.definitions(
+        """
+        kind: Service
+        name: sleeper
+        command: ["sleep", "100"]
+        """
+    )
+    assert module_status(cartel, "sleeper") is None
+
+    # WHEN
+    out = cartel.client_cmd(["deploy", "sleeper"])
+
+    # THEN
+    assert "Deploying sleeper (Deployed)" in out
+    assert module_status(cartel, "sleeper") == "running"
+
+
+def test_status_transitions_through_stop_and_redeploy(cartel):
+    # GIVEN
+    cartel.definitions(
+        """
+        kind: Service
+        name: sleeper
+        command: ["sleep", "100"]
+        """
+    )
+
+    # WHEN / THEN
+    cartel.client_cmd(["deploy", "sleeper"])
+    assert module_status(cartel, "sleeper") == "running"
+
+    cartel.client_cmd(["stop", "sleeper"])
+    assert module_status(cartel, "sleeper") == "stopped"
+
+    cartel.client_cmd(["deploy", "sleeper"])
+    assert module_status(cartel, "sleeper") == "running"
+
+
+def test_status_reports_exited_when_service_dies(cartel):
+    # GIVEN
+    cartel.definitions(
+        """
+        kind: Service
+        name: short-lived
+        command: ["sleep", "0"]
+        """
+    )
+
+    # WHEN
+    cartel.client_cmd(["deploy", "short-lived"], delay=0.5)
+
+    # THEN
+    assert module_status(cartel, "short-lived") == "exited"